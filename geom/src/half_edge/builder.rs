@@ -0,0 +1,167 @@
+//! Construction of kernels out of lists of polygons.
+
+use std::collections::HashMap;
+
+use half_edge::kernel::{ ConnectivityKernel, HalfEdge, EdgeId, FaceId, VertexId, no_edge };
+use half_edge::id_internals::{ is_valid, Index, KernelIndex };
+
+#[cfg(test)]
+use half_edge::kernel::vertex_id;
+
+/// The reason why a polygon can't be added to a `PolygonBuilder`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PolygonError<H: KernelIndex = Index> {
+    /// The polygon has less than three vertices, or uses the same vertex twice.
+    Degenerate,
+    /// The half edge going from the first vertex to the second one is already used by another
+    /// polygon. Either the edge is shared by more than two polygons, or the polygons that share
+    /// it are not wound consistently.
+    NonManifoldEdge(VertexId<H>, VertexId<H>),
+}
+
+/// Builds a kernel out of polygons that share vertices.
+///
+/// The half edges of neighbouring polygons are linked as opposites, and the half edges on the
+/// boundary of the mesh don't have an opposite.
+pub struct PolygonBuilder<H: KernelIndex = Index> {
+    kernel: ConnectivityKernel<H>,
+    edges: HashMap<(VertexId<H>, VertexId<H>), EdgeId<H>>,
+}
+
+impl<H: KernelIndex> PolygonBuilder<H> {
+    pub fn new() -> PolygonBuilder<H> {
+        PolygonBuilder {
+            kernel: ConnectivityKernel::new(),
+            edges: HashMap::new(),
+        }
+    }
+
+    pub fn with_capacities(v: H, e: H, f: H) -> PolygonBuilder<H> {
+        PolygonBuilder {
+            kernel: ConnectivityKernel::with_capacities(v, e, f),
+            edges: HashMap::with_capacity(e.to_index()),
+        }
+    }
+
+    /// Add a polygon and return its face, the first edge of which goes from the first vertex to
+    /// the second one.
+    ///
+    /// The kernel is not modified if the polygon is rejected.
+    pub fn add_polygon(&mut self, vertices: &[VertexId<H>]) -> Result<FaceId<H>, PolygonError<H>> {
+        let n = vertices.len();
+        if n < 3 {
+            return Err(PolygonError::Degenerate);
+        }
+        for i in 0..n {
+            let (from, to) = (vertices[i], vertices[(i + 1) % n]);
+            if vertices[i + 1..].contains(&from) {
+                return Err(PolygonError::Degenerate);
+            }
+            if self.edges.contains_key(&(from, to)) {
+                return Err(PolygonError::NonManifoldEdge(from, to));
+            }
+        }
+
+        let face = self.kernel.add_face();
+        let mut ids = Vec::with_capacity(n);
+        for i in 0..n {
+            let (from, to) = (vertices[i], vertices[(i + 1) % n]);
+            let opposite = self.edges.get(&(to, from)).cloned().unwrap_or(no_edge());
+            let id = self.kernel.add_edge(HalfEdge {
+                next: no_edge(),
+                prev: no_edge(),
+                opposite: opposite,
+                vertex: from,
+                face: face,
+            });
+            if is_valid(opposite) {
+                self.kernel[opposite].opposite = id;
+            }
+            self.edges.insert((from, to), id);
+            ids.push(id);
+        }
+        for i in 0..n {
+            let edge = &mut self.kernel[ids[i]];
+            edge.next = ids[(i + 1) % n];
+            edge.prev = ids[(i + n - 1) % n];
+        }
+        self.kernel[face].first_edge = ids[0];
+
+        return Ok(face);
+    }
+
+    pub fn kernel(&self) -> &ConnectivityKernel<H> { &self.kernel }
+
+    /// Return the vertices where polygons only touch by a corner, which the edge checks of
+    /// `add_polygon` can't detect.
+    ///
+    /// The edges leaving such a vertex are not all connected to each other through opposite
+    /// edges, so that iterating around the vertex misses some of them.
+    pub fn non_manifold_vertices(&self) -> Vec<VertexId<H>> {
+        let mut num_edges: HashMap<VertexId<H>, usize> = HashMap::new();
+        for &(from, _) in self.edges.keys() {
+            *num_edges.entry(from).or_insert(0) += 1;
+        }
+        let mut vertices: Vec<VertexId<H>> = num_edges.iter().filter(|&(&v, &n)| {
+            self.kernel.walk_edge_ids_around_vertex(v).count() != n
+        }).map(|(&v, _)| v).collect();
+        vertices.sort_by_key(|v| v.handle);
+        return vertices;
+    }
+
+    pub fn finish(self) -> ConnectivityKernel<H> { self.kernel }
+}
+
+#[test]
+fn test_polygon_builder() {
+    //  0---1---2
+    //  |   |  /
+    //  3---4-'
+    let v: Vec<VertexId> = (0..5).map(vertex_id).collect();
+    let mut builder = PolygonBuilder::new();
+    let quad = builder.add_polygon(&[v[0], v[3], v[4], v[1]]).unwrap();
+    let triangle = builder.add_polygon(&[v[1], v[4], v[2]]).unwrap();
+
+    assert_eq!(builder.add_polygon(&[v[0], v[1]]), Err(PolygonError::Degenerate));
+    assert_eq!(builder.add_polygon(&[v[0], v[1], v[0]]), Err(PolygonError::Degenerate));
+    // Same winding as the triangle on the shared edge.
+    assert_eq!(
+        builder.add_polygon(&[v[4], v[1], v[3]]),
+        Err(PolygonError::NonManifoldEdge(v[4], v[1]))
+    );
+
+    let kernel = builder.finish();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.num_faces(), 2);
+    assert_eq!(kernel.num_edges(), 7);
+    assert_eq!(kernel.num_vertices(), 5);
+
+    let first_edge = kernel[quad].first_edge;
+    assert_eq!(kernel[first_edge].vertex, v[0]);
+    assert_eq!(kernel[kernel[first_edge].next].vertex, v[3]);
+
+    // The shared edge is the only one with an opposite.
+    let shared = kernel[kernel[first_edge].prev].prev;
+    assert_eq!(kernel[shared].vertex, v[4]);
+    assert_eq!(kernel[kernel[shared].opposite].face, triangle);
+    let boundary = kernel.edge_ids().filter(|&e| !is_valid(kernel[e].opposite)).count();
+    assert_eq!(boundary, 5);
+
+    assert_eq!(kernel.walk_face_ids_around_vertex(v[4]).count(), 2);
+    assert_eq!(kernel.walk_vertex_ids_around_vertex(v[1]).count(), 2);
+}
+
+#[test]
+fn test_non_manifold_vertices() {
+    // Two triangles that only share vertex 0.
+    let v: Vec<VertexId> = (0..5).map(vertex_id).collect();
+    let mut builder = PolygonBuilder::new();
+    builder.add_polygon(&[v[0], v[1], v[2]]).unwrap();
+    builder.add_polygon(&[v[0], v[3], v[4]]).unwrap();
+    assert_eq!(builder.non_manifold_vertices(), vec![v[0]]);
+
+    // Filling the gaps between them on one side makes it a regular boundary vertex.
+    builder.add_polygon(&[v[0], v[2], v[3]]).unwrap();
+    assert_eq!(builder.non_manifold_vertices(), vec![]);
+    assert_eq!(builder.kernel().validate(), vec![]);
+}
@@ -1,16 +1,44 @@
-use vodk_id::{ Id, FromIndex };
-use vodk_id::id_list::IdCheck;
+use vodk_id::{ Id, FromIndex, IntegerHandle, NullId };
+use std::fmt::{ Debug, Display };
+use std::hash::Hash;
 use std::marker::PhantomData;
+use std::{ u16, u32 };
 
+/// The default handle type of the kernel ids.
 pub type Index = u16;
 
+/// The integer types that can be used as the handles of kernel ids.
+///
+/// The largest value of the type marks invalid ids, so a kernel can hold one less vertex, half
+/// edge or face than the type can count. `u16` handles are enough for meshes of up to 65534 half
+/// edges, larger meshes need `u32` handles.
+pub trait KernelIndex: IntegerHandle + Eq + Ord + Hash + Debug + Display + 'static {
+    fn invalid() -> Self;
+
+    /// The number of vertices, half edges or faces that a kernel can hold with these handles.
+    fn max_count() -> usize { Self::invalid().to_index() }
+
+    /// Convert an index to a handle, or return None if the index doesn't fit in a valid handle.
+    fn checked_from_index(index: usize) -> Option<Self> {
+        if index < Self::max_count() { Some(Self::from_index(index)) } else { None }
+    }
+}
+
+impl KernelIndex for u16 { fn invalid() -> u16 { u16::MAX } }
+impl KernelIndex for u32 { fn invalid() -> u32 { u32::MAX } }
+
+/// Convert an index to an id, or return None if the index doesn't fit in a valid handle.
+pub fn checked_id<T, H: KernelIndex>(index: usize) -> Option<Id<T, H>> {
+    return H::checked_from_index(index).map(Id::new);
+}
+
 // We use a magic value to
-pub fn is_valid<T>(id: Id<T, Index>) -> bool { id.handle != ::std::u16::MAX }
+pub fn is_valid<T, H: KernelIndex>(id: Id<T, H>) -> bool { id.handle != H::invalid() }
 
 pub struct MagicValueMax<T> {
     _marker: PhantomData<T>,
 }
 
-impl<T> IdCheck<Id<T, Index>> for MagicValueMax<T> {
-    fn none() -> Id<T, Index> { return FromIndex::from_index(::std::u16::MAX as usize); }
+impl<T, H: KernelIndex> NullId<Id<T, H>> for MagicValueMax<T> {
+    fn null_id() -> Id<T, H> { return FromIndex::from_index(H::invalid().to_index()); }
 }
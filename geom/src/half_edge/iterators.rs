@@ -2,8 +2,9 @@ use half_edge::kernel::{
     ConnectivityKernel, HalfEdge,
     EdgeId, FaceId, VertexId,
     EdgeContainer, GetNext, GetPrev,
-    NO_EDGE,
+    no_edge,
 };
+use half_edge::id_internals::{ Index, KernelIndex };
 
 use half_edge::id_internals::is_valid;
 
@@ -11,17 +12,18 @@ use std::cmp::PartialEq;
 use std::mem::transmute;
 
 /// Iterates over the half edges around a face.
-pub struct EdgeIdLoop<'l, Kernel:'l> {
+pub struct EdgeIdLoop<'l, Kernel: EdgeContainer + 'l> {
     kernel: &'l Kernel,
-    current_edge: EdgeId,
-    last_edge: EdgeId,
+    current_edge: EdgeId<Kernel::Index>,
+    last_edge: EdgeId<Kernel::Index>,
     done: bool,
 }
 
-impl<'l, Kernel: EdgeContainer> Iterator for EdgeIdLoop<'l, Kernel> where Kernel::EdgeType: GetNext {
-    type Item = EdgeId;
+impl<'l, Kernel: EdgeContainer> Iterator for EdgeIdLoop<'l, Kernel>
+where Kernel::EdgeType: GetNext<Kernel::Index> {
+    type Item = EdgeId<Kernel::Index>;
 
-    fn next(&mut self) -> Option<EdgeId> {
+    fn next(&mut self) -> Option<EdgeId<Kernel::Index>> {
         let res = self.current_edge;
         if self.done {
             return None;
@@ -29,7 +31,7 @@ impl<'l, Kernel: EdgeContainer> Iterator for EdgeIdLoop<'l, Kernel> where Kernel
         if self.current_edge == self.last_edge {
             self.done = true;
         }
-        if self.current_edge == NO_EDGE {
+        if self.current_edge == no_edge() {
             return None;
         }
         self.current_edge = self.kernel.edge(self.current_edge).next();
@@ -37,11 +39,11 @@ impl<'l, Kernel: EdgeContainer> Iterator for EdgeIdLoop<'l, Kernel> where Kernel
     }
 }
 
-impl<'l, Kernel> EdgeIdLoop<'l, Kernel> {
+impl<'l, Kernel: EdgeContainer> EdgeIdLoop<'l, Kernel> {
     pub fn new(
         kernel: &'l Kernel,
-        first: EdgeId,
-        last: EdgeId,
+        first: EdgeId<Kernel::Index>,
+        last: EdgeId<Kernel::Index>,
     ) -> EdgeIdLoop<'l, Kernel> {
         EdgeIdLoop {
             kernel: kernel,
@@ -52,9 +54,11 @@ impl<'l, Kernel> EdgeIdLoop<'l, Kernel> {
     }
 }
 
-pub fn iter_edge_loop<'l, Container: EdgeContainer>(container: &'l Container, edge_loop: EdgeId)
--> EdgeIdLoop<'l, Container>
-where Container::EdgeType: GetNext+GetPrev
+pub fn iter_edge_loop<'l, Container: EdgeContainer>(
+    container: &'l Container,
+    edge_loop: EdgeId<Container::Index>,
+) -> EdgeIdLoop<'l, Container>
+where Container::EdgeType: GetNext<Container::Index> + GetPrev<Container::Index>
 {
     EdgeIdLoop {
         kernel: container,
@@ -65,17 +69,17 @@ where Container::EdgeType: GetNext+GetPrev
 }
 
 /// Iterates over the half edges around a face.
-pub struct MutEdgeLoop<'l> {
-    kernel: &'l mut ConnectivityKernel,
-    current_edge: EdgeId,
-    last_edge: EdgeId,
+pub struct MutEdgeLoop<'l, H: KernelIndex = Index> {
+    kernel: &'l mut ConnectivityKernel<H>,
+    current_edge: EdgeId<H>,
+    last_edge: EdgeId<H>,
     done: bool,
 }
 
-impl<'l> Iterator for MutEdgeLoop<'l> {
-    type Item = &'l mut HalfEdge;
+impl<'l, H: KernelIndex> Iterator for MutEdgeLoop<'l, H> {
+    type Item = &'l mut HalfEdge<H>;
 
-    fn next(&mut self) -> Option<&'l mut HalfEdge> {
+    fn next(&mut self) -> Option<&'l mut HalfEdge<H>> {
         let res = self.current_edge;
         if self.done {
             return None;
@@ -83,7 +87,7 @@ impl<'l> Iterator for MutEdgeLoop<'l> {
         if self.current_edge == self.last_edge {
             self.done = true;
         }
-        if self.current_edge == NO_EDGE {
+        if self.current_edge == no_edge() {
             return None;
         }
         self.current_edge = self.kernel[self.current_edge].next;
@@ -91,12 +95,12 @@ impl<'l> Iterator for MutEdgeLoop<'l> {
     }
 }
 
-impl<'l> MutEdgeLoop<'l> {
+impl<'l, H: KernelIndex> MutEdgeLoop<'l, H> {
     pub fn new(
-        kernel: &'l mut ConnectivityKernel,
-        first: EdgeId,
-        last: EdgeId,
-    ) -> MutEdgeLoop<'l> {
+        kernel: &'l mut ConnectivityKernel<H>,
+        first: EdgeId<H>,
+        last: EdgeId<H>,
+    ) -> MutEdgeLoop<'l, H> {
         MutEdgeLoop {
             kernel: kernel,
             current_edge: first,
@@ -107,17 +111,17 @@ impl<'l> MutEdgeLoop<'l> {
 }
 
 /// Iterates over the half edges around a face in reverse order.
-pub struct ReverseEdgeIdLoop<'l> {
-    kernel: &'l ConnectivityKernel,
-    current_edge: EdgeId,
-    last_edge: EdgeId,
+pub struct ReverseEdgeIdLoop<'l, H: KernelIndex = Index> {
+    kernel: &'l ConnectivityKernel<H>,
+    current_edge: EdgeId<H>,
+    last_edge: EdgeId<H>,
     done: bool,
 }
 
-impl<'l> Iterator for ReverseEdgeIdLoop<'l> {
-    type Item = EdgeId;
+impl<'l, H: KernelIndex> Iterator for ReverseEdgeIdLoop<'l, H> {
+    type Item = EdgeId<H>;
 
-    fn next(&mut self) -> Option<EdgeId> {
+    fn next(&mut self) -> Option<EdgeId<H>> {
         let res = self.current_edge;
         if self.done {
             return None;
@@ -130,12 +134,12 @@ impl<'l> Iterator for ReverseEdgeIdLoop<'l> {
     }
 }
 
-impl<'l> ReverseEdgeIdLoop<'l> {
+impl<'l, H: KernelIndex> ReverseEdgeIdLoop<'l, H> {
     pub fn new(
-        kernel: &'l ConnectivityKernel,
-        first: EdgeId,
-        last: EdgeId,
-    ) -> ReverseEdgeIdLoop<'l> {
+        kernel: &'l ConnectivityKernel<H>,
+        first: EdgeId<H>,
+        last: EdgeId<H>,
+    ) -> ReverseEdgeIdLoop<'l, H> {
         ReverseEdgeIdLoop {
             kernel: kernel,
             current_edge: first,
@@ -146,28 +150,128 @@ impl<'l> ReverseEdgeIdLoop<'l> {
 }
 
 /// Iterates over the half edges that point to a vertex.
-pub struct VertexEdgeIterator<'l> {
-    kernel: &'l ConnectivityKernel,
-    current_edge: EdgeId,
-    first_edge: EdgeId,
+pub struct VertexEdgeIterator<'l, H: KernelIndex = Index> {
+    kernel: &'l ConnectivityKernel<H>,
+    current_edge: EdgeId<H>,
+    first_edge: EdgeId<H>,
 }
 
-impl<'l> Iterator for VertexEdgeIterator<'l> {
-    type Item = EdgeId;
+impl<'l, H: KernelIndex> Iterator for VertexEdgeIterator<'l, H> {
+    type Item = EdgeId<H>;
 
-    fn next(&mut self) -> Option<EdgeId> {
+    fn next(&mut self) -> Option<EdgeId<H>> {
         if !is_valid(self.current_edge) {
             return None;
         }
         let temp = self.current_edge;
         self.current_edge = self.kernel[self.kernel[self.current_edge].next].opposite;
         if self.current_edge == self.first_edge {
-            self.current_edge = NO_EDGE;
+            self.current_edge = no_edge();
         }
         return Some(temp);
     }
 }
 
+/// Iterates over the half edges that originate from a vertex.
+///
+/// On boundaries, the iteration goes in one direction until an edge without opposite is
+/// found, and then continues from the first edge in the other direction.
+pub struct EdgeIdsAroundVertex<'l, H: KernelIndex = Index> {
+    kernel: &'l ConnectivityKernel<H>,
+    first_edge: EdgeId<H>,
+    current_edge: EdgeId<H>,
+    forward: bool,
+}
+
+impl<'l, H: KernelIndex> EdgeIdsAroundVertex<'l, H> {
+    pub fn new(kernel: &'l ConnectivityKernel<H>, first_edge: EdgeId<H>) -> EdgeIdsAroundVertex<'l, H> {
+        EdgeIdsAroundVertex {
+            kernel: kernel,
+            first_edge: first_edge,
+            current_edge: first_edge,
+            forward: true,
+        }
+    }
+}
+
+impl<'l, H: KernelIndex> Iterator for EdgeIdsAroundVertex<'l, H> {
+    type Item = EdgeId<H>;
+
+    fn next(&mut self) -> Option<EdgeId<H>> {
+        let res = self.current_edge;
+        if !is_valid(res) {
+            return None;
+        }
+        let kernel = self.kernel;
+        if self.forward {
+            let next = kernel[kernel[res].prev].opposite;
+            if next == self.first_edge {
+                self.current_edge = no_edge();
+            } else if is_valid(next) {
+                self.current_edge = next;
+            } else {
+                // Reached a boundary, go back to the first edge and rotate the other way.
+                self.forward = false;
+                self.current_edge = self.backward_step(self.first_edge);
+            }
+        } else {
+            self.current_edge = self.backward_step(res);
+        }
+        return Some(res);
+    }
+}
+
+impl<'l, H: KernelIndex> EdgeIdsAroundVertex<'l, H> {
+    fn backward_step(&self, edge: EdgeId<H>) -> EdgeId<H> {
+        let opposite = self.kernel[edge].opposite;
+        if !is_valid(opposite) {
+            return no_edge();
+        }
+        let next = self.kernel[opposite].next;
+        return if next == self.first_edge { no_edge() } else { next };
+    }
+}
+
+/// Iterates over the vertices connected to a vertex by an edge.
+pub struct VertexIdsAroundVertex<'l, H: KernelIndex = Index> {
+    edges: EdgeIdsAroundVertex<'l, H>,
+}
+
+impl<'l, H: KernelIndex> VertexIdsAroundVertex<'l, H> {
+    pub fn new(kernel: &'l ConnectivityKernel<H>, first_edge: EdgeId<H>) -> VertexIdsAroundVertex<'l, H> {
+        VertexIdsAroundVertex { edges: EdgeIdsAroundVertex::new(kernel, first_edge) }
+    }
+}
+
+impl<'l, H: KernelIndex> Iterator for VertexIdsAroundVertex<'l, H> {
+    type Item = VertexId<H>;
+
+    fn next(&mut self) -> Option<VertexId<H>> {
+        let kernel = self.edges.kernel;
+        return self.edges.next().map(|e| kernel[kernel[e].next].vertex);
+    }
+}
+
+/// Iterates over the faces around a vertex.
+pub struct FaceIdsAroundVertex<'l, H: KernelIndex = Index> {
+    edges: EdgeIdsAroundVertex<'l, H>,
+}
+
+impl<'l, H: KernelIndex> FaceIdsAroundVertex<'l, H> {
+    pub fn new(kernel: &'l ConnectivityKernel<H>, first_edge: EdgeId<H>) -> FaceIdsAroundVertex<'l, H> {
+        FaceIdsAroundVertex { edges: EdgeIdsAroundVertex::new(kernel, first_edge) }
+    }
+}
+
+impl<'l, H: KernelIndex> Iterator for FaceIdsAroundVertex<'l, H> {
+    type Item = FaceId<H>;
+
+    fn next(&mut self) -> Option<FaceId<H>> {
+        let kernel = self.edges.kernel;
+        return self.edges.next().map(|e| kernel[e].face);
+    }
+}
+
 //pub struct VertexIdIterator {
 //    current: Index,
 //    stop: Index,
@@ -232,111 +336,111 @@ impl Direction {
 }
 
 #[derive(Copy, Clone)]
-pub struct EdgeCirculator<'l> {
-    kernel: &'l ConnectivityKernel,
-    edge: EdgeId,
+pub struct EdgeCirculator<'l, H: KernelIndex = Index> {
+    kernel: &'l ConnectivityKernel<H>,
+    edge: EdgeId<H>,
 }
 
-impl<'l> EdgeCirculator<'l> {
-    pub fn new(kernel: &'l ConnectivityKernel, edge: EdgeId) -> EdgeCirculator{
+impl<'l, H: KernelIndex> EdgeCirculator<'l, H> {
+    pub fn new(kernel: &'l ConnectivityKernel<H>, edge: EdgeId<H>) -> EdgeCirculator<'l, H> {
         EdgeCirculator {
             kernel: kernel,
             edge: edge,
         }
     }
 
-    pub fn edge(&'l self) -> &'l HalfEdge { &self.kernel[self.edge] }
+    pub fn edge(&'l self) -> &'l HalfEdge<H> { &self.kernel[self.edge] }
 
-    pub fn next(self) -> EdgeCirculator<'l> {
+    pub fn next(self) -> EdgeCirculator<'l, H> {
         EdgeCirculator {
             kernel: self.kernel,
             edge: self.edge().next,
         }
     }
 
-    pub fn prev(self) -> EdgeCirculator<'l> {
+    pub fn prev(self) -> EdgeCirculator<'l, H> {
         EdgeCirculator {
             kernel: self.kernel,
             edge: self.edge().prev,
         }
     }
 
-    pub fn advance(self, direction: Direction) -> EdgeCirculator<'l> {
+    pub fn advance(self, direction: Direction) -> EdgeCirculator<'l, H> {
         match direction {
             Direction::Forward => self.next(),
             Direction::Backward => self.prev(),
         }
     }
 
-    pub fn edge_id(&self) -> EdgeId { self.edge }
+    pub fn edge_id(&self) -> EdgeId<H> { self.edge }
 
-    pub fn vertex_id(&self) -> VertexId { self.edge().vertex }
+    pub fn vertex_id(&self) -> VertexId<H> { self.edge().vertex }
 
-    pub fn face_id(&self) -> FaceId { self.edge().face }
+    pub fn face_id(&self) -> FaceId<H> { self.edge().face }
 }
 
-impl<'l> PartialEq<EdgeCirculator<'l>> for EdgeCirculator<'l> {
-    fn eq(&self, other: &EdgeCirculator) -> bool {
+impl<'l, H: KernelIndex> PartialEq<EdgeCirculator<'l, H>> for EdgeCirculator<'l, H> {
+    fn eq(&self, other: &EdgeCirculator<'l, H>) -> bool {
         return self.edge.eq(&other.edge);
     }
-    fn ne(&self, other: &EdgeCirculator) -> bool {
+    fn ne(&self, other: &EdgeCirculator<'l, H>) -> bool {
         return self.edge.ne(&other.edge);
     }
 }
 
 #[derive(Copy, Clone)]
-pub struct DirectedEdgeCirculator<'l> {
-    circulator: EdgeCirculator<'l>,
+pub struct DirectedEdgeCirculator<'l, H: KernelIndex = Index> {
+    circulator: EdgeCirculator<'l, H>,
     direction: Direction,
 }
 
-impl<'l> DirectedEdgeCirculator<'l> {
-    pub fn new(kernel: &'l ConnectivityKernel, edge: EdgeId, direction: Direction) -> DirectedEdgeCirculator {
+impl<'l, H: KernelIndex> DirectedEdgeCirculator<'l, H> {
+    pub fn new(kernel: &'l ConnectivityKernel<H>, edge: EdgeId<H>, direction: Direction) -> DirectedEdgeCirculator<'l, H> {
         DirectedEdgeCirculator {
             circulator: EdgeCirculator::new(kernel, edge),
             direction: direction,
         }
     }
 
-    pub fn edge(&'l self) -> &'l HalfEdge { self.circulator.edge() }
+    pub fn edge(&'l self) -> &'l HalfEdge<H> { self.circulator.edge() }
 
-    pub fn next(self) -> DirectedEdgeCirculator<'l> {
+    pub fn next(self) -> DirectedEdgeCirculator<'l, H> {
         DirectedEdgeCirculator {
             circulator: self.circulator.advance(self.direction),
             direction: self.direction,
         }
     }
 
-    pub fn prev(self) -> DirectedEdgeCirculator<'l> {
+    pub fn prev(self) -> DirectedEdgeCirculator<'l, H> {
         DirectedEdgeCirculator {
             circulator: self.circulator.advance(self.direction.reverse()),
             direction: self.direction,
         }
     }
 
-    pub fn advance(self, direction: Direction) -> DirectedEdgeCirculator<'l> {
+    pub fn advance(self, direction: Direction) -> DirectedEdgeCirculator<'l, H> {
         match self.direction == direction {
             true => self.next(),
             false => self.prev(),
         }
     }
 
-    pub fn edge_id(&self) -> EdgeId { self.circulator.edge }
+    pub fn edge_id(&self) -> EdgeId<H> { self.circulator.edge }
 
-    pub fn vertex_id(&self) -> VertexId { self.circulator.vertex_id() }
+    pub fn vertex_id(&self) -> VertexId<H> { self.circulator.vertex_id() }
 
-    pub fn face_id(&self) -> FaceId { self.circulator.face_id() }
+    pub fn face_id(&self) -> FaceId<H> { self.circulator.face_id() }
 
     pub fn direction(&self) -> Direction { self.direction }
 
     pub fn set_direction(&mut self, direction: Direction) { self.direction = direction; }
 }
 
-impl<'l> PartialEq<DirectedEdgeCirculator<'l>> for DirectedEdgeCirculator<'l> {
-    fn eq(&self, other: &DirectedEdgeCirculator) -> bool {
+impl<'l, H: KernelIndex> PartialEq<DirectedEdgeCirculator<'l, H>> for DirectedEdgeCirculator<'l, H> {
+    fn eq(&self, other: &DirectedEdgeCirculator<'l, H>) -> bool {
         return self.circulator.edge.eq(&other.circulator.edge);
     }
-    fn ne(&self, other: &DirectedEdgeCirculator) -> bool {
+    fn ne(&self, other: &DirectedEdgeCirculator<'l, H>) -> bool {
         return self.circulator.edge.ne(&other.circulator.edge);
     }
 }
@@ -2,14 +2,15 @@ use std::ops;
 use std::u16;
 use std::marker::PhantomData;
 
-pub use half_edge::id_internals::Index;
+pub use half_edge::id_internals::{ Index, KernelIndex };
 
 use half_edge::id_internals::{ is_valid };
 use half_edge::iterators::{
     EdgeIdLoop, ReverseEdgeIdLoop, MutEdgeLoop,
+    EdgeIdsAroundVertex, VertexIdsAroundVertex, FaceIdsAroundVertex,
 };
 use vodk_id::*;
-use vodk_id::sparse_id_vector::SparseIdVector;
+use vodk_id::sparse_id_vector::{ SparseIdVector, SparseIdIter };
 
 #[derive(Debug)]
 pub struct Vertex_;
@@ -18,14 +19,27 @@ pub struct Edge_;
 #[derive(Debug)]
 pub struct Face_;
 
-pub type VertexId = Id<Vertex_, Index>;
-pub type EdgeId = Id<Edge_, Index>;
-pub type FaceId = Id<Face_, Index>;
+/// The id of a vertex. Kernels use `u16` handles by default, see `KernelIndex`.
+pub type VertexId<H = Index> = Id<Vertex_, H>;
+pub type EdgeId<H = Index> = Id<Edge_, H>;
+pub type FaceId<H = Index> = Id<Face_, H>;
 
 pub const NO_EDGE: EdgeId = EdgeId { handle: u16::MAX, _marker: PhantomData };
 pub const NO_FACE: FaceId = FaceId { handle: u16::MAX, _marker: PhantomData };
 pub const NO_VERTEX: VertexId = VertexId { handle: u16::MAX, _marker: PhantomData };
 
+/// The invalid edge id for any handle type, `NO_EDGE` being the one of the default handles.
+#[inline]
+pub fn no_edge<H: KernelIndex>() -> EdgeId<H> { EdgeId::new(H::invalid()) }
+
+/// The invalid face id for any handle type.
+#[inline]
+pub fn no_face<H: KernelIndex>() -> FaceId<H> { FaceId::new(H::invalid()) }
+
+/// The invalid vertex id for any handle type.
+#[inline]
+pub fn no_vertex<H: KernelIndex>() -> VertexId<H> { VertexId::new(H::invalid()) }
+
 /// Create an EdgeId from an index (the offset in the ConnectivityKernel's half edge vector)
 #[inline]
 pub fn edge_id(index: Index) -> EdgeId { EdgeId::new(index) }
@@ -39,104 +53,156 @@ pub fn face_id(index: Index) -> FaceId { FaceId::new(index) }
 pub fn vertex_id(index: Index) -> VertexId { VertexId::new(index) }
 
 /// A range of Id pointing to contiguous vertices.
-pub type VertexIdRange = IdRange<Vertex_, Index>;
+pub type VertexIdRange<H = Index> = IdRange<Vertex_, H>;
 
 /// A range of Id pointing to contiguous half edges.
-pub type EdgeIdRange = IdRange<Edge_, Index>;
+pub type EdgeIdRange<H = Index> = IdRange<Edge_, H>;
 
 /// A range of Id pointing to contiguous faces.
-pub type FaceIdRange = IdRange<Face_, Index>;
-
-pub trait GetNext { fn next(&self) -> EdgeId; }
-pub trait GetPrev { fn prev(&self) -> EdgeId; }
-pub trait GetOpposite { fn opposite(&self) -> EdgeId; }
-pub trait GetVertex { fn vertex(&self) -> VertexId; }
-pub trait GetFace { fn face(&self) -> FaceId; }
-pub trait SetNext { fn set_next(&mut self, id: EdgeId); }
-pub trait SetPrev { fn set_prev(&mut self, id: EdgeId); }
-pub trait SetOpposite { fn set_opposite(&mut self, id: EdgeId); }
-pub trait SetVertex { fn set_vertex(&mut self, id: VertexId); }
-pub trait SetFace { fn set_face(&mut self, id: FaceId); }
+pub type FaceIdRange<H = Index> = IdRange<Face_, H>;
+
+pub trait GetNext<H: KernelIndex = Index> { fn next(&self) -> EdgeId<H>; }
+pub trait GetPrev<H: KernelIndex = Index> { fn prev(&self) -> EdgeId<H>; }
+pub trait GetOpposite<H: KernelIndex = Index> { fn opposite(&self) -> EdgeId<H>; }
+pub trait GetVertex<H: KernelIndex = Index> { fn vertex(&self) -> VertexId<H>; }
+pub trait GetFace<H: KernelIndex = Index> { fn face(&self) -> FaceId<H>; }
+pub trait SetNext<H: KernelIndex = Index> { fn set_next(&mut self, id: EdgeId<H>); }
+pub trait SetPrev<H: KernelIndex = Index> { fn set_prev(&mut self, id: EdgeId<H>); }
+pub trait SetOpposite<H: KernelIndex = Index> { fn set_opposite(&mut self, id: EdgeId<H>); }
+pub trait SetVertex<H: KernelIndex = Index> { fn set_vertex(&mut self, id: VertexId<H>); }
+pub trait SetFace<H: KernelIndex = Index> { fn set_face(&mut self, id: FaceId<H>); }
 
 pub trait EdgeContainer {
     type EdgeType;
-    fn edge(&self, id: EdgeId) -> &Self::EdgeType;
+    type Index: KernelIndex;
+    fn edge(&self, id: EdgeId<Self::Index>) -> &Self::EdgeType;
 }
 pub trait MutEdgeContainer : EdgeContainer {
-    fn mut_edge(&mut self, id: EdgeId) -> &mut Self::EdgeType;
+    fn mut_edge(&mut self, id: EdgeId<Self::Index>) -> &mut Self::EdgeType;
 }
 
 /// The structure holding the data specific to each half edge.
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub struct HalfEdge {
-    pub next: EdgeId, // next half edge around the face
-    pub prev: EdgeId, // previous half edge around the face
-    pub opposite: EdgeId, // oppositely oriented adjacent half edge
-    pub vertex: VertexId, // vertex this edge originates from
-    pub face: FaceId, // adjacent face
+pub struct HalfEdge<H: KernelIndex = Index> {
+    pub next: EdgeId<H>, // next half edge around the face
+    pub prev: EdgeId<H>, // previous half edge around the face
+    pub opposite: EdgeId<H>, // oppositely oriented adjacent half edge
+    pub vertex: VertexId<H>, // vertex this edge originates from
+    pub face: FaceId<H>, // adjacent face
 }
 
-impl GetNext for HalfEdge { fn next(&self) -> EdgeId { self.next } }
-impl GetPrev for HalfEdge { fn prev(&self) -> EdgeId { self.prev } }
-impl GetOpposite for HalfEdge { fn opposite(&self) -> EdgeId { self.opposite } }
-impl GetVertex for HalfEdge { fn vertex(&self) -> VertexId { self.vertex } }
-impl GetFace for HalfEdge { fn face(&self) -> FaceId { self.face } }
-impl SetNext for HalfEdge { fn set_next(&mut self, id: EdgeId) { self.next = id; } }
-impl SetPrev for HalfEdge { fn set_prev(&mut self, id: EdgeId) { self.prev = id; } }
-impl SetOpposite for HalfEdge { fn set_opposite(&mut self, id: EdgeId) { self.opposite = id; } }
-impl SetVertex for HalfEdge { fn set_vertex(&mut self, id: VertexId) { self.vertex = id; } }
-impl SetFace for HalfEdge { fn set_face(&mut self, id: FaceId) { self.face = id; } }
+impl<H: KernelIndex> GetNext<H> for HalfEdge<H> { fn next(&self) -> EdgeId<H> { self.next } }
+impl<H: KernelIndex> GetPrev<H> for HalfEdge<H> { fn prev(&self) -> EdgeId<H> { self.prev } }
+impl<H: KernelIndex> GetOpposite<H> for HalfEdge<H> { fn opposite(&self) -> EdgeId<H> { self.opposite } }
+impl<H: KernelIndex> GetVertex<H> for HalfEdge<H> { fn vertex(&self) -> VertexId<H> { self.vertex } }
+impl<H: KernelIndex> GetFace<H> for HalfEdge<H> { fn face(&self) -> FaceId<H> { self.face } }
+impl<H: KernelIndex> SetNext<H> for HalfEdge<H> { fn set_next(&mut self, id: EdgeId<H>) { self.next = id; } }
+impl<H: KernelIndex> SetPrev<H> for HalfEdge<H> { fn set_prev(&mut self, id: EdgeId<H>) { self.prev = id; } }
+impl<H: KernelIndex> SetOpposite<H> for HalfEdge<H> { fn set_opposite(&mut self, id: EdgeId<H>) { self.opposite = id; } }
+impl<H: KernelIndex> SetVertex<H> for HalfEdge<H> { fn set_vertex(&mut self, id: VertexId<H>) { self.vertex = id; } }
+impl<H: KernelIndex> SetFace<H> for HalfEdge<H> { fn set_face(&mut self, id: FaceId<H>) { self.face = id; } }
+
+impl<H: KernelIndex> EdgeContainer for ConnectivityKernel<H> {
+    type EdgeType = HalfEdge<H>;
+    type Index = H;
+    fn edge(&self, id: EdgeId<H>) -> &HalfEdge<H> { &self[id] }
+}
 
-impl EdgeContainer for ConnectivityKernel {
-    type EdgeType = HalfEdge;
-    fn edge(&self, id: EdgeId) -> &HalfEdge { &self[id] }
+impl<H: KernelIndex> MutEdgeContainer for ConnectivityKernel<H> {
+    fn mut_edge(&mut self, id: EdgeId<H>) -> &mut HalfEdge<H> { &mut self[id] }
+}
+
+/// The structure holding the data specific to each vertex.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vertex<H: KernelIndex = Index> {
+    pub first_edge: EdgeId<H>, // one of the half edges originating from this vertex
 }
 
-impl MutEdgeContainer for ConnectivityKernel {
-    fn mut_edge(&mut self, id: EdgeId) -> &mut HalfEdge { &mut self[id] }
+impl<H: KernelIndex> Default for Vertex<H> {
+    fn default() -> Vertex<H> { Vertex { first_edge: no_edge() } }
 }
 
 /// The structure holding the data specific to each face.
 #[derive(Clone, Debug, PartialEq)]
-pub struct Face {
-    pub inner_edges: Vec<EdgeId>,
-    pub first_edge: EdgeId,
+pub struct Face<H: KernelIndex = Index> {
+    pub inner_edges: Vec<EdgeId<H>>,
+    pub first_edge: EdgeId<H>,
 }
 
 /// The data structure that contains a mesh's connectivity information
 ///
 /// It does not contain other attributes such as positions. Use IdVector for that.
-pub struct ConnectivityKernel {
-    edges: SparseIdVector<EdgeId, HalfEdge>,
-    faces: SparseIdVector<FaceId, Face>,
+pub struct ConnectivityKernel<H: KernelIndex = Index> {
+    vertices: SparseIdVector<VertexId<H>, Vertex<H>>,
+    edges: SparseIdVector<EdgeId<H>, HalfEdge<H>>,
+    faces: SparseIdVector<FaceId<H>, Face<H>>,
 }
 
 
-pub fn get_edge(kernel: &ConnectivityKernel, id: EdgeId) -> &HalfEdge { &kernel.edges[id] }
+/// Tables mapping the ids of a kernel before `ConnectivityKernel::compact` to the ids after it.
+///
+/// The ids of removed elements map to None. Attribute vectors indexed by the old ids can be
+/// moved to the new ones with `remap_attributes`.
+pub struct IdRemapping<H: KernelIndex = Index> {
+    pub vertices: IdVec<VertexId<H>, Option<VertexId<H>>>,
+    pub edges: IdVec<EdgeId<H>, Option<EdgeId<H>>>,
+    pub faces: IdVec<FaceId<H>, Option<FaceId<H>>>,
+}
+
+/// Move the attributes of the elements that are kept by a compaction to their new ids, and
+/// drop the attributes of the removed elements.
+pub fn remap_attributes<ID: Identifier, T>(
+    attributes: IdVec<ID, T>,
+    remap: &IdVec<ID, Option<ID>>,
+) -> IdVec<ID, T> {
+    let mut result = Vec::with_capacity(attributes.len());
+    for (index, attribute) in attributes.into_vec().into_iter().enumerate() {
+        if index < remap.len() && remap.nth(index).is_some() {
+            result.push(attribute);
+        }
+    }
+    return IdVec::from_vec(result);
+}
+
+// Ids past the maximum count would wrap around and alias the invalid id or existing elements.
+fn check_capacity<H: KernelIndex>(count: usize, elements: &str) {
+    if count >= H::max_count() {
+        panic!("The connectivity kernel can't hold more than {} {} with {}-bit ids.",
+            H::max_count(), elements, ::std::mem::size_of::<H>() * 8);
+    }
+}
+
+pub fn get_edge<H: KernelIndex>(kernel: &ConnectivityKernel<H>, id: EdgeId<H>) -> &HalfEdge<H> { &kernel.edges[id] }
 
-impl ConnectivityKernel {
+impl<H: KernelIndex> ConnectivityKernel<H> {
 
     /// Create an empty kernel.
-    pub fn new() -> ConnectivityKernel {
+    pub fn new() -> ConnectivityKernel<H> {
         ConnectivityKernel {
+            vertices: SparseIdVector::new(),
             edges: SparseIdVector::new(),
             faces: SparseIdVector::new(),
         }
     }
 
     /// Create an empty kernel and preallocate memory for vertices, edges and faces.
-    pub fn with_capacities(e: u16, f: u16) -> ConnectivityKernel {
+    pub fn with_capacities(v: H, e: H, f: H) -> ConnectivityKernel<H> {
         ConnectivityKernel {
+            vertices: SparseIdVector::with_capacity(v),
             edges: SparseIdVector::with_capacity(e),
             faces: SparseIdVector::with_capacity(f),
         }
     }
 
     /// Create a ConnectivityKernel initialized with a loop
-    pub fn from_loop(vertices: VertexIdRange) -> ConnectivityKernel {
+    pub fn from_loop(vertices: VertexIdRange<H>) -> ConnectivityKernel<H> {
         let (lower, upper) = vertices.size_hint();
-        let capacity = if let Some(size) = upper { size } else { lower } as u16;
-        let mut kernel = ConnectivityKernel::with_capacities(capacity*2, 2);
+        let capacity = if let Some(size) = upper { size } else { lower };
+        let mut kernel = ConnectivityKernel::with_capacities(
+            H::from_index(capacity),
+            H::from_index(capacity * 2),
+            H::from_index(2)
+        );
 
         let back_face = kernel.add_face();
         let main_face = kernel.add_face();
@@ -149,44 +215,85 @@ impl ConnectivityKernel {
         return kernel;
     }
 
-    pub fn edge(&self, id: EdgeId) -> &HalfEdge { &self.edges[id] }
+    pub fn vertex(&self, id: VertexId<H>) -> &Vertex<H> { &self.vertices[id] }
+
+    pub fn edge(&self, id: EdgeId<H>) -> &HalfEdge<H> { &self.edges[id] }
+
+    pub fn face(&self, id: FaceId<H>) -> &Face<H> { &self.faces[id] }
+
+    pub fn first_edge(&self) -> Option<EdgeId<H>> { self.edges.first_id() }
+
+    pub fn first_face(&self) -> Option<FaceId<H>> { self.faces.first_id() }
 
-    pub fn face(&self, id: FaceId) -> &Face { &self.faces[id] }
+    pub fn contains_vertex(&self, id: VertexId<H>) -> bool { self.vertices.has_id(id) }
 
-    pub fn first_edge(&self) -> Option<EdgeId> { self.edges.first_id() }
+    pub fn contains_edge(&self, id: EdgeId<H>) -> bool { self.edges.has_id(id) }
 
-    pub fn first_face(&self) -> Option<FaceId> { self.faces.first_id() }
+    pub fn contains_face(&self, id: FaceId<H>) -> bool { self.faces.has_id(id) }
 
-    pub fn contains_edge(&self, id: EdgeId) -> bool { self.edges.has_id(id) }
+    /// Number of vertices in the kernel.
+    pub fn num_vertices(&self) -> usize { self.vertices.count() }
 
-    pub fn contains_face(&self, id: FaceId) -> bool { self.faces.has_id(id) }
+    /// Number of half edges in the kernel.
+    pub fn num_edges(&self) -> usize { self.edges.count() }
 
-    pub fn walk_edge_ids_around_face<'l>(&'l self, id: FaceId) -> EdgeIdLoop<'l, ConnectivityKernel> {
+    /// Number of faces in the kernel.
+    pub fn num_faces(&self) -> usize { self.faces.count() }
+
+    /// Iterate over the ids of the vertices in the kernel.
+    pub fn vertex_ids<'l>(&'l self) -> SparseIdIter<'l, VertexId<H>, Vertex<H>> { self.vertices.ids() }
+
+    /// Iterate over the ids of the half edges in the kernel.
+    pub fn edge_ids<'l>(&'l self) -> SparseIdIter<'l, EdgeId<H>, HalfEdge<H>> { self.edges.ids() }
+
+    /// Iterate over the ids of the faces in the kernel.
+    pub fn face_ids<'l>(&'l self) -> SparseIdIter<'l, FaceId<H>, Face<H>> { self.faces.ids() }
+
+    pub fn walk_edge_ids_around_face<'l>(&'l self, id: FaceId<H>) -> EdgeIdLoop<'l, ConnectivityKernel<H>> {
         let edge = self[id].first_edge;
-        let prev = if is_valid(edge) { self[edge].prev } else { NO_EDGE };
+        let prev = if is_valid(edge) { self[edge].prev } else { no_edge() };
         EdgeIdLoop::new(self, edge, prev)
     }
 
     /// Iterate over halfedge ids around a loop
-    pub fn walk_edge_ids<'l>(&'l self, first: EdgeId) -> EdgeIdLoop<'l, ConnectivityKernel> {
+    pub fn walk_edge_ids<'l>(&'l self, first: EdgeId<H>) -> EdgeIdLoop<'l, ConnectivityKernel<H>> {
         EdgeIdLoop::new(self, first, self[first].prev)
     }
 
     /// Iterate over halfedges around a loop
-    pub fn walk_edges_mut<'l>(&'l mut self, first: EdgeId) -> MutEdgeLoop<'l> {
+    pub fn walk_edges_mut<'l>(&'l mut self, first: EdgeId<H>) -> MutEdgeLoop<'l, H> {
         let stop = self[first].prev;
         return MutEdgeLoop::new(self, first, stop);
     }
 
     /// Shorthand for walk_edge_ids for a given face's loop
-    pub fn walk_edge_ids_around_face_reverse<'l>(&'l self, id: FaceId) -> ReverseEdgeIdLoop<'l> {
+    pub fn walk_edge_ids_around_face_reverse<'l>(&'l self, id: FaceId<H>) -> ReverseEdgeIdLoop<'l, H> {
         let edge = self[id].first_edge;
         ReverseEdgeIdLoop::new(self, edge, self[edge].next)
     }
 
+    /// Iterate over the ids of the half edges that originate from a vertex.
+    ///
+    /// If the vertex is on a boundary (some of its edges don't have an opposite edge), the
+    /// iteration goes around the vertex in one direction up to the boundary, and then in the
+    /// other direction from the vertex's first edge, so that all edges are visited.
+    pub fn walk_edge_ids_around_vertex<'l>(&'l self, id: VertexId<H>) -> EdgeIdsAroundVertex<'l, H> {
+        EdgeIdsAroundVertex::new(self, self[id].first_edge)
+    }
+
+    /// Iterate over the ids of the vertices connected to a vertex by an edge.
+    pub fn walk_vertex_ids_around_vertex<'l>(&'l self, id: VertexId<H>) -> VertexIdsAroundVertex<'l, H> {
+        VertexIdsAroundVertex::new(self, self[id].first_edge)
+    }
+
+    /// Iterate over the ids of the faces that a vertex is on.
+    pub fn walk_face_ids_around_vertex<'l>(&'l self, id: VertexId<H>) -> FaceIdsAroundVertex<'l, H> {
+        FaceIdsAroundVertex::new(self, self[id].first_edge)
+    }
+
     /// Return the next edge id when circulating around a vertex.
     /// TODO: needs tests
-    pub fn next_edge_id_around_vertex(&self, id: EdgeId) -> Option<EdgeId> {
+    pub fn next_edge_id_around_vertex(&self, id: EdgeId<H>) -> Option<EdgeId<H>> {
         let opposite = self[id].opposite;
         if !is_valid(opposite) {
             return None;
@@ -199,7 +306,7 @@ impl ConnectivityKernel {
     }
 
     /// Run a few debug-only assertions to check the state of a given edge.
-    pub fn debug_assert_edge_invariants(&self, id: EdgeId) {
+    pub fn debug_assert_edge_invariants(&self, id: EdgeId<H>) {
         //return;
         debug_assert!(is_valid(self[id].face));
         debug_assert_eq!(self[self[id].next].prev, id);
@@ -217,7 +324,7 @@ impl ConnectivityKernel {
 
     /// Run a few debug-only assertions to check the state of a given face,
     /// and the edges in its loop.
-    pub fn debug_assert_face_invariants(&self, face: FaceId) {
+    pub fn debug_assert_face_invariants(&self, face: FaceId<H>) {
         if !is_valid(face) {
             return;
         }
@@ -227,7 +334,7 @@ impl ConnectivityKernel {
     }
 
     /// Insert new_vertex on this edge.
-    pub fn split_edge(&mut self, id: EdgeId, new_vertex: VertexId) {
+    pub fn split_edge(&mut self, id: EdgeId<H>, new_vertex: VertexId<H>) {
         // from:
         //     a ---[id]-----------------------------------------> b
         //     a <----------------------------------[opposite]---- b
@@ -251,6 +358,7 @@ impl ConnectivityKernel {
         });
         // patch up existing edges
         self[id].next = new_edge;
+        self[edge.next].prev = new_edge;
 
         // new_opposite
         if is_valid(opposite_edge) {
@@ -263,7 +371,13 @@ impl ConnectivityKernel {
                 prev: opposite_edge,
             });
             self[opposite_edge].next = new_opposite;
+            self[opposite.next].prev = new_opposite;
+            self[opposite_edge].opposite = new_edge;
+            self[id].opposite = new_opposite;
+            self.debug_assert_face_invariants(opposite.face);
         }
+
+        self.debug_assert_face_invariants(edge.face);
     }
 
     /// Connect edges e1 and e2 such that e1->[new edge]->e2.
@@ -273,9 +387,9 @@ impl ConnectivityKernel {
     /// provided face will be used instead of creating a new one.
     pub fn connect_edges(
         &mut self,
-        e1: EdgeId,
-        e2: EdgeId,
-    ) -> Option<FaceId> {
+        e1: EdgeId<H>,
+        e2: EdgeId<H>,
+    ) -> Option<FaceId<H>> {
         //
         //   -e1--> v1 --e1_next->
         //          |^
@@ -325,7 +439,7 @@ impl ConnectivityKernel {
         let new_edge = self.add_edge(HalfEdge {
             next: e2,
             prev: e1,
-            opposite: NO_EDGE,
+            opposite: no_edge(),
             face: original_face,
             vertex: v1
         });
@@ -338,7 +452,6 @@ impl ConnectivityKernel {
         });
         self[new_edge].opposite = new_opposite_edge;
 
-        println!(" connect_edges({}, {}) -> {}", e1.handle, e2.handle, new_edge.handle);
 
         self[e1].next = new_edge;
         self[e2].prev = new_edge;
@@ -355,7 +468,6 @@ impl ConnectivityKernel {
                 let edge = &mut self[it];
                 edge.face = opposite_face;
                 it = edge.next;
-                println!(" -> new face loop {}", it.handle);
                 if it == new_opposite_edge { break; }
             }
             self.debug_assert_face_invariants(opposite_face);
@@ -372,9 +484,9 @@ impl ConnectivityKernel {
     /// provided face will be used instead of creating a new one.
     pub fn connect_edges2(
         &mut self,
-        e1: EdgeId,
-        e2: EdgeId,
-    ) -> Option<FaceId> {
+        e1: EdgeId<H>,
+        e2: EdgeId<H>,
+    ) -> Option<FaceId<H>> {
         //
         // -e1prev-> v1 --e1----->
         //           |^
@@ -424,7 +536,7 @@ impl ConnectivityKernel {
         let new_edge = self.add_edge(HalfEdge {
             next: e2,
             prev: e1_prev,
-            opposite: NO_EDGE,
+            opposite: no_edge(),
             face: original_face,
             vertex: v1
         });
@@ -437,7 +549,6 @@ impl ConnectivityKernel {
         });
         self[new_edge].opposite = new_opposite_edge;
 
-        println!(" connect_edges({}, {}) -> {}", e1.handle, e2.handle, new_edge.handle);
 
         self[e1].prev = new_opposite_edge;
         self[e2].prev = new_edge;
@@ -454,7 +565,6 @@ impl ConnectivityKernel {
                 let edge = &mut self[it];
                 edge.face = opposite_face;
                 it = edge.next;
-                println!(" -> new face loop {}", it.handle);
                 if it == new_opposite_edge { break; }
             }
             self.debug_assert_face_invariants(opposite_face);
@@ -465,28 +575,61 @@ impl ConnectivityKernel {
     }
 
     /// Insert a half edge in the kernel
-    pub fn add_empty_edge(&mut self) -> EdgeId {
+    pub fn add_empty_edge(&mut self) -> EdgeId<H> {
         self.add_edge(HalfEdge {
-            next: NO_EDGE,
-            prev: NO_EDGE,
-            opposite: NO_EDGE,
-            face: NO_FACE,
-            vertex: NO_VERTEX,
+            next: no_edge(),
+            prev: no_edge(),
+            opposite: no_edge(),
+            face: no_face(),
+            vertex: no_vertex(),
         })
     }
 
-    /// Insert a half-edge in the kernel.
-    fn add_edge(&mut self, data: HalfEdge) -> EdgeId {
+    /// Insert a half-edge in the kernel, along with the vertex it originates from if that vertex
+    /// isn't in the kernel yet.
+    ///
+    /// The edges it refers to are not modified.
+    pub fn add_edge(&mut self, data: HalfEdge<H>) -> EdgeId<H> {
+        check_capacity::<H>(self.edges.count(), "half edges");
         let id = self.edges.add(data);
-        println!("add edge {}", id.handle);
+        self.register_edge(id);
         return id;
     }
 
+    /// Insert a vertex that isn't connected to any edge in the kernel.
+    pub fn add_vertex(&mut self) -> VertexId<H> {
+        check_capacity::<H>(self.vertices.count(), "vertices");
+        return self.vertices.add(Vertex::default());
+    }
+
+    /// Remove a vertex that isn't connected to any edge, see `remove_vertex` for the others.
+    pub fn remove_isolated_vertex(&mut self, id: VertexId<H>) {
+        debug_assert!(!is_valid(self[id].first_edge));
+        self.vertices.remove(id);
+    }
+
+    // Make sure that the vertex an edge originates from is in the kernel and has a first edge.
+    fn register_edge(&mut self, id: EdgeId<H>) {
+        let vertex = self[id].vertex;
+        if !is_valid(vertex) {
+            return;
+        }
+        if vertex.to_index() < self.vertices.len() && !self.vertices.has_id(vertex) {
+            // The vertex was removed, take its slot back so that add_vertex doesn't reuse it.
+            self.vertices.revive(vertex, Vertex { first_edge: id });
+        } else if !self.vertices.has_id(vertex) {
+            self.vertices.set(vertex, Vertex { first_edge: id });
+        } else if !is_valid(self.vertices[vertex].first_edge) {
+            self.vertices[vertex].first_edge = id;
+        }
+    }
+
     /// Insert a Face in the kernel.
-    pub fn add_face(&mut self) -> FaceId { self.add_face_with_edge(NO_EDGE) }
+    pub fn add_face(&mut self) -> FaceId<H> { self.add_face_with_edge(no_edge()) }
 
     /// Insert a Face in the kernel.
-    pub fn add_face_with_edge(&mut self, first_edge: EdgeId) -> FaceId {
+    pub fn add_face_with_edge(&mut self, first_edge: EdgeId<H>) -> FaceId<H> {
+        check_capacity::<H>(self.faces.count(), "faces");
         return self.faces.add(Face{
             first_edge: first_edge,
             inner_edges: vec![],
@@ -497,7 +640,7 @@ impl ConnectivityKernel {
     /// half edges to the kernel.
     ///
     /// The original edge *must* have a next vertex
-    pub fn extrude_vertex(&mut self, edge: EdgeId, to: VertexId) -> EdgeId {
+    pub fn extrude_vertex(&mut self, edge: EdgeId<H>, to: VertexId<H>) -> EdgeId<H> {
         //              to
         //              ^|
         //    (new_edge)||new_opposite
@@ -512,13 +655,13 @@ impl ConnectivityKernel {
 
         let new_edge = if is_valid(edge_data.prev) {
             self.add_edge(HalfEdge {
-                next: NO_EDGE, // will be new_oppsite
+                next: no_edge(), // will be new_oppsite
                 prev: edge_data.prev,
-                opposite: NO_EDGE, // will be new_oppsite
+                opposite: no_edge(), // will be new_oppsite
                 face: edge_data.face,
                 vertex: v1,
             })
-        } else { NO_EDGE };
+        } else { no_edge() };
 
         let new_opposite = self.add_edge(HalfEdge {
             next: edge,
@@ -543,11 +686,11 @@ impl ConnectivityKernel {
     /// Connect two vertices.
     ///
     /// Only use this on isolated vertices.
-    pub fn add_segment(&mut self, v1: VertexId, v2: VertexId, face: FaceId) -> EdgeId {
+    pub fn add_segment(&mut self, v1: VertexId<H>, v2: VertexId<H>, face: FaceId<H>) -> EdgeId<H> {
         let e12 = self.add_edge(HalfEdge{
-            next: NO_EDGE,
-            prev: NO_EDGE,
-            opposite: NO_EDGE,
+            next: no_edge(),
+            prev: no_edge(),
+            opposite: no_edge(),
             vertex: v1,
             face: face,
         });
@@ -570,27 +713,25 @@ impl ConnectivityKernel {
     // Add a loop of edges, using existing vertices.
     pub fn add_loop(
         &mut self,
-        vertices: VertexIdRange,
-        inner_face: Option<FaceId>,
-        outer_face: Option<FaceId>
-    ) -> EdgeId {
-        println!(" ------ adding loop {} - {}", vertices.first.handle, vertices.count);
+        vertices: VertexIdRange<H>,
+        inner_face: Option<FaceId<H>>,
+        outer_face: Option<FaceId<H>>
+    ) -> EdgeId<H> {
         let add_inner_loop = inner_face.is_some();
         let add_outer_loop = outer_face.is_some();
         debug_assert!(add_inner_loop || add_outer_loop);
 
-        let num_vertices = vertices.count;
+        let num_vertices = vertices.len();
 
         debug_assert!(num_vertices > 1);
         let num_vert_i32 = num_vertices as i32;
 
-        let base_edge: Index = self.edges.len() as Index;
-        let base_vertex: Index = vertices.first.handle;
+        let base_edge = self.edges.len();
+        let base_vertex = vertices.first.to_index();
+        let edge = |index: usize| -> EdgeId<H> { EdgeId::from_index(index) };
 
-        println!("base edge {} base vertex {}", base_edge, base_vertex);
-
-        let first_inner_edge = edge_id(base_edge);
-        let first_outer_edge = edge_id(base_edge + if add_inner_loop { num_vertices } else { 0 });
+        let first_inner_edge = edge(base_edge);
+        let first_outer_edge = edge(base_edge + if add_inner_loop { num_vertices } else { 0 });
 
         if add_inner_loop {
             let face = inner_face.unwrap();
@@ -598,10 +739,10 @@ impl ConnectivityKernel {
             let mut i = 0;
             for vertex in vertices {
                 debug_assert!(is_valid(vertex));
-                let next_edge = edge_id(base_edge + modulo(i as i32 + 1, num_vert_i32) as Index);
-                let prev_edge = edge_id(base_edge + modulo(i as i32 - 1, num_vert_i32) as Index);
-                let opposite = if add_outer_loop { edge_id(base_edge + 2 * num_vertices - 1 - i) }
-                               else { NO_EDGE };
+                let next_edge = edge(base_edge + modulo(i as i32 + 1, num_vert_i32) as usize);
+                let prev_edge = edge(base_edge + modulo(i as i32 - 1, num_vert_i32) as usize);
+                let opposite = if add_outer_loop { edge(base_edge + 2 * num_vertices - 1 - i) }
+                               else { no_edge() };
                 let id = self.edges.push(HalfEdge {
                     vertex: vertex,
                     next: next_edge,
@@ -609,9 +750,8 @@ impl ConnectivityKernel {
                     opposite: opposite,
                     face: face,
                 });
-                    println!(" ------ adding edge id {:?} vertex {:?} next {:?} prev {:?} opp {:?}",
-                        id.handle, vertex.handle, next_edge.handle, prev_edge.handle, opposite.handle);
-                debug_assert_eq!(id, edge_id(base_edge + i));
+                self.register_edge(id);
+                debug_assert_eq!(id, edge(base_edge + i));
                 i += 1;
             }
             self[face].first_edge = first_inner_edge;
@@ -622,13 +762,13 @@ impl ConnectivityKernel {
             debug_assert!(is_valid(face));
             let base_edge_2 = if add_inner_loop { base_edge + num_vertices } else { base_edge } ;
             for i in 0..num_vert_i32 {
-                let next_edge = edge_id(base_edge_2 + modulo(i + 1, num_vert_i32) as Index);
-                let prev_edge = edge_id(base_edge_2 + modulo(i - 1, num_vert_i32) as Index);
-                let opposite = if add_inner_loop { edge_id(base_edge + (num_vertices - 1) - (i as Index)) }
-                               else { NO_EDGE };
-                let vertex = vertex_id(
+                let next_edge = edge(base_edge_2 + modulo(i + 1, num_vert_i32) as usize);
+                let prev_edge = edge(base_edge_2 + modulo(i - 1, num_vert_i32) as usize);
+                let opposite = if add_inner_loop { edge(base_edge + (num_vertices - 1) - (i as usize)) }
+                               else { no_edge() };
+                let vertex = VertexId::from_index(
                     base_vertex + (num_vertices - 1)
-                     - modulo(i - 1, num_vert_i32) as Index
+                     - modulo(i - 1, num_vert_i32) as usize
                 );
                 let id = self.edges.push(HalfEdge {
                     vertex: vertex,
@@ -637,14 +777,12 @@ impl ConnectivityKernel {
                     opposite: opposite,
                     face: face,
                 });
-                println!(" ------ adding opp edge id {:?} vertex {:?} next {:?} prev {:?} opp {:?}",
-                    id.handle, vertex.handle, next_edge.handle, prev_edge.handle, opposite.handle);
-                debug_assert_eq!(id, edge_id(base_edge_2 + i as Index));
+                self.register_edge(id);
+                debug_assert_eq!(id, edge(base_edge_2 + i as usize));
             }
             // If outer_face already has edges, we assume that the loop is a hole in f2
             let face_data = &mut self[face];
             if is_valid(face_data.first_edge) {
-                println!(" - add hole ");
                 face_data.inner_edges.push(first_outer_edge);
             } else {
                 face_data.first_edge = first_outer_edge;
@@ -656,34 +794,524 @@ impl ConnectivityKernel {
     }
 
     /// Add a loop of edges adn a face, creating a hole in an existing face.
-    pub fn add_hole(&mut self, outer_face: FaceId, vertices: VertexIdRange) -> FaceId {
+    pub fn add_hole(&mut self, outer_face: FaceId<H>, vertices: VertexIdRange<H>) -> FaceId<H> {
         let hole_face = self.add_face();
         let _ = self.add_loop(vertices, Some(hole_face), Some(outer_face));
         return hole_face;
     }
 
-    pub fn set_hole(&mut self, outer_face: FaceId, hole_interior_loop: EdgeId) {
+    pub fn set_hole(&mut self, outer_face: FaceId<H>, hole_interior_loop: EdgeId<H>) {
         let opp = self[hole_interior_loop].opposite;
         self[outer_face].inner_edges.push(opp);
     }
+
+    /// Remove an edge and its opposite, merging the faces on each side.
+    ///
+    /// The face of the removed edge is kept and returned, while the face of the opposite edge is
+    /// removed after its edges and holes are moved to the kept face. If both half edges belong
+    /// to the same loop, the loop is either shortened (when one of the vertices has no other
+    /// edge), or split in two, in which case the part that contains the face's first edge (or
+    /// else starts after the opposite edge) keeps the role of the original loop, and the other
+    /// part becomes a hole.
+    pub fn remove_edge(&mut self, id: EdgeId<H>) -> FaceId<H> {
+        //     e_prev                  o_next
+        //   ---------> a ---[id]---> b --------->
+        //   <--------- a <--[opp]--- b <---------
+        //     o_prev                  e_next
+        // to:
+        //   ---------> a             b --------->
+        //   <---------'               '<---------
+
+        let edge = self[id];
+        let opposite_id = edge.opposite;
+        debug_assert!(is_valid(opposite_id));
+        let opposite = self[opposite_id];
+        let face = edge.face;
+        let opposite_face = opposite.face;
+
+        let first_edge = self[face].first_edge;
+        let same_loop = self.loop_contains(id, opposite_id);
+        let mut references = vec![self.forget_loop(face, id)];
+        if !same_loop {
+            references.push(self.forget_loop(opposite_face, opposite_id));
+        }
+
+        let dangling_end = edge.next == opposite_id;
+        let dangling_start = opposite.next == id;
+        if !dangling_end {
+            self[opposite.prev].next = edge.next;
+            self[edge.next].prev = opposite.prev;
+        }
+        if !dangling_start {
+            self[edge.prev].next = opposite.next;
+            self[opposite.next].prev = edge.prev;
+        }
+
+        let mut new_loops = Vec::new();
+        if !dangling_start {
+            new_loops.push(opposite.next);
+        }
+        if !dangling_end && !(new_loops.len() == 1 && self.loop_contains(new_loops[0], edge.next)) {
+            new_loops.push(edge.next);
+        }
+        if new_loops.len() == 2 && first_edge != id && first_edge != opposite_id
+            && self.loop_contains(new_loops[1], first_edge) {
+            new_loops.swap(0, 1);
+        }
+        // Keep refering to the loops through the same edges when possible.
+        for edge_loop in &mut new_loops {
+            for &reference in &references {
+                if let Some(e) = reference {
+                    if e != id && e != opposite_id && self.loop_contains(*edge_loop, e) {
+                        *edge_loop = e;
+                    }
+                }
+            }
+        }
+
+        let mut outer = self[face].first_edge;
+        let mut holes = ::std::mem::replace(&mut self[face].inner_edges, Vec::new());
+        if opposite_face != face {
+            let other = self[opposite_face].clone();
+            if !is_valid(outer) {
+                outer = other.first_edge;
+            } else if is_valid(other.first_edge) {
+                holes.push(other.first_edge);
+            }
+            holes.extend(other.inner_edges);
+            self.faces.remove(opposite_face);
+        }
+        for (i, &edge_loop) in new_loops.iter().enumerate() {
+            if i == 0 && !is_valid(outer) {
+                outer = edge_loop;
+            } else {
+                holes.push(edge_loop);
+            }
+        }
+
+        if opposite_face != face {
+            if is_valid(outer) {
+                self.set_loop_face(outer, face);
+            }
+            for &hole in &holes {
+                self.set_loop_face(hole, face);
+            }
+        }
+
+        self[face].first_edge = outer;
+        self[face].inner_edges = holes;
+
+        self.edges.remove(id);
+        self.edges.remove(opposite_id);
+        self.fix_vertex_edge(edge.vertex, &[opposite.next]);
+        self.fix_vertex_edge(opposite.vertex, &[edge.next]);
+
+        self.debug_assert_face_invariants(face);
+
+        return face;
+    }
+
+    /// Collapse an edge, merging the vertex it points to into the vertex it originates from,
+    /// which is returned.
+    ///
+    /// Faces that are reduced to two edges are removed and the opposites of their edges are
+    /// connected together, unless they have holes. If one of the two edges is on the boundary,
+    /// the opposite of the other one becomes a boundary edge, and if both are, the face is kept.
+    /// The caller is responsible for checking that the collapse does not produce a non-manifold
+    /// configuration.
+    pub fn collapse_edge(&mut self, id: EdgeId<H>) -> VertexId<H> {
+        //       c                   c
+        //      ^ \                  |^
+        //     /   v                 ||
+        //    a-[id]->b     ->       v|
+        //    ^ \   /                 a
+        //     \ v /
+        //       d
+
+        let edge = self[id];
+        let opposite_id = edge.opposite;
+        debug_assert!(is_valid(opposite_id));
+        let opposite = self[opposite_id];
+        debug_assert!(edge.next != opposite_id && opposite.next != id);
+
+        let kept_vertex = edge.vertex;
+        let removed_vertex = opposite.vertex;
+        let moved_edges = self.edges_leaving_vertex(edge.next);
+        for &e in &moved_edges {
+            self[e].vertex = kept_vertex;
+        }
+
+        for &(e, e_data) in &[(id, edge), (opposite_id, opposite)] {
+            debug_assert!(self.walk_edge_ids(e).count() > 2);
+            self[e_data.prev].next = e_data.next;
+            self[e_data.next].prev = e_data.prev;
+            self.replace_loop_reference(e_data.face, e, e_data.next);
+        }
+
+        self.edges.remove(id);
+        self.edges.remove(opposite_id);
+
+        self.remove_degenerate_face(edge.next);
+        if self.contains_edge(opposite.next) {
+            self.remove_degenerate_face(opposite.next);
+        }
+
+        let mut candidates = moved_edges;
+        candidates.push(opposite.next);
+        self.fix_vertex_edge(kept_vertex, &candidates[..]);
+        if self.vertices.has_id(removed_vertex) {
+            self.vertices.remove(removed_vertex);
+        }
+
+        return kept_vertex;
+    }
+
+    /// Rotate the edge shared by two triangles so that it connects the two other vertices.
+    ///
+    /// Both faces must be triangles without holes.
+    pub fn flip_edge(&mut self, id: EdgeId<H>) {
+        //       c                c
+        //      ^ \              ^|^
+        //     /   v            / | \
+        //    a-[id]->b   ->   a  |  b
+        //     ^     /          \ v /
+        //      \   v            v d
+        //        d
+        //    (a, b, c) and (b, a, d) become (c, a, d) and (d, b, c).
+
+        let opposite_id = self[id].opposite;
+        debug_assert!(is_valid(opposite_id));
+        let e = self[id];
+        let o = self[opposite_id];
+        debug_assert!(self[e.next].next == e.prev && self[o.next].next == o.prev);
+        debug_assert!(self[e.face].inner_edges.is_empty() && self[o.face].inner_edges.is_empty());
+
+        let vertex_a = e.vertex;
+        let vertex_b = o.vertex;
+        let vertex_c = self[e.prev].vertex;
+        let vertex_d = self[o.prev].vertex;
+
+        self.link_triangle(e.face, e.prev, o.next, id);
+        self.link_triangle(o.face, o.prev, e.next, opposite_id);
+        self[id].vertex = vertex_d;
+        self[opposite_id].vertex = vertex_c;
+
+        self[e.face].first_edge = id;
+        self[o.face].first_edge = opposite_id;
+
+        self.fix_vertex_edge(vertex_a, &[o.next]);
+        self.fix_vertex_edge(vertex_b, &[e.next]);
+
+        self.debug_assert_face_invariants(e.face);
+        self.debug_assert_face_invariants(o.face);
+    }
+
+    /// Remove the vertex that an edge originates from, along with all of the edges connected to
+    /// it, merging the faces around the vertex into one, which is returned.
+    pub fn remove_vertex(&mut self, id: EdgeId<H>) -> FaceId<H> {
+        let vertex = self[id].vertex;
+        let mut face = self[id].face;
+        for e in self.edges_leaving_vertex(id) {
+            face = self.remove_edge(e);
+        }
+        self.vertices.remove(vertex);
+        return face;
+    }
+
+    /// Merge two adjacent faces by removing the edges they share, and return the remaining face.
+    pub fn merge_faces(&mut self, f1: FaceId<H>, f2: FaceId<H>) -> FaceId<H> {
+        debug_assert!(f1 != f2);
+        let mut shared_edges = Vec::new();
+        for edge_loop in self.face_loops(f1) {
+            for e in self.walk_edge_ids(edge_loop) {
+                let opposite = self[e].opposite;
+                if is_valid(opposite) && self[opposite].face == f2 {
+                    shared_edges.push(e);
+                }
+            }
+        }
+        debug_assert!(!shared_edges.is_empty());
+
+        for e in shared_edges {
+            self.remove_edge(e);
+        }
+        return f1;
+    }
+
+    /// Split a face by connecting the vertices that e1 and e2 originate from, and return the
+    /// new face.
+    ///
+    /// e1 and e2 must be on the same loop of the face and must not be next to each other. The
+    /// edges from e1 to e2 (excluded) are moved to the new face, the holes of the original face
+    /// are not.
+    pub fn split_face(&mut self, e1: EdgeId<H>, e2: EdgeId<H>) -> FaceId<H> {
+        //
+        // -e1_prev-> v1 --e1----->
+        //            |^
+        //            n|
+        //            ||   new_face
+        //            |o
+        //            v|
+        //    <--e2-- v2 <--e2_prev-
+        // ______________________
+        //
+        // n: new_edge
+        // o: new_opposite_edge
+
+        debug_assert!(self.loop_contains(e1, e2));
+        debug_assert!(self[e1].next != e2 && self[e2].next != e1);
+
+        let face = self[e1].face;
+        let role = self.loop_role(face, e1);
+        let e1_prev = self[e1].prev;
+        let e2_prev = self[e2].prev;
+
+        let new_face = self.add_face();
+        let new_edge = self.add_edge(HalfEdge {
+            next: e2,
+            prev: e1_prev,
+            opposite: no_edge(),
+            face: face,
+            vertex: self[e1].vertex,
+        });
+        let new_opposite_edge = self.add_edge(HalfEdge {
+            next: e1,
+            prev: e2_prev,
+            opposite: new_edge,
+            face: new_face,
+            vertex: self[e2].vertex,
+        });
+        self[new_edge].opposite = new_opposite_edge;
+
+        self[e1_prev].next = new_edge;
+        self[e2].prev = new_edge;
+        self[e2_prev].next = new_opposite_edge;
+        self[e1].prev = new_opposite_edge;
+
+        self.set_loop_face(new_opposite_edge, new_face);
+        self[new_face].first_edge = new_opposite_edge;
+        match role {
+            LoopRole::Outer => { self[face].first_edge = new_edge; }
+            LoopRole::Hole(i) => { self[face].inner_edges[i] = new_edge; }
+            LoopRole::Unreferenced => {}
+        }
+
+        self.debug_assert_face_invariants(face);
+        self.debug_assert_face_invariants(new_face);
+
+        return new_face;
+    }
+
+    /// Remove the free slots left by the removed vertices, edges and faces, so that the ids
+    /// are contiguous again, and return the tables that map the old ids to the new ones.
+    ///
+    /// The elements keep their relative order, which means that a kernel without removed
+    /// elements is left unchanged.
+    pub fn compact(&mut self) -> IdRemapping<H> {
+        let remap = IdRemapping {
+            vertices: self.vertices.compact(),
+            edges: self.edges.compact(),
+            faces: self.faces.compact(),
+        };
+
+        // Removed elements shouldn't be referenced, but if they are they become invalid ids.
+        let edge = |id: EdgeId<H>| {
+            if is_valid(id) { remap.edges[id].unwrap_or(no_edge()) } else { id }
+        };
+        let vertex = |id: VertexId<H>| {
+            if is_valid(id) { remap.vertices[id].unwrap_or(no_vertex()) } else { id }
+        };
+        let face = |id: FaceId<H>| {
+            if is_valid(id) { remap.faces[id].unwrap_or(no_face()) } else { id }
+        };
+        for e in self.edges.ids().collect::<Vec<_>>() {
+            let edge_data = &mut self.edges[e];
+            edge_data.next = edge(edge_data.next);
+            edge_data.prev = edge(edge_data.prev);
+            edge_data.opposite = edge(edge_data.opposite);
+            edge_data.vertex = vertex(edge_data.vertex);
+            edge_data.face = face(edge_data.face);
+        }
+        for v in self.vertices.ids().collect::<Vec<_>>() {
+            self.vertices[v].first_edge = edge(self.vertices[v].first_edge);
+        }
+        for f in self.faces.ids().collect::<Vec<_>>() {
+            let face = &mut self.faces[f];
+            face.first_edge = edge(face.first_edge);
+            for inner_edge in &mut face.inner_edges {
+                *inner_edge = edge(*inner_edge);
+            }
+        }
+
+        return remap;
+    }
+
+    /// The first edge of each loop of a face, starting with its outer boundary.
+    fn face_loops(&self, face: FaceId<H>) -> Vec<EdgeId<H>> {
+        let mut loops = Vec::new();
+        if is_valid(self[face].first_edge) {
+            loops.push(self[face].first_edge);
+        }
+        loops.extend_from_slice(&self[face].inner_edges[..]);
+        return loops;
+    }
+
+    fn loop_contains(&self, edge_loop: EdgeId<H>, id: EdgeId<H>) -> bool {
+        return self.walk_edge_ids(edge_loop).any(|e| e == id);
+    }
+
+    fn loop_role(&self, face: FaceId<H>, id: EdgeId<H>) -> LoopRole {
+        let first_edge = self[face].first_edge;
+        if is_valid(first_edge) && self.loop_contains(first_edge, id) {
+            return LoopRole::Outer;
+        }
+        for (i, &hole) in self[face].inner_edges.iter().enumerate() {
+            if self.loop_contains(hole, id) {
+                return LoopRole::Hole(i);
+            }
+        }
+        return LoopRole::Unreferenced;
+    }
+
+    // Remove the face's reference to the loop that contains an edge and return it.
+    fn forget_loop(&mut self, face: FaceId<H>, id: EdgeId<H>) -> Option<EdgeId<H>> {
+        return match self.loop_role(face, id) {
+            LoopRole::Outer => {
+                let first_edge = self[face].first_edge;
+                self[face].first_edge = no_edge();
+                Some(first_edge)
+            }
+            LoopRole::Hole(i) => Some(self[face].inner_edges.remove(i)),
+            LoopRole::Unreferenced => None,
+        };
+    }
+
+    // Make the face refer to a loop through another of its edges, if it refered to it
+    // through an edge that is about to be removed.
+    fn replace_loop_reference(&mut self, face: FaceId<H>, old: EdgeId<H>, new: EdgeId<H>) {
+        let face_data = &mut self[face];
+        if face_data.first_edge == old {
+            face_data.first_edge = new;
+        }
+        for hole in &mut face_data.inner_edges {
+            if *hole == old {
+                *hole = new;
+            }
+        }
+    }
+
+    fn set_loop_face(&mut self, edge_loop: EdgeId<H>, face: FaceId<H>) {
+        for edge in self.walk_edges_mut(edge_loop) {
+            edge.face = face;
+        }
+    }
+
+    fn link_triangle(&mut self, face: FaceId<H>, e1: EdgeId<H>, e2: EdgeId<H>, e3: EdgeId<H>) {
+        for &(e, next, prev) in &[(e1, e2, e3), (e2, e3, e1), (e3, e1, e2)] {
+            let edge = &mut self[e];
+            edge.next = next;
+            edge.prev = prev;
+            edge.face = face;
+        }
+    }
+
+    // Remove the face of an edge if it only has two edges, connecting their opposites together.
+    fn remove_degenerate_face(&mut self, id: EdgeId<H>) {
+        let next = self[id].next;
+        let face = self[id].face;
+        if self[next].next != id || !self[face].inner_edges.is_empty() {
+            return;
+        }
+        let opposite = self[id].opposite;
+        let next_opposite = self[next].opposite;
+        if (!is_valid(opposite) && !is_valid(next_opposite)) || opposite == next {
+            return;
+        }
+        //        id
+        //    .--------.
+        //   a          b
+        //    '--------'
+        //       next
+        // The surviving edges leaving a are next_opposite and the edge after opposite, and
+        // symmetrically for b.
+        let mut a_candidates = Vec::new();
+        let mut b_candidates = Vec::new();
+        if is_valid(opposite) {
+            self[opposite].opposite = next_opposite;
+            b_candidates.push(opposite);
+            a_candidates.push(self[opposite].next);
+        }
+        if is_valid(next_opposite) {
+            self[next_opposite].opposite = opposite;
+            a_candidates.push(next_opposite);
+            b_candidates.push(self[next_opposite].next);
+        }
+        let vertices = [self[id].vertex, self[next].vertex];
+        self.edges.remove(id);
+        self.edges.remove(next);
+        self.faces.remove(face);
+        self.fix_vertex_edge(vertices[0], &a_candidates[..]);
+        self.fix_vertex_edge(vertices[1], &b_candidates[..]);
+    }
+
+    // Make sure that the first edge of a vertex is still in the kernel and originates from it,
+    // picking one of the candidates otherwise.
+    fn fix_vertex_edge(&mut self, vertex: VertexId<H>, candidates: &[EdgeId<H>]) {
+        if !self.vertices.has_id(vertex) {
+            return;
+        }
+        let first_edge = self.vertices[vertex].first_edge;
+        if self.edges.has_id(first_edge) && self[first_edge].vertex == vertex {
+            return;
+        }
+        let mut new_first_edge = no_edge();
+        for &e in candidates {
+            if self.edges.has_id(e) && self[e].vertex == vertex {
+                new_first_edge = e;
+                break;
+            }
+        }
+        self.vertices[vertex].first_edge = new_first_edge;
+    }
+
+    // The edges that originate from the same vertex as a given edge, starting with it.
+    fn edges_leaving_vertex(&self, id: EdgeId<H>) -> Vec<EdgeId<H>> {
+        return EdgeIdsAroundVertex::new(self, id).collect();
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum LoopRole {
+    Outer,
+    Hole(usize),
+    Unreferenced,
+}
+
+impl<H: KernelIndex> ops::Index<EdgeId<H>> for ConnectivityKernel<H> {
+    type Output = HalfEdge<H>;
+    fn index<'l>(&'l self, id: EdgeId<H>) -> &'l HalfEdge<H> { &self.edges[id] }
 }
 
-impl ops::Index<EdgeId> for ConnectivityKernel {
-    type Output = HalfEdge;
-    fn index<'l>(&'l self, id: EdgeId) -> &'l HalfEdge { &self.edges[id] }
+impl<H: KernelIndex> ops::IndexMut<EdgeId<H>> for ConnectivityKernel<H> {
+    fn index_mut<'l>(&'l mut self, id: EdgeId<H>) -> &'l mut HalfEdge<H> { &mut self.edges[id] }
 }
 
-impl ops::IndexMut<EdgeId> for ConnectivityKernel {
-    fn index_mut<'l>(&'l mut self, id: EdgeId) -> &'l mut HalfEdge { &mut self.edges[id] }
+impl<H: KernelIndex> ops::Index<VertexId<H>> for ConnectivityKernel<H> {
+    type Output = Vertex<H>;
+    fn index<'l>(&'l self, id: VertexId<H>) -> &'l Vertex<H> { &self.vertices[id] }
 }
 
-impl ops::Index<FaceId> for ConnectivityKernel {
-    type Output = Face;
-    fn index<'l>(&'l self, id: FaceId) -> &'l Face { &self.faces[id] }
+impl<H: KernelIndex> ops::IndexMut<VertexId<H>> for ConnectivityKernel<H> {
+    fn index_mut<'l>(&'l mut self, id: VertexId<H>) -> &'l mut Vertex<H> { &mut self.vertices[id] }
 }
 
-impl ops::IndexMut<FaceId> for ConnectivityKernel {
-    fn index_mut<'l>(&'l mut self, id: FaceId) -> &'l mut Face { &mut self.faces[id] }
+impl<H: KernelIndex> ops::Index<FaceId<H>> for ConnectivityKernel<H> {
+    type Output = Face<H>;
+    fn index<'l>(&'l self, id: FaceId<H>) -> &'l Face<H> { &self.faces[id] }
+}
+
+impl<H: KernelIndex> ops::IndexMut<FaceId<H>> for ConnectivityKernel<H> {
+    fn index_mut<'l>(&'l mut self, id: FaceId<H>) -> &'l mut Face<H> { &mut self.faces[id] }
 }
 
 pub fn vertex_range(first: u16, count: u16) -> VertexIdRange {
@@ -976,3 +1604,441 @@ fn test_connect_2() {
         }
     }
 }
+
+#[cfg(test)]
+use half_edge::builder::PolygonBuilder;
+
+#[cfg(test)]
+fn assert_all_edge_invariants(kernel: &ConnectivityKernel) {
+    for e in kernel.edge_ids() {
+        kernel.debug_assert_edge_invariants(e);
+        assert!(kernel.contains_face(kernel[e].face));
+    }
+    assert_eq!(kernel.validate(), vec![]);
+}
+
+#[test]
+fn test_split_and_merge_faces() {
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let f1 = kernel.first_face().unwrap();
+    let e1 = kernel[f1].first_edge;
+    let e3 = kernel[kernel[e1].next].next;
+
+    let f2 = kernel.split_face(e1, e3);
+    assert_all_edge_invariants(&kernel);
+    assert_eq!(kernel.walk_edge_ids_around_face(f1).count(), 3);
+    assert_eq!(kernel.walk_edge_ids_around_face(f2).count(), 3);
+    assert_eq!(kernel[e1].face, f2);
+    assert_eq!(kernel[e3].face, f1);
+
+    assert_eq!(kernel.merge_faces(f1, f2), f1);
+    assert_all_edge_invariants(&kernel);
+    assert!(!kernel.contains_face(f2));
+    assert_eq!(kernel.walk_edge_ids_around_face(f1).count(), 4);
+    assert_eq!(kernel[e1].face, f1);
+    assert_eq!(kernel.num_edges(), 8);
+}
+
+#[test]
+fn test_remove_bridge_edge() {
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let f1 = kernel.first_face().unwrap();
+    kernel.add_hole(f1, vertex_range(4, 3));
+    let outer = kernel[f1].first_edge;
+    let hole = kernel[f1].inner_edges[0];
+
+    // Connecting the outer loop to the hole merges them into one loop.
+    assert!(kernel.connect_edges(outer, hole).is_none());
+    assert!(kernel[f1].inner_edges.is_empty());
+    let bridge = kernel[outer].next;
+    assert_eq!(kernel.walk_edge_ids_around_face(f1).count(), 9);
+
+    // Removing the bridge splits the loop back, the part that doesn't contain the face's
+    // first edge becomes a hole again.
+    kernel[f1].first_edge = outer;
+    assert_eq!(kernel.remove_edge(bridge), f1);
+    assert_all_edge_invariants(&kernel);
+    assert_eq!(kernel[f1].first_edge, outer);
+    assert_eq!(kernel[f1].inner_edges.len(), 1);
+    assert_eq!(kernel.walk_edge_ids_around_face(f1).count(), 4);
+    assert_eq!(kernel.walk_edge_ids(kernel[f1].inner_edges[0]).count(), 3);
+    assert!(kernel.loop_contains(kernel[f1].inner_edges[0], hole));
+}
+
+#[test]
+fn test_remove_dangling_edges() {
+    let mut kernel = ConnectivityKernel::new();
+    let f1 = kernel.add_face();
+    let e1 = kernel.add_segment(vertex_id(0), vertex_id(1), f1);
+    let o1 = kernel[e1].opposite;
+    let e2 = kernel.extrude_vertex(o1, vertex_id(2));
+    kernel[f1].first_edge = e1;
+
+    kernel.remove_edge(e2);
+    assert_all_edge_invariants(&kernel);
+    assert!(!kernel.contains_edge(e2));
+    assert_eq!(kernel.walk_edge_ids(e1).count(), 2);
+    assert_eq!(kernel[f1].first_edge, e1);
+
+    kernel.remove_edge(e1);
+    assert!(kernel.first_edge().is_none());
+    assert_eq!(kernel[f1].first_edge, NO_EDGE);
+}
+
+#[test]
+fn test_split_edge() {
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 3));
+    let face = kernel.first_face().unwrap();
+    let e = kernel[face].first_edge;
+    let opposite = kernel[e].opposite;
+    let (a, b) = (kernel[e].vertex, kernel[opposite].vertex);
+
+    kernel.split_edge(e, vertex_id(3));
+    assert_all_edge_invariants(&kernel);
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.walk_edge_ids_around_face(face).count(), 4);
+    assert_eq!(kernel.walk_edge_ids_around_face(kernel[opposite].face).count(), 4);
+    let new_edge = kernel[e].next;
+    assert_eq!(kernel[new_edge].vertex, vertex_id(3));
+    assert_eq!(kernel[kernel[new_edge].next].vertex, b);
+    assert_eq!(kernel[new_edge].opposite, opposite);
+    assert_eq!(kernel[kernel[e].opposite].vertex, vertex_id(3));
+    assert_eq!(kernel[kernel[kernel[e].opposite].next].vertex, a);
+    assert_eq!(kernel.walk_edge_ids_around_vertex(vertex_id(3)).count(), 2);
+}
+
+#[test]
+fn test_flip_edge() {
+    //  3 <----- 2
+    //  |      ^ ^
+    //  |    /   |
+    //  v  /     |
+    //  0 -----> 1
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let f1 = kernel.first_face().unwrap();
+    let e0 = kernel[f1].first_edge;
+    assert_eq!(kernel[e0].vertex, vertex_id(0));
+    let e2 = kernel[kernel[e0].next].next;
+    let f2 = kernel.split_face(e0, e2);
+    let diagonal = kernel[f1].first_edge;
+    assert_eq!(kernel[diagonal].vertex, vertex_id(0));
+    assert_eq!(kernel[kernel[diagonal].opposite].vertex, vertex_id(2));
+
+    kernel.flip_edge(diagonal);
+    assert_all_edge_invariants(&kernel);
+    let flipped = [kernel[diagonal].vertex, kernel[kernel[diagonal].opposite].vertex];
+    assert!(flipped == [vertex_id(1), vertex_id(3)] || flipped == [vertex_id(3), vertex_id(1)]);
+    for &face in &[f1, f2] {
+        assert_eq!(kernel.walk_edge_ids_around_face(face).count(), 3);
+        let vertices: Vec<VertexId> = kernel.walk_edge_ids_around_face(face).map(|e| kernel[e].vertex).collect();
+        assert!(vertices.contains(&vertex_id(1)) && vertices.contains(&vertex_id(3)));
+    }
+
+    kernel.flip_edge(diagonal);
+    assert_all_edge_invariants(&kernel);
+    let flipped = [kernel[diagonal].vertex, kernel[kernel[diagonal].opposite].vertex];
+    assert!(flipped == [vertex_id(0), vertex_id(2)] || flipped == [vertex_id(2), vertex_id(0)]);
+}
+
+#[test]
+fn test_collapse_edge() {
+    // A square split in two triangles by a diagonal.
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let f1 = kernel.first_face().unwrap();
+    let e0 = kernel[f1].first_edge;
+    let e1 = kernel[e0].next;
+    let e2 = kernel[e1].next;
+    let f2 = kernel.split_face(e0, e2);
+    let back_face = kernel[kernel[e2].opposite].face;
+    let v0 = kernel[e0].vertex;
+    let v1 = kernel[e1].vertex;
+
+    // Collapse v1 into v0: the triangle (v0, v1, v2) disappears.
+    assert_eq!(kernel.collapse_edge(e0), v0);
+    assert_all_edge_invariants(&kernel);
+    assert!(!kernel.contains_vertex(v1));
+    assert_eq!(kernel.num_vertices(), 3);
+    assert_eq!(kernel.walk_edge_ids_around_vertex(v0).count(), 2);
+    assert!(!kernel.contains_face(f2));
+    assert!(!kernel.contains_edge(e0));
+    assert!(!kernel.contains_edge(e1));
+    assert_eq!(kernel.walk_edge_ids_around_face(f1).count(), 3);
+    assert_eq!(kernel.walk_edge_ids_around_face(back_face).count(), 3);
+    for e in kernel.walk_edge_ids_around_face(back_face) {
+        assert!(kernel[e].vertex != v1);
+    }
+    assert_eq!(kernel.num_edges(), 6);
+}
+
+#[test]
+fn test_collapse_edge_next_to_boundary() {
+    // A fan of four triangles around vertex 4, with boundary edges on the outside.
+    let mut builder = PolygonBuilder::new();
+    for i in 0..4 {
+        builder.add_polygon(&[vertex_id(i), vertex_id((i + 1) % 4), vertex_id(4)]).unwrap();
+    }
+    let mut kernel = builder.finish();
+    let e = kernel.edge_ids().find(|&e| {
+        kernel[e].vertex == vertex_id(0) && kernel[kernel[e].next].vertex == vertex_id(4)
+    }).unwrap();
+
+    // The two triangles on each side of the edge are reduced to a boundary edge and an
+    // interior edge.
+    assert_eq!(kernel.collapse_edge(kernel[e].opposite), vertex_id(4));
+    assert_all_edge_invariants(&kernel);
+    assert_eq!(kernel.num_faces(), 2);
+    assert_eq!(kernel.num_edges(), 6);
+    assert!(!kernel.contains_vertex(vertex_id(0)));
+    let boundary = kernel.edge_ids().filter(|&e| !is_valid(kernel[e].opposite)).count();
+    assert_eq!(boundary, 4);
+    assert_eq!(kernel.walk_face_ids_around_vertex(vertex_id(4)).count(), 2);
+}
+
+#[test]
+fn test_remove_vertex() {
+    // A fan of four triangles around vertex 4.
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let f1 = kernel.first_face().unwrap();
+    let e0 = kernel[f1].first_edge;
+    let center = kernel.extrude_vertex(e0, vertex_id(4));
+    let mut faces = vec![f1];
+    let mut center_edge = center;
+    let mut target = kernel[e0].next;
+    for _ in 0..3 {
+        faces.push(kernel.split_face(center_edge, target));
+        center_edge = kernel[target].prev;
+        target = kernel[target].next;
+    }
+    assert_all_edge_invariants(&kernel);
+    for &face in &faces {
+        assert_eq!(kernel.walk_edge_ids_around_face(face).count(), 3);
+    }
+
+    assert_eq!(kernel.walk_edge_ids_around_vertex(vertex_id(4)).count(), 4);
+
+    let face = kernel.remove_vertex(center);
+    assert_all_edge_invariants(&kernel);
+    assert!(!kernel.contains_vertex(vertex_id(4)));
+    assert_eq!(kernel.walk_edge_ids_around_face(face).count(), 4);
+    for e in kernel.walk_edge_ids_around_face(face) {
+        assert!(kernel[e].vertex != vertex_id(4));
+    }
+    assert_eq!(faces.iter().filter(|&&f| kernel.contains_face(f)).count(), 1);
+    assert_eq!(kernel.num_edges(), 8);
+}
+
+#[test]
+fn test_vertex_one_ring() {
+    // A fan of four triangles around vertex 4, with a back face.
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let f1 = kernel.first_face().unwrap();
+    let e0 = kernel[f1].first_edge;
+    let center = kernel.extrude_vertex(e0, vertex_id(4));
+    let mut center_edge = center;
+    let mut target = kernel[e0].next;
+    for _ in 0..3 {
+        kernel.split_face(center_edge, target);
+        center_edge = kernel[target].prev;
+        target = kernel[target].next;
+    }
+    assert_eq!(kernel.num_vertices(), 5);
+    assert_eq!(kernel[vertex_id(4)].first_edge, center);
+
+    let mut edges: Vec<EdgeId> = kernel.walk_edge_ids_around_vertex(vertex_id(4)).collect();
+    assert_eq!(edges.len(), 4);
+    for &e in &edges {
+        assert_eq!(kernel[e].vertex, vertex_id(4));
+    }
+    edges.sort_by_key(|e| e.handle);
+    edges.dedup();
+    assert_eq!(edges.len(), 4);
+
+    let mut neighbours: Vec<VertexId> = kernel.walk_vertex_ids_around_vertex(vertex_id(4)).collect();
+    neighbours.sort_by_key(|v| v.handle);
+    assert_eq!(neighbours, vec![vertex_id(0), vertex_id(1), vertex_id(2), vertex_id(3)]);
+
+    let mut faces: Vec<FaceId> = kernel.walk_face_ids_around_vertex(vertex_id(4)).collect();
+    faces.sort_by_key(|f| f.handle);
+    faces.dedup();
+    assert_eq!(faces.len(), 4);
+    assert!(!faces.contains(&kernel[kernel[e0].opposite].face));
+
+    // Corners are on the back face and on one or two of the triangles.
+    for i in 0..4 {
+        let v = vertex_id(i);
+        let ring: Vec<VertexId> = kernel.walk_vertex_ids_around_vertex(v).collect();
+        assert!(ring.contains(&vertex_id(4)));
+        assert_eq!(ring.len(), kernel.walk_face_ids_around_vertex(v).count());
+    }
+    assert_eq!(kernel.walk_vertex_ids_around_vertex(vertex_id(0)).count(), 3);
+}
+
+#[test]
+fn test_vertex_one_ring_on_boundary() {
+    // A single-sided square split by a diagonal, its outer edges don't have opposites.
+    let mut kernel = ConnectivityKernel::new();
+    let face = kernel.add_face();
+    let e0 = kernel.add_loop(vertex_range(0, 4), Some(face), None);
+    let e2 = kernel[kernel[e0].next].next;
+    kernel.split_face(e0, e2);
+    assert_eq!(kernel.validate(), vec![]);
+
+    let v0 = kernel[e0].vertex;
+    let outgoing: Vec<EdgeId> = kernel.walk_edge_ids_around_vertex(v0).collect();
+    assert_eq!(outgoing.len(), 2);
+    // The whole ring is found whichever edge the iteration starts from.
+    for &first in &outgoing {
+        let mut ring: Vec<EdgeId> = EdgeIdsAroundVertex::new(&kernel, first).collect();
+        assert_eq!(ring[0], first);
+        ring.sort_by_key(|e| e.handle);
+        let mut expected = outgoing.clone();
+        expected.sort_by_key(|e| e.handle);
+        assert_eq!(ring, expected);
+    }
+    assert_eq!(kernel.walk_face_ids_around_vertex(v0).count(), 2);
+
+    let v1 = kernel[kernel[e0].next].vertex;
+    assert_eq!(kernel.walk_edge_ids_around_vertex(v1).count(), 1);
+    assert_eq!(kernel.walk_vertex_ids_around_vertex(v1).count(), 1);
+}
+
+#[test]
+fn test_compact() {
+    // A square split by a diagonal, with the edge between vertex 0 and vertex 1 collapsed.
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let f1 = kernel.first_face().unwrap();
+    let e0 = kernel[f1].first_edge;
+    let e2 = kernel[kernel[e0].next].next;
+    kernel.split_face(e0, e2);
+    let removed_vertex = kernel[kernel[e0].next].vertex;
+    let kept_vertex = kernel.collapse_edge(e0);
+    let num_edges = kernel.num_edges();
+    let kept_edges: Vec<EdgeId> = kernel.edge_ids().collect();
+    let kept_edge_vertices: Vec<VertexId> = kept_edges.iter().map(|&e| kernel[e].vertex).collect();
+
+    let remap = kernel.compact();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_all_edge_invariants(&kernel);
+    assert_eq!(kernel.num_vertices(), 3);
+    assert_eq!(kernel.num_edges(), num_edges);
+    assert_eq!(kernel.num_faces(), 2);
+    assert_eq!(kernel.vertex_ids().collect::<Vec<_>>(), vertex_range(0, 3).collect::<Vec<_>>());
+    let edges: Vec<EdgeId> = (0..num_edges as u16).map(edge_id).collect();
+    assert_eq!(kernel.edge_ids().collect::<Vec<_>>(), edges);
+    assert_eq!(remap.vertices[kept_vertex], Some(kept_vertex));
+    assert_eq!(remap.vertices[removed_vertex], None);
+    assert_eq!(remap.edges[e0], None);
+    for (&e, &v) in kept_edges.iter().zip(kept_edge_vertices.iter()) {
+        let new_vertex = remap.vertices[v].unwrap();
+        assert_eq!(kernel[remap.edges[e].unwrap()].vertex, new_vertex);
+    }
+
+    let attributes: IdVec<VertexId, u16> = IdVec::from_vec(vec![0, 1, 2, 3]);
+    let attributes = remap_attributes(attributes, &remap.vertices);
+    let mut expected = vec![0, 1, 2, 3];
+    expected.retain(|&i| vertex_id(i) != removed_vertex);
+    assert_eq!(attributes.into_vec(), expected);
+
+    // Compacting again doesn't change anything.
+    let remap = kernel.compact();
+    for v in kernel.vertex_ids() {
+        assert_eq!(remap.vertices[v], Some(v));
+    }
+}
+
+#[test]
+fn test_u32_handles() {
+    // A grid of quads with more half edges than u16 handles can count.
+    let (w, h) = (150u32, 120u32);
+    let mut builder: PolygonBuilder<u32> = PolygonBuilder::new();
+    for y in 0..h {
+        for x in 0..w {
+            let v = |x: u32, y: u32| VertexId::new(y * (w + 1) + x);
+            builder.add_polygon(&[v(x, y), v(x + 1, y), v(x + 1, y + 1), v(x, y + 1)]).unwrap();
+        }
+    }
+    let mut kernel = builder.finish();
+    assert_eq!(kernel.num_edges(), (w * h * 4) as usize);
+    assert!(kernel.num_edges() > u16::MAX as usize);
+    assert_eq!(kernel.validate(), vec![]);
+
+    let last_face = FaceId::new(w * h - 1);
+    let e = kernel[last_face].first_edge;
+    assert_eq!(kernel.walk_edge_ids_around_face(last_face).count(), 4);
+    assert_eq!(kernel.walk_edge_ids_around_vertex(kernel[e].vertex).count(), 4);
+
+    let other_face = kernel[kernel[e].opposite].face;
+    let face = kernel.remove_edge(e);
+    let removed_face = if face == last_face { other_face } else { last_face };
+    assert!(!kernel.contains_face(removed_face));
+    let remap = kernel.compact();
+    assert_eq!(remap.faces[removed_face], None);
+    assert_eq!(kernel.num_faces(), (w * h - 1) as usize);
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.walk_edge_ids_around_face(remap.faces[face].unwrap()).count(), 6);
+}
+
+#[test]
+fn test_compact_empty_edge() {
+    let mut kernel: ConnectivityKernel = ConnectivityKernel::new();
+    let v0 = kernel.add_vertex();
+    let v1 = kernel.add_vertex();
+    let e0 = kernel.add_empty_edge();
+    let e1 = kernel.add_empty_edge();
+    kernel[e1].vertex = v1;
+    kernel.remove_isolated_vertex(v0);
+
+    let remap = kernel.compact();
+    assert_eq!(remap.vertices[v1], Some(vertex_id(0)));
+    let e0 = remap.edges[e0].unwrap();
+    assert_eq!(kernel[e0].vertex, no_vertex());
+    assert_eq!(kernel[e0].face, no_face());
+    assert_eq!(kernel[e0].next, no_edge());
+    assert_eq!(kernel[remap.edges[e1].unwrap()].vertex, vertex_id(0));
+}
+
+#[test]
+#[should_panic(expected = "can't hold more than 65535 half edges")]
+fn test_capacity_overflow() {
+    let mut kernel: ConnectivityKernel<u16> = ConnectivityKernel::new();
+    for _ in 0..70000 {
+        kernel.add_empty_edge();
+    }
+}
+
+#[test]
+fn test_capacity_reuses_removed_slots() {
+    let mut kernel: ConnectivityKernel<u16> = ConnectivityKernel::new();
+    for _ in 0..u16::MAX {
+        kernel.add_vertex();
+    }
+    let v = vertex_id(100);
+    kernel.remove_isolated_vertex(v);
+    assert_eq!(kernel.add_vertex(), v);
+    assert_eq!(kernel.num_vertices(), u16::MAX as usize);
+}
+
+#[test]
+fn test_add_edge_to_removed_vertex() {
+    let mut kernel: ConnectivityKernel = ConnectivityKernel::new();
+    let a = kernel.add_vertex();
+    let b = kernel.add_vertex();
+    kernel.remove_isolated_vertex(a);
+
+    // An edge that originates from the removed vertex brings it back.
+    let e = kernel.add_edge(HalfEdge {
+        next: no_edge(),
+        prev: no_edge(),
+        opposite: no_edge(),
+        vertex: a,
+        face: no_face(),
+    });
+    assert_eq!(kernel[a].first_edge, e);
+    assert_eq!(kernel.num_vertices(), 2);
+
+    // Its slot isn't reused by the next vertex.
+    let c = kernel.add_vertex();
+    assert!(c != a && c != b);
+    assert_eq!(kernel.num_vertices(), 3);
+    assert_eq!(kernel.vertex_ids().count(), 3);
+}
@@ -1,12 +1,17 @@
 
+pub mod builder;
 pub mod id_internals;
 pub mod iterators;
 pub mod kernel;
+pub mod triangulation;
+pub mod validation;
 
 pub use half_edge::kernel::{
-    ConnectivityKernel,
+    ConnectivityKernel, IdRemapping, KernelIndex,
     EdgeId, VertexId, FaceId,
     EdgeIdRange, VertexIdRange, FaceIdRange,
     edge_id, vertex_id, face_id
 };
 pub use half_edge::iterators::*;
+pub use half_edge::validation::Violation;
+pub use half_edge::builder::{ PolygonBuilder, PolygonError };
@@ -0,0 +1,434 @@
+//! Triangulation of the faces of a kernel.
+//!
+//! Faces are triangulated in place by ear clipping: each hole is first connected to the outer
+//! loop with a bridge edge, and triangles are then cut off the resulting loop one at a time.
+//! All of the edges are inserted with `connect_edges`, so the result is a regular kernel in
+//! which every triangle is a face.
+
+use half_edge::kernel::{ ConnectivityKernel, EdgeId, FaceId, VertexId };
+use half_edge::id_internals::{ is_valid, KernelIndex };
+
+use vodk_math::Vec2;
+
+#[cfg(test)]
+use half_edge::kernel::vertex_range;
+
+impl<H: KernelIndex> ConnectivityKernel<H> {
+    /// Split a face into triangles, using the positions of its vertices to choose diagonals.
+    ///
+    /// The face can be concave and have holes. It keeps one of the triangles and the faces that
+    /// are added for the other ones are pushed to `new_faces`. Loops can be wound either way as
+    /// long as the holes are wound consistently with the outer loop, which is the case when the
+    /// vertices given to `from_loop` and `add_hole` are in the same order.
+    ///
+    /// Fails if the face doesn't have an outer loop of at least three edges, if one of its
+    /// vertices has a position that isn't finite, or if its loops intersect, in which case the
+    /// face can be left partially triangulated.
+    pub fn triangulate_face<P: Fn(VertexId<H>) -> Vec2>(
+        &mut self,
+        face: FaceId<H>,
+        positions: &P,
+        new_faces: &mut Vec<FaceId<H>>,
+    ) -> Result<(), ()> {
+        let first_edge = self[face].first_edge;
+        if !is_valid(first_edge) || self.walk_edge_ids(first_edge).count() < 3 {
+            return Err(());
+        }
+
+        // Non-finite positions can't be ordered, which the hole and bridge sorts below rely on.
+        let finite = Some(first_edge).into_iter()
+            .chain(self[face].inner_edges.iter().cloned())
+            .all(|first| self.walk_edge_ids(first).all(|e| {
+                let p = positions(self[e].vertex);
+                p.x.is_finite() && p.y.is_finite()
+            }));
+        if !finite {
+            return Err(());
+        }
+
+        let orientation = if self.loop_area(first_edge, positions) < 0.0 { -1.0 } else { 1.0 };
+
+        // Bridge the holes starting with the rightmost ones, so that the bridges of the holes
+        // to the left can't be blocked by the holes that are still to be bridged.
+        let mut holes: Vec<(Vec2, EdgeId<H>)> = self[face].inner_edges.iter().map(|&hole| {
+            let mut rightmost = (positions(self[hole].vertex), hole);
+            for e in self.walk_edge_ids(hole) {
+                let p = positions(self[e].vertex);
+                if p.x > rightmost.0.x || (p.x == rightmost.0.x && p.y < rightmost.0.y) {
+                    rightmost = (p, e);
+                }
+            }
+            rightmost
+        }).collect();
+        holes.sort_by(|a, b| { b.0.x.partial_cmp(&a.0.x).unwrap() });
+
+        for &(_, hole_edge) in &holes {
+            let corner = try!(self.find_bridge(face, hole_edge, positions, orientation));
+            let prev = self[corner].prev;
+            self.connect_edges(prev, hole_edge);
+        }
+
+        // The corners that are not strictly convex are the only vertices that can be inside of
+        // an ear, so they are tracked separately instead of testing the whole loop for each
+        // candidate. Clipping an ear can only make the corners next to it more convex.
+        let first_edge = self[face].first_edge;
+        let mut len = self.walk_edge_ids(first_edge).count();
+        let mut non_convex: Vec<EdgeId<H>> = self.walk_edge_ids(first_edge).filter(|&e| {
+            self.corner_turn(e, positions, orientation) <= 0.0
+        }).collect();
+
+        let mut e = first_edge;
+        let mut attempts = 0;
+        let mut flat_corner = None;
+        while len > 3 {
+            let turn = self.corner_turn(e, positions, orientation);
+            let is_ear = turn > 0.0
+                && !self.ear_contains_vertex(e, &non_convex, positions, orientation);
+            if !is_ear {
+                if turn == 0.0 && flat_corner.is_none() {
+                    flat_corner = Some(e);
+                }
+                attempts += 1;
+                if attempts < len {
+                    e = self[e].next;
+                    continue;
+                }
+                // Flat corners are only clipped when there is no proper ear, to get rid of
+                // collinear vertices.
+                e = match flat_corner {
+                    Some(flat) => flat,
+                    None => { return Err(()); }
+                };
+            }
+
+            //   a ---prev---> p ---ear---> b
+            //    ^                         |
+            //     '-------[diagonal]------'
+            let prev = self[e].prev;
+            let next = self[e].next;
+            let before = self[prev].prev;
+            match self.connect_edges(before, next) {
+                Some(triangle) => { new_faces.push(triangle); }
+                None => { return Err(()); }
+            }
+            len -= 1;
+
+            let diagonal = self[next].prev;
+            non_convex.retain(|&c| c != prev && c != e && c != next);
+            for &corner in &[diagonal, next] {
+                if self.corner_turn(corner, positions, orientation) <= 0.0 {
+                    non_convex.push(corner);
+                }
+            }
+
+            e = diagonal;
+            attempts = 0;
+            flat_corner = None;
+        }
+
+        return Ok(());
+    }
+
+    // Twice the signed area of a loop.
+    fn loop_area<P: Fn(VertexId<H>) -> Vec2>(&self, first_edge: EdgeId<H>, positions: &P) -> f32 {
+        let mut area = 0.0;
+        for e in self.walk_edge_ids(first_edge) {
+            let p1 = positions(self[e].vertex);
+            let p2 = positions(self[self[e].next].vertex);
+            area += p1.cross(p2);
+        }
+        return area;
+    }
+
+    // Find a corner of the outer loop of a face that can be connected to the vertex a hole
+    // edge originates from without crossing any of the face's loops.
+    fn find_bridge<P: Fn(VertexId<H>) -> Vec2>(
+        &self,
+        face: FaceId<H>,
+        hole_edge: EdgeId<H>,
+        positions: &P,
+        orientation: f32,
+    ) -> Result<EdgeId<H>, ()> {
+        let hole_vertex = self[hole_edge].vertex;
+        let m = positions(hole_vertex);
+
+        let mut candidates: Vec<(f32, EdgeId<H>)> = self.walk_edge_ids(self[face].first_edge).map(|e| {
+            let d = positions(self[e].vertex) - m;
+            (d.dot(&d), e)
+        }).collect();
+        candidates.sort_by(|a, b| { a.0.partial_cmp(&b.0).unwrap() });
+
+        let mut loops = vec![self[face].first_edge];
+        loops.extend(self[face].inner_edges.iter().cloned());
+
+        for &(_, corner) in &candidates {
+            let vertex = self[corner].vertex;
+            let p = positions(vertex);
+            if !self.in_cone(corner, m, positions, orientation)
+                || !self.in_cone(hole_edge, p, positions, orientation) {
+                continue;
+            }
+            let blocked = loops.iter().any(|&first| {
+                self.walk_edge_ids(first).any(|e| {
+                    self.blocks_segment(e, (vertex, p), (hole_vertex, m), positions)
+                })
+            });
+            if !blocked {
+                return Ok(corner);
+            }
+        }
+
+        return Err(());
+    }
+
+    // How much the loop turns towards the inside of its face at the vertex an edge originates
+    // from: positive for convex corners, negative for reflex ones and zero for flat ones.
+    fn corner_turn<P: Fn(VertexId<H>) -> Vec2>(
+        &self,
+        edge: EdgeId<H>,
+        positions: &P,
+        orientation: f32,
+    ) -> f32 {
+        let a = positions(self[self[edge].prev].vertex);
+        let p = positions(self[edge].vertex);
+        let b = positions(self[self[edge].next].vertex);
+        return orient(a, p, b) * orientation;
+    }
+
+    // Whether the triangle formed by an edge and its previous edge contains, or has on its
+    // boundary, the vertex of one of the given corners.
+    fn ear_contains_vertex<P: Fn(VertexId<H>) -> Vec2>(
+        &self,
+        edge: EdgeId<H>,
+        corners: &[EdgeId<H>],
+        positions: &P,
+        orientation: f32,
+    ) -> bool {
+        let va = self[self[edge].prev].vertex;
+        let vp = self[edge].vertex;
+        let vb = self[self[edge].next].vertex;
+        let (a, p, b) = (positions(va), positions(vp), positions(vb));
+        return corners.iter().any(|&other| {
+            let v = self[other].vertex;
+            if v == va || v == vp || v == vb {
+                return false;
+            }
+            let q = positions(v);
+            orient(a, p, q) * orientation >= 0.0
+                && orient(p, b, q) * orientation >= 0.0
+                && orient(b, a, q) * orientation >= 0.0
+        });
+    }
+
+    // Whether a direction from the vertex an edge originates from points to the inside of its
+    // face, that is to the left of both the edge and its previous edge.
+    fn in_cone<P: Fn(VertexId<H>) -> Vec2>(
+        &self,
+        edge: EdgeId<H>,
+        target: Vec2,
+        positions: &P,
+        orientation: f32,
+    ) -> bool {
+        let a = positions(self[self[edge].prev].vertex);
+        let p = positions(self[edge].vertex);
+        let b = positions(self[self[edge].next].vertex);
+        let after_prev = orient(a, p, target) * orientation > 0.0;
+        let after_edge = orient(p, b, target) * orientation > 0.0;
+        if orient(a, p, b) * orientation >= 0.0 {
+            return after_prev && after_edge;
+        }
+        return after_prev || after_edge;
+    }
+
+    // Whether an edge crosses a segment or passes through its interior, ignoring the edges
+    // that share one of the segment's vertices.
+    fn blocks_segment<P: Fn(VertexId<H>) -> Vec2>(
+        &self,
+        edge: EdgeId<H>,
+        from: (VertexId<H>, Vec2),
+        to: (VertexId<H>, Vec2),
+        positions: &P,
+    ) -> bool {
+        let v1 = self[edge].vertex;
+        let v2 = self[self[edge].next].vertex;
+        if v1 == from.0 || v1 == to.0 || v2 == from.0 || v2 == to.0 {
+            return false;
+        }
+        let (p1, p2) = (positions(v1), positions(v2));
+        let (a, b) = (from.1, to.1);
+        let d1 = orient(a, b, p1);
+        let d2 = orient(a, b, p2);
+        let d3 = orient(p1, p2, a);
+        let d4 = orient(p1, p2, b);
+        if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+            return true;
+        }
+        // A vertex of the edge lying on the segment.
+        return (d1 == 0.0 && between(a, b, p1)) || (d2 == 0.0 && between(a, b, p2));
+    }
+}
+
+// Positive if c is to the left of the line going from a to b.
+fn orient(a: Vec2, b: Vec2, c: Vec2) -> f32 { (b - a).cross(c - a) }
+
+// Whether a point that is on the line going through a and b is strictly between them.
+fn between(a: Vec2, b: Vec2, p: Vec2) -> bool {
+    let d = (p - a).dot(&(b - a));
+    return d > 0.0 && d < (b - a).dot(&(b - a));
+}
+
+#[test]
+fn test_triangulate_concave_face() {
+    // An L shape.
+    let positions = [
+        Vec2::new(0.0, 0.0), Vec2::new(2.0, 0.0), Vec2::new(2.0, 1.0),
+        Vec2::new(1.0, 1.0), Vec2::new(1.0, 2.0), Vec2::new(0.0, 2.0),
+    ];
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 6));
+    let face = kernel.first_face().unwrap();
+    let back_face = kernel[kernel[kernel[face].first_edge].opposite].face;
+
+    let mut new_faces = Vec::new();
+    let result = kernel.triangulate_face(face, &|v: VertexId| positions[v.handle as usize], &mut new_faces);
+    assert_eq!(result, Ok(()));
+    assert_eq!(new_faces.len(), 3);
+    assert_eq!(kernel.walk_edge_ids_around_face(face).count(), 3);
+    for &f in &new_faces {
+        assert_eq!(kernel.walk_edge_ids_around_face(f).count(), 3);
+    }
+    // The back face is left untouched.
+    assert_eq!(kernel.walk_edge_ids_around_face(back_face).count(), 6);
+    assert_eq!(kernel.validate(), vec![]);
+
+    // None of the triangles covers the notch of the L.
+    for f in new_faces.iter().cloned().chain(Some(face).into_iter()) {
+        let first_edge = kernel[f].first_edge;
+        assert!(kernel.loop_area(first_edge, &|v: VertexId| positions[v.handle as usize]) != 0.0);
+        for e in kernel.walk_edge_ids(first_edge) {
+            let p = positions[kernel[e].vertex.handle as usize];
+            let q = positions[kernel[kernel[e].next].vertex.handle as usize];
+            let mid = (p + q) * 0.5;
+            assert!(!(mid.x > 1.0 && mid.y > 1.0));
+        }
+    }
+}
+
+#[test]
+fn test_triangulate_comb() {
+    // A comb with 50 teeth, which has almost as many reflex corners as convex ones.
+    let teeth = 50;
+    let mut positions = vec![Vec2::new(0.0, 0.0)];
+    for i in 0..teeth {
+        let x = (i * 2) as f32;
+        positions.push(Vec2::new(x, 2.0));
+        positions.push(Vec2::new(x + 1.0, 2.0));
+        positions.push(Vec2::new(x + 1.0, 1.0));
+        positions.push(Vec2::new(x + 2.0, 1.0));
+    }
+    positions.push(Vec2::new((teeth * 2) as f32, 0.0));
+    let n = positions.len() as u16;
+
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, n));
+    let face = kernel.first_face().unwrap();
+    let face = if kernel.loop_area(kernel[face].first_edge, &|v: VertexId| positions[v.handle as usize]) > 0.0 {
+        face
+    } else {
+        kernel[kernel[kernel[face].first_edge].opposite].face
+    };
+
+    let mut new_faces = Vec::new();
+    let result = kernel.triangulate_face(face, &|v: VertexId| positions[v.handle as usize], &mut new_faces);
+    assert_eq!(result, Ok(()));
+    assert_eq!(new_faces.len() + 1, n as usize - 2);
+
+    let mut area = 0.0;
+    for f in new_faces.iter().cloned().chain(Some(face).into_iter()) {
+        assert_eq!(kernel.walk_edge_ids_around_face(f).count(), 3);
+        let triangle_area = kernel.loop_area(kernel[f].first_edge, &|v: VertexId| positions[v.handle as usize]);
+        assert!(triangle_area > 0.0);
+        area += triangle_area * 0.5;
+    }
+    // The base is 1 unit high and each tooth adds one unit.
+    assert!((area - (teeth * 2 + teeth) as f32).abs() < 0.01);
+    assert_eq!(kernel.validate(), vec![]);
+}
+
+#[test]
+fn test_triangulate_face_with_holes() {
+    // A 4x4 square with two square holes.
+    let positions = [
+        Vec2::new(0.0, 0.0), Vec2::new(4.0, 0.0), Vec2::new(4.0, 4.0), Vec2::new(0.0, 4.0),
+        Vec2::new(1.0, 1.0), Vec2::new(2.0, 1.0), Vec2::new(2.0, 2.0), Vec2::new(1.0, 2.0),
+        Vec2::new(2.5, 2.5), Vec2::new(3.5, 2.5), Vec2::new(3.5, 3.5), Vec2::new(2.5, 3.5),
+    ];
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let face = kernel.first_face().unwrap();
+    let face = if kernel.loop_area(kernel[face].first_edge, &|v: VertexId| positions[v.handle as usize]) > 0.0 {
+        face
+    } else {
+        kernel[kernel[kernel[face].first_edge].opposite].face
+    };
+    let hole1 = kernel.add_hole(face, vertex_range(4, 4));
+    let hole2 = kernel.add_hole(face, vertex_range(8, 4));
+    assert_eq!(kernel.validate(), vec![]);
+
+    let mut new_faces = Vec::new();
+    let result = kernel.triangulate_face(face, &|v: VertexId| positions[v.handle as usize], &mut new_faces);
+    assert_eq!(result, Ok(()));
+    assert!(kernel[face].inner_edges.is_empty());
+    // 12 vertices and 2 holes: 12 + 2 * 2 - 2 triangles.
+    assert_eq!(new_faces.len() + 1, 14);
+    assert_eq!(kernel.walk_edge_ids_around_face(hole1).count(), 4);
+    assert_eq!(kernel.walk_edge_ids_around_face(hole2).count(), 4);
+
+    let mut area = 0.0;
+    for f in new_faces.iter().cloned().chain(Some(face).into_iter()) {
+        assert_eq!(kernel.walk_edge_ids_around_face(f).count(), 3);
+        let triangle_area = kernel.loop_area(kernel[f].first_edge, &|v: VertexId| positions[v.handle as usize]);
+        assert!(triangle_area > 0.0);
+        area += triangle_area * 0.5;
+    }
+    assert!((area - 14.0).abs() < 0.001);
+    assert_eq!(kernel.validate(), vec![]);
+}
+
+#[test]
+fn test_triangulate_degenerate_faces() {
+    let positions = [
+        Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(2.0, 0.0), Vec2::new(1.0, 1.0),
+    ];
+    // Collinear vertices.
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let face = kernel.first_face().unwrap();
+    let mut new_faces = Vec::new();
+    let result = kernel.triangulate_face(face, &|v: VertexId| positions[v.handle as usize], &mut new_faces);
+    assert_eq!(result, Ok(()));
+    assert_eq!(new_faces.len(), 1);
+    assert_eq!(kernel.validate(), vec![]);
+
+    // A face without an outer loop.
+    let mut kernel = ConnectivityKernel::new();
+    let face = kernel.add_face();
+    assert_eq!(kernel.triangulate_face(face, &|_| Vec2::new(0.0, 0.0), &mut new_faces), Err(()));
+
+    // Positions that can't be ordered.
+    let positions = [
+        Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(::std::f32::NAN, 1.0),
+        Vec2::new(0.0, 1.0),
+    ];
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let face = kernel.first_face().unwrap();
+    let result = kernel.triangulate_face(face, &|v: VertexId| positions[v.handle as usize], &mut new_faces);
+    assert_eq!(result, Err(()));
+    assert_eq!(kernel.walk_edge_ids_around_face(face).count(), 4);
+
+    // A self-intersecting loop.
+    let positions = [
+        Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0), Vec2::new(1.0, 0.0), Vec2::new(0.0, 1.0),
+        Vec2::new(0.5, 2.0),
+    ];
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 5));
+    let face = kernel.first_face().unwrap();
+    let _ = kernel.triangulate_face(face, &|v: VertexId| positions[v.handle as usize], &mut new_faces);
+    assert_eq!(kernel.validate(), vec![]);
+}
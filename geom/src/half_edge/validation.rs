@@ -0,0 +1,266 @@
+//! Exhaustive checks of the connectivity of a kernel.
+//!
+//! Unlike the debug assertions of the kernel, validation runs in release builds, looks at the
+//! whole structure and reports what it finds instead of panicking, which makes it suitable to
+//! check meshes that come from untrusted sources.
+
+use half_edge::kernel::{ ConnectivityKernel, EdgeId, FaceId, VertexId };
+use half_edge::id_internals::{ is_valid, Index, KernelIndex };
+
+#[cfg(test)]
+use half_edge::kernel::{ vertex_range, edge_id, face_id, vertex_id };
+
+/// A broken invariant found by `ConnectivityKernel::validate`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Violation<H: KernelIndex = Index> {
+    /// The edge refers to a next, previous or opposite edge that is not in the kernel.
+    DanglingEdgeId { edge: EdgeId<H>, target: EdgeId<H> },
+    /// The edge originates from a vertex that is not in the kernel.
+    DanglingVertexId { edge: EdgeId<H>, vertex: VertexId<H> },
+    /// The edge refers to a face that is not in the kernel.
+    DanglingFaceId { edge: EdgeId<H>, face: FaceId<H> },
+    /// The face refers to a first edge or a hole edge that is not in the kernel.
+    DanglingLoopId { face: FaceId<H>, edge: EdgeId<H> },
+    /// The vertex refers to a first edge that is not in the kernel.
+    DanglingVertexEdge { vertex: VertexId<H>, edge: EdgeId<H> },
+    /// The first edge of the vertex doesn't originate from it.
+    VertexEdgeMismatch(VertexId<H>),
+    /// The previous edge of the edge's next edge is not the edge.
+    NextPrevMismatch(EdgeId<H>),
+    /// The next edge of the edge's previous edge is not the edge.
+    PrevNextMismatch(EdgeId<H>),
+    /// The opposite of the edge's opposite edge is not the edge.
+    AsymmetricOpposite(EdgeId<H>),
+    /// The edge does not originate from the vertex its opposite edge leads to.
+    OppositeVertexMismatch(EdgeId<H>),
+    /// The edge and its next edge don't belong to the same face.
+    FaceMismatch(EdgeId<H>),
+    /// Following the next edges from one of the face's loops never comes back to its first edge.
+    UnclosedLoop { face: FaceId<H>, edge: EdgeId<H> },
+    /// One of the face's loops (its outer loop, or a hole if `hole` is true) contains edges
+    /// that belong to another face.
+    ForeignLoop { face: FaceId<H>, edge: EdgeId<H>, hole: bool },
+}
+
+impl<H: KernelIndex> ConnectivityKernel<H> {
+    /// Check the whole connectivity and return the list of broken invariants, which is empty
+    /// if the kernel is valid.
+    ///
+    /// Edges are checked first, in the order of their ids, followed by the vertices and the
+    /// loops of each face.
+    pub fn validate(&self) -> Vec<Violation<H>> {
+        let mut violations = Vec::new();
+
+        for id in self.edge_ids() {
+            let edge = self[id];
+            let mut dangling = false;
+            for &target in &[edge.next, edge.prev] {
+                if !self.contains_edge(target) {
+                    violations.push(Violation::DanglingEdgeId { edge: id, target: target });
+                    dangling = true;
+                }
+            }
+            if is_valid(edge.opposite) && !self.contains_edge(edge.opposite) {
+                violations.push(Violation::DanglingEdgeId { edge: id, target: edge.opposite });
+                dangling = true;
+            }
+            if !self.contains_vertex(edge.vertex) {
+                violations.push(Violation::DanglingVertexId { edge: id, vertex: edge.vertex });
+            }
+            if !self.contains_face(edge.face) {
+                violations.push(Violation::DanglingFaceId { edge: id, face: edge.face });
+            }
+            if dangling {
+                continue;
+            }
+
+            if self[edge.next].prev != id {
+                violations.push(Violation::NextPrevMismatch(id));
+            }
+            if self[edge.prev].next != id {
+                violations.push(Violation::PrevNextMismatch(id));
+            }
+            if self[edge.next].face != edge.face {
+                violations.push(Violation::FaceMismatch(id));
+            }
+            if is_valid(edge.opposite) {
+                let opposite = self[edge.opposite];
+                if opposite.opposite != id {
+                    violations.push(Violation::AsymmetricOpposite(id));
+                }
+                if self.contains_edge(opposite.next) && self[opposite.next].vertex != edge.vertex {
+                    violations.push(Violation::OppositeVertexMismatch(id));
+                }
+            }
+        }
+
+        for vertex in self.vertex_ids() {
+            let first_edge = self[vertex].first_edge;
+            if !is_valid(first_edge) {
+                continue;
+            }
+            if !self.contains_edge(first_edge) {
+                violations.push(Violation::DanglingVertexEdge { vertex: vertex, edge: first_edge });
+            } else if self[first_edge].vertex != vertex {
+                violations.push(Violation::VertexEdgeMismatch(vertex));
+            }
+        }
+
+        let max_loop_length = self.num_edges();
+        for face in self.face_ids() {
+            let first_edge = self[face].first_edge;
+            let outer = if is_valid(first_edge) { Some((first_edge, false)) } else { None };
+            let holes = self[face].inner_edges.iter().map(|&e| (e, true));
+            for (first, hole) in outer.into_iter().chain(holes) {
+                if !self.contains_edge(first) {
+                    violations.push(Violation::DanglingLoopId { face: face, edge: first });
+                    continue;
+                }
+                let mut foreign = false;
+                let mut closed = false;
+                let mut e = first;
+                for _ in 0..max_loop_length {
+                    foreign |= self[e].face != face;
+                    e = self[e].next;
+                    if e == first {
+                        closed = true;
+                        break;
+                    }
+                    if !self.contains_edge(e) {
+                        break;
+                    }
+                }
+                if !closed {
+                    violations.push(Violation::UnclosedLoop { face: face, edge: first });
+                }
+                if foreign {
+                    violations.push(Violation::ForeignLoop { face: face, edge: first, hole: hole });
+                }
+            }
+        }
+
+        return violations;
+    }
+}
+
+#[test]
+fn test_validate_valid_kernels() {
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 5));
+    assert_eq!(kernel.validate(), vec![]);
+
+    let face = kernel.first_face().unwrap();
+    kernel.add_hole(face, vertex_range(5, 3));
+    assert_eq!(kernel.validate(), vec![]);
+
+    let e1 = kernel[face].first_edge;
+    let e3 = kernel[kernel[e1].next].next;
+    let new_face = kernel.split_face(e1, e3);
+    assert_eq!(kernel.validate(), vec![]);
+
+    kernel.merge_faces(face, new_face);
+    assert_eq!(kernel.validate(), vec![]);
+}
+
+#[test]
+fn test_validate_broken_links() {
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let face = kernel.first_face().unwrap();
+    let e1 = kernel[face].first_edge;
+    let e2 = kernel[e1].next;
+    let e3 = kernel[e2].next;
+
+    kernel[e1].next = e3;
+    let violations = kernel.validate();
+    assert!(violations.contains(&Violation::NextPrevMismatch(e1)));
+    assert!(violations.contains(&Violation::PrevNextMismatch(e2)));
+    // The opposite of e1 now looks like it leads to the vertex of e3.
+    assert!(violations.contains(&Violation::OppositeVertexMismatch(kernel[e1].opposite)));
+    kernel[e1].next = e2;
+
+    let o1 = kernel[e1].opposite;
+    kernel[e1].opposite = kernel[e2].opposite;
+    let violations = kernel.validate();
+    assert!(violations.contains(&Violation::AsymmetricOpposite(e1)));
+    assert!(violations.contains(&Violation::OppositeVertexMismatch(e1)));
+    kernel[e1].opposite = o1;
+
+    let v2 = kernel[e2].vertex;
+    kernel[e2].vertex = vertex_id(10);
+    let violations = kernel.validate();
+    assert!(violations.contains(&Violation::DanglingVertexId { edge: e2, vertex: vertex_id(10) }));
+    assert!(violations.contains(&Violation::OppositeVertexMismatch(e2)));
+    assert!(violations.contains(&Violation::OppositeVertexMismatch(kernel[e1].opposite)));
+    kernel[e2].vertex = v2;
+
+    assert_eq!(kernel.validate(), vec![]);
+}
+
+#[test]
+fn test_validate_dangling_ids() {
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 3));
+    let face = kernel.first_face().unwrap();
+    let e1 = kernel[face].first_edge;
+
+    let next = kernel[e1].next;
+    kernel[e1].next = edge_id(100);
+    kernel[e1].face = face_id(100);
+    let violations = kernel.validate();
+    assert_eq!(violations[0], Violation::DanglingEdgeId { edge: e1, target: edge_id(100) });
+    assert_eq!(violations[1], Violation::DanglingFaceId { edge: e1, face: face_id(100) });
+    assert!(violations.contains(&Violation::UnclosedLoop { face: face, edge: e1 }));
+    assert!(violations.contains(&Violation::ForeignLoop { face: face, edge: e1, hole: false }));
+    kernel[e1].next = next;
+    kernel[e1].face = face;
+
+    kernel[face].inner_edges.push(edge_id(200));
+    assert_eq!(kernel.validate(), vec![
+        Violation::DanglingLoopId { face: face, edge: edge_id(200) }
+    ]);
+}
+
+#[test]
+fn test_validate_foreign_hole() {
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let face = kernel.first_face().unwrap();
+    let other_face = kernel[kernel[kernel[face].first_edge].opposite].face;
+
+    // A hole that is the outer loop of the other face.
+    let other_loop = kernel[other_face].first_edge;
+    kernel[face].inner_edges.push(other_loop);
+    assert_eq!(kernel.validate(), vec![
+        Violation::ForeignLoop { face: face, edge: other_loop, hole: true }
+    ]);
+}
+
+#[test]
+fn test_validate_unclosed_loop() {
+    // A loop that ends in a cycle that doesn't contain its first edge.
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let face = kernel.first_face().unwrap();
+    let e1 = kernel[face].first_edge;
+    let e2 = kernel[e1].next;
+    let e4 = kernel[e1].prev;
+    kernel[e4].next = e2;
+    let violations = kernel.validate();
+    assert!(violations.contains(&Violation::UnclosedLoop { face: face, edge: e1 }));
+    assert!(violations.contains(&Violation::NextPrevMismatch(e4)));
+}
+
+#[test]
+fn test_validate_vertices() {
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 3));
+    let face = kernel.first_face().unwrap();
+    let e1 = kernel[face].first_edge;
+    let v1 = kernel[e1].vertex;
+    let v2 = kernel[kernel[e1].next].vertex;
+
+    let first_edge = kernel[v1].first_edge;
+    kernel[v1].first_edge = kernel[e1].next;
+    assert_eq!(kernel.validate(), vec![Violation::VertexEdgeMismatch(v1)]);
+    kernel[v1].first_edge = first_edge;
+
+    kernel[v2].first_edge = edge_id(100);
+    assert_eq!(kernel.validate(), vec![
+        Violation::DanglingVertexEdge { vertex: v2, edge: edge_id(100) }
+    ]);
+}
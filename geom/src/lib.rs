@@ -0,0 +1,4 @@
+extern crate vodk_id;
+extern crate vodk_math;
+
+pub mod half_edge;
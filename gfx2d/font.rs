@@ -0,0 +1,753 @@
+//! Minimal TrueType/OpenType font loading.
+//!
+//! Reads the glyph outlines (glyf and loca), the character to glyph mapping (cmap),
+//! the horizontal metrics (hhea and hmtx) and the kerning pairs (kern) of a font,
+//! and turns glyph outlines into lyon paths made of quadratic bézier curves so that
+//! text can go through the same tessellation and rasterization pipeline as any other
+//! path.
+//!
+//! Fonts with CFF outlines are not supported.
+
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path;
+
+use lyon::tesselation::path_builder::PathBuilder;
+
+use vodk_math::Vec2;
+
+pub type GlyphIndex = u16;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(io::Error),
+    /// A required table is missing from the font.
+    MissingTable(&'static str),
+    /// The font data is truncated or otherwise inconsistent.
+    Malformed,
+    /// The font uses a feature that is not supported, for example CFF outlines.
+    Unsupported,
+}
+
+impl From<io::Error> for FontError {
+    fn from(err: io::Error) -> FontError { FontError::Io(err) }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HorizontalMetrics {
+    pub advance_width: u16,
+    pub left_side_bearing: i16,
+}
+
+/// A point of a glyph contour, in font units.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OutlinePoint {
+    pub x: f32,
+    pub y: f32,
+    pub on_curve: bool,
+}
+
+pub struct Font {
+    data: Vec<u8>,
+    glyf: usize,
+    loca: usize,
+    hmtx: usize,
+    cmap_subtable: Option<usize>,
+    kern_pairs: Option<(usize, usize)>,
+    units_per_em: u16,
+    num_glyphs: u16,
+    num_h_metrics: u16,
+    long_loca: bool,
+}
+
+// Nested composite glyphs deeper than this are considered malformed.
+const MAX_COMPOSITE_DEPTH: u32 = 8;
+
+impl Font {
+    pub fn from_file(file_path: &path::Path) -> Result<Font, FontError> {
+        let mut data = Vec::new();
+        try!(try!(File::open(file_path)).read_to_end(&mut data));
+        return Font::from_bytes(data);
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, FontError> {
+        let version = try!(read_u32(&data, 0));
+        if version == 0x4F54544F {
+            // 'OTTO': CFF outlines.
+            return Err(FontError::Unsupported);
+        }
+        if version != 0x00010000 && version != 0x74727565 {
+            return Err(FontError::Malformed);
+        }
+
+        let head = try!(try!(find_table(&data, b"head")).ok_or(FontError::MissingTable("head")));
+        let maxp = try!(try!(find_table(&data, b"maxp")).ok_or(FontError::MissingTable("maxp")));
+        let hhea = try!(try!(find_table(&data, b"hhea")).ok_or(FontError::MissingTable("hhea")));
+        let hmtx = try!(try!(find_table(&data, b"hmtx")).ok_or(FontError::MissingTable("hmtx")));
+        let loca = try!(try!(find_table(&data, b"loca")).ok_or(FontError::MissingTable("loca")));
+        let glyf = try!(try!(find_table(&data, b"glyf")).ok_or(FontError::MissingTable("glyf")));
+        let cmap = try!(find_table(&data, b"cmap"));
+        let kern = try!(find_table(&data, b"kern"));
+
+        let cmap_subtable = match cmap {
+            Some(offset) => try!(select_cmap_subtable(&data, offset)),
+            None => None,
+        };
+        let kern_pairs = match kern {
+            Some(offset) => try!(find_kern_pairs(&data, offset)),
+            None => None,
+        };
+
+        let units_per_em = try!(read_u16(&data, head + 18));
+        let long_loca = try!(read_i16(&data, head + 50)) != 0;
+        let num_glyphs = try!(read_u16(&data, maxp + 4));
+        let num_h_metrics = try!(read_u16(&data, hhea + 34));
+
+        if units_per_em == 0 || num_h_metrics == 0 {
+            return Err(FontError::Malformed);
+        }
+
+        return Ok(Font {
+            data: data,
+            glyf: glyf,
+            loca: loca,
+            hmtx: hmtx,
+            cmap_subtable: cmap_subtable,
+            kern_pairs: kern_pairs,
+            units_per_em: units_per_em,
+            num_glyphs: num_glyphs,
+            num_h_metrics: num_h_metrics,
+            long_loca: long_loca,
+        });
+    }
+
+    pub fn units_per_em(&self) -> u16 { self.units_per_em }
+
+    pub fn num_glyphs(&self) -> u16 { self.num_glyphs }
+
+    /// Returns the glyph that the font maps to a character, if any.
+    pub fn glyph_index(&self, c: char) -> Option<GlyphIndex> {
+        let subtable = match self.cmap_subtable {
+            Some(offset) => offset,
+            None => { return None; }
+        };
+        let glyph = match read_u16(&self.data, subtable) {
+            Ok(4) => cmap_format_4(&self.data, subtable, c as u32),
+            Ok(12) => cmap_format_12(&self.data, subtable, c as u32),
+            _ => Ok(0),
+        };
+        return match glyph {
+            Ok(0) | Err(_) => None,
+            Ok(glyph) => Some(glyph),
+        };
+    }
+
+    pub fn horizontal_metrics(&self, glyph: GlyphIndex) -> Result<HorizontalMetrics, FontError> {
+        if glyph >= self.num_glyphs {
+            return Err(FontError::Malformed);
+        }
+        if glyph < self.num_h_metrics {
+            let offset = self.hmtx + glyph as usize * 4;
+            return Ok(HorizontalMetrics {
+                advance_width: try!(read_u16(&self.data, offset)),
+                left_side_bearing: try!(read_i16(&self.data, offset + 2)),
+            });
+        }
+        // Glyphs after the last long metric share its advance width.
+        let last = self.hmtx + (self.num_h_metrics as usize - 1) * 4;
+        let lsb_offset = self.hmtx + self.num_h_metrics as usize * 4
+            + (glyph - self.num_h_metrics) as usize * 2;
+        return Ok(HorizontalMetrics {
+            advance_width: try!(read_u16(&self.data, last)),
+            left_side_bearing: try!(read_i16(&self.data, lsb_offset)),
+        });
+    }
+
+    /// Advance width of a glyph in font units.
+    pub fn advance_width(&self, glyph: GlyphIndex) -> u16 {
+        return self.horizontal_metrics(glyph).map(|m| m.advance_width).unwrap_or(0);
+    }
+
+    /// Horizontal kerning adjustment between two glyphs in font units.
+    pub fn kerning(&self, left: GlyphIndex, right: GlyphIndex) -> i16 {
+        let (pairs, count) = match self.kern_pairs {
+            Some(pairs) => pairs,
+            None => { return 0; }
+        };
+        let key = (left as u32) << 16 | right as u32;
+        // The pairs are sorted by their combined key.
+        let mut low = 0;
+        let mut high = count;
+        while low < high {
+            let mid = (low + high) / 2;
+            let offset = pairs + mid * 6;
+            let mid_key = match read_u32(&self.data, offset) {
+                Ok(k) => k,
+                Err(_) => { return 0; }
+            };
+            if mid_key == key {
+                return read_i16(&self.data, offset + 4).unwrap_or(0);
+            } else if mid_key < key {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        return 0;
+    }
+
+    /// Returns the contours of a glyph in font units, with composite glyphs resolved.
+    pub fn glyph_contours(&self, glyph: GlyphIndex) -> Result<Vec<Vec<OutlinePoint>>, FontError> {
+        let mut contours = Vec::new();
+        try!(self.read_glyph(glyph, &[1.0, 0.0, 0.0, 1.0, 0.0, 0.0], 0, &mut contours));
+        return Ok(contours);
+    }
+
+    /// Adds the outline of a glyph to a path builder.
+    ///
+    /// The glyph is scaled so that an em square measures size units, its origin is
+    /// placed at the provided position and the y axis is flipped so that it points
+    /// down, like in the rest of lyon.
+    pub fn build_glyph_path(
+        &self,
+        glyph: GlyphIndex,
+        size: f32,
+        origin: Vec2,
+        builder: &mut PathBuilder
+    ) -> Result<(), FontError> {
+        let scale = size / self.units_per_em as f32;
+        let to_path = |p: OutlinePoint| { Vec2::new(origin.x + p.x * scale, origin.y - p.y * scale) };
+        for contour in try!(self.glyph_contours(glyph)) {
+            build_contour(&contour, &to_path, builder);
+        }
+        return Ok(());
+    }
+
+    /// Adds the outlines of a line of text to a path builder, applying advance widths
+    /// and kerning. Characters that are not in the font are skipped.
+    ///
+    /// Returns the horizontal advance of the text.
+    pub fn build_text_path(
+        &self,
+        text: &str,
+        size: f32,
+        origin: Vec2,
+        builder: &mut PathBuilder
+    ) -> Result<f32, FontError> {
+        let scale = size / self.units_per_em as f32;
+        let mut x = 0.0;
+        let mut previous = None;
+        for c in text.chars() {
+            let glyph = match self.glyph_index(c) {
+                Some(glyph) => glyph,
+                None => { continue; }
+            };
+            if let Some(previous) = previous {
+                x += self.kerning(previous, glyph) as f32 * scale;
+            }
+            try!(self.build_glyph_path(glyph, size, Vec2::new(origin.x + x, origin.y), builder));
+            x += self.advance_width(glyph) as f32 * scale;
+            previous = Some(glyph);
+        }
+        return Ok(x);
+    }
+
+    fn glyph_range(&self, glyph: GlyphIndex) -> Result<(usize, usize), FontError> {
+        if glyph >= self.num_glyphs {
+            return Err(FontError::Malformed);
+        }
+        let i = glyph as usize;
+        let (start, end) = if self.long_loca {
+            (try!(read_u32(&self.data, self.loca + i * 4)) as usize,
+             try!(read_u32(&self.data, self.loca + i * 4 + 4)) as usize)
+        } else {
+            (try!(read_u16(&self.data, self.loca + i * 2)) as usize * 2,
+             try!(read_u16(&self.data, self.loca + i * 2 + 2)) as usize * 2)
+        };
+        if end < start || self.glyf + end > self.data.len() {
+            return Err(FontError::Malformed);
+        }
+        return Ok((self.glyf + start, self.glyf + end));
+    }
+
+    // transform is [a, b, c, d, e, f] with x' = a*x + c*y + e and y' = b*x + d*y + f.
+    fn read_glyph(
+        &self,
+        glyph: GlyphIndex,
+        transform: &[f32; 6],
+        depth: u32,
+        contours: &mut Vec<Vec<OutlinePoint>>
+    ) -> Result<(), FontError> {
+        if depth > MAX_COMPOSITE_DEPTH {
+            return Err(FontError::Malformed);
+        }
+        let (start, end) = try!(self.glyph_range(glyph));
+        if start == end {
+            // Empty glyph, for example a space.
+            return Ok(());
+        }
+        let num_contours = try!(read_i16(&self.data, start));
+        if num_contours >= 0 {
+            return self.read_simple_glyph(start, num_contours as usize, transform, contours);
+        }
+        return self.read_composite_glyph(start, transform, depth, contours);
+    }
+
+    fn read_simple_glyph(
+        &self,
+        start: usize,
+        num_contours: usize,
+        transform: &[f32; 6],
+        contours: &mut Vec<Vec<OutlinePoint>>
+    ) -> Result<(), FontError> {
+        const ON_CURVE: u8 = 1;
+        const X_SHORT: u8 = 2;
+        const Y_SHORT: u8 = 4;
+        const REPEAT: u8 = 8;
+        const X_SAME_OR_POSITIVE: u8 = 16;
+        const Y_SAME_OR_POSITIVE: u8 = 32;
+
+        let data = &self.data[..];
+        let mut offset = start + 10;
+        let mut end_points = Vec::with_capacity(num_contours);
+        for _ in 0..num_contours {
+            end_points.push(try!(read_u16(data, offset)) as usize);
+            offset += 2;
+        }
+        let num_points = match end_points.last() {
+            Some(&last) => last + 1,
+            None => { return Ok(()); }
+        };
+        let instructions_len = try!(read_u16(data, offset)) as usize;
+        offset += 2 + instructions_len;
+
+        let mut flags = Vec::with_capacity(num_points);
+        while flags.len() < num_points {
+            let flag = try!(read_u8(data, offset));
+            offset += 1;
+            flags.push(flag);
+            if flag & REPEAT != 0 {
+                let count = try!(read_u8(data, offset));
+                offset += 1;
+                for _ in 0..count {
+                    flags.push(flag);
+                }
+            }
+        }
+        flags.truncate(num_points);
+
+        let mut xs = Vec::with_capacity(num_points);
+        let mut x: i32 = 0;
+        for &flag in &flags {
+            if flag & X_SHORT != 0 {
+                let dx = try!(read_u8(data, offset)) as i32;
+                offset += 1;
+                x += if flag & X_SAME_OR_POSITIVE != 0 { dx } else { -dx };
+            } else if flag & X_SAME_OR_POSITIVE == 0 {
+                x += try!(read_i16(data, offset)) as i32;
+                offset += 2;
+            }
+            xs.push(x);
+        }
+
+        let mut y: i32 = 0;
+        let mut point_index = 0;
+        for &end_point in &end_points {
+            if end_point < point_index || end_point >= num_points {
+                return Err(FontError::Malformed);
+            }
+            let mut contour = Vec::with_capacity(end_point + 1 - point_index);
+            while point_index <= end_point {
+                let flag = flags[point_index];
+                if flag & Y_SHORT != 0 {
+                    let dy = try!(read_u8(data, offset)) as i32;
+                    offset += 1;
+                    y += if flag & Y_SAME_OR_POSITIVE != 0 { dy } else { -dy };
+                } else if flag & Y_SAME_OR_POSITIVE == 0 {
+                    y += try!(read_i16(data, offset)) as i32;
+                    offset += 2;
+                }
+                let (px, py) = (xs[point_index] as f32, y as f32);
+                contour.push(OutlinePoint {
+                    x: transform[0] * px + transform[2] * py + transform[4],
+                    y: transform[1] * px + transform[3] * py + transform[5],
+                    on_curve: flag & ON_CURVE != 0,
+                });
+                point_index += 1;
+            }
+            contours.push(contour);
+        }
+
+        return Ok(());
+    }
+
+    fn read_composite_glyph(
+        &self,
+        start: usize,
+        transform: &[f32; 6],
+        depth: u32,
+        contours: &mut Vec<Vec<OutlinePoint>>
+    ) -> Result<(), FontError> {
+        const ARG_1_AND_2_ARE_WORDS: u16 = 0x1;
+        const ARGS_ARE_XY_VALUES: u16 = 0x2;
+        const WE_HAVE_A_SCALE: u16 = 0x8;
+        const MORE_COMPONENTS: u16 = 0x20;
+        const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x40;
+        const WE_HAVE_A_TWO_BY_TWO: u16 = 0x80;
+
+        let data = &self.data[..];
+        let mut offset = start + 10;
+        loop {
+            let flags = try!(read_u16(data, offset));
+            let component = try!(read_u16(data, offset + 2));
+            offset += 4;
+
+            let (arg1, arg2) = if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                offset += 4;
+                (try!(read_i16(data, offset - 4)) as f32, try!(read_i16(data, offset - 2)) as f32)
+            } else {
+                offset += 2;
+                (try!(read_u8(data, offset - 2)) as i8 as f32, try!(read_u8(data, offset - 1)) as i8 as f32)
+            };
+            // Components positioned by matching points are not supported and are
+            // placed at the origin.
+            let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 { (arg1, arg2) } else { (0.0, 0.0) };
+
+            let mut m = [1.0, 0.0, 0.0, 1.0];
+            if flags & WE_HAVE_A_SCALE != 0 {
+                let s = try!(read_f2dot14(data, offset));
+                offset += 2;
+                m = [s, 0.0, 0.0, s];
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                m = [try!(read_f2dot14(data, offset)), 0.0, 0.0, try!(read_f2dot14(data, offset + 2))];
+                offset += 4;
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                m = [
+                    try!(read_f2dot14(data, offset)), try!(read_f2dot14(data, offset + 2)),
+                    try!(read_f2dot14(data, offset + 4)), try!(read_f2dot14(data, offset + 6)),
+                ];
+                offset += 8;
+            }
+
+            // Apply the component transform first, then the parent one.
+            let t = transform;
+            let combined = [
+                t[0] * m[0] + t[2] * m[1],
+                t[1] * m[0] + t[3] * m[1],
+                t[0] * m[2] + t[2] * m[3],
+                t[1] * m[2] + t[3] * m[3],
+                t[0] * dx + t[2] * dy + t[4],
+                t[1] * dx + t[3] * dy + t[5],
+            ];
+            try!(self.read_glyph(component, &combined, depth + 1, contours));
+
+            if flags & MORE_COMPONENTS == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Converts a TrueType contour into quadratic bézier curves. Two consecutive off-curve
+// points have an implicit on-curve point in the middle.
+fn build_contour<F: Fn(OutlinePoint) -> Vec2>(
+    contour: &[OutlinePoint],
+    to_path: &F,
+    builder: &mut PathBuilder
+) {
+    let n = contour.len();
+    if n < 2 {
+        return;
+    }
+    let midpoint = |a: OutlinePoint, b: OutlinePoint| {
+        OutlinePoint { x: (a.x + b.x) * 0.5, y: (a.y + b.y) * 0.5, on_curve: true }
+    };
+
+    let (start, first) = if contour[0].on_curve {
+        (contour[0], 1)
+    } else if contour[n - 1].on_curve {
+        (contour[n - 1], 0)
+    } else {
+        (midpoint(contour[n - 1], contour[0]), 0)
+    };
+    let last = if first == 0 && contour[n - 1].on_curve { n - 1 } else { n };
+
+    builder.move_to(to_path(start));
+    let mut ctrl: Option<OutlinePoint> = None;
+    for i in first..last {
+        let p = contour[i];
+        match (p.on_curve, ctrl) {
+            (true, Some(c)) => {
+                builder.quadratic_bezier_to(to_path(c), to_path(p));
+                ctrl = None;
+            }
+            (true, None) => {
+                builder.line_to(to_path(p));
+            }
+            (false, Some(c)) => {
+                builder.quadratic_bezier_to(to_path(c), to_path(midpoint(c, p)));
+                ctrl = Some(p);
+            }
+            (false, None) => {
+                ctrl = Some(p);
+            }
+        }
+    }
+    if let Some(c) = ctrl {
+        builder.quadratic_bezier_to(to_path(c), to_path(start));
+    }
+    builder.close();
+}
+
+fn find_table(data: &[u8], tag: &[u8; 4]) -> Result<Option<usize>, FontError> {
+    let num_tables = try!(read_u16(data, 4)) as usize;
+    for i in 0..num_tables {
+        let record = 12 + i * 16;
+        if try!(read_slice(data, record, 4)) == &tag[..] {
+            let offset = try!(read_u32(data, record + 8)) as usize;
+            let length = try!(read_u32(data, record + 12)) as usize;
+            if offset + length > data.len() {
+                return Err(FontError::Malformed);
+            }
+            return Ok(Some(offset));
+        }
+    }
+    return Ok(None);
+}
+
+// Picks the best supported unicode subtable. Format 12 can map characters outside of
+// the basic multilingual plane so it is preferred over format 4.
+fn select_cmap_subtable(data: &[u8], cmap: usize) -> Result<Option<usize>, FontError> {
+    let num_tables = try!(read_u16(data, cmap + 2)) as usize;
+    let mut best = None;
+    let mut best_score = 0;
+    for i in 0..num_tables {
+        let record = cmap + 4 + i * 8;
+        let platform = try!(read_u16(data, record));
+        let encoding = try!(read_u16(data, record + 2));
+        let subtable = cmap + try!(read_u32(data, record + 4)) as usize;
+        let is_unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        if !is_unicode {
+            continue;
+        }
+        let score = match try!(read_u16(data, subtable)) {
+            12 => 2,
+            4 => 1,
+            _ => 0,
+        };
+        if score > best_score {
+            best = Some(subtable);
+            best_score = score;
+        }
+    }
+    return Ok(best);
+}
+
+fn cmap_format_4(data: &[u8], subtable: usize, c: u32) -> Result<GlyphIndex, FontError> {
+    if c > 0xFFFF {
+        return Ok(0);
+    }
+    let c = c as u16;
+    let seg_count = try!(read_u16(data, subtable + 6)) as usize / 2;
+    let end_codes = subtable + 14;
+    let start_codes = end_codes + seg_count * 2 + 2;
+    let deltas = start_codes + seg_count * 2;
+    let range_offsets = deltas + seg_count * 2;
+    for i in 0..seg_count {
+        if try!(read_u16(data, end_codes + i * 2)) < c {
+            continue;
+        }
+        let start = try!(read_u16(data, start_codes + i * 2));
+        if start > c {
+            return Ok(0);
+        }
+        let delta = try!(read_u16(data, deltas + i * 2));
+        let range_offset = try!(read_u16(data, range_offsets + i * 2)) as usize;
+        if range_offset == 0 {
+            return Ok(c.wrapping_add(delta));
+        }
+        let glyph_offset = range_offsets + i * 2 + range_offset + (c - start) as usize * 2;
+        let glyph = try!(read_u16(data, glyph_offset));
+        return Ok(if glyph == 0 { 0 } else { glyph.wrapping_add(delta) });
+    }
+    return Ok(0);
+}
+
+fn cmap_format_12(data: &[u8], subtable: usize, c: u32) -> Result<GlyphIndex, FontError> {
+    let num_groups = try!(read_u32(data, subtable + 12)) as usize;
+    for i in 0..num_groups {
+        let group = subtable + 16 + i * 12;
+        let start = try!(read_u32(data, group));
+        let end = try!(read_u32(data, group + 4));
+        if c >= start && c <= end {
+            let glyph = try!(read_u32(data, group + 8)) + (c - start);
+            return Ok(if glyph > 0xFFFF { 0 } else { glyph as GlyphIndex });
+        }
+    }
+    return Ok(0);
+}
+
+// Returns the offset and number of pairs of the first horizontal format 0 subtable
+// of a version 0 kern table.
+fn find_kern_pairs(data: &[u8], kern: usize) -> Result<Option<(usize, usize)>, FontError> {
+    if try!(read_u16(data, kern)) != 0 {
+        // Apple's kern table format is not supported.
+        return Ok(None);
+    }
+    let num_tables = try!(read_u16(data, kern + 2)) as usize;
+    let mut subtable = kern + 4;
+    for _ in 0..num_tables {
+        let length = try!(read_u16(data, subtable + 2)) as usize;
+        let coverage = try!(read_u16(data, subtable + 4));
+        let format = coverage >> 8;
+        let horizontal = coverage & 1 != 0;
+        let cross_stream = coverage & 4 != 0;
+        if format == 0 && horizontal && !cross_stream {
+            let num_pairs = try!(read_u16(data, subtable + 6)) as usize;
+            let pairs = subtable + 14;
+            try!(read_slice(data, pairs, num_pairs * 6));
+            return Ok(Some((pairs, num_pairs)));
+        }
+        subtable += length;
+    }
+    return Ok(None);
+}
+
+fn read_slice(data: &[u8], offset: usize, len: usize) -> Result<&[u8], FontError> {
+    if offset + len > data.len() {
+        return Err(FontError::Malformed);
+    }
+    return Ok(&data[offset..offset + len]);
+}
+
+fn read_u8(data: &[u8], offset: usize) -> Result<u8, FontError> {
+    return data.get(offset).map(|v| *v).ok_or(FontError::Malformed);
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, FontError> {
+    let bytes = try!(read_slice(data, offset, 2));
+    return Ok((bytes[0] as u16) << 8 | bytes[1] as u16);
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Result<i16, FontError> {
+    return read_u16(data, offset).map(|v| v as i16);
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, FontError> {
+    let bytes = try!(read_slice(data, offset, 4));
+    return Ok((bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32);
+}
+
+fn read_f2dot14(data: &[u8], offset: usize) -> Result<f32, FontError> {
+    return read_i16(data, offset).map(|v| v as f32 / 16384.0);
+}
+
+// Builds a font with three glyphs: .notdef (empty), 'A' which is a 1000 units square
+// with a quadratic curve on the top edge, and 'B' which is a composite of 'A' shifted
+// by 100 units to the right. The kerning between 'A' and 'B' is -50.
+#[cfg(test)]
+fn build_test_font() -> Vec<u8> {
+    fn u16_be(v: u16) -> Vec<u8> { vec![(v >> 8) as u8, v as u8] }
+    fn u32_be(v: u32) -> Vec<u8> { vec![(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8] }
+    fn words(values: &[u16]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for v in values { bytes.extend(u16_be(*v)); }
+        return bytes;
+    }
+
+    let mut head = vec![0; 54];
+    head[18] = (1000 >> 8) as u8;
+    head[19] = (1000 & 0xFF) as u8;
+    let mut maxp = vec![0; 6];
+    maxp[5] = 3;
+    let mut hhea = vec![0; 36];
+    hhea[35] = 2;
+    // Two long metrics, the third glyph only has a left side bearing.
+    let hmtx = words(&[0, 0, 1200, 10, 5]);
+
+    // Simple glyph: points (0,0) (1000,0) (1000,1000) [500,1200 off curve] (0,1000).
+    let mut glyph_a = words(&[1, 0, 0, 1000, 1200, 4, 0]);
+    glyph_a.extend(vec![1, 1, 1, 0, 1]);
+    for x in &[0i16, 1000, 0, -500, -500] { glyph_a.extend(u16_be(*x as u16)); }
+    for y in &[0i16, 0, 1000, 200, -200] { glyph_a.extend(u16_be(*y as u16)); }
+    // Short loca offsets require glyphs to be aligned on two bytes.
+    glyph_a.push(0);
+    // Composite glyph with word arguments and xy offsets.
+    let glyph_b = words(&[0xFFFF, 100, 0, 1100, 1200, 0x3, 1, 100, 0]);
+
+    let mut glyf = glyph_a.clone();
+    glyf.extend(glyph_b.clone());
+    let loca = words(&[0, 0, glyph_a.len() as u16 / 2, (glyph_a.len() + glyph_b.len()) as u16 / 2]);
+
+    // Format 4 with a segment mapping 'A'..'B' to glyphs 1..2 and the end segment.
+    let mut cmap = words(&[0, 1, 3, 1]);
+    cmap.extend(u32_be(12));
+    cmap.extend(words(&[4, 32, 0, 4, 4, 1, 0]));
+    cmap.extend(words(&[66, 0xFFFF, 0, 65, 0xFFFF, (1i16 - 65) as u16, 1, 0, 0]));
+
+    let mut kern = words(&[0, 1, 0, 20, 0x0001, 1, 6, 0, 0]);
+    kern.extend(words(&[1, 2, (-50i16) as u16]));
+
+    let tables: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"cmap", cmap), (b"glyf", glyf), (b"head", head), (b"hhea", hhea),
+        (b"hmtx", hmtx), (b"kern", kern), (b"loca", loca), (b"maxp", maxp),
+    ];
+    let mut font = u32_be(0x00010000);
+    font.extend(words(&[tables.len() as u16, 0, 0, 0]));
+    let mut offset = 12 + tables.len() * 16;
+    let mut table_data = Vec::new();
+    for &(tag, ref data) in &tables {
+        font.extend(tag.iter().cloned());
+        font.extend(u32_be(0));
+        font.extend(u32_be(offset as u32));
+        font.extend(u32_be(data.len() as u32));
+        table_data.extend(data.iter().cloned());
+        offset += data.len();
+    }
+    font.extend(table_data);
+    return font;
+}
+
+#[test]
+fn test_font_metrics() {
+    let font = Font::from_bytes(build_test_font()).unwrap();
+    assert_eq!(font.units_per_em(), 1000);
+    assert_eq!(font.num_glyphs(), 3);
+    assert_eq!(font.glyph_index('A'), Some(1));
+    assert_eq!(font.glyph_index('B'), Some(2));
+    assert_eq!(font.glyph_index('C'), None);
+    assert_eq!(font.horizontal_metrics(1).unwrap(), HorizontalMetrics { advance_width: 1200, left_side_bearing: 10 });
+    assert_eq!(font.horizontal_metrics(2).unwrap(), HorizontalMetrics { advance_width: 1200, left_side_bearing: 5 });
+    assert_eq!(font.kerning(1, 2), -50);
+    assert_eq!(font.kerning(2, 1), 0);
+}
+
+#[test]
+fn test_glyph_outlines() {
+    let font = Font::from_bytes(build_test_font()).unwrap();
+    let contours = font.glyph_contours(1).unwrap();
+    assert_eq!(contours.len(), 1);
+    assert_eq!(contours[0].len(), 5);
+    assert_eq!(contours[0][3], OutlinePoint { x: 500.0, y: 1200.0, on_curve: false });
+
+    let composite = font.glyph_contours(2).unwrap();
+    assert_eq!(composite[0][1], OutlinePoint { x: 1100.0, y: 0.0, on_curve: true });
+
+    assert!(font.glyph_contours(0).unwrap().is_empty());
+
+    let mut builder = PathBuilder::new();
+    font.build_glyph_path(1, 10.0, Vec2::new(0.0, 20.0), &mut builder).unwrap();
+    let path = builder.finish();
+    let aabb = path.aabb();
+    assert_eq!(aabb.x, 0.0);
+    assert_eq!(aabb.y, 8.0);
+    assert_eq!(aabb.width, 10.0);
+    assert_eq!(aabb.height, 12.0);
+    assert_eq!(path.num_vertices(), 5);
+
+    let mut builder = PathBuilder::new();
+    let advance = font.build_text_path("AB", 10.0, Vec2::new(0.0, 0.0), &mut builder).unwrap();
+    assert_eq!(advance, 23.5);
+    assert_eq!(builder.finish().path_ids().count, 2);
+}
@@ -1,4 +1,4 @@
-use vodk_math::units::world;
+use vodk_math::{Vec2, vec2};
 use vodk_math::vec2::Vector2D;
 
 pub fn line_intersection<U>(
@@ -21,17 +21,17 @@ pub fn line_intersection<U>(
     ));
 }
 
-pub fn tangent(v: world::Vec2) -> world::Vec2 {
+pub fn tangent(v: Vec2) -> Vec2 {
     let l = v.length();
-    return world::vec2(-v.y / l, v.x / l);
+    return vec2(-v.y / l, v.x / l);
 }
 
 pub fn extrude_along_tangent(
-    path: &[world::Vec2],
+    path: &[Vec2],
     i: usize,
     amount: f32,
     is_closed: bool
-) -> world::Vec2 {
+) -> Vec2 {
 
     let p1 = if i > 0 { path[i - 1] }
              else if is_closed { path[path.len()-1] }
@@ -43,8 +43,8 @@ pub fn extrude_along_tangent(
              else if is_closed { path[0] }
              else { path[i] + path[i] - path[i - 1] };
 
-    let n1 = tangent(px - p1).times(amount);
-    let n2 = tangent(p2 - px).times(amount);
+    let n1 = tangent(px - p1) * amount;
+    let n2 = tangent(p2 - px) * amount;
 
     // Segment P1-->PX
     let pn1  = p1 + n1; // p1 extruded along the tangent n1
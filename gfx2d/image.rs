@@ -10,6 +10,16 @@ pub enum Format {
     A32,
 }
 
+impl Format {
+    pub fn bytes_per_pixel(self) -> u32 {
+        return match self {
+            Format::A8 => 1,
+            Format::L8A8 => 2,
+            _ => 4,
+        };
+    }
+}
+
 pub struct Image {
     pub data: Vec<u8>,
     pub format: Format,
@@ -18,6 +28,20 @@ pub struct Image {
     pub stride: u32,
 }
 
+impl Image {
+    /// Create a tightly packed image with all bytes set to zero.
+    pub fn new(width: u32, height: u32, format: Format) -> Image {
+        let stride = width * format.bytes_per_pixel();
+        Image {
+            data: vec![0; (stride * height) as usize],
+            format: format,
+            width: width,
+            height: height,
+            stride: stride,
+        }
+    }
+}
+
 pub struct ImageView<'l> {
     pub data: &'l [u8],
     pub format: Format,
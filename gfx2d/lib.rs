@@ -1,5 +1,6 @@
 extern crate vodk_math;
 extern crate vodk_data;
+extern crate lyon;
 
 pub mod simple_allocator;
 pub mod tesselation;
@@ -10,4 +11,5 @@ pub mod range;
 pub mod style;
 pub mod geom_utils;
 pub mod image;
-pub mod triangulation;
+pub mod font;
+pub mod rasterizer;
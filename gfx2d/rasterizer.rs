@@ -0,0 +1,596 @@
+//! Simple CPU rasterizers.
+//!
+//! The anti-aliased path rasterizer renders paths into alpha masks or color images
+//! without going through tessellation, which is useful for thumbnails, glyph caches
+//! or exporting images on the server side.
+//!
+//! The other rasterizers are meant to validate the output of the tessellators.
+//! Triangles are sampled at pixel centers using the top-left fill convention, so
+//! that triangles sharing an edge never both cover the same pixel. The reference
+//! path rasterizer samples the same pixel centers with a scanline algorithm which
+//! makes it possible to compare the tessellated geometry with the source path
+//! pixel for pixel.
+
+use image::{ Image, Format };
+use color::Rgba;
+
+use lyon::tesselation::path::{ PathSlice, FillRule };
+use lyon::tesselation::vertex_builder::VertexBuffers;
+
+use vodk_math::{ Vec2, Mat3 };
+
+/// Number of sub-scanlines sampled per row of pixels by the anti-aliased rasterizer.
+///
+/// Horizontal coverage is computed exactly, vertical coverage is quantized to this
+/// number of levels.
+pub const SUB_SCANLINES: u32 = 16;
+
+/// Fill the triangles described by a vertex and an index buffer with a solid color.
+pub fn fill_triangles(vertices: &[Vec2], indices: &[u16], color: Rgba<u8>, target: &mut Image) {
+    let (w, h) = (target.width, target.height);
+    for tri in indices.chunks(3) {
+        if tri.len() < 3 {
+            break;
+        }
+        for_each_covered_pixel(
+            vertices[tri[0] as usize],
+            vertices[tri[1] as usize],
+            vertices[tri[2] as usize],
+            w, h,
+            &mut|x, y| { write_pixel(target, x, y, color); }
+        );
+    }
+}
+
+/// Fill the triangles of the vertex buffers with a solid color.
+pub fn fill_vertex_buffers(buffers: &VertexBuffers<Vec2>, color: Rgba<u8>, target: &mut Image) {
+    fill_triangles(&buffers.vertices[..], &buffers.indices[..], color, target);
+}
+
+/// Count how many triangles cover each pixel.
+///
+/// The target must be an A8 image. Each covering triangle increments the value of the
+/// pixel, which makes overlapping triangles easy to spot.
+pub fn count_triangle_coverage(vertices: &[Vec2], indices: &[u16], target: &mut Image) {
+    assert_eq!(target.format, Format::A8);
+    let (w, h, stride) = (target.width, target.height, target.stride);
+    for tri in indices.chunks(3) {
+        if tri.len() < 3 {
+            break;
+        }
+        for_each_covered_pixel(
+            vertices[tri[0] as usize],
+            vertices[tri[1] as usize],
+            vertices[tri[2] as usize],
+            w, h,
+            &mut|x, y| {
+                let offset = (y * stride + x) as usize;
+                target.data[offset] = target.data[offset].saturating_add(1);
+            }
+        );
+    }
+}
+
+/// Reference scanline rasterizer for paths.
+///
+/// The curves of the path are flattened using the provided tolerance and every
+/// sub-path is implicitly closed. A pixel is filled if its center is inside the
+/// path according to the fill rule.
+pub fn fill_path(
+    path: PathSlice,
+    fill_rule: FillRule,
+    tolerance: f32,
+    color: Rgba<u8>,
+    target: &mut Image
+) {
+    let edges = flattened_edges(path, tolerance);
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    for y in 0..target.height {
+        let cy = y as f32 + 0.5;
+        crossings.clear();
+        for &(a, b) in &edges {
+            let (top, bottom, winding) = if a.y < b.y { (a, b, 1) } else { (b, a, -1) };
+            // Half-open on the y axis so that vertices shared by two edges are
+            // only counted once.
+            if cy < top.y || cy >= bottom.y {
+                continue;
+            }
+            let t = (cy - top.y) / (bottom.y - top.y);
+            crossings.push((top.x + (bottom.x - top.x) * t, winding));
+        }
+        crossings.sort_by(|a, b| { a.0.partial_cmp(&b.0).unwrap() });
+
+        let mut winding_number = 0;
+        let mut i = 0;
+        for x in 0..target.width {
+            let cx = x as f32 + 0.5;
+            while i < crossings.len() && crossings[i].0 <= cx {
+                winding_number += crossings[i].1;
+                i += 1;
+            }
+            if fill_rule.is_inside(winding_number) {
+                write_pixel(target, x, y, color);
+            }
+        }
+    }
+}
+
+/// Anti-aliased scanline rasterizer for paths.
+///
+/// The path is flattened with the provided tolerance (in path space), transformed,
+/// and its coverage is accumulated with sub-pixel accuracy. The color is then
+/// composited over the target using the coverage: A8 targets receive the alpha
+/// channel of the color, and color targets are treated as having premultiplied alpha.
+pub fn fill_path_antialiased(
+    path: PathSlice,
+    fill_rule: FillRule,
+    tolerance: f32,
+    transform: &Mat3,
+    color: Rgba<u8>,
+    target: &mut Image
+) {
+    let mut edges = flattened_edges(path, tolerance);
+    if edges.is_empty() {
+        return;
+    }
+    let mut min_y = ::std::f32::MAX;
+    let mut max_y = ::std::f32::MIN;
+    for edge in &mut edges {
+        edge.0 = transform.transform_2d(edge.0);
+        edge.1 = transform.transform_2d(edge.1);
+        min_y = min_y.min(edge.0.y).min(edge.1.y);
+        max_y = max_y.max(edge.0.y).max(edge.1.y);
+    }
+
+    let width = target.width;
+    let first_row = min_y.floor().max(0.0) as u32;
+    let end_row = (max_y.ceil().max(0.0) as u32).min(target.height);
+    let sample_weight = 1.0 / SUB_SCANLINES as f32;
+
+    let mut row_edges: Vec<(Vec2, Vec2, i32)> = Vec::new();
+    let mut crossings: Vec<(f32, i32)> = Vec::new();
+    let mut coverage: Vec<f32> = vec![0.0; width as usize];
+
+    for y in first_row..end_row {
+        let row_top = y as f32;
+        let row_bottom = row_top + 1.0;
+
+        row_edges.clear();
+        for &(a, b) in &edges {
+            if a.y == b.y {
+                continue;
+            }
+            let (top, bottom, winding) = if a.y < b.y { (a, b, 1) } else { (b, a, -1) };
+            if bottom.y <= row_top || top.y >= row_bottom {
+                continue;
+            }
+            row_edges.push((top, bottom, winding));
+        }
+        if row_edges.is_empty() {
+            continue;
+        }
+
+        for c in &mut coverage {
+            *c = 0.0;
+        }
+
+        for s in 0..SUB_SCANLINES {
+            let sy = row_top + (s as f32 + 0.5) * sample_weight;
+            crossings.clear();
+            for &(top, bottom, winding) in &row_edges {
+                if sy < top.y || sy >= bottom.y {
+                    continue;
+                }
+                let t = (sy - top.y) / (bottom.y - top.y);
+                crossings.push((top.x + (bottom.x - top.x) * t, winding));
+            }
+            crossings.sort_by(|a, b| { a.0.partial_cmp(&b.0).unwrap() });
+
+            let mut winding_number = 0;
+            let mut span_start = 0.0;
+            for &(x, winding) in &crossings {
+                let was_inside = fill_rule.is_inside(winding_number);
+                winding_number += winding;
+                let is_inside = fill_rule.is_inside(winding_number);
+                if !was_inside && is_inside {
+                    span_start = x;
+                } else if was_inside && !is_inside {
+                    accumulate_span(&mut coverage[..], span_start, x, sample_weight);
+                }
+            }
+        }
+
+        for x in 0..width {
+            let c = coverage[x as usize];
+            if c > 0.0 {
+                blend_pixel(target, x, y, color, c.min(1.0));
+            }
+        }
+    }
+}
+
+/// Render the coverage of a path into a new A8 image.
+pub fn render_path_mask(
+    path: PathSlice,
+    fill_rule: FillRule,
+    tolerance: f32,
+    transform: &Mat3,
+    width: u32,
+    height: u32
+) -> Image {
+    let mut mask = Image::new(width, height, Format::A8);
+    fill_path_antialiased(
+        path, fill_rule, tolerance, transform,
+        Rgba { r: 255, g: 255, b: 255, a: 255 },
+        &mut mask
+    );
+    return mask;
+}
+
+/// The result of comparing tessellated geometry with the path it was generated from.
+pub struct CoverageComparison {
+    /// Number of pixels covered by the path.
+    pub expected: u32,
+    /// Pixels covered by the path but not by any triangle.
+    pub missing: u32,
+    /// Pixels covered by at least one triangle but not by the path.
+    pub extra: u32,
+    /// Pixels covered by more than one triangle.
+    pub overlapping: u32,
+    /// An R8G8B8A8 image showing missing pixels in red, extra pixels in blue and
+    /// overlapping pixels in yellow. Correctly covered pixels are white.
+    pub diff: Image,
+}
+
+impl CoverageComparison {
+    pub fn num_errors(&self) -> u32 { self.missing + self.extra + self.overlapping }
+
+    pub fn is_exact(&self) -> bool { self.num_errors() == 0 }
+}
+
+/// Rasterize both the tessellated geometry and the path it was generated from and
+/// compare the coverage of each pixel.
+///
+/// Coordinates are interpreted in pixels, the path is expected to be positioned
+/// within the first width x height pixels.
+pub fn compare_with_path(
+    path: PathSlice,
+    fill_rule: FillRule,
+    tolerance: f32,
+    buffers: &VertexBuffers<Vec2>,
+    width: u32,
+    height: u32
+) -> CoverageComparison {
+    let mut reference = Image::new(width, height, Format::A8);
+    fill_path(path, fill_rule, tolerance, Rgba { r: 0, g: 0, b: 0, a: 1 }, &mut reference);
+
+    let mut coverage = Image::new(width, height, Format::A8);
+    count_triangle_coverage(&buffers.vertices[..], &buffers.indices[..], &mut coverage);
+
+    let mut result = CoverageComparison {
+        expected: 0,
+        missing: 0,
+        extra: 0,
+        overlapping: 0,
+        diff: Image::new(width, height, Format::R8G8B8A8),
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let offset = (y * width + x) as usize;
+            let expected = reference.data[offset] != 0;
+            let count = coverage.data[offset];
+            if expected {
+                result.expected += 1;
+            }
+            let color = if count > 1 {
+                result.overlapping += 1;
+                Rgba { r: 255, g: 255, b: 0, a: 255 }
+            } else if expected && count == 0 {
+                result.missing += 1;
+                Rgba { r: 255, g: 0, b: 0, a: 255 }
+            } else if !expected && count != 0 {
+                result.extra += 1;
+                Rgba { r: 0, g: 0, b: 255, a: 255 }
+            } else if expected {
+                Rgba { r: 255, g: 255, b: 255, a: 255 }
+            } else {
+                continue;
+            };
+            write_pixel(&mut result.diff, x, y, color);
+        }
+    }
+
+    return result;
+}
+
+/// Write a color into a pixel, converting it to the format of the image.
+pub fn write_pixel(target: &mut Image, x: u32, y: u32, color: Rgba<u8>) {
+    let offset = (y * target.stride + x * target.format.bytes_per_pixel()) as usize;
+    let data = &mut target.data[..];
+    match target.format {
+        Format::R8G8B8A8 | Format::R8G8B8X8 => {
+            data[offset] = color.r;
+            data[offset + 1] = color.g;
+            data[offset + 2] = color.b;
+            data[offset + 3] = color.a;
+        }
+        Format::B8G8R8A8 | Format::B8G8R8X8 => {
+            data[offset] = color.b;
+            data[offset + 1] = color.g;
+            data[offset + 2] = color.r;
+            data[offset + 3] = color.a;
+        }
+        Format::A8 => {
+            data[offset] = color.a;
+        }
+        _ => {
+            panic!("Unsupported format {:?}", target.format);
+        }
+    }
+}
+
+/// Composite a color over a pixel with the given coverage (between 0 and 1).
+///
+/// Color targets are treated as having premultiplied alpha.
+pub fn blend_pixel(target: &mut Image, x: u32, y: u32, color: Rgba<u8>, coverage: f32) {
+    let offset = (y * target.stride + x * target.format.bytes_per_pixel()) as usize;
+    let alpha = color.a as f32 / 255.0 * coverage;
+    let inv_alpha = 1.0 - alpha;
+    let blend = |src: u8, dst: u8| -> u8 {
+        (src as f32 * alpha + dst as f32 * inv_alpha + 0.5) as u8
+    };
+    let data = &mut target.data[..];
+    match target.format {
+        Format::R8G8B8A8 | Format::R8G8B8X8 => {
+            data[offset] = blend(color.r, data[offset]);
+            data[offset + 1] = blend(color.g, data[offset + 1]);
+            data[offset + 2] = blend(color.b, data[offset + 2]);
+            data[offset + 3] = blend(255, data[offset + 3]);
+        }
+        Format::B8G8R8A8 | Format::B8G8R8X8 => {
+            data[offset] = blend(color.b, data[offset]);
+            data[offset + 1] = blend(color.g, data[offset + 1]);
+            data[offset + 2] = blend(color.r, data[offset + 2]);
+            data[offset + 3] = blend(255, data[offset + 3]);
+        }
+        Format::A8 => {
+            data[offset] = blend(255, data[offset]);
+        }
+        _ => {
+            panic!("Unsupported format {:?}", target.format);
+        }
+    }
+}
+
+// Add the horizontal coverage of the span [x0, x1] to the pixels it touches.
+fn accumulate_span(coverage: &mut [f32], x0: f32, x1: f32, weight: f32) {
+    let width = coverage.len() as f32;
+    let x0 = x0.max(0.0).min(width);
+    let x1 = x1.max(0.0).min(width);
+    if x1 <= x0 {
+        return;
+    }
+    let first = x0.floor() as usize;
+    let last = x1.floor() as usize;
+    if first == last {
+        coverage[first] += (x1 - x0) * weight;
+        return;
+    }
+    coverage[first] += (first as f32 + 1.0 - x0) * weight;
+    for i in (first + 1)..last {
+        coverage[i] += weight;
+    }
+    if last < coverage.len() {
+        coverage[last] += (x1 - last as f32) * weight;
+    }
+}
+
+fn flattened_edges(path: PathSlice, tolerance: f32) -> Vec<(Vec2, Vec2)> {
+    let mut edges = Vec::new();
+    let mut points: Vec<Vec2> = Vec::new();
+    for id in path.path_ids() {
+        points.clear();
+        path.sub_path(id).flatten(tolerance, &mut points);
+        let n = points.len();
+        if n < 2 {
+            continue;
+        }
+        for i in 0..n {
+            edges.push((points[i], points[(i + 1) % n]));
+        }
+    }
+    return edges;
+}
+
+// Positive if p is on the inner side of a->b for a triangle with positive area.
+fn edge_function(a: Vec2, b: Vec2, p: Vec2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// Pixels exactly on an edge are only covered if the edge is a top or a left edge.
+fn is_top_left(a: Vec2, b: Vec2) -> bool {
+    let d = b - a;
+    d.y < 0.0 || (d.y == 0.0 && d.x > 0.0)
+}
+
+fn for_each_covered_pixel<F: FnMut(u32, u32)>(
+    a: Vec2, b: Vec2, c: Vec2,
+    width: u32, height: u32,
+    callback: &mut F
+) {
+    let area = edge_function(a, b, c);
+    if area == 0.0 {
+        return;
+    }
+    // Make the winding order consistent.
+    let (b, c) = if area < 0.0 { (c, b) } else { (b, c) };
+
+    let min_x = a.x.min(b.x).min(c.x).floor().max(0.0);
+    let min_y = a.y.min(b.y).min(c.y).floor().max(0.0);
+    let max_x = a.x.max(b.x).max(c.x).ceil().min(width as f32);
+    let max_y = a.y.max(b.y).max(c.y).ceil().min(height as f32);
+    if min_x >= max_x || min_y >= max_y {
+        return;
+    }
+
+    let edges = [(a, b), (b, c), (c, a)];
+    let top_left = [is_top_left(a, b), is_top_left(b, c), is_top_left(c, a)];
+
+    for y in (min_y as u32)..(max_y as u32) {
+        'pixels: for x in (min_x as u32)..(max_x as u32) {
+            let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            for i in 0..3 {
+                let w = edge_function(edges[i].0, edges[i].1, p);
+                if w < 0.0 || (w == 0.0 && !top_left[i]) {
+                    continue 'pixels;
+                }
+            }
+            callback(x, y);
+        }
+    }
+}
+
+#[cfg(test)]
+use lyon::tesselation::path_builder::PathBuilder;
+#[cfg(test)]
+use lyon::tesselation::path_tesselator::{ tesselate_path_fill, TesselatorOptions };
+#[cfg(test)]
+use lyon::tesselation::vertex_builder::simple_vertex_builder;
+
+#[test]
+fn test_shared_edges_covered_once() {
+    // A quad split along its diagonal, going through pixel centers.
+    let vertices = [
+        Vec2::new(0.5, 0.5), Vec2::new(8.5, 0.5),
+        Vec2::new(8.5, 8.5), Vec2::new(0.5, 8.5),
+    ];
+    let indices = [0, 1, 2, 0, 2, 3];
+    let mut coverage = Image::new(10, 10, Format::A8);
+    count_triangle_coverage(&vertices, &indices, &mut coverage);
+    let mut covered = 0;
+    for &value in &coverage.data {
+        assert!(value <= 1);
+        covered += value as u32;
+    }
+    assert_eq!(covered, 64);
+}
+
+#[test]
+fn test_fill_path_rules() {
+    // Two overlapping squares with the same orientation.
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::new(1.0, 1.0));
+    builder.line_to(Vec2::new(5.0, 1.0));
+    builder.line_to(Vec2::new(5.0, 5.0));
+    builder.line_to(Vec2::new(1.0, 5.0));
+    builder.close();
+    builder.move_to(Vec2::new(3.0, 3.0));
+    builder.line_to(Vec2::new(7.0, 3.0));
+    builder.line_to(Vec2::new(7.0, 7.0));
+    builder.line_to(Vec2::new(3.0, 7.0));
+    builder.close();
+    let path = builder.finish();
+
+    let white = Rgba { r: 255, g: 255, b: 255, a: 255 };
+    let mut even_odd = Image::new(8, 8, Format::A8);
+    fill_path(path.as_slice(), FillRule::EvenOdd, 0.1, white, &mut even_odd);
+    let mut non_zero = Image::new(8, 8, Format::A8);
+    fill_path(path.as_slice(), FillRule::NonZero, 0.1, white, &mut non_zero);
+
+    let count = |img: &Image| { img.data.iter().filter(|v| **v != 0).count() };
+    assert_eq!(count(&non_zero), 28);
+    assert_eq!(count(&even_odd), 24);
+    assert_eq!(non_zero.data[4 * 8 + 4], 255);
+    assert_eq!(even_odd.data[4 * 8 + 4], 0);
+}
+
+#[test]
+fn test_compare_tessellation() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::new(2.0, 2.0));
+    builder.line_to(Vec2::new(30.0, 4.0));
+    builder.line_to(Vec2::new(16.0, 12.0));
+    builder.line_to(Vec2::new(28.0, 30.0));
+    builder.line_to(Vec2::new(4.0, 26.0));
+    builder.close();
+    let path = builder.finish();
+
+    let mut buffers: VertexBuffers<Vec2> = VertexBuffers::new();
+    tesselate_path_fill(
+        path.as_slice(),
+        &TesselatorOptions::new(),
+        &mut simple_vertex_builder(&mut buffers)
+    ).unwrap();
+
+    let result = compare_with_path(path.as_slice(), FillRule::EvenOdd, 0.1, &buffers, 32, 32);
+    assert!(result.expected > 0);
+    assert!(result.is_exact());
+
+    // Dropping a triangle must be detected.
+    let n = buffers.indices.len();
+    buffers.indices.truncate(n - 3);
+    let result = compare_with_path(path.as_slice(), FillRule::EvenOdd, 0.1, &buffers, 32, 32);
+    assert!(result.missing > 0);
+    assert_eq!(result.extra, 0);
+}
+
+#[test]
+fn test_antialiased_coverage() {
+    // A square with its edges in the middle of pixels.
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::new(1.5, 1.5));
+    builder.line_to(Vec2::new(3.5, 1.5));
+    builder.line_to(Vec2::new(3.5, 3.5));
+    builder.line_to(Vec2::new(1.5, 3.5));
+    builder.close();
+    let path = builder.finish();
+
+    let mask = render_path_mask(path.as_slice(), FillRule::NonZero, 0.1, &Mat3::identity(), 5, 5);
+    let expected = [
+        0,   0,   0,   0,  0,
+        0,  64, 128,  64,  0,
+        0, 128, 255, 128,  0,
+        0,  64, 128,  64,  0,
+        0,   0,   0,   0,  0,
+    ];
+    assert_eq!(&mask.data[..], &expected[..]);
+}
+
+#[test]
+fn test_antialiased_fill_rules() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::new(0.0, 0.0));
+    builder.line_to(Vec2::new(4.0, 0.0));
+    builder.line_to(Vec2::new(4.0, 4.0));
+    builder.line_to(Vec2::new(0.0, 4.0));
+    builder.close();
+    builder.move_to(Vec2::new(1.0, 1.0));
+    builder.line_to(Vec2::new(3.0, 1.0));
+    builder.line_to(Vec2::new(3.0, 3.0));
+    builder.line_to(Vec2::new(1.0, 3.0));
+    builder.close();
+    let path = builder.finish();
+
+    let transform = Mat3::scale(Vec2::new(0.5, 0.5));
+    let non_zero = render_path_mask(path.as_slice(), FillRule::NonZero, 0.1, &transform, 2, 2);
+    let even_odd = render_path_mask(path.as_slice(), FillRule::EvenOdd, 0.1, &transform, 2, 2);
+    assert_eq!(&non_zero.data[..], &[255, 255, 255, 255][..]);
+    assert_eq!(&even_odd.data[..], &[191, 191, 191, 191][..]);
+}
+
+#[test]
+fn test_antialiased_color_blending() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(Vec2::new(0.0, 0.0));
+    builder.line_to(Vec2::new(1.0, 0.0));
+    builder.line_to(Vec2::new(1.0, 0.5));
+    builder.line_to(Vec2::new(0.0, 0.5));
+    builder.close();
+    let path = builder.finish();
+
+    let mut target = Image::new(1, 1, Format::R8G8B8A8);
+    let red = Rgba { r: 255, g: 0, b: 0, a: 255 };
+    fill_path_antialiased(path.as_slice(), FillRule::NonZero, 0.1, &Mat3::identity(), red, &mut target);
+    assert_eq!(&target.data[..], &[128, 0, 0, 128][..]);
+}
@@ -278,6 +278,8 @@ impl<'l> Iterator for BlockIterator<'l> {
 
 #[test]
 fn test_allocator() {
+    use std::iter::FromIterator;
+
     let mut alloc = AllocatorHelper::new(Range::new(0, 100), BlockState::Unused);
     assert_eq!(alloc.get_first(), alloc.get_last());
     let a0 = alloc.get_first();
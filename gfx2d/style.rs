@@ -1,14 +1,14 @@
-use vodk_math::units::texels;
+use vodk_math::Mat3;
 use color::Rgba;
 
 pub enum FillStyle<'l> {
-    Texture(&'l texels::Mat3),
+    Texture(&'l Mat3),
     Color(&'l Rgba<u8>),
     None,
 }
 
 pub enum StrokeStyle<'l> {
-    Texture(&'l texels::Mat3),
+    Texture(&'l Mat3),
     Color(&'l Rgba<u8>),
     None,
 }
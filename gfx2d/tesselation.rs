@@ -1,12 +1,12 @@
-use vodk_math::units::world;
-use vodk_math::units::texels;
+use vodk_math::{Vec2, vec2, Mat3, Rectangle};
 use geom_utils::{extrude_along_tangent, tangent, line_intersection};
 use style::{FillStyle, StrokeStyle, StrokeFlags};
 use style;
 use color::Rgba;
 use shapes;
+use lyon::tesselation::math_utils::num_arc_segments;
 
-static PI: f32 = 3.1415;
+use std::f32::consts::PI;
 
 pub type TesselationFlags = u32;
 pub static VERTEX_ANTIALIASING: TesselationFlags = 1;
@@ -15,8 +15,8 @@ pub static CONVEX_SHAPE: TesselationFlags = 2;
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Pos2DNormal2DColorExtrusion {
-    pub pos: world::Vec2,
-    pub normal: world::Vec2,
+    pub pos: Vec2,
+    pub normal: Vec2,
     pub color: Rgba<f32>,
     pub extrusion : f32,
 }
@@ -35,12 +35,12 @@ pub enum PointType {
 
 
 pub fn path_to_line_vbo(
-    path: &[world::Vec2],
+    path: &[Vec2],
     is_closed: bool,
     flags: TesselationFlags,
     line_width_fn: &Fn(usize) -> f32,
     color_fn: &Fn(usize, PointType) -> Rgba<f32>,
-    transform: world::Mat3,
+    transform: Mat3,
     vbo: &mut [Pos2DNormal2DColorExtrusion],
 ) {
     let vertex_antialiasing = (flags & VERTEX_ANTIALIASING) != 0;
@@ -57,7 +57,7 @@ pub fn path_to_line_vbo(
     let mut n1 = tangent(px - p1);
 
     for i in 0 .. path.len() {
-        let pos = transform.transform_2d(&path[i]);
+        let pos = transform.transform_2d(path[i]);
 
         let color = color_fn(i, PointType::Border);
         let color_aa = color_fn(i, PointType::Antialias);
@@ -89,7 +89,7 @@ pub fn path_to_line_vbo(
                 }
             }
         };
-        let normal = transform.transform_2d(&(inter - px));
+        let normal = transform.transform_2d(inter - px);
 
         // Shift towards the next point; some values don't need to be recomputed
         // since the segment 1 is the segment 2 of the previous iteration.
@@ -100,7 +100,7 @@ pub fn path_to_line_vbo(
 
         let line_width = line_width_fn(i) / 2.0;
         let aa_width = 1.0;
-        let extrusion_ws = normal.times(line_width);
+        let extrusion_ws = normal * line_width;
 
         vbo[i * stride].pos = pos + extrusion_ws;
         vbo[i * stride].normal = normal;
@@ -255,18 +255,18 @@ impl<'l, T: Copy> VertexStream<'l, T> {
 
 pub fn fill_rectangle<'l, T: VertexType2D>(
     stream: &mut VertexStream<'l, T>,
-    rectangle: &world::Rectangle,
-    transform: &world::Mat3,
+    rectangle: &Rectangle,
+    transform: &Mat3,
     fill: FillStyle<'l>,
 ) -> Range {
     let first_vertex = stream.vertex_cursor as u16;
     let first_index = stream.index_cursor as u16;
-    let uv_rect = texels::rect(0.0, 0.0, 1.0, 1.0);
+    let uv_rect = Rectangle::new(0.0, 0.0, 1.0, 1.0);
 
-    let mut a: T = VertexType2D::from_pos(&transform.transform_2d(&rectangle.top_left()));
-    let mut b: T = VertexType2D::from_pos(&transform.transform_2d(&rectangle.top_right()));
-    let mut c: T = VertexType2D::from_pos(&transform.transform_2d(&rectangle.bottom_right()));
-    let mut d: T = VertexType2D::from_pos(&transform.transform_2d(&rectangle.bottom_left()));
+    let mut a: T = VertexType2D::from_pos(&transform.transform_2d(rectangle.top_left()));
+    let mut b: T = VertexType2D::from_pos(&transform.transform_2d(rectangle.top_right()));
+    let mut c: T = VertexType2D::from_pos(&transform.transform_2d(rectangle.bottom_right()));
+    let mut d: T = VertexType2D::from_pos(&transform.transform_2d(rectangle.bottom_left()));
 
     match fill {
         FillStyle::None => {}
@@ -277,10 +277,10 @@ pub fn fill_rectangle<'l, T: VertexType2D>(
             d.set_color(color);
         }
         FillStyle::Texture(uv_transform) => {
-            a.set_uv(&uv_transform.transform_2d(&uv_rect.top_left()));
-            b.set_uv(&uv_transform.transform_2d(&uv_rect.top_right()));
-            c.set_uv(&uv_transform.transform_2d(&uv_rect.bottom_right()));
-            d.set_uv(&uv_transform.transform_2d(&uv_rect.bottom_left()));
+            a.set_uv(&uv_transform.transform_2d(uv_rect.top_left()));
+            b.set_uv(&uv_transform.transform_2d(uv_rect.top_right()));
+            c.set_uv(&uv_transform.transform_2d(uv_rect.bottom_right()));
+            d.set_uv(&uv_transform.transform_2d(uv_rect.bottom_left()));
         }
     }
     stream.push_quad(&a, &b, &c, &d);
@@ -296,14 +296,14 @@ pub fn fill_circle<'l, T: VertexType2D>(
     stream: &mut VertexStream<'l, T>,
     circle: &shapes::Circle,
     num_points: u32,
-    transform: &world::Mat3,
+    transform: &Mat3,
     fill: FillStyle<'l>,
 ) -> Range {
     let first_vertex = stream.vertex_cursor as u16;
     let first_index = stream.index_cursor as u16;
 
     let mut center: T = VertexType2D::from_pos(
-        &transform.transform_2d(&world::vec2(
+        &transform.transform_2d(vec2(
             circle.center.x,
             circle.center.y
         )
@@ -313,7 +313,7 @@ pub fn fill_circle<'l, T: VertexType2D>(
         FillStyle::None => {}
         FillStyle::Color(color) => { center.set_color(color) }
         FillStyle::Texture(uv_transform) => {
-            center.set_uv(&uv_transform.transform_2d(&texels::vec2(0.5, 0.5)))
+            center.set_uv(&uv_transform.transform_2d(vec2(0.5, 0.5)))
         }
     }
     stream.push_vertex(&center);
@@ -323,7 +323,7 @@ pub fn fill_circle<'l, T: VertexType2D>(
         let dy = (i as f32 / num_points as f32 * 2.0 * PI).sin();
 
         let mut vertex: T = VertexType2D::from_pos(
-            &transform.transform_2d(&world::vec2(
+            &transform.transform_2d(vec2(
                 circle.center.x + circle.radius * dx,
                 circle.center.y + circle.radius * dy
             )
@@ -334,7 +334,7 @@ pub fn fill_circle<'l, T: VertexType2D>(
             FillStyle::Color(color) => { vertex.set_color(color) }
             FillStyle::Texture(uv_transform) => {
                 vertex.set_uv(
-                    &uv_transform.transform_2d(&texels::vec2(
+                    &uv_transform.transform_2d(vec2(
                         0.5 + dx * 0.5,
                         0.5 + dy * 0.5
                     ))
@@ -355,11 +355,24 @@ pub fn fill_circle<'l, T: VertexType2D>(
     };
 }
 
+/// Same as fill_circle, the number of points following the radius of the circle so that the
+/// distance between the circle and the generated triangles is at most the tolerance.
+pub fn fill_circle_with_tolerance<'l, T: VertexType2D>(
+    stream: &mut VertexStream<'l, T>,
+    circle: &shapes::Circle,
+    tolerance: f32,
+    transform: &Mat3,
+    fill: FillStyle<'l>,
+) -> Range {
+    let num_points = num_arc_segments(circle.radius, 2.0 * PI, tolerance).max(3);
+    return fill_circle(stream, circle, num_points, transform, fill);
+}
+
 pub fn fill_grid<'l, T: VertexType2D>(
     stream: &mut VertexStream<'l, T>,
     columns: &[f32],
     lines: &[f32],
-    transform: &world::Mat3,
+    transform: &Mat3,
     fill: FillStyle<'l>,
     uv_grid: Option<(&'l[f32], &'l[f32])>
 ) -> Range {
@@ -372,7 +385,7 @@ pub fn fill_grid<'l, T: VertexType2D>(
     for j in 0 .. lines.len() {
         for i in 0 .. columns.len() {
             let mut vertex: T = VertexType2D::from_pos(
-                &transform.transform_2d(&world::vec2(columns[i],lines[j]))
+                &transform.transform_2d(vec2(columns[i],lines[j]))
             );
 
             match fill {
@@ -381,12 +394,12 @@ pub fn fill_grid<'l, T: VertexType2D>(
                 FillStyle::Texture(uv_transform) => {
                     let uv = match uv_grid {
                         Some((uv_columns, uv_lines)) => {
-                            uv_transform.transform_2d(&texels::vec2(
+                            uv_transform.transform_2d(vec2(
                                 uv_columns[i], uv_lines[j]
                             ))
                         }
                         None => {
-                            uv_transform.transform_2d(&texels::vec2(
+                            uv_transform.transform_2d(vec2(
                                 (columns[i] - columns[0]) / (columns[columns.len()-1] - columns[0]),
                                 (lines[i] - lines[0]) / (lines[lines.len()-1] - lines[0])
                             ))
@@ -422,22 +435,22 @@ pub fn fill_grid<'l, T: VertexType2D>(
 
 pub fn fill_convex_path<'l, T: VertexType2D>(
     stream: &mut VertexStream<'l, T>,
-    path: &[world::Vec2],
-    aabb: &world::Rectangle,
-    transform: &world::Mat3,
+    path: &[Vec2],
+    aabb: &Rectangle,
+    transform: &Mat3,
     fill: FillStyle<'l>,
 ) -> Range {
     let first_vertex = stream.vertex_cursor as u16;
     let first_index = stream.index_cursor as u16;
 
     for i in 0 .. path.len() {
-        let mut vertex: T = VertexType2D::from_pos(&transform.transform_2d(&path[i]));
+        let mut vertex: T = VertexType2D::from_pos(&transform.transform_2d(path[i]));
 
         match fill {
             FillStyle::None => {},
             FillStyle::Color(color) => { vertex.set_color(color) }
             FillStyle::Texture(uv_transform) => {
-                vertex.set_uv(&uv_transform.transform_2d(&texels::vec2(
+                vertex.set_uv(&uv_transform.transform_2d(vec2(
                     (path[i].x - aabb.x) / aabb.width,
                     (path[i].y - aabb.y) / aabb.height
                 )));
@@ -462,9 +475,9 @@ pub fn fill_convex_path<'l, T: VertexType2D>(
 
 pub fn stroke_path<'l, T: VertexType2D>(
     stream: &mut VertexStream<'l, T>,
-    path: &[world::Vec2],
-    aabb: &world::Rectangle,
-    transform: &world::Mat3,
+    path: &[Vec2],
+    aabb: &Rectangle,
+    transform: &Mat3,
     style: StrokeStyle<'l>,
     thickness: f32,
     flags: StrokeFlags
@@ -489,8 +502,8 @@ pub fn stroke_path<'l, T: VertexType2D>(
             panic!("unreached");
         }
 
-        let mut v1: T = VertexType2D::from_pos(&transform.transform_2d(&p1));
-        let mut v2: T = VertexType2D::from_pos(&transform.transform_2d(&p2));
+        let mut v1: T = VertexType2D::from_pos(&transform.transform_2d(p1));
+        let mut v2: T = VertexType2D::from_pos(&transform.transform_2d(p2));
 
         match style {
             StrokeStyle::None => {},
@@ -534,10 +547,53 @@ pub fn stroke_path<'l, T: VertexType2D>(
 }
 
 pub trait VertexType2D: Copy {
-    fn from_pos(pos: &world::Vec2) -> Self;
-    fn set_pos(&mut self, &world::Vec2);
-    fn set_uv(&mut self, &texels::Vec2);
+    fn from_pos(pos: &Vec2) -> Self;
+    fn set_pos(&mut self, &Vec2);
+    fn set_uv(&mut self, &Vec2);
     fn set_color(&mut self, &Rgba<u8>);
 }
 
+#[cfg(test)]
+#[derive(Copy, Clone, Debug)]
+struct TestVertex {
+    pos: Vec2,
+}
 
+#[cfg(test)]
+impl VertexType2D for TestVertex {
+    fn from_pos(pos: &Vec2) -> TestVertex { TestVertex { pos: *pos } }
+    fn set_pos(&mut self, pos: &Vec2) { self.pos = *pos; }
+    fn set_uv(&mut self, _: &Vec2) {}
+    fn set_color(&mut self, _: &Rgba<u8>) {}
+}
+
+#[test]
+fn test_fill_circle_with_tolerance() {
+    let circle = shapes::Circle { center: vec2(1.0, 2.0), radius: 10.0 };
+    let mut previous_count = 0;
+    for &tolerance in &[1.0, 0.1, 0.01] {
+        let mut vertices = [TestVertex { pos: vec2(0.0, 0.0) }; 512];
+        let mut indices = [0; 1536];
+        let range = {
+            let mut stream = VertexStream {
+                vertices: &mut vertices,
+                indices: &mut indices,
+                vertex_cursor: 0,
+                index_cursor: 0,
+                base_vertex: 0,
+            };
+            fill_circle_with_tolerance(
+                &mut stream, &circle, tolerance, &Mat3::identity(), FillStyle::None
+            )
+        };
+        assert!(range.vertex_count > previous_count);
+        previous_count = range.vertex_count;
+
+        // The middle of each edge along the border is the point farthest from the circle.
+        for i in 1 .. range.vertex_count as usize - 1 {
+            let mid = (vertices[i].pos + vertices[i + 1].pos) * 0.5;
+            let distance = circle.radius - (mid - circle.center).length();
+            assert!(distance <= tolerance * 1.01, "{} > {}", distance, tolerance);
+        }
+    }
+}
@@ -4,7 +4,7 @@ use std::hash::{ Hash, Hasher };
 
 mod id_vector;
 mod id_list;
-//pub mod sparse_id_vector;
+pub mod sparse_id_vector;
 
 pub use id_vector::{ IdSlice, MutIdSlice, IdVec };
 pub use id_list::{ IdFreeList, NullId, NoneAsNullId };
@@ -1,11 +1,15 @@
-use super::{ Identifier, FromIndex, ToIndex };
+use super::{ Identifier, FromIndex, ToIndex, IdVec };
 use std::default::Default;
 use std::slice;
 use std::marker::PhantomData;
 use std::ops;
 
+/// A vector indexed by ids, which slots can be removed and reused.
 pub struct SparseIdVector<ID:Identifier, Data> {
     data: Vec<Data>,
+    // One flag per slot, set for the slots that are not in use.
+    free: Vec<bool>,
+    free_slots: Vec<usize>,
     _idtype: PhantomData<ID>
 }
 
@@ -16,6 +20,8 @@ impl<ID:Identifier, Data> SparseIdVector<ID, Data> {
     pub fn new() -> SparseIdVector<ID, Data> {
         SparseIdVector {
             data: Vec::new(),
+            free: Vec::new(),
+            free_slots: Vec::new(),
             _idtype: PhantomData
         }
     }
@@ -24,6 +30,8 @@ impl<ID:Identifier, Data> SparseIdVector<ID, Data> {
     pub fn with_capacity(size: ID::Handle) -> SparseIdVector<ID, Data> {
         SparseIdVector {
             data: Vec::with_capacity(size.to_index()),
+            free: Vec::with_capacity(size.to_index()),
+            free_slots: Vec::new(),
             _idtype: PhantomData
         }
     }
@@ -31,17 +39,24 @@ impl<ID:Identifier, Data> SparseIdVector<ID, Data> {
     /// Create an SparseIdVector by recycling a Vec and its content.
     pub fn from_vec(vec: Vec<Data>) -> SparseIdVector<ID, Data> {
         SparseIdVector {
+            free: vec![false; vec.len()],
             data: vec,
+            free_slots: Vec::new(),
             _idtype: PhantomData
         }
     }
 
     /// Consume the SparseIdVector and create a Vec.
+    ///
+    /// The Vec also contains the elements of the removed slots.
     pub fn into_vec(self) -> Vec<Data> { self.data }
 
-    /// Number of elements in the SparseIdVector
+    /// Number of slots in the SparseIdVector, including the removed ones.
     pub fn len(&self) -> usize { self.data.len() }
 
+    /// Number of elements in the SparseIdVector, not counting the removed slots.
+    pub fn count(&self) -> usize { self.data.len() - self.free_slots.len() }
+
     /// Return the nth element of the SparseIdVector using an usize index rather than an Id (à la Vec).
     pub fn nth(&self, idx: usize) -> &Data { &self.data[idx] }
 
@@ -54,9 +69,14 @@ impl<ID:Identifier, Data> SparseIdVector<ID, Data> {
     // pub fn iter_mut<'l>(&'l mut self) -> slice::IterMut<'l, Data> { self.data.iter_mut() }
 
     /// Add an element to the SparseIdVector and return its Id.
-    /// This method can cause the storage to be reallocated.
+    /// This method reuses the slots of removed elements if any, otherwise it can cause the
+    /// storage to be reallocated.
     pub fn add(&mut self, elt: Data) -> ID {
-        // TODO: have a proper allocator-like logic
+        if let Some(index) = self.free_slots.pop() {
+            self.data[index] = elt;
+            self.free[index] = false;
+            return FromIndex::from_index(index);
+        }
         return self.push(elt);
     }
 
@@ -66,38 +86,104 @@ impl<ID:Identifier, Data> SparseIdVector<ID, Data> {
     pub fn push(&mut self, elt: Data) -> ID {
         let index = self.data.len();
         self.data.push(elt);
+        self.free.push(false);
         return FromIndex::from_index(index);
     }
 
-    pub fn remove(&mut self, _id: ID) {
-        // TODO
+    /// Mark the slot of an element as free so that it can be reused by add.
+    /// Note that this does not attempt to drop the element.
+    ///
+    /// Panics if the element was already removed or was never added.
+    pub fn remove(&mut self, id: ID) {
+        assert!(self.has_id(id), "Can't remove slot {}, it is not in use.", id.to_index());
+        self.free[id.to_index()] = true;
+        self.free_slots.push(id.to_index());
+    }
+
+    /// Put an element in the slot of a removed element, which add won't reuse anymore.
+    ///
+    /// Panics if the slot is in use or was never added.
+    pub fn revive(&mut self, id: ID, elt: Data) {
+        let index = id.to_index();
+        assert!(
+            index < self.data.len() && self.free[index],
+            "Can't revive slot {}, it is not removed.", index
+        );
+        self.data[index] = elt;
+        self.free[index] = false;
+        self.free_slots.retain(|&slot| slot != index);
     }
 
-    pub fn has_id(&self, id: ID) -> bool { id.to_index() < self.data.len() }
+    pub fn has_id(&self, id: ID) -> bool {
+        let index = id.to_index();
+        index < self.data.len() && !self.free[index]
+    }
 
-    pub fn first_id(&self) -> Option<ID> {
-        return if self.data.len() > 0 { Some(ID::from_index(0)) } else { None };
+    /// Iterate over the ids of the elements that haven't been removed.
+    pub fn ids<'l>(&'l self) -> SparseIdIter<'l, ID, Data> {
+        SparseIdIter { vector: self, index: 0 }
+    }
+
+    /// Return the id of the first element that hasn't been removed.
+    pub fn first_id(&self) -> Option<ID> { self.next_id_from(0) }
+
+    /// Return the id of the next element that hasn't been removed.
+    pub fn next_id(&self, id: ID) -> Option<ID> { self.next_id_from(id.to_index() + 1) }
+
+    fn next_id_from(&self, index: usize) -> Option<ID> {
+        for i in index..self.data.len() {
+            if !self.free[i] {
+                return Some(ID::from_index(i));
+            }
+        }
+        None
     }
 
     /// Drop all of the contained elements and clear the SparseIdVector's storage.
     pub fn clear(&mut self) {
         self.data.clear();
+        self.free.clear();
+        self.free_slots.clear();
     }
 
     pub fn reserve(&mut self, size: ID::Handle) {
         self.data.reserve(size.to_index());
+        self.free.reserve(size.to_index());
+    }
+
+    /// Drop the elements of the removed slots and move the remaining elements to the front of
+    /// the storage, keeping their order.
+    ///
+    /// Return a table that maps the old ids to the new ones, with None for the removed slots.
+    pub fn compact(&mut self) -> IdVec<ID, Option<ID>> {
+        let mut remap = Vec::with_capacity(self.data.len());
+        let mut data = Vec::with_capacity(self.count());
+        for (index, elt) in self.data.drain(..).enumerate() {
+            if self.free[index] {
+                remap.push(None);
+            } else {
+                remap.push(Some(ID::from_index(data.len())));
+                data.push(elt);
+            }
+        }
+        self.free = vec![false; data.len()];
+        self.free_slots.clear();
+        self.data = data;
+        IdVec::from_vec(remap)
     }
 }
 
 impl<ID:Identifier, Data: Default> SparseIdVector<ID, Data> {
     /// Set the value for a certain Id, possibly adding default values if the Id's index is Greater
-    /// than the size of the underlying vector.
+    /// than the size of the underlying vector. The slot is revived if it was removed.
     pub fn set(&mut self, id: ID, val: Data) {
         while self.len() < id.to_index() {
             self.push(Data::default());
         }
         if self.len() == id.to_index() {
             self.push(val);
+        } else if !self.has_id(id) {
+            self.revive(id, val);
         } else {
             self[id] = val;
         }
@@ -110,12 +196,15 @@ impl<Data:Default, ID:Identifier> SparseIdVector<ID, Data> {
         if d > 0 {
             self.data.reserve(d as usize);
             for _ in 0 .. d {
-                self.data.push(Default::default());
+                self.push(Default::default());
             }
         } else {
             for _ in 0 .. -d {
                 self.data.pop();
+                self.free.pop();
             }
+            let len = self.data.len();
+            self.free_slots.retain(|&index| index < len);
         }
     }
 
@@ -126,6 +215,23 @@ impl<Data:Default, ID:Identifier> SparseIdVector<ID, Data> {
     }
 }
 
+/// Iterates over the ids of the elements of a SparseIdVector that haven't been removed.
+pub struct SparseIdIter<'l, ID:Identifier, Data> where Data:'l {
+    vector: &'l SparseIdVector<ID, Data>,
+    index: usize,
+}
+
+impl<'l, ID:Identifier, Data> Iterator for SparseIdIter<'l, ID, Data> where Data:'l {
+    type Item = ID;
+    fn next(&mut self) -> Option<ID> {
+        let next = self.vector.next_id_from(self.index);
+        if let Some(id) = next {
+            self.index = id.to_index() + 1;
+        }
+        next
+    }
+}
+
 impl<ID:Identifier, Data> ops::Index<ID> for SparseIdVector<ID, Data> {
     type Output = Data;
     fn index<'l>(&'l self, id: ID) -> &'l Data { &self.data[id.to_index()] }
@@ -196,3 +302,102 @@ impl<'l, ID:Identifier, Data:'l> ops::Index<ID> for MutIdSlice<'l, ID, Data> {
 impl<'l, ID:Identifier, Data:'l> ops::IndexMut<ID> for MutIdSlice<'l, ID, Data> {
     fn index_mut<'a>(&'a mut self, id: ID) -> &'a mut Data { &mut self.slice[id.to_index()] }
 }
+
+#[test]
+fn test_sparse_id_vector_remove() {
+    use super::Id;
+    #[derive(Debug)]
+    struct T;
+    let mut v: SparseIdVector<Id<T, u16>, u32> = SparseIdVector::new();
+    let a = v.add(1);
+    let b = v.add(2);
+    let c = v.add(3);
+    assert_eq!(v.count(), 3);
+
+    v.remove(a);
+    v.remove(b);
+    assert!(!v.has_id(a));
+    assert!(!v.has_id(b));
+    assert!(v.has_id(c));
+    assert_eq!(v.count(), 1);
+    assert_eq!(v.len(), 3);
+    assert_eq!(v.first_id(), Some(c));
+    assert_eq!(v.next_id(c), None);
+    assert_eq!(v.ids().collect::<Vec<_>>(), vec![c]);
+
+    // Removed slots are reused by add, but not by push.
+    let d = v.add(4);
+    assert!(d == a || d == b);
+    assert_eq!(v[d], 4);
+    let e = v.push(5);
+    assert_eq!(e, Id::new(3));
+    assert_eq!(v.count(), 3);
+    assert_eq!(v.first_id(), Some(d));
+}
+
+#[test]
+fn test_sparse_id_vector_compact() {
+    use super::Id;
+    #[derive(Debug)]
+    struct T;
+    let mut v: SparseIdVector<Id<T, u16>, u32> = SparseIdVector::new();
+    let ids: Vec<Id<T, u16>> = (0..5).map(|i| v.add(i)).collect();
+    v.remove(ids[0]);
+    v.remove(ids[3]);
+
+    let remap = v.compact();
+    assert_eq!(remap.len(), 5);
+    assert_eq!(remap[ids[0]], None);
+    assert_eq!(remap[ids[1]], Some(Id::new(0)));
+    assert_eq!(remap[ids[2]], Some(Id::new(1)));
+    assert_eq!(remap[ids[3]], None);
+    assert_eq!(remap[ids[4]], Some(Id::new(2)));
+    assert_eq!(v.len(), 3);
+    assert_eq!(v.count(), 3);
+    assert_eq!(v.ids().collect::<Vec<_>>(), vec![Id::new(0), Id::new(1), Id::new(2)]);
+    assert_eq!((v[Id::new(0)], v[Id::new(1)], v[Id::new(2)]), (1, 2, 4));
+
+    // There are no free slots left, so add pushes at the end.
+    assert_eq!(v.add(5), Id::new(3));
+}
+
+#[test]
+fn test_sparse_id_vector_revive() {
+    use super::Id;
+    #[derive(Debug)]
+    struct T;
+    let mut v: SparseIdVector<Id<T, u16>, u32> = SparseIdVector::new();
+    let ids: Vec<Id<T, u16>> = (0..3).map(|i| v.add(i)).collect();
+    v.remove(ids[0]);
+    v.remove(ids[1]);
+
+    v.revive(ids[1], 5);
+    assert!(v.has_id(ids[1]));
+    assert_eq!(v[ids[1]], 5);
+    assert_eq!(v.count(), 2);
+    // Only the slot that is still removed is reused.
+    assert_eq!(v.add(6), ids[0]);
+    assert_eq!(v.add(7), Id::new(3));
+
+    // set revives removed slots too.
+    v.remove(ids[2]);
+    v.set(ids[2], 8);
+    assert!(v.has_id(ids[2]));
+    assert_eq!(v.count(), 4);
+    assert_eq!(v.add(9), Id::new(4));
+}
+
+#[test]
+#[should_panic(expected = "Can't remove slot 0, it is not in use.")]
+fn test_sparse_id_vector_remove_twice() {
+    use super::Id;
+    #[derive(Debug)]
+    struct T;
+    let mut v: SparseIdVector<Id<T, u16>, u32> = SparseIdVector::new();
+    let a = v.add(1);
+    v.add(2);
+    v.remove(a);
+    // Removing twice would put the slot twice in the free list, and add would hand it out to
+    // two elements.
+    v.remove(a);
+}
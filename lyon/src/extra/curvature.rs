@@ -0,0 +1,230 @@
+//! Discrete mean and Gaussian curvature of triangle meshes.
+//!
+//! The curvatures are computed with the operators of Meyer et al., "Discrete
+//! Differential-Geometry Operators for Triangulated 2-Manifolds": the cotangent formula for the
+//! mean curvature, the angle defect for the Gaussian curvature, both divided by the mixed
+//! Voronoi area of the vertex.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use half_edge::kernel::*;
+use half_edge::id_internals::is_valid;
+use extra::mesh::{ Mesh, Position3D };
+use vodk_math::Vec3;
+
+#[cfg(test)]
+use extra::mesh::Blend;
+#[cfg(test)]
+use extra::test_utils::test_mesh;
+
+/// Attributes that can receive the computed curvatures of a vertex.
+pub trait SetCurvature { fn set_curvature(&mut self, mean: f32, gaussian: f32); }
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CurvatureError<H: KernelIndex = Index> {
+    NonTriangularFace(FaceId<H>),
+}
+
+// What the triangles around a vertex contribute to its curvatures.
+#[derive(Copy, Clone, Default)]
+struct VertexSums {
+    area: f32,
+    angle: f32,
+    laplacian: Vec3,
+    normal: Vec3,
+}
+
+impl<V: Position3D + SetCurvature, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    /// Give each vertex its mean and Gaussian curvature. The mesh must only have triangles.
+    ///
+    /// The mean curvature is positive where the surface bends away from its normal, like a
+    /// sphere with outward normals. On the boundary, the Gaussian curvature measures how much
+    /// the surface turns compared to a half disc, and the mean curvature only sees the inner
+    /// side of the vertex, so both are rough estimates.
+    pub fn compute_curvature(&mut self) -> Result<(), CurvatureError<H>> {
+        let mut sums: HashMap<VertexId<H>, VertexSums> = HashMap::new();
+        // Whether each vertex is on the boundary, indexed by vertex id.
+        let mut boundary = Vec::new();
+        {
+            let kernel = self.connectivity_kernel();
+            let num_ids = kernel.vertex_ids().map(|v| v.handle.to_index() + 1).max();
+            boundary.resize(num_ids.unwrap_or(0), false);
+            for face in kernel.face_ids() {
+                let edges: Vec<EdgeId<H>> = kernel.walk_edge_ids_around_face(face).collect();
+                if edges.len() != 3 {
+                    return Err(CurvatureError::NonTriangularFace(face));
+                }
+                let v: Vec<VertexId<H>> = edges.iter().map(|&e| kernel[e].vertex).collect();
+                let p: Vec<Vec3> = v.iter().map(|&v| self.position3d(v)).collect();
+                let area_vector = (p[1] - p[0]).cross(&(p[2] - p[0]));
+                let area = area_vector.length() * 0.5;
+                if area == 0.0 {
+                    continue;
+                }
+
+                let mut angles = [0.0; 3];
+                let mut cotangents = [0.0; 3];
+                for i in 0..3 {
+                    let a = p[(i + 1) % 3] - p[i];
+                    let b = p[(i + 2) % 3] - p[i];
+                    angles[i] = a.cross(&b).length().atan2(a.dot(&b));
+                    cotangents[i] = a.dot(&b) / (2.0 * area);
+                }
+                let obtuse = (0..3).find(|&i| angles[i] > PI * 0.5);
+
+                for i in 0..3 {
+                    let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+                    // The edges i-j and i-k are opposite to the corners k and j.
+                    let (to_j, to_k) = (p[i] - p[j], p[i] - p[k]);
+                    let voronoi_area = match obtuse {
+                        None => {
+                            (to_j.square_length() * cotangents[k]
+                                + to_k.square_length() * cotangents[j]) / 8.0
+                        }
+                        Some(o) if o == i => area * 0.5,
+                        Some(_) => area * 0.25,
+                    };
+                    let sum = sums.entry(v[i]).or_insert(VertexSums::default());
+                    sum.area += voronoi_area;
+                    sum.angle += angles[i];
+                    sum.laplacian = sum.laplacian + to_j * cotangents[k] + to_k * cotangents[j];
+                    sum.normal = sum.normal + area_vector;
+                }
+            }
+
+            for e in kernel.edge_ids() {
+                if !is_valid(kernel[e].opposite) {
+                    boundary[kernel[e].vertex.handle.to_index()] = true;
+                    boundary[kernel[kernel[e].next].vertex.handle.to_index()] = true;
+                }
+            }
+        }
+
+        for (vertex, sum) in sums {
+            if sum.area == 0.0 {
+                continue;
+            }
+            let full_angle = if boundary[vertex.handle.to_index()] { PI } else { 2.0 * PI };
+            let gaussian = (full_angle - sum.angle) / sum.area;
+            let normal_length = sum.normal.length();
+            let mean = if normal_length > 0.0 {
+                sum.laplacian.dot(&sum.normal) / normal_length / (4.0 * sum.area)
+            } else {
+                0.0
+            };
+            self.vertex_mut(vertex).set_curvature(mean, gaussian);
+        }
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct TestVertex { position: Vec3, mean: f32, gaussian: f32 }
+
+#[cfg(test)]
+impl Position3D for TestVertex { fn position(&self) -> Vec3 { self.position } }
+
+#[cfg(test)]
+impl SetCurvature for TestVertex {
+    fn set_curvature(&mut self, mean: f32, gaussian: f32) {
+        self.mean = mean;
+        self.gaussian = gaussian;
+    }
+}
+
+#[cfg(test)]
+impl Blend for TestVertex {
+    fn blend(vertices: &[(&TestVertex, f32)]) -> TestVertex {
+        let positions: Vec<(&Vec3, f32)> = vertices.iter().map(|&(v, w)| (&v.position, w)).collect();
+        TestVertex { position: Vec3::blend(&positions), .. TestVertex::default() }
+    }
+}
+
+#[cfg(test)]
+fn triangle_mesh(positions: Vec<Vec3>, triangles: &[[u16; 3]]) -> Mesh<TestVertex, (), ()> {
+    let vertices = positions.iter().map(|&p| TestVertex { position: p, .. TestVertex::default() });
+    return test_mesh(vertices.collect(), triangles, (), &|_| ());
+}
+
+// An icosahedron, with its faces found as the triangles of neighbouring vertices.
+#[cfg(test)]
+fn icosahedron() -> Mesh<TestVertex, (), ()> {
+    let phi = (1.0 + 5.0f32.sqrt()) * 0.5;
+    let mut positions = Vec::new();
+    for &a in &[-1.0, 1.0] {
+        for &b in &[-phi, phi] {
+            positions.push(Vec3::new(0.0, a, b));
+            positions.push(Vec3::new(a, b, 0.0));
+            positions.push(Vec3::new(b, 0.0, a));
+        }
+    }
+    let neighbours = |i: usize, j: usize| ((positions[i] - positions[j]).length() - 2.0).abs() < 0.001;
+    let mut triangles = Vec::new();
+    for i in 0..12 {
+        for j in (i + 1)..12 {
+            for k in (j + 1)..12 {
+                if neighbours(i, j) && neighbours(j, k) && neighbours(k, i) {
+                    let normal = (positions[j] - positions[i]).cross(&(positions[k] - positions[i]));
+                    let outward = normal.dot(&positions[i]) > 0.0;
+                    let (j, k) = if outward { (j, k) } else { (k, j) };
+                    triangles.push([i as u16, j as u16, k as u16]);
+                }
+            }
+        }
+    }
+    assert_eq!(triangles.len(), 20);
+    return triangle_mesh(positions, &triangles);
+}
+
+#[test]
+fn test_sphere_curvature() {
+    let mut mesh = icosahedron();
+    for _ in 0..3 {
+        mesh = mesh.loop_subdivision().unwrap();
+    }
+    let vertices: Vec<VertexId> = mesh.connectivity_kernel().vertex_ids().collect();
+    for &v in &vertices {
+        let p = mesh.vertex(v).position;
+        mesh.vertex_mut(v).position = p / p.length();
+    }
+
+    assert_eq!(mesh.compute_curvature(), Ok(()));
+    for &v in &vertices {
+        let vertex = mesh.vertex(v);
+        assert!((vertex.mean - 1.0).abs() < 0.05, "mean curvature {}", vertex.mean);
+        assert!((vertex.gaussian - 1.0).abs() < 0.05, "gaussian curvature {}", vertex.gaussian);
+    }
+}
+
+#[test]
+fn test_flat_curvature() {
+    // A fan of triangles around vertex 0, in the xy plane.
+    let mut positions = vec![Vec3::new(0.0, 0.0, 0.0)];
+    let mut triangles = Vec::new();
+    for i in 0..6 {
+        let angle = i as f32 * PI / 3.0;
+        positions.push(Vec3::new(angle.cos(), angle.sin(), 0.0));
+        triangles.push([0, i + 1, (i + 1) % 6 + 1]);
+    }
+    let mut mesh = triangle_mesh(positions, &triangles);
+    assert_eq!(mesh.compute_curvature(), Ok(()));
+    let center = *mesh.vertex(vertex_id(0));
+    assert!(center.mean.abs() < 0.0001);
+    assert!(center.gaussian.abs() < 0.0001);
+    // The boundary turns by 60 degrees at each corner of the hexagon.
+    let corner = *mesh.vertex(vertex_id(1));
+    let corner_area = 3.0f32.sqrt() / 4.0 * 2.0 / 3.0;
+    assert!((corner.gaussian - (PI / 3.0) / corner_area).abs() < 0.001);
+
+    // Lifting the center makes a cone, which is curved at its apex.
+    mesh.vertex_mut(vertex_id(0)).position = Vec3::new(0.0, 0.0, 1.0);
+    mesh.compute_curvature().unwrap();
+    assert!(mesh.vertex(vertex_id(0)).gaussian > 0.1);
+    assert!(mesh.vertex(vertex_id(0)).mean > 0.1);
+
+    let mut quad = test_mesh(vec![TestVertex::default(); 4], &[[0, 1, 2, 3]], (), &|_| ());
+    assert_eq!(quad.compute_curvature(), Err(CurvatureError::NonTriangularFace(face_id(0))));
+}
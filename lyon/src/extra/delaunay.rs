@@ -0,0 +1,847 @@
+//! Delaunay triangulation of a set of points, with constraint edges.
+//!
+//! Points are inserted one at a time: the triangle or the edge that contains the new point is
+//! split, or the convex hull is extended to it, and the edges around the point are then flipped
+//! until the triangulation is Delaunay again (Lawson's algorithm, which gives the same result
+//! as Bowyer-Watson). Constraint edges are inserted by flipping the edges that cross them and
+//! are never flipped afterwards, so the result is a constrained Delaunay triangulation.
+
+use std::collections::{ HashSet, VecDeque };
+
+use half_edge::kernel::*;
+use half_edge::id_internals::{ is_valid, Index };
+use vodk_id::IdVec;
+use vodk_math::Vec2;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConstraintError<H: KernelIndex = Index> {
+    /// This end of the constraint is not a vertex of the triangulation.
+    UnknownVertex(VertexId<H>),
+    /// The two ends of the constraint are the same vertex.
+    Degenerate,
+    /// The points are all on a line, so there is no edge to constrain yet.
+    NotTriangulated,
+    /// The constraint crosses another constraint edge, given by its vertices sorted by id.
+    Intersection(VertexId<H>, VertexId<H>),
+    /// The segment couldn't be followed through the triangles, because of rounding errors
+    /// with points that are almost on a line.
+    Precision,
+}
+
+/// The reason why a point can't be inserted or removed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TriangulationError {
+    /// There would be more vertices, half edges or faces than the ids can address.
+    TooLarge,
+    /// The position to insert has a NaN or infinite coordinate.
+    NonFinite,
+    /// The vertex to remove is not in the triangulation.
+    UnknownVertex,
+    /// The point couldn't be located, or the hole left by a removed point couldn't be
+    /// triangulated, because of rounding errors with points that are almost on a line. The
+    /// triangulation is left as it was for insertions, and can be left with a hole that isn't
+    /// triangulated for removals.
+    Precision,
+}
+
+// Where a point falls in the triangulation.
+enum Location<H: KernelIndex> {
+    Vertex(VertexId<H>),
+    Face(FaceId<H>),
+    // Inside of an edge of a triangle.
+    Edge(EdgeId<H>),
+    // Outside of the convex hull, which this edge of a triangle is part of and faces the point.
+    Outside(EdgeId<H>),
+}
+
+/// A Delaunay triangulation, stored in a connectivity kernel and the positions of its vertices.
+///
+/// The triangles are counter-clockwise and are surrounded by an outer face, the loop of which
+/// is the convex hull of the points. As long as all of the points are on a line they are
+/// isolated vertices of the kernel, and the triangles are built when a point that isn't on the
+/// line is inserted.
+pub struct DelaunayTriangulation<H: KernelIndex = Index> {
+    kernel: ConnectivityKernel<H>,
+    positions: IdVec<VertexId<H>, Vec2>,
+    outer_face: Option<FaceId<H>>,
+    // The constraint edges, as pairs of vertices sorted by id.
+    constraints: HashSet<(VertexId<H>, VertexId<H>)>,
+    // A triangle near the last inserted point, to start looking for the next one from.
+    last_face: Option<FaceId<H>>,
+}
+
+impl<H: KernelIndex> DelaunayTriangulation<H> {
+    pub fn new() -> DelaunayTriangulation<H> {
+        DelaunayTriangulation {
+            kernel: ConnectivityKernel::new(),
+            positions: IdVec::new(),
+            outer_face: None,
+            constraints: HashSet::new(),
+            last_face: None,
+        }
+    }
+
+    pub fn kernel(&self) -> &ConnectivityKernel<H> { &self.kernel }
+
+    /// The positions of the vertices, indexed by their ids.
+    ///
+    /// The positions of the removed vertices are kept, the kernel tells which vertices exist.
+    pub fn positions(&self) -> &IdVec<VertexId<H>, Vec2> { &self.positions }
+
+    pub fn position(&self, id: VertexId<H>) -> Vec2 { self.positions[id] }
+
+    /// The face around the triangles, or None if the points are all on a line.
+    pub fn outer_face(&self) -> Option<FaceId<H>> { self.outer_face }
+
+    pub fn num_points(&self) -> usize { self.kernel.num_vertices() }
+
+    pub fn is_constrained(&self, a: VertexId<H>, b: VertexId<H>) -> bool {
+        self.constraints.contains(&edge_key(a, b))
+    }
+
+    /// Insert a point and return its vertex, or the vertex that is already at this position.
+    ///
+    /// A point that falls on a constraint edge splits it into two constraint edges.
+    pub fn insert(&mut self, position: Vec2) -> Result<VertexId<H>, TriangulationError> {
+        if !position.x.is_finite() || !position.y.is_finite() {
+            return Err(TriangulationError::NonFinite);
+        }
+        if self.outer_face.is_none() {
+            if let Some(v) = self.kernel.vertex_ids().find(|&v| self.positions[v] == position) {
+                return Ok(v);
+            }
+            let vertex = try!(self.add_vertex(position));
+            if let Err(error) = self.build_first_triangle() {
+                self.kernel.remove_isolated_vertex(vertex);
+                return Err(error);
+            }
+            return Ok(vertex);
+        }
+
+        let location = try!(self.locate(position));
+        if let Location::Vertex(v) = location {
+            return Ok(v);
+        }
+        let vertex = try!(self.add_vertex(position));
+        self.insert_vertex(vertex, location);
+        return Ok(vertex);
+    }
+
+    /// Remove a point, along with the constraint edges that end at it.
+    ///
+    /// If the remaining points are all on a line, the triangles are removed and the constraint
+    /// edges are dropped.
+    pub fn remove(&mut self, vertex: VertexId<H>) -> Result<(), TriangulationError> {
+        if !self.kernel.contains_vertex(vertex) {
+            return Err(TriangulationError::UnknownVertex);
+        }
+        self.constraints.retain(|&(a, b)| a != vertex && b != vertex);
+        let first_edge = self.kernel[vertex].first_edge;
+        let outer_face = match self.outer_face {
+            Some(face) if is_valid(first_edge) => face,
+            _ => {
+                self.kernel.remove_isolated_vertex(vertex);
+                return Ok(());
+            }
+        };
+
+        let on_hull = self.kernel.walk_face_ids_around_vertex(vertex).any(|f| f == outer_face);
+        if on_hull && !self.spans_triangle_without(vertex) {
+            self.kernel.remove_vertex(first_edge);
+            self.remove_triangles();
+            return Ok(());
+        }
+
+        let face = self.kernel.remove_vertex(first_edge);
+        let new_faces = if on_hull {
+            self.outer_face = Some(face);
+            self.fill_hull()
+        } else {
+            let mut new_faces = vec![face];
+            let result = {
+                let positions = &self.positions;
+                self.kernel.triangulate_face(face, &|v| positions[v], &mut new_faces)
+            };
+            if result.is_err() {
+                self.last_face = None;
+                return Err(TriangulationError::Precision);
+            }
+            new_faces
+        };
+
+        self.last_face = new_faces.first().cloned();
+        let mut edges = Vec::new();
+        for &face in &new_faces {
+            edges.extend(self.kernel.walk_edge_ids_around_face(face));
+        }
+        self.legalize(edges);
+        return Ok(());
+    }
+
+    /// Make sure that the segment between two vertices is made of edges of the triangulation,
+    /// and keep these edges from being flipped.
+    ///
+    /// If the segment goes through other vertices, it is split into several constraint edges.
+    /// Fails if the segment crosses another constraint edge.
+    pub fn insert_constraint(
+        &mut self,
+        from: VertexId<H>,
+        to: VertexId<H>,
+    ) -> Result<(), ConstraintError<H>> {
+        for &vertex in &[from, to] {
+            if !self.kernel.contains_vertex(vertex) {
+                return Err(ConstraintError::UnknownVertex(vertex));
+            }
+        }
+        if from == to {
+            return Err(ConstraintError::Degenerate);
+        }
+        if self.outer_face.is_none() {
+            return Err(ConstraintError::NotTriangulated);
+        }
+
+        let mut current = from;
+        while current != to {
+            let (next, crossed_edges) = try!(self.edges_crossed_by(current, to));
+            self.constraints.insert(edge_key(current, next));
+            if !crossed_edges.is_empty() {
+                self.flip_crossed_edges(current, next, crossed_edges);
+            }
+            current = next;
+        }
+
+        return Ok(());
+    }
+
+    fn add_vertex(&mut self, position: Vec2) -> Result<VertexId<H>, TriangulationError> {
+        // A triangulation of n points has less than 6n half edges and 2n faces.
+        if 6 * (self.kernel.num_vertices() + 1) > H::max_count() {
+            return Err(TriangulationError::TooLarge);
+        }
+        let vertex = self.kernel.add_vertex();
+        self.positions.set(vertex, position);
+        return Ok(vertex);
+    }
+
+    // Go back to isolated vertices.
+    fn remove_triangles(&mut self) {
+        while let Some(e) = self.kernel.first_edge() {
+            self.kernel.remove_edge(e);
+        }
+        self.outer_face = None;
+        self.last_face = None;
+        self.constraints.clear();
+    }
+
+    fn orient(&self, a: VertexId<H>, b: VertexId<H>, c: VertexId<H>) -> f64 {
+        orient(self.positions[a], self.positions[b], self.positions[c])
+    }
+
+    fn origin(&self, e: EdgeId<H>) -> VertexId<H> { self.kernel[e].vertex }
+
+    fn destination(&self, e: EdgeId<H>) -> VertexId<H> { self.kernel[self.kernel[e].next].vertex }
+
+    // Positive if the position is on the left of the edge.
+    fn side(&self, e: EdgeId<H>, position: Vec2) -> f64 {
+        orient(self.positions[self.origin(e)], self.positions[self.destination(e)], position)
+    }
+
+    // Build a triangle and the outer face around it once three of the isolated vertices are
+    // not on a line, and insert the other vertices. The triangles are removed if one of them
+    // can't be inserted.
+    fn build_first_triangle(&mut self) -> Result<(), TriangulationError> {
+        let vertices: Vec<VertexId<H>> = self.kernel.vertex_ids().collect();
+        if vertices.len() < 3 {
+            return Ok(());
+        }
+        let (a, b) = (vertices[0], vertices[1]);
+        let c = match vertices.iter().find(|&&v| self.orient(a, b, v) != 0.0) {
+            Some(&c) => c,
+            None => { return Ok(()); }
+        };
+        let (b, c) = if self.orient(a, b, c) > 0.0 { (b, c) } else { (c, b) };
+
+        // Faces without edges are left behind when the triangles are removed.
+        let face = match self.kernel.first_face() {
+            Some(face) => face,
+            None => self.kernel.add_face(),
+        };
+        let e = self.kernel.add_segment(a, b, face);
+        self.kernel[face].first_edge = e;
+        let spoke = self.kernel.extrude_vertex(e, c);
+        let opposite = self.kernel[e].opposite;
+        // The new face gets (b, a, c), which is clockwise.
+        let outer_face = self.kernel.split_face(opposite, spoke);
+        self.outer_face = Some(outer_face);
+        self.last_face = Some(face);
+
+        for v in vertices {
+            if v != a && v != b && v != c {
+                match self.locate(self.positions[v]) {
+                    Ok(location) => { self.insert_vertex(v, location); }
+                    Err(error) => {
+                        self.remove_triangles();
+                        return Err(error);
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn insert_vertex(&mut self, vertex: VertexId<H>, location: Location<H>) {
+        let outer_face = self.outer_face.unwrap();
+        match location {
+            Location::Vertex(_) => { unreachable!(); }
+            Location::Face(face) => {
+                let first_edge = self.kernel[face].first_edge;
+                let spoke = self.kernel.extrude_vertex(first_edge, vertex);
+                self.connect_fan(spoke);
+            }
+            Location::Edge(e) => {
+                let opposite = self.kernel[e].opposite;
+                let (a, b) = (self.origin(e), self.origin(opposite));
+                self.kernel.split_edge(e, vertex);
+                if self.constraints.remove(&edge_key(a, b)) {
+                    self.constraints.insert(edge_key(a, vertex));
+                    self.constraints.insert(edge_key(vertex, b));
+                }
+                let spoke = self.kernel[e].next;
+                self.connect_fan(spoke);
+                if self.kernel[opposite].face != outer_face {
+                    let spoke = self.kernel[opposite].next;
+                    self.connect_fan(spoke);
+                }
+            }
+            Location::Outside(e) => {
+                // Add the triangle between the point and the edge, then fill the notches that
+                // it leaves in the outer face.
+                let opposite = self.kernel[e].opposite;
+                let spoke = self.kernel.extrude_vertex(opposite, vertex);
+                let after = self.kernel[opposite].next;
+                self.kernel.split_face(spoke, after);
+                self.fill_hull();
+            }
+        }
+
+        let mut edges = Vec::new();
+        for e in self.kernel.walk_edge_ids_around_vertex(vertex) {
+            if self.kernel[e].face != outer_face {
+                self.last_face = Some(self.kernel[e].face);
+                edges.push(self.kernel[e].next);
+            }
+        }
+        self.legalize(edges);
+    }
+
+    // Split the face of an edge that starts at a vertex inside of it into triangles around
+    // this vertex.
+    fn connect_fan(&mut self, spoke: EdgeId<H>) {
+        let mut spoke = spoke;
+        loop {
+            let target = self.kernel[self.kernel[spoke].next].next;
+            if self.kernel[target].next == spoke {
+                return;
+            }
+            let face = self.kernel.split_face(target, spoke);
+            spoke = self.kernel[face].first_edge;
+        }
+    }
+
+    // Add triangles in the notches of the outer face until its loop is convex, and return them.
+    fn fill_hull(&mut self) -> Vec<FaceId<H>> {
+        let outer_face = self.outer_face.unwrap();
+        let mut new_faces = Vec::new();
+        'fill: loop {
+            // The outer face goes around the triangles clockwise, so a counter-clockwise turn
+            // is a notch.
+            let edges: Vec<EdgeId<H>> = self.kernel.walk_edge_ids_around_face(outer_face).collect();
+            for e in edges {
+                let next = self.kernel[e].next;
+                let after = self.kernel[next].next;
+                if self.orient(self.origin(e), self.origin(next), self.origin(after)) > 0.0 {
+                    new_faces.push(self.kernel.split_face(e, after));
+                    continue 'fill;
+                }
+            }
+            return new_faces;
+        }
+    }
+
+    // Flip the edges that are not locally Delaunay, starting with the given ones.
+    fn legalize(&mut self, edges: Vec<EdgeId<H>>) {
+        let mut edges = edges;
+        while let Some(e) = edges.pop() {
+            if !self.kernel.contains_edge(e) || !self.should_flip(e) {
+                continue;
+            }
+            let opposite = self.kernel[e].opposite;
+            edges.push(self.kernel[e].next);
+            edges.push(self.kernel[e].prev);
+            edges.push(self.kernel[opposite].next);
+            edges.push(self.kernel[opposite].prev);
+            self.kernel.flip_edge(e);
+        }
+    }
+
+    fn should_flip(&self, e: EdgeId<H>) -> bool {
+        let opposite = self.kernel[e].opposite;
+        if Some(self.kernel[e].face) == self.outer_face
+            || Some(self.kernel[opposite].face) == self.outer_face {
+            return false;
+        }
+        let (a, b) = (self.origin(e), self.origin(opposite));
+        if self.is_constrained(a, b) {
+            return false;
+        }
+        let c = self.origin(self.kernel[e].prev);
+        let d = self.origin(self.kernel[opposite].prev);
+        let p = &self.positions;
+        return in_circle(p[a], p[b], p[c], p[d]) && self.is_convex(a, b, c, d);
+    }
+
+    // Whether the edge between a and b can be flipped, with the triangles (a, b, c) and
+    // (b, a, d) becoming (c, a, d) and (d, b, c).
+    fn is_convex(&self, a: VertexId<H>, b: VertexId<H>, c: VertexId<H>, d: VertexId<H>) -> bool {
+        self.orient(c, a, d) > 0.0 && self.orient(d, b, c) > 0.0
+    }
+
+    fn locate(&self, position: Vec2) -> Result<Location<H>, TriangulationError> {
+        let outer_face = self.outer_face.unwrap();
+        let mut face = match self.last_face {
+            Some(face) if self.kernel.contains_face(face) && face != outer_face => face,
+            _ => {
+                let hull_edge = self.kernel[outer_face].first_edge;
+                self.kernel[self.kernel[hull_edge].opposite].face
+            }
+        };
+
+        // Walk towards the point, which always ends for Delaunay triangulations but can go
+        // around in circles with constraints or rounding errors.
+        'walk: for _ in 0..self.kernel.num_faces() {
+            for e in self.kernel.walk_edge_ids_around_face(face) {
+                let side = self.side(e, position);
+                if side < 0.0 {
+                    let next_face = self.kernel[self.kernel[e].opposite].face;
+                    if next_face == outer_face {
+                        return Ok(Location::Outside(e));
+                    }
+                    face = next_face;
+                    continue 'walk;
+                }
+            }
+            return Ok(self.locate_in_face(face, position));
+        }
+
+        for face in self.kernel.face_ids() {
+            if face != outer_face && self.kernel.walk_edge_ids_around_face(face).all(|e| {
+                self.side(e, position) >= 0.0
+            }) {
+                return Ok(self.locate_in_face(face, position));
+            }
+        }
+        for e in self.kernel.walk_edge_ids_around_face(outer_face) {
+            let e = self.kernel[e].opposite;
+            if self.side(e, position) < 0.0 {
+                return Ok(Location::Outside(e));
+            }
+        }
+        return Err(TriangulationError::Precision);
+    }
+
+    // Tell whether a point that is in a triangle or on its edges is on a vertex, an edge, or
+    // inside of the triangle.
+    fn locate_in_face(&self, face: FaceId<H>, position: Vec2) -> Location<H> {
+        let mut location = Location::Face(face);
+        for e in self.kernel.walk_edge_ids_around_face(face) {
+            let (a, b) = (self.origin(e), self.destination(e));
+            if self.positions[a] == position {
+                return Location::Vertex(a);
+            }
+            if orient(self.positions[a], self.positions[b], position) == 0.0 {
+                location = Location::Edge(e);
+            }
+        }
+        return location;
+    }
+
+    // Whether the points other than the given vertex span a triangle.
+    fn spans_triangle_without(&self, vertex: VertexId<H>) -> bool {
+        let others: Vec<VertexId<H>> = self.kernel.vertex_ids().filter(|&v| v != vertex).collect();
+        if others.len() < 3 {
+            return false;
+        }
+        return others.iter().any(|&v| self.orient(others[0], others[1], v) != 0.0);
+    }
+
+    // The edges crossed by the segment from a to b, up to b or to the first vertex that is on
+    // the segment, which is returned with them.
+    fn edges_crossed_by(
+        &self,
+        a: VertexId<H>,
+        b: VertexId<H>,
+    ) -> Result<(VertexId<H>, Vec<EdgeId<H>>), ConstraintError<H>> {
+        let outer_face = self.outer_face.unwrap();
+        let (pa, pb) = (self.positions[a], self.positions[b]);
+        let mut crossed_edge = None;
+        for e in self.kernel.walk_edge_ids_around_vertex(a) {
+            let x = self.destination(e);
+            let px = self.positions[x];
+            if x == b || (orient(pa, pb, px) == 0.0 && (px - pa).dot(&(pb - pa)) > 0.0) {
+                return Ok((x, Vec::new()));
+            }
+            if self.kernel[e].face != outer_face {
+                // The triangle (a, x, y) contains the start of the segment.
+                let y = self.origin(self.kernel[e].prev);
+                if orient(pa, px, pb) > 0.0 && orient(pa, self.positions[y], pb) < 0.0 {
+                    crossed_edge = Some(self.kernel[e].next);
+                }
+            }
+        }
+
+        // The crossed edges go from the right of the segment to its left.
+        let mut edge = match crossed_edge {
+            Some(edge) => edge,
+            None => { return Err(ConstraintError::Precision); }
+        };
+        let mut crossed_edges = Vec::new();
+        loop {
+            let (x, y) = (self.origin(edge), self.destination(edge));
+            if self.is_constrained(x, y) {
+                let (x, y) = edge_key(x, y);
+                return Err(ConstraintError::Intersection(x, y));
+            }
+            crossed_edges.push(edge);
+
+            let opposite = self.kernel[edge].opposite;
+            let z = self.origin(self.kernel[opposite].prev);
+            let side = orient(pa, pb, self.positions[z]);
+            if z == b || side == 0.0 {
+                return Ok((z, crossed_edges));
+            }
+            edge = if side > 0.0 { self.kernel[opposite].next } else { self.kernel[opposite].prev };
+        }
+    }
+
+    // Flip the edges that cross the segment between a and b until one of them joins a and b.
+    fn flip_crossed_edges(
+        &mut self,
+        a: VertexId<H>,
+        b: VertexId<H>,
+        crossed_edges: Vec<EdgeId<H>>,
+    ) {
+        let p = self.positions[a];
+        let q = self.positions[b];
+        let mut queue: VecDeque<EdgeId<H>> = crossed_edges.into_iter().collect();
+        let mut new_edges = Vec::new();
+        while let Some(e) = queue.pop_front() {
+            let opposite = self.kernel[e].opposite;
+            let (x, y) = (self.origin(e), self.origin(opposite));
+            let c = self.origin(self.kernel[e].prev);
+            let d = self.origin(self.kernel[opposite].prev);
+            if !self.is_convex(x, y, c, d) {
+                queue.push_back(e);
+                continue;
+            }
+            self.kernel.flip_edge(e);
+            let (pc, pd) = (self.positions[c], self.positions[d]);
+            if c != a && c != b && d != a && d != b && segments_cross(p, q, pc, pd) {
+                queue.push_back(e);
+            } else {
+                new_edges.push(e);
+            }
+        }
+        self.legalize(new_edges);
+    }
+}
+
+fn edge_key<H: KernelIndex>(a: VertexId<H>, b: VertexId<H>) -> (VertexId<H>, VertexId<H>) {
+    if a.handle < b.handle { (a, b) } else { (b, a) }
+}
+
+// Twice the signed area of the triangle, positive if it is counter-clockwise.
+fn orient(a: Vec2, b: Vec2, c: Vec2) -> f64 {
+    let (abx, aby) = (b.x as f64 - a.x as f64, b.y as f64 - a.y as f64);
+    let (acx, acy) = (c.x as f64 - a.x as f64, c.y as f64 - a.y as f64);
+    return abx * acy - aby * acx;
+}
+
+// Whether d is inside of the circle through the counter-clockwise triangle (a, b, c).
+//
+// Points that are almost on the circle are considered outside, so that rounding errors can't
+// make two triangles flip their shared edge back and forth.
+fn in_circle(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let (adx, ady) = (a.x as f64 - d.x as f64, a.y as f64 - d.y as f64);
+    let (bdx, bdy) = (b.x as f64 - d.x as f64, b.y as f64 - d.y as f64);
+    let (cdx, cdy) = (c.x as f64 - d.x as f64, c.y as f64 - d.y as f64);
+    let ad = adx * adx + ady * ady;
+    let bd = bdx * bdx + bdy * bdy;
+    let cd = cdx * cdx + cdy * cdy;
+    let det = ad * (bdx * cdy - cdx * bdy)
+        + bd * (cdx * ady - adx * cdy)
+        + cd * (adx * bdy - bdx * ady);
+    let magnitude = ad * ((bdx * cdy).abs() + (cdx * bdy).abs())
+        + bd * ((cdx * ady).abs() + (adx * cdy).abs())
+        + cd * ((adx * bdy).abs() + (bdx * ady).abs());
+    return det > magnitude * 1e-12;
+}
+
+// Whether the segments cross at a point that is inside of both of them.
+fn segments_cross(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    orient(a, b, c) * orient(a, b, d) < 0.0 && orient(c, d, a) * orient(c, d, b) < 0.0
+}
+
+// Points spread over a square in a deterministic order.
+#[cfg(test)]
+fn scattered_points(n: usize) -> Vec<Vec2> {
+    let mut state = 12345u32;
+    let mut random = || {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (state >> 8) as f32 / (1 << 24) as f32
+    };
+    return (0..n).map(|_| Vec2::new(random() * 100.0, random() * 100.0)).collect();
+}
+
+#[cfg(test)]
+fn triangles(t: &DelaunayTriangulation) -> Vec<[VertexId; 3]> {
+    let kernel = t.kernel();
+    return kernel.face_ids().filter(|&f| Some(f) != t.outer_face()).map(|f| {
+        let v: Vec<VertexId> = kernel.walk_edge_ids_around_face(f)
+            .map(|e| kernel[e].vertex).collect();
+        assert_eq!(v.len(), 3);
+        [v[0], v[1], v[2]]
+    }).collect();
+}
+
+// Check the structure of the triangulation, and that the circumcircles of the triangles are
+// empty unless the triangles are separated by constraint edges.
+#[cfg(test)]
+fn check_triangulation(t: &DelaunayTriangulation, check_circles: bool) {
+    let kernel = t.kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    let outer_face = t.outer_face().unwrap();
+    let hull: Vec<VertexId> = kernel.walk_edge_ids_around_face(outer_face)
+        .map(|e| kernel[e].vertex).collect();
+    for i in 0..hull.len() {
+        let (a, b, c) = (hull[i], hull[(i + 1) % hull.len()], hull[(i + 2) % hull.len()]);
+        assert!(t.orient(a, b, c) <= 0.0);
+    }
+    // Euler's formula for a triangulated polygon.
+    let triangles = triangles(t);
+    assert_eq!(triangles.len(), 2 * t.num_points() - hull.len() - 2);
+
+    let p = t.positions();
+    for triangle in &triangles {
+        let [a, b, c] = *triangle;
+        assert!(t.orient(a, b, c) > 0.0);
+        if check_circles {
+            for v in kernel.vertex_ids() {
+                assert!(!in_circle(p[a], p[b], p[c], p[v]), "{:?} in circle of {:?}", v, triangle);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_delaunay_insert() {
+    let mut t = DelaunayTriangulation::new();
+    let points = scattered_points(300);
+    let vertices: Vec<VertexId> = points.iter().map(|&p| t.insert(p).unwrap()).collect();
+    assert_eq!(t.num_points(), 300);
+    check_triangulation(&t, true);
+    for (&v, &p) in vertices.iter().zip(points.iter()) {
+        assert_eq!(t.position(v), p);
+    }
+
+    // Points outside of the hull, on edges and on vertices.
+    let far = t.insert(Vec2::new(500.0, -200.0)).unwrap();
+    let kernel_edge = t.kernel().first_edge().unwrap();
+    let (a, b) = (t.origin(kernel_edge), t.destination(kernel_edge));
+    let middle = t.insert(Vec2::new(
+        (t.position(a).x + t.position(b).x) * 0.5,
+        (t.position(a).y + t.position(b).y) * 0.5,
+    )).unwrap();
+    assert_eq!(t.insert(points[10]).unwrap(), vertices[10]);
+    assert_eq!(t.num_points(), 302);
+    check_triangulation(&t, true);
+    assert!(t.kernel().walk_face_ids_around_vertex(far).any(|f| Some(f) == t.outer_face()));
+    assert!(t.kernel().walk_edge_ids_around_vertex(middle).count() >= 3);
+}
+
+#[test]
+fn test_delaunay_non_finite() {
+    let mut t = DelaunayTriangulation::new();
+    t.insert(Vec2::new(0.0, 0.0)).unwrap();
+    t.insert(Vec2::new(1.0, 0.0)).unwrap();
+    assert_eq!(t.insert(Vec2::new(::std::f32::NAN, 0.0)), Err(TriangulationError::NonFinite));
+    assert_eq!(t.num_points(), 2);
+    for p in scattered_points(50) {
+        t.insert(p).unwrap();
+    }
+    check_triangulation(&t, true);
+
+    assert_eq!(t.insert(Vec2::new(0.0, ::std::f32::NAN)), Err(TriangulationError::NonFinite));
+    assert_eq!(
+        t.insert(Vec2::new(::std::f32::INFINITY, 0.0)),
+        Err(TriangulationError::NonFinite)
+    );
+    assert_eq!(
+        t.insert(Vec2::new(1.0, ::std::f32::NEG_INFINITY)),
+        Err(TriangulationError::NonFinite)
+    );
+    assert_eq!(t.num_points(), 52);
+    check_triangulation(&t, true);
+}
+
+#[test]
+fn test_delaunay_grid() {
+    // Cocircular points, on a grid with points on the edges of the hull.
+    let mut t = DelaunayTriangulation::new();
+    for y in 0..6 {
+        for x in 0..6 {
+            t.insert(Vec2::new(x as f32, y as f32)).unwrap();
+        }
+    }
+    check_triangulation(&t, true);
+    assert_eq!(t.kernel().walk_edge_ids_around_face(t.outer_face().unwrap()).count(), 20);
+}
+
+#[test]
+fn test_delaunay_collinear_points() {
+    let mut t = DelaunayTriangulation::new();
+    let a = t.insert(Vec2::new(0.0, 0.0)).unwrap();
+    let b = t.insert(Vec2::new(2.0, 2.0)).unwrap();
+    assert_eq!(t.insert(Vec2::new(0.0, 0.0)).unwrap(), a);
+    t.insert(Vec2::new(1.0, 1.0)).unwrap();
+    t.insert(Vec2::new(-1.0, -1.0)).unwrap();
+    assert_eq!(t.outer_face(), None);
+    assert_eq!(t.num_points(), 4);
+    assert_eq!(t.kernel().num_edges(), 0);
+    assert_eq!(t.insert_constraint(a, b), Err(ConstraintError::NotTriangulated));
+
+    let c = t.insert(Vec2::new(2.0, 0.0)).unwrap();
+    assert!(t.outer_face().is_some());
+    check_triangulation(&t, true);
+    assert_eq!(triangles(&t).len(), 3);
+
+    // Removing the only point that isn't on the line brings the first state back.
+    t.remove(c).unwrap();
+    assert_eq!(t.outer_face(), None);
+    assert_eq!(t.num_points(), 4);
+    assert_eq!(t.kernel().num_edges(), 0);
+    t.remove(a).unwrap();
+    assert_eq!(t.num_points(), 3);
+    t.insert(Vec2::new(0.0, 3.0)).unwrap();
+    check_triangulation(&t, true);
+    // (1, 1) is on the hull, between (-1, -1) and (2, 2).
+    assert_eq!(triangles(&t).len(), 2);
+}
+
+#[test]
+fn test_delaunay_remove() {
+    let mut t = DelaunayTriangulation::new();
+    let mut vertices: Vec<VertexId> = scattered_points(100).iter().map(|&p| {
+        t.insert(p).unwrap()
+    }).collect();
+
+    // Remove points inside and on the hull, until there are only three left.
+    let mut removed_hull_points = 0;
+    let mut i = 0;
+    while vertices.len() > 3 {
+        i = (i + 37) % vertices.len();
+        let v = vertices.remove(i);
+        let outer_face = t.outer_face();
+        if t.kernel().walk_face_ids_around_vertex(v).any(|f| Some(f) == outer_face) {
+            removed_hull_points += 1;
+        }
+        t.remove(v).unwrap();
+        assert!(!t.kernel().contains_vertex(v));
+        assert_eq!(t.remove(v), Err(TriangulationError::UnknownVertex));
+        check_triangulation(&t, true);
+    }
+    assert!(removed_hull_points > 3);
+    assert_eq!(triangles(&t).len(), 1);
+
+    for p in scattered_points(20) {
+        t.insert(p * 0.5 + Vec2::new(25.0, 25.0)).unwrap();
+    }
+    check_triangulation(&t, true);
+}
+
+#[test]
+fn test_delaunay_constraints() {
+    let mut t = DelaunayTriangulation::new();
+    let vertices: Vec<VertexId> = scattered_points(200).iter().map(|&p| {
+        t.insert(p).unwrap()
+    }).collect();
+    let a = t.insert(Vec2::new(10.0, 50.0)).unwrap();
+    let b = t.insert(Vec2::new(90.0, 50.0)).unwrap();
+    let on_segment = t.insert(Vec2::new(30.0, 50.0)).unwrap();
+    assert_eq!(t.insert_constraint(a, a), Err(ConstraintError::Degenerate));
+    assert_eq!(t.insert_constraint(a, b), Ok(()));
+    check_triangulation(&t, false);
+
+    // The constraint goes through a vertex, so it is split in two edges.
+    let edge_exists = |t: &DelaunayTriangulation, x: VertexId, y: VertexId| {
+        t.kernel().edge_ids().any(|e| t.origin(e) == x && t.destination(e) == y)
+    };
+    assert!(t.is_constrained(a, on_segment) && t.is_constrained(on_segment, b));
+    assert!(!t.is_constrained(a, b));
+    assert!(edge_exists(&t, a, on_segment) && edge_exists(&t, on_segment, b));
+
+    // A segment that crosses it is rejected.
+    let c = t.insert(Vec2::new(50.0, 10.5)).unwrap();
+    let d = t.insert(Vec2::new(50.0, 89.5)).unwrap();
+    let (x, y) = edge_key(on_segment, b);
+    assert_eq!(t.insert_constraint(c, d), Err(ConstraintError::Intersection(x, y)));
+
+    // The constraint edges stay in the triangulation when points are added and removed, and
+    // are split by the points inserted on them.
+    let middle = t.insert(Vec2::new(60.0, 50.0)).unwrap();
+    assert!(t.is_constrained(on_segment, middle) && t.is_constrained(middle, b));
+    for p in scattered_points(50) {
+        t.insert(p + Vec2::new(0.5, 0.25)).unwrap();
+    }
+    for &v in &vertices[100..150] {
+        t.remove(v).unwrap();
+    }
+    check_triangulation(&t, false);
+    let path = [a, on_segment, middle, b];
+    for i in 0..3 {
+        assert!(t.is_constrained(path[i], path[i + 1]));
+        assert!(edge_exists(&t, path[i], path[i + 1]));
+    }
+
+    // Removing a vertex removes the constraint edges around it.
+    t.remove(middle).unwrap();
+    assert!(!t.is_constrained(on_segment, middle) && !t.is_constrained(middle, b));
+    assert_eq!(t.insert_constraint(a, middle), Err(ConstraintError::UnknownVertex(middle)));
+    assert_eq!(t.insert_constraint(middle, middle), Err(ConstraintError::UnknownVertex(middle)));
+    assert!(t.is_constrained(a, on_segment));
+    assert_eq!(t.insert_constraint(c, d), Ok(()));
+    check_triangulation(&t, false);
+}
+
+#[test]
+fn test_delaunay_id_range() {
+    // 16-bit ids can address the half edges of a bit less than 11000 points.
+    let points = scattered_points(11000);
+    let mut small: DelaunayTriangulation<u16> = DelaunayTriangulation::new();
+    let mut result = Ok(vertex_id(0));
+    for &p in &points {
+        result = small.insert(p);
+        if result.is_err() {
+            break;
+        }
+    }
+    assert_eq!(result, Err(TriangulationError::TooLarge));
+    assert_eq!(small.num_points(), 10922);
+    check_triangulation(&small, false);
+
+    let mut large: DelaunayTriangulation<u32> = DelaunayTriangulation::new();
+    for &p in &points {
+        large.insert(p).unwrap();
+    }
+    assert_eq!(large.num_points(), 11000);
+    assert_eq!(large.kernel().validate(), vec![]);
+}
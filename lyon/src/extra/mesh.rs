@@ -1,86 +1,283 @@
+use std::mem;
+
 use half_edge::kernel::*;
-use tesselation::vectors::*;
-use vodk_id::id_vector::IdVector;
+use half_edge::id_internals::is_valid;
+use vodk_id::IdVec;
+use vodk_math::{ Vec2, Vec3, Vec4 };
+
+pub trait Position2D { fn position(&self) -> Vec2; }
+pub trait Position3D { fn position(&self) -> Vec3; }
+pub trait Position4D { fn position(&self) -> Vec4; }
+pub trait Normal2D { fn normal(&self) -> Vec2; }
+pub trait Normal3D { fn normal(&self) -> Vec3; }
+pub trait Normal4D { fn normal(&self) -> Vec4; }
+pub trait TextureCoordinates { fn uv(&self) -> Vec2; }
+
+impl Position2D for Vec2 { fn position(&self) -> Vec2 { *self } }
+impl Position3D for Vec3 { fn position(&self) -> Vec3 { *self } }
+
+/// Vertex attributes that can be interpolated.
+pub trait Blend: Sized {
+    /// Compute the weighted sum of some attributes. The weights add up to one.
+    fn blend(attributes: &[(&Self, f32)]) -> Self;
+}
+
+impl Blend for f32 {
+    fn blend(attributes: &[(&f32, f32)]) -> f32 {
+        attributes.iter().fold(0.0, |sum, &(a, w)| sum + *a * w)
+    }
+}
+
+impl Blend for Vec2 {
+    fn blend(attributes: &[(&Vec2, f32)]) -> Vec2 {
+        attributes.iter().fold(Vec2::new(0.0, 0.0), |sum, &(a, w)| sum + *a * w)
+    }
+}
+
+impl Blend for Vec3 {
+    fn blend(attributes: &[(&Vec3, f32)]) -> Vec3 {
+        attributes.iter().fold(Vec3::new(0.0, 0.0, 0.0), |sum, &(a, w)| sum + *a * w)
+    }
+}
 
 /// Convenience class that wraps a mesh's connectivity kernel and attribute data
-pub struct Mesh<VertexAttribute, EdgeAttribute, FaceAttribute> {
-    kernel: ConnectivityKernel,
-    vertex_attributes: IdVector<VertexId, VertexAttribute>,
-    edge_attributes: IdVector<EdgeId, EdgeAttribute>,
-    face_attributes: IdVector<FaceId, FaceAttribute>,
+///
+/// The ids use `u16` handles by default, `Mesh<V, E, F, u32>` can hold larger meshes.
+pub struct Mesh<VertexAttribute, EdgeAttribute, FaceAttribute, H: KernelIndex = Index> {
+    kernel: ConnectivityKernel<H>,
+    vertex_attributes: IdVec<VertexId<H>, VertexAttribute>,
+    edge_attributes: IdVec<EdgeId<H>, EdgeAttribute>,
+    face_attributes: IdVec<FaceId<H>, FaceAttribute>,
 }
 
-impl<V, E, F> Mesh<V, E, F> {
+impl<V, E, F, H: KernelIndex> Mesh<V, E, F, H> {
 
-    pub fn new() -> Mesh<V, E, F> {
+    pub fn new() -> Mesh<V, E, F, H> {
         Mesh {
             kernel: ConnectivityKernel::new(),
-            vertex_attributes: IdVector::new(),
-            edge_attributes: IdVector::new(),
-            face_attributes: IdVector::new(),
+            vertex_attributes: IdVec::new(),
+            edge_attributes: IdVec::new(),
+            face_attributes: IdVec::new(),
+        }
+    }
+
+    pub fn with_capacities(v: H, e: H, f: H) -> Mesh<V, E, F, H> {
+        Mesh {
+            kernel: ConnectivityKernel::with_capacities(v, e, f),
+            vertex_attributes: IdVec::with_capacity(v),
+            edge_attributes: IdVec::with_capacity(e),
+            face_attributes: IdVec::with_capacity(f),
         }
     }
 
-    pub fn with_capacities(v: u16, e: u16, f: u16) -> Mesh<V, E, F> {
+    /// Create a mesh from a kernel and attributes that are indexed by its ids.
+    pub fn from_parts(
+        kernel: ConnectivityKernel<H>,
+        vertex_attributes: IdVec<VertexId<H>, V>,
+        edge_attributes: IdVec<EdgeId<H>, E>,
+        face_attributes: IdVec<FaceId<H>, F>,
+    ) -> Mesh<V, E, F, H> {
         Mesh {
-            kernel: ConnectivityKernel::with_capacities(e, f),
-            vertex_attributes: IdVector::with_capacity(v),
-            edge_attributes: IdVector::with_capacity(e),
-            face_attributes: IdVector::with_capacity(f),
+            kernel: kernel,
+            vertex_attributes: vertex_attributes,
+            edge_attributes: edge_attributes,
+            face_attributes: face_attributes,
         }
     }
 
-    pub fn connectivity_kernel(&self) -> &ConnectivityKernel { &self.kernel }
+    pub fn connectivity_kernel(&self) -> &ConnectivityKernel<H> { &self.kernel }
 
-    pub fn vertex(&self, id: VertexId) -> &V { &self.vertex_attributes[id] }
+    pub fn vertex(&self, id: VertexId<H>) -> &V { &self.vertex_attributes[id] }
 
-    pub fn vertex_mut(&mut self, id: VertexId) -> &mut V { &mut self.vertex_attributes[id] }
+    pub fn vertex_mut(&mut self, id: VertexId<H>) -> &mut V { &mut self.vertex_attributes[id] }
 
-    pub fn egde(&self, id: EdgeId) -> &E { &self.edge_attributes[id] }
+    pub fn egde(&self, id: EdgeId<H>) -> &E { &self.edge_attributes[id] }
 
-    pub fn egde_mut(&mut self, id: EdgeId) -> &mut E { &mut self.edge_attributes[id] }
+    pub fn egde_mut(&mut self, id: EdgeId<H>) -> &mut E { &mut self.edge_attributes[id] }
 
-    pub fn face(&self, id: FaceId) -> &F { &self.face_attributes[id] }
+    pub fn face(&self, id: FaceId<H>) -> &F { &self.face_attributes[id] }
 
-    pub fn face_mut(&mut self, id: FaceId) -> &mut F { &mut self.face_attributes[id] }
+    pub fn face_mut(&mut self, id: FaceId<H>) -> &mut F { &mut self.face_attributes[id] }
 
-    pub fn add_edge(&mut self, data: E) -> EdgeId {
+    pub fn add_edge(&mut self, data: E) -> EdgeId<H> {
         let id = self.kernel.add_empty_edge();
         self.edge_attributes[id] = data;
         return id;
     }
 
-    pub fn add_face(&mut self, data: F) -> FaceId {
+    /// Collapse an edge, see `ConnectivityKernel::collapse_edge`, and give a new attribute to the
+    /// vertex that is kept, which is returned.
+    pub fn collapse_edge(&mut self, id: EdgeId<H>, vertex: V) -> VertexId<H> {
+        let kept_vertex = self.kernel.collapse_edge(id);
+        self.vertex_attributes[kept_vertex] = vertex;
+        return kept_vertex;
+    }
+
+    pub fn add_face(&mut self, data: F) -> FaceId<H> {
         let id = self.kernel.add_face();
         self.face_attributes[id] = data;
         return id;
     }
+
+    /// Remove the free slots of the kernel, see `ConnectivityKernel::compact`, and move the
+    /// attributes to the new ids.
+    pub fn compact(&mut self) -> IdRemapping<H> {
+        let remap = self.kernel.compact();
+        let vertices = mem::replace(&mut self.vertex_attributes, IdVec::new());
+        self.vertex_attributes = remap_attributes(vertices, &remap.vertices);
+        let edges = mem::replace(&mut self.edge_attributes, IdVec::new());
+        self.edge_attributes = remap_attributes(edges, &remap.edges);
+        let faces = mem::replace(&mut self.face_attributes, IdVec::new());
+        self.face_attributes = remap_attributes(faces, &remap.faces);
+        return remap;
+    }
+}
+
+impl<V:Position2D, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    pub fn position2d(&self, id: VertexId<H>) -> Vec2 { self.vertex(id).position() }
+}
+
+impl<V:Position3D, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    pub fn position3d(&self, id: VertexId<H>) -> Vec3 { self.vertex(id).position() }
 }
 
-impl<V:Position2D, E, F> Mesh<V, E, F> {
-    pub fn position2d(&self, id: VertexId) -> Vec2 { self.vertex(id).position() }
+impl<V:Position4D, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    pub fn position4d(&self, id: VertexId<H>) -> Vec4 { self.vertex(id).position() }
 }
 
-impl<V:Position3D, E, F> Mesh<V, E, F> {
-    pub fn position3d(&self, id: VertexId) -> Vec3 { self.vertex(id).position() }
+impl<V:Normal2D, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    pub fn normal2d(&self, id: VertexId<H>) -> Vec2 { self.vertex(id).normal() }
 }
 
-impl<V:Position4D, E, F> Mesh<V, E, F> {
-    pub fn position3d(&self, id: VertexId) -> Vec4 { self.vertex(id).position() }
+impl<V:Normal3D, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    pub fn normal3d(&self, id: VertexId<H>) -> Vec3 { self.vertex(id).normal() }
 }
 
-impl<V:Normal2D, E, F> Mesh<V, E, F> {
-    pub fn normal2d(&self, id: VertexId) -> Vec2 { self.vertex(id).normal() }
+impl<V:Normal4D, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    pub fn normal4d(&self, id: VertexId<H>) -> Vec4 { self.vertex(id).normal() }
 }
 
-impl<V:Normal3D, E, F> Mesh<V, E, F> {
-    pub fn normal3d(&self, id: VertexId) -> Vec3 { self.vertex(id).normal() }
+impl<V:TextureCoordinates, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    pub fn uv(&self, id: VertexId<H>) -> Vec2 { self.vertex(id).uv() }
+}
+
+impl<V: Position2D, E: Default, F: Clone + Default, H: KernelIndex> Mesh<V, E, F, H> {
+    /// Split a face into triangles, see `ConnectivityKernel::triangulate_face`.
+    ///
+    /// The new triangles get a copy of the face's attribute and the new edges get the default
+    /// edge attribute.
+    pub fn triangulate_face(&mut self, face: FaceId<H>) -> Result<(), ()> {
+        let mut new_faces = Vec::new();
+        let result = {
+            let vertices = &self.vertex_attributes;
+            self.kernel.triangulate_face(face, &|v| vertices[v].position(), &mut new_faces)
+        };
+
+        for &new_face in &new_faces {
+            let attribute = self.face_attributes[face].clone();
+            self.face_attributes.set(new_face, attribute);
+        }
+        for &f in Some(face).iter().chain(new_faces.iter()) {
+            for e in self.kernel.walk_edge_ids_around_face(f) {
+                if e.handle.to_index() >= self.edge_attributes.len() {
+                    self.edge_attributes.set(e, E::default());
+                }
+            }
+        }
+
+        return result;
+    }
 }
 
-impl<V:Normal4D, E, F> Mesh<V, E, F> {
-    pub fn normal4d(&self, id: VertexId) -> Vec4 { self.vertex(id).normal() }
+/// The half edge that stands for an edge and its opposite, the one with the smallest id.
+pub fn edge_key<H: KernelIndex>(kernel: &ConnectivityKernel<H>, e: EdgeId<H>) -> EdgeId<H> {
+    let opposite = kernel[e].opposite;
+    return if is_valid(opposite) && opposite.handle < e.handle { opposite } else { e };
 }
 
-impl<V:TextureCoordinates, E, F> Mesh<V, E, F> {
-    pub fn uv(&self, id: VertexId) -> Vec2 { self.vertex(id).uv() }
+/// The half edges leaving or reaching a vertex, one per edge.
+///
+/// On the boundary, the half edge that comes to the vertex isn't the opposite of any of the half
+/// edges leaving it, so it is the only one that reaches the vertex.
+pub fn edges_around_vertex<H: KernelIndex>(
+    kernel: &ConnectivityKernel<H>,
+    v: VertexId<H>,
+) -> Vec<EdgeId<H>> {
+    let mut edges = Vec::new();
+    for e in kernel.walk_edge_ids_around_vertex(v) {
+        edges.push(e);
+        let prev = kernel[e].prev;
+        if !is_valid(kernel[prev].opposite) {
+            edges.push(prev);
+        }
+    }
+    return edges;
+}
+
+#[cfg(test)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct TestFace(u32);
+
+#[test]
+fn test_triangulate_mesh_face() {
+    let positions = vec![
+        Vec2::new(0.0, 0.0), Vec2::new(2.0, 0.0), Vec2::new(2.0, 1.0),
+        Vec2::new(1.0, 1.0), Vec2::new(1.0, 2.0), Vec2::new(0.0, 2.0),
+    ];
+    let kernel = ConnectivityKernel::from_loop(vertex_range(0, 6));
+    let edges: IdVec<EdgeId, u32> = IdVec::from_vec(vec![1; 12]);
+    let faces = IdVec::from_vec(vec![TestFace(1), TestFace(2)]);
+    let mut mesh = Mesh::from_parts(kernel, IdVec::from_vec(positions), edges, faces);
+
+    let face = mesh.connectivity_kernel().first_face().unwrap();
+    assert_eq!(mesh.triangulate_face(face), Ok(()));
+    assert_eq!(mesh.connectivity_kernel().num_faces(), 5);
+    assert_eq!(mesh.connectivity_kernel().num_edges(), 18);
+
+    // The triangles all come from the first face, the other face is untouched.
+    for f in mesh.connectivity_kernel().face_ids() {
+        let expected = match mesh.connectivity_kernel().walk_edge_ids_around_face(f).count() {
+            3 => TestFace(1),
+            _ => TestFace(2),
+        };
+        assert_eq!(*mesh.face(f), expected);
+    }
+    for e in mesh.connectivity_kernel().edge_ids() {
+        let expected = if e.handle < 12 { 1 } else { 0 };
+        assert_eq!(*mesh.egde(e), expected);
+    }
+}
+
+#[test]
+fn test_compact_mesh() {
+    // A square split by a diagonal, with u32 ids and an edge collapsed.
+    let kernel: ConnectivityKernel<u32> = ConnectivityKernel::from_loop(VertexIdRange::new(0, 4));
+    let positions = vec![
+        Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(1.0, 1.0), Vec2::new(0.0, 1.0),
+    ];
+    let edges: IdVec<EdgeId<u32>, u32> = IdVec::from_vec((0..8).collect());
+    let faces = IdVec::from_vec(vec![TestFace(1), TestFace(2)]);
+    let mut mesh = Mesh::from_parts(kernel, IdVec::from_vec(positions), edges, faces);
+    let face = FaceId::new(1);
+    assert_eq!(mesh.triangulate_face(face), Ok(()));
+    let e = mesh.connectivity_kernel()[face].first_edge;
+    let removed_vertex = mesh.connectivity_kernel()[mesh.connectivity_kernel()[e].next].vertex;
+    let removed_position = *mesh.vertex(removed_vertex);
+    let kept_vertex = mesh.collapse_edge(e, Vec2::new(0.5, 0.5));
+
+    let before: Vec<(Vec2, u32)> = mesh.connectivity_kernel().edge_ids().map(|e| {
+        (*mesh.vertex(mesh.connectivity_kernel()[e].vertex), *mesh.egde(e))
+    }).collect();
+    let remap = mesh.compact();
+    let kernel = mesh.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.num_vertices(), 3);
+    let after: Vec<(Vec2, u32)> = kernel.edge_ids().map(|e| {
+        (*mesh.vertex(kernel[e].vertex), *mesh.egde(e))
+    }).collect();
+    assert_eq!(after, before);
+    assert_eq!(*mesh.vertex(remap.vertices[kept_vertex].unwrap()), Vec2::new(0.5, 0.5));
+    for v in kernel.vertex_ids() {
+        assert!(*mesh.vertex(v) != removed_position);
+    }
 }
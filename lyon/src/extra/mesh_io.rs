@@ -0,0 +1,210 @@
+//! Types shared by the mesh readers and writers of the `obj` and `ply` modules.
+//!
+//! Imported meshes store positions in the vertices, and normals and texture coordinates in the
+//! corners of the faces. A corner is represented by the half edge that leaves its vertex, which
+//! lets neighbouring faces have different normals or texture coordinates at a shared vertex.
+
+use std::collections::HashMap;
+use std::io;
+
+use half_edge::kernel::*;
+use half_edge::builder::{ PolygonBuilder, PolygonError };
+use half_edge::id_internals::checked_id;
+use extra::mesh::Mesh;
+use vodk_id::IdVec;
+use vodk_math::{ Vec2, Vec3 };
+
+/// The attributes of a face corner.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Corner {
+    pub normal: Option<Vec3>,
+    pub uv: Option<Vec2>,
+}
+
+/// Edge attributes that can be exported as the attributes of the face corners.
+pub trait CornerAttributes {
+    fn corner_normal(&self) -> Option<Vec3>;
+    fn corner_uv(&self) -> Option<Vec2>;
+}
+
+impl CornerAttributes for Corner {
+    fn corner_normal(&self) -> Option<Vec3> { self.normal }
+    fn corner_uv(&self) -> Option<Vec2> { self.uv }
+}
+
+impl CornerAttributes for () {
+    fn corner_normal(&self) -> Option<Vec3> { None }
+    fn corner_uv(&self) -> Option<Vec2> { None }
+}
+
+/// The mesh produced by the readers.
+///
+/// Meshes with more than 65534 vertices, half edges or faces need `u32` ids.
+pub type ImportedMesh<H = Index> = Mesh<Vec3, Corner, (), H>;
+
+/// The reason why a file could not be imported.
+///
+/// Faces are numbered in the order in which they appear in the file, starting at zero.
+#[derive(Debug)]
+pub enum ImportError<H: KernelIndex = Index> {
+    Io(io::Error),
+    /// The file is malformed, the message tells where and why.
+    Syntax(String),
+    /// A face refers to a vertex that does not exist.
+    InvalidIndex { face: usize, index: i64 },
+    /// A face has less than three vertices, or uses the same vertex twice.
+    DegenerateFace(usize),
+    /// A face uses an edge that is already used by two faces, or by a face that is not wound
+    /// consistently with it.
+    NonManifoldEdge { face: usize, from: VertexId<H>, to: VertexId<H> },
+    /// Faces only touch at this vertex, without sharing edges around it.
+    NonManifoldVertex(VertexId<H>),
+    /// The mesh has more vertices, edges or faces than the ids can address.
+    TooLarge,
+}
+
+impl<H: KernelIndex> From<io::Error> for ImportError<H> {
+    fn from(error: io::Error) -> ImportError<H> { ImportError::Io(error) }
+}
+
+/// Build a mesh out of positions and of polygons given as lists of corners, each corner being
+/// the index of its position and its attributes.
+///
+/// The vertex ids of the mesh are the indices of the positions, and polygons are wound
+/// counter-clockwise around their normal.
+pub fn mesh_from_polygons<H: KernelIndex>(
+    positions: Vec<Vec3>,
+    polygons: &[Vec<(usize, Corner)>],
+) -> Result<ImportedMesh<H>, ImportError<H>> {
+    let num_edges: usize = polygons.iter().map(|p| p.len()).sum();
+    let max = H::max_count();
+    if positions.len() > max || num_edges > max || polygons.len() > max {
+        return Err(ImportError::TooLarge);
+    }
+
+    let mut builder = PolygonBuilder::with_capacities(
+        H::from_index(positions.len()),
+        H::from_index(num_edges),
+        H::from_index(polygons.len()),
+    );
+    let mut corners = Vec::with_capacity(num_edges);
+    let mut vertices = Vec::new();
+    for (i, polygon) in polygons.iter().enumerate() {
+        vertices.clear();
+        for &(index, _) in polygon {
+            if index >= positions.len() {
+                return Err(ImportError::InvalidIndex { face: i, index: index as i64 });
+            }
+            // The number of positions was checked above.
+            vertices.push(checked_id(index).unwrap());
+        }
+        let face = match builder.add_polygon(&vertices) {
+            Ok(face) => face,
+            Err(PolygonError::Degenerate) => {
+                return Err(ImportError::DegenerateFace(i));
+            }
+            Err(PolygonError::NonManifoldEdge(from, to)) => {
+                return Err(ImportError::NonManifoldEdge { face: i, from: from, to: to });
+            }
+        };
+        let edges: Vec<EdgeId<H>> = builder.kernel().walk_edge_ids_around_face(face).collect();
+        for (&edge, &(_, corner)) in edges.iter().zip(polygon.iter()) {
+            corners.push((edge, corner));
+        }
+    }
+    if let Some(&vertex) = builder.non_manifold_vertices().first() {
+        return Err(ImportError::NonManifoldVertex(vertex));
+    }
+
+    let kernel = builder.finish();
+    let mut edge_attributes = IdVec::with_capacity(H::from_index(num_edges));
+    for (edge, corner) in corners {
+        edge_attributes.set(edge, corner);
+    }
+    let face_attributes = IdVec::from_vec(vec![(); polygons.len()]);
+
+    return Ok(Mesh::from_parts(kernel, IdVec::from_vec(positions), edge_attributes, face_attributes));
+}
+
+/// Number the vertices of a kernel from zero, in the order of their ids, for the writers.
+pub fn vertex_indices<H: KernelIndex>(
+    kernel: &ConnectivityKernel<H>,
+) -> HashMap<VertexId<H>, usize> {
+    return kernel.vertex_ids().enumerate().map(|(i, v)| (v, i)).collect();
+}
+
+/// The error returned by the writers for faces that have holes, which the file formats can't
+/// represent.
+pub fn check_no_holes<H: KernelIndex>(kernel: &ConnectivityKernel<H>) -> io::Result<()> {
+    for face in kernel.face_ids() {
+        if !kernel[face].inner_edges.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "faces with holes can't be exported, triangulate them first"
+            ));
+        }
+    }
+    return Ok(());
+}
+
+#[test]
+fn test_mesh_from_polygons() {
+    let positions = vec![
+        Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0),
+    ];
+    let uv = |i: usize| Corner { normal: None, uv: Some(Vec2::new(i as f32, 0.0)) };
+    let polygons = vec![
+        vec![(0, uv(0)), (1, uv(1)), (2, uv(2))],
+        vec![(0, uv(3)), (2, uv(4)), (3, uv(5))],
+    ];
+    let mesh: ImportedMesh = mesh_from_polygons(positions.clone(), &polygons).unwrap();
+    let kernel = mesh.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.num_faces(), 2);
+    assert_eq!(kernel.num_edges(), 6);
+    assert_eq!(*mesh.vertex(vertex_id(2)), positions[2]);
+
+    // Each half edge carries the attributes of the corner at its origin.
+    for e in kernel.edge_ids() {
+        let face = kernel[e].face.handle as usize;
+        let vertex = kernel[e].vertex.handle as usize;
+        let expected = polygons[face].iter().find(|c| c.0 == vertex).unwrap().1;
+        assert_eq!(*mesh.egde(e), expected);
+    }
+
+    let out_of_range = [vec![(0, uv(0)), (1, uv(0)), (7, uv(0))]];
+    match mesh_from_polygons::<Index>(positions.clone(), &out_of_range) {
+        Err(ImportError::InvalidIndex { face: 0, index: 7 }) => {}
+        other => panic!("{:?}", other.err()),
+    }
+    match mesh_from_polygons::<Index>(positions.clone(), &[vec![(0, uv(0)), (1, uv(0))]]) {
+        Err(ImportError::DegenerateFace(0)) => {}
+        other => panic!("{:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_mesh_from_polygons_id_range() {
+    // A strip of triangles with more half edges than 16-bit ids can address.
+    let n = 11000;
+    let mut positions = Vec::new();
+    let mut polygons = Vec::new();
+    for i in 0..n {
+        positions.push(Vec3::new(i as f32, 0.0, 0.0));
+        positions.push(Vec3::new(i as f32, 1.0, 0.0));
+        if i > 0 {
+            let (a, b, c, d) = (2 * i - 2, 2 * i, 2 * i + 1, 2 * i - 1);
+            let corner = |v: usize| (v, Corner::default());
+            polygons.push(vec![corner(a), corner(b), corner(c)]);
+            polygons.push(vec![corner(a), corner(c), corner(d)]);
+        }
+    }
+    match mesh_from_polygons::<u16>(positions.clone(), &polygons) {
+        Err(ImportError::TooLarge) => {}
+        other => panic!("{:?}", other.err()),
+    }
+    let mesh: ImportedMesh<u32> = mesh_from_polygons(positions, &polygons).unwrap();
+    assert_eq!(mesh.connectivity_kernel().num_edges(), 6 * (n - 1));
+    assert_eq!(mesh.connectivity_kernel().num_faces(), 2 * (n - 1));
+}
@@ -1,2 +1,13 @@
-
 pub mod mesh;
+pub mod mesh_io;
+pub mod obj;
+pub mod ply;
+pub mod normals;
+pub mod curvature;
+pub mod subdivision;
+pub mod simplification;
+pub mod delaunay;
+pub mod voronoi;
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,391 @@
+//! Face normals, vertex normals and tangent frames of 3D meshes.
+//!
+//! Faces are expected to be wound counter-clockwise around their normal. The holes of faces are
+//! ignored.
+
+use std::collections::HashMap;
+use std::f32;
+
+use half_edge::kernel::*;
+use extra::mesh::{ Mesh, Position3D, Normal3D, TextureCoordinates };
+use extra::mesh_io::Corner;
+use vodk_math::{ Vec3, Vec4 };
+
+#[cfg(test)]
+use extra::test_utils::{ test_mesh, assert_close };
+#[cfg(test)]
+use extra::mesh_io::{ ImportedMesh, mesh_from_polygons };
+#[cfg(test)]
+use vodk_math::Vec2;
+
+/// Attributes that can receive a computed normal.
+pub trait SetNormal3D { fn set_normal(&mut self, normal: Vec3); }
+
+/// Attributes that only hold a normal, typically those of faces.
+impl SetNormal3D for Vec3 { fn set_normal(&mut self, normal: Vec3) { *self = normal; } }
+
+impl SetNormal3D for Corner {
+    fn set_normal(&mut self, normal: Vec3) { self.normal = Some(normal); }
+}
+
+/// Attributes that can receive a computed tangent frame.
+///
+/// As in MikkTSpace, the tangent is stored in `xyz` and `w` is the sign of the bitangent, which
+/// is `w * normal.cross(&tangent)`.
+pub trait SetTangent { fn set_tangent(&mut self, tangent: Vec4); }
+
+/// How the normals of the faces around a vertex are averaged.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NormalWeighting {
+    /// Large faces count more, which is cheap and works well on regular meshes.
+    Area,
+    /// Faces count in proportion to their angle at the vertex, which doesn't depend on how the
+    /// surface is split into faces.
+    Angle,
+}
+
+impl<V: Position3D, E, F: SetNormal3D, H: KernelIndex> Mesh<V, E, F, H> {
+    /// Give each face its unit normal.
+    ///
+    /// The normal of a non-planar polygon is its average normal, computed with Newell's method.
+    pub fn compute_face_normals(&mut self) {
+        let faces: Vec<FaceId<H>> = self.connectivity_kernel().face_ids().collect();
+        for face in faces {
+            let normal = normalized(face_area_vector(self, face));
+            self.face_mut(face).set_normal(normal);
+        }
+    }
+}
+
+impl<V: Position3D + SetNormal3D, E, F, H: KernelIndex> Mesh<V, E, F, H> {
+    /// Give each vertex the weighted average of the normals of its faces.
+    pub fn compute_vertex_normals(&mut self, weighting: NormalWeighting) {
+        for (vertex, sum) in vertex_normal_sums(self, weighting) {
+            self.vertex_mut(vertex).set_normal(normalized(sum));
+        }
+    }
+}
+
+impl<V: Position3D, E: SetNormal3D, F, H: KernelIndex> Mesh<V, E, F, H> {
+    /// Give each face corner the weighted average of the normals of the faces around its
+    /// vertex, for meshes that store normals in their edges, like the ones that are imported.
+    pub fn compute_corner_normals(&mut self, weighting: NormalWeighting) {
+        let normals = vertex_normal_sums(self, weighting);
+        let edges: Vec<EdgeId<H>> = self.connectivity_kernel().edge_ids().collect();
+        for e in edges {
+            let vertex = self.connectivity_kernel()[e].vertex;
+            self.egde_mut(e).set_normal(normalized(normals[&vertex]));
+        }
+    }
+}
+
+impl<V, E, F, H> Mesh<V, E, F, H>
+where V: Position3D, E: Normal3D + TextureCoordinates + SetTangent, H: KernelIndex {
+    /// Give each face corner a tangent frame that follows its texture coordinates, using the
+    /// corner normals computed beforehand.
+    ///
+    /// Like MikkTSpace, the tangent of each corner is computed from the texture coordinates of
+    /// its face, projected on the plane of its normal and normalized. It is then averaged, in
+    /// proportion to their angle, with the other corners of its vertex that have the same
+    /// normal, texture coordinates and handedness, so that vertices are split along texture
+    /// seams and where the texture is mirrored. Corners where the texture coordinates are
+    /// degenerate take the tangent of the other corners, and get an arbitrary tangent if there
+    /// are none.
+    pub fn compute_tangents(&mut self) {
+        let mut sums: HashMap<CornerKey<H>, Vec3> = HashMap::new();
+        // The handedness of each corner, if its texture coordinates are usable.
+        let mut corners: Vec<(EdgeId<H>, Option<bool>)> = Vec::new();
+        {
+            let kernel = self.connectivity_kernel();
+            for e in kernel.edge_ids() {
+                let (prev, vertex, next) = corner_vertices(kernel, e);
+                let p = self.position3d(vertex);
+                let (e1, e2) = (self.position3d(next) - p, self.position3d(prev) - p);
+                let corner = self.egde(e);
+                let uv = corner.uv();
+                let duv1 = self.egde(kernel[e].next).uv() - uv;
+                let duv2 = self.egde(kernel[e].prev).uv() - uv;
+
+                // The signed area of the corner's triangle in texture space.
+                let det = duv1.x * duv2.y - duv2.x * duv1.y;
+                if det.abs() <= f32::EPSILON {
+                    corners.push((e, None));
+                    continue;
+                }
+                let tangent = (e1 * duv2.y - e2 * duv1.y) / det;
+                let normal = corner.normal();
+                let tangent = normalized(tangent - normal * normal.dot(&tangent));
+                let angle = corner_angle(self, e);
+
+                let key = corner_key(vertex, corner, det > 0.0);
+                let sum = sums.entry(key).or_insert(Vec3::default());
+                *sum = *sum + tangent * angle;
+                corners.push((e, Some(det > 0.0)));
+            }
+        }
+
+        for (e, handedness) in corners {
+            let vertex = self.connectivity_kernel()[e].vertex;
+            let right_handed = match handedness {
+                Some(right_handed) => right_handed,
+                None => sums.contains_key(&corner_key(vertex, self.egde(e), true))
+                    || !sums.contains_key(&corner_key(vertex, self.egde(e), false)),
+            };
+            let key = corner_key(vertex, self.egde(e), right_handed);
+            let sum = sums.get(&key).cloned().unwrap_or(Vec3::default());
+            let normal = self.egde(e).normal();
+            let mut tangent = normalized(sum - normal * normal.dot(&sum));
+            if tangent == Vec3::default() {
+                tangent = any_perpendicular(normal);
+            }
+            let sign = if right_handed { 1.0 } else { -1.0 };
+            self.egde_mut(e).set_tangent(tangent.to_vec4(sign));
+        }
+    }
+}
+
+// The corners that share a tangent frame: the vertex, the bits of the normal and texture
+// coordinates, and the handedness.
+type CornerKey<H> = (VertexId<H>, [u32; 5], bool);
+
+fn corner_key<E: Normal3D + TextureCoordinates, H: KernelIndex>(
+    vertex: VertexId<H>,
+    corner: &E,
+    right_handed: bool,
+) -> CornerKey<H> {
+    let (n, uv) = (corner.normal(), corner.uv());
+    let bits = [n.x.to_bits(), n.y.to_bits(), n.z.to_bits(), uv.x.to_bits(), uv.y.to_bits()];
+    return (vertex, bits, right_handed);
+}
+
+// The weighted sums of the normals of the faces around each vertex.
+fn vertex_normal_sums<V: Position3D, E, F, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    weighting: NormalWeighting,
+) -> HashMap<VertexId<H>, Vec3> {
+    let mut sums: HashMap<VertexId<H>, Vec3> = HashMap::new();
+    let kernel = mesh.connectivity_kernel();
+    for face in kernel.face_ids() {
+        // The length of the area vector is twice the area of the face.
+        let area_vector = face_area_vector(mesh, face);
+        let normal = normalized(area_vector);
+        for e in kernel.walk_edge_ids_around_face(face) {
+            let weighted = match weighting {
+                NormalWeighting::Area => area_vector,
+                NormalWeighting::Angle => normal * corner_angle(mesh, e),
+            };
+            let sum = sums.entry(kernel[e].vertex).or_insert(Vec3::default());
+            *sum = *sum + weighted;
+        }
+    }
+    return sums;
+}
+
+fn normalized(v: Vec3) -> Vec3 {
+    let length = v.length();
+    return if length > 0.0 { v / length } else { v };
+}
+
+fn any_perpendicular(normal: Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+    return normalized(axis - normal * normal.dot(&axis));
+}
+
+// The previous vertex, the vertex and the next vertex of the corner at the origin of an edge.
+fn corner_vertices<H: KernelIndex>(
+    kernel: &ConnectivityKernel<H>,
+    e: EdgeId<H>,
+) -> (VertexId<H>, VertexId<H>, VertexId<H>) {
+    let edge = &kernel[e];
+    return (kernel[edge.prev].vertex, edge.vertex, kernel[edge.next].vertex);
+}
+
+fn corner_angle<V: Position3D, E, F, H: KernelIndex>(mesh: &Mesh<V, E, F, H>, e: EdgeId<H>) -> f32 {
+    let (prev, vertex, next) = corner_vertices(mesh.connectivity_kernel(), e);
+    let p = mesh.position3d(vertex);
+    let (a, b) = (mesh.position3d(next) - p, mesh.position3d(prev) - p);
+    return a.cross(&b).length().atan2(a.dot(&b));
+}
+
+// The normal of a face scaled by twice its area (Newell's method).
+fn face_area_vector<V: Position3D, E, F, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    face: FaceId<H>,
+) -> Vec3 {
+    let kernel = mesh.connectivity_kernel();
+    let mut sum = Vec3::default();
+    for e in kernel.walk_edge_ids_around_face(face) {
+        let a = mesh.position3d(kernel[e].vertex);
+        let b = mesh.position3d(kernel[kernel[e].next].vertex);
+        sum = sum + a.cross(&b);
+    }
+    return sum;
+}
+
+#[cfg(test)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct TestVertex { position: Vec3, normal: Vec3 }
+
+#[cfg(test)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct TestCorner { uv: Vec2, normal: Vec3, tangent: Vec4 }
+
+#[cfg(test)]
+impl Position3D for TestVertex { fn position(&self) -> Vec3 { self.position } }
+#[cfg(test)]
+impl Normal3D for TestVertex { fn normal(&self) -> Vec3 { self.normal } }
+#[cfg(test)]
+impl SetNormal3D for TestVertex { fn set_normal(&mut self, normal: Vec3) { self.normal = normal; } }
+#[cfg(test)]
+impl TextureCoordinates for TestCorner { fn uv(&self) -> Vec2 { self.uv } }
+#[cfg(test)]
+impl Normal3D for TestCorner { fn normal(&self) -> Vec3 { self.normal } }
+#[cfg(test)]
+impl SetNormal3D for TestCorner { fn set_normal(&mut self, normal: Vec3) { self.normal = normal; } }
+#[cfg(test)]
+impl SetTangent for TestCorner { fn set_tangent(&mut self, tangent: Vec4) { self.tangent = tangent; } }
+
+#[cfg(test)]
+fn polygon_mesh(vertices: Vec<TestVertex>, polygons: &[&[u16]]) -> Mesh<TestVertex, (), Vec3> {
+    return test_mesh(vertices, polygons, (), &|_| Vec3::default());
+}
+
+// A mesh with texture coordinates computed from the face and the position of each corner,
+// and corner normals.
+#[cfg(test)]
+fn textured_mesh(
+    positions: &[Vec3],
+    polygons: &[&[u16]],
+    uv: &Fn(usize, Vec3) -> Vec2,
+) -> Mesh<Vec3, TestCorner, ()> {
+    let mut mesh = test_mesh(positions.to_vec(), polygons, TestCorner::default(), &|_| ());
+    let edges: Vec<EdgeId> = mesh.connectivity_kernel().edge_ids().collect();
+    for e in edges {
+        let edge = mesh.connectivity_kernel()[e];
+        let face = edge.face.handle as usize;
+        let position = *mesh.vertex(edge.vertex);
+        mesh.egde_mut(e).uv = uv(face, position);
+    }
+    mesh.compute_corner_normals(NormalWeighting::Angle);
+    return mesh;
+}
+
+#[test]
+fn test_normals() {
+    // A large triangle in the xy plane and a small one in the yz plane, folded along the y axis.
+    // Both have a right angle at vertex 0.
+    let positions = [
+        Vec3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0),
+        Vec3::new(0.0, 10.0, 0.0), Vec3::new(0.0, 0.0, 1.0),
+    ];
+    let vertices = positions.iter().map(|&p| TestVertex { position: p, .. TestVertex::default() });
+    let mut mesh = polygon_mesh(vertices.collect(), &[&[0, 1, 2], &[0, 2, 3]]);
+
+    mesh.compute_face_normals();
+    assert_close(*mesh.face(face_id(0)), Vec3::new(0.0, 0.0, 1.0));
+    assert_close(*mesh.face(face_id(1)), Vec3::new(1.0, 0.0, 0.0));
+
+    mesh.compute_vertex_normals(NormalWeighting::Angle);
+    assert_close(mesh.normal3d(vertex_id(0)), normalized(Vec3::new(1.0, 0.0, 1.0)));
+    assert_close(mesh.normal3d(vertex_id(1)), Vec3::new(0.0, 0.0, 1.0));
+
+    // The first triangle is ten times larger.
+    mesh.compute_vertex_normals(NormalWeighting::Area);
+    assert_close(mesh.normal3d(vertex_id(0)), normalized(Vec3::new(1.0, 0.0, 10.0)));
+    assert_close(mesh.normal3d(vertex_id(3)), Vec3::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn test_corner_normals() {
+    // The normals of imported meshes go to the corners.
+    let positions = vec![
+        Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0),
+    ];
+    let polygons = vec![
+        vec![0, 1, 2].into_iter().map(|i| (i, Corner::default())).collect(),
+        vec![0, 2, 3].into_iter().map(|i| (i, Corner::default())).collect(),
+    ];
+    let mut mesh: ImportedMesh = mesh_from_polygons(positions, &polygons).unwrap();
+    mesh.compute_corner_normals(NormalWeighting::Angle);
+    let kernel = mesh.connectivity_kernel();
+    for e in kernel.edge_ids() {
+        let expected = match kernel[e].vertex.handle {
+            0 | 2 => normalized(Vec3::new(1.0, 0.0, 1.0)),
+            1 => Vec3::new(0.0, 0.0, 1.0),
+            _ => Vec3::new(1.0, 0.0, 0.0),
+        };
+        assert_close(mesh.egde(e).normal.unwrap(), expected);
+    }
+    // The positions are left untouched.
+    assert_eq!(*mesh.vertex(vertex_id(1)), Vec3::new(1.0, 0.0, 0.0));
+}
+
+#[test]
+fn test_tangents() {
+    //  3---2
+    //  |   |
+    //  0---1
+    let positions = [
+        Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(2.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0),
+    ];
+    let cases: [(fn(usize, Vec3) -> Vec2, Vec4); 3] = [
+        (|_, p| Vec2::new(p.x, p.y), Vec4::new(1.0, 0.0, 0.0, 1.0)),
+        // Mirrored texture.
+        (|_, p| Vec2::new(p.x, -p.y), Vec4::new(1.0, 0.0, 0.0, -1.0)),
+        // Rotated texture.
+        (|_, p| Vec2::new(p.y, -p.x), Vec4::new(0.0, 1.0, 0.0, 1.0)),
+    ];
+    for &(uv, expected) in &cases {
+        let mut mesh = textured_mesh(&positions, &[&[0, 1, 2, 3]], &uv);
+        mesh.compute_tangents();
+        for e in mesh.connectivity_kernel().edge_ids() {
+            let tangent = mesh.egde(e).tangent;
+            assert_close(tangent.xyz(), expected.xyz());
+            assert_eq!(tangent.w, expected.w);
+        }
+    }
+
+    // Without usable texture coordinates, the tangent is still perpendicular to the normal.
+    let mut mesh = textured_mesh(&positions, &[&[0, 1, 2, 3]], &|_, _| Vec2::new(0.0, 0.0));
+    mesh.compute_tangents();
+    for e in mesh.connectivity_kernel().edge_ids() {
+        let corner = mesh.egde(e);
+        assert!((corner.tangent.xyz().length() - 1.0).abs() < 0.0001);
+        assert!(corner.tangent.xyz().dot(&corner.normal).abs() < 0.0001);
+    }
+}
+
+#[test]
+fn test_tangent_seams() {
+    //  3---4---5
+    //  |   |   |
+    //  0---1---2
+    let positions = [
+        Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 1.0, 0.0), Vec3::new(2.0, 1.0, 0.0),
+    ];
+    let quads: [&[u16]; 2] = [&[0, 1, 4, 3], &[1, 2, 5, 4]];
+    let cases: [(fn(usize, Vec3) -> Vec2, Vec4); 2] = [
+        // The texture is mirrored on the right quad, and the texture coordinates of the
+        // vertices they share are the same on both sides.
+        (|face, p| Vec2::new(if face == 0 { p.x } else { 2.0 - p.x }, p.y),
+         Vec4::new(-1.0, 0.0, 0.0, -1.0)),
+        // The right quad uses a rotated part of the texture.
+        (|face, p| if face == 0 { Vec2::new(p.x, p.y) } else { Vec2::new(p.y + 10.0, -p.x) },
+         Vec4::new(0.0, 1.0, 0.0, 1.0)),
+    ];
+    for &(uv, right) in &cases {
+        let mut mesh = textured_mesh(&positions, &quads, &uv);
+        mesh.compute_tangents();
+        let kernel = mesh.connectivity_kernel();
+        for e in kernel.edge_ids() {
+            let tangent = mesh.egde(e).tangent;
+            let left = Vec4::new(1.0, 0.0, 0.0, 1.0);
+            let expected = if kernel[e].face == face_id(0) { left } else { right };
+            assert_close(tangent.xyz(), expected.xyz());
+            assert_eq!(tangent.w, expected.w);
+        }
+    }
+}
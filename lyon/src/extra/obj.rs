@@ -0,0 +1,250 @@
+//! Reading and writing Wavefront OBJ files.
+//!
+//! Positions, texture coordinates, normals and polygonal faces are supported. The other
+//! statements (objects, groups, materials, lines, ...) are ignored by the reader.
+
+use std::io;
+use std::io::{ BufRead, BufReader, Read, Write };
+
+use half_edge::kernel::KernelIndex;
+use extra::mesh::{ Mesh, Position3D };
+use extra::mesh_io::{ Corner, CornerAttributes, ImportedMesh, ImportError };
+use extra::mesh_io::{ mesh_from_polygons, vertex_indices, check_no_holes };
+use vodk_math::{ Vec2, Vec3 };
+
+#[cfg(test)]
+use half_edge::kernel::vertex_id;
+#[cfg(test)]
+use half_edge::id_internals::is_valid;
+
+/// Read a mesh from the content of an OBJ file.
+///
+/// The vertex ids of the mesh follow the order of the positions in the file.
+pub fn read_obj<H: KernelIndex>(input: &mut Read) -> Result<ImportedMesh<H>, ImportError<H>> {
+    let mut positions = Vec::new();
+    let mut uvs = Vec::new();
+    let mut normals = Vec::new();
+    let mut polygons = Vec::new();
+
+    for (i, line) in BufReader::new(input).lines().enumerate() {
+        let line = try!(line);
+        let line_number = i + 1;
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let v = try!(parse_floats(tokens, 3, line_number));
+                positions.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            Some("vt") => {
+                let v = try!(parse_floats(tokens, 2, line_number));
+                uvs.push(Vec2::new(v[0], v[1]));
+            }
+            Some("vn") => {
+                let v = try!(parse_floats(tokens, 3, line_number));
+                normals.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            Some("f") => {
+                let face = polygons.len();
+                let mut polygon = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let position = match parts.next() {
+                        Some(index) => try!(parse_index(index, positions.len(), face, line_number)),
+                        None => { return Err(syntax_error(line_number, "empty face corner")); }
+                    };
+                    let mut corner = Corner::default();
+                    if let Some(index) = parts.next() {
+                        if !index.is_empty() {
+                            let index = try!(parse_index(index, uvs.len(), face, line_number));
+                            corner.uv = Some(uvs[index]);
+                        }
+                    }
+                    if let Some(index) = parts.next() {
+                        if !index.is_empty() {
+                            let index = try!(parse_index(index, normals.len(), face, line_number));
+                            corner.normal = Some(normals[index]);
+                        }
+                    }
+                    polygon.push((position, corner));
+                }
+                polygons.push(polygon);
+            }
+            _ => {}
+        }
+    }
+
+    return mesh_from_polygons(positions, &polygons);
+}
+
+/// Write a mesh in the OBJ format.
+///
+/// The normals and texture coordinates are written for each corner that has them, without
+/// removing duplicates.
+pub fn write_obj<V: Position3D, E: CornerAttributes, F, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    output: &mut Write,
+) -> io::Result<()> {
+    let kernel = mesh.connectivity_kernel();
+    try!(check_no_holes(kernel));
+
+    let indices = vertex_indices(kernel);
+    for v in kernel.vertex_ids() {
+        let p = mesh.vertex(v).position();
+        try!(writeln!(output, "v {} {} {}", p.x, p.y, p.z));
+    }
+
+    let mut num_uvs = 0;
+    let mut num_normals = 0;
+    let mut face_line = String::new();
+    for face in kernel.face_ids() {
+        face_line.clear();
+        face_line.push_str("f");
+        for e in kernel.walk_edge_ids_around_face(face) {
+            let corner = mesh.egde(e);
+            face_line.push_str(&format!(" {}", indices[&kernel[e].vertex] + 1));
+            let uv = corner.corner_uv();
+            let normal = corner.corner_normal();
+            if let Some(uv) = uv {
+                try!(writeln!(output, "vt {} {}", uv.x, uv.y));
+                num_uvs += 1;
+                face_line.push_str(&format!("/{}", num_uvs));
+            }
+            if let Some(n) = normal {
+                try!(writeln!(output, "vn {} {} {}", n.x, n.y, n.z));
+                num_normals += 1;
+                let separator = if uv.is_some() { "/" } else { "//" };
+                face_line.push_str(&format!("{}{}", separator, num_normals));
+            }
+        }
+        try!(writeln!(output, "{}", face_line));
+    }
+
+    return Ok(());
+}
+
+fn syntax_error<H: KernelIndex>(line: usize, message: &str) -> ImportError<H> {
+    return ImportError::Syntax(format!("line {}: {}", line, message));
+}
+
+fn parse_floats<'l, I: Iterator<Item=&'l str>, H: KernelIndex>(
+    tokens: I,
+    count: usize,
+    line: usize,
+) -> Result<Vec<f32>, ImportError<H>> {
+    let mut values = Vec::with_capacity(count);
+    for token in tokens.take(count) {
+        match token.parse::<f32>() {
+            Ok(value) => { values.push(value); }
+            Err(_) => { return Err(syntax_error(line, &format!("invalid number {:?}", token))); }
+        }
+    }
+    if values.len() < count {
+        return Err(syntax_error(line, &format!("expected {} numbers", count)));
+    }
+    return Ok(values);
+}
+
+// Indices start at one, and negative indices are relative to the end of the elements read so far.
+fn parse_index<H: KernelIndex>(
+    token: &str,
+    len: usize,
+    face: usize,
+    line: usize,
+) -> Result<usize, ImportError<H>> {
+    let index = match token.parse::<i64>() {
+        Ok(index) => index,
+        Err(_) => { return Err(syntax_error(line, &format!("invalid index {:?}", token))); }
+    };
+    let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(ImportError::InvalidIndex { face: face, index: index });
+    }
+    return Ok(resolved as usize);
+}
+
+#[cfg(test)]
+static TWO_QUADS: &'static str = "
+# Two quads sharing an edge.
+o quads
+v 0 0 0
+v 1 0 0
+v 2 0 0
+v 0 1 0
+v 1 1 0
+v 2 1 0
+vt 0 0
+vt 1 1
+vn 0 0 1
+s off
+f 1/1/1 2/2/1 5/2/1 4/1/1
+f -5//-1 3//-1 6//-1 -2//-1
+";
+
+#[test]
+fn test_read_obj() {
+    let mesh = read_obj(&mut TWO_QUADS.as_bytes()).unwrap();
+    let kernel = mesh.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.num_vertices(), 6);
+    assert_eq!(kernel.num_faces(), 2);
+    assert_eq!(kernel.num_edges(), 8);
+    assert_eq!(kernel.edge_ids().filter(|&e| is_valid(kernel[e].opposite)).count(), 2);
+    assert_eq!(*mesh.vertex(vertex_id(5)), Vec3::new(2.0, 1.0, 0.0));
+
+    let normal = Some(Vec3::new(0.0, 0.0, 1.0));
+    for e in kernel.edge_ids() {
+        let corner = mesh.egde(e);
+        assert_eq!(corner.normal, normal);
+        if kernel[e].face.handle == 0 {
+            let expected = if kernel[e].vertex.handle % 3 == 0 { 0.0 } else { 1.0 };
+            assert_eq!(corner.uv, Some(Vec2::new(expected, expected)));
+        } else {
+            assert_eq!(corner.uv, None);
+        }
+    }
+}
+
+#[test]
+fn test_read_invalid_obj() {
+    let read = |text: &str| read_obj(&mut text.as_bytes()).err().unwrap();
+
+    match read("v 0 0 0\nv 1 x 0\n") {
+        ImportError::Syntax(message) => { assert!(message.starts_with("line 2")); }
+        error => panic!("{:?}", error),
+    }
+    match read("v 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 4\n") {
+        ImportError::InvalidIndex { face: 0, index: 4 } => {}
+        error => panic!("{:?}", error),
+    }
+    // Three triangles around the edge 1-2.
+    match read("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nf 1 2 3\nf 2 1 4\nf 1 2 5\n") {
+        ImportError::NonManifoldEdge { face: 2, from, to } => {
+            assert_eq!((from, to), (vertex_id(0), vertex_id(1)));
+        }
+        error => panic!("{:?}", error),
+    }
+    // Two triangles touching at a vertex.
+    match read("v 0 0 0\nv 1 0 0\nv 1 1 0\nv -1 0 0\nv -1 -1 0\nf 1 2 3\nf 1 4 5\n") {
+        ImportError::NonManifoldVertex(v) => { assert_eq!(v, vertex_id(0)); }
+        error => panic!("{:?}", error),
+    }
+}
+
+#[test]
+fn test_obj_round_trip() {
+    let mesh: ImportedMesh = read_obj(&mut TWO_QUADS.as_bytes()).unwrap();
+    let mut text = Vec::new();
+    write_obj(&mesh, &mut text).unwrap();
+    let copy = read_obj(&mut &text[..]).unwrap();
+
+    let (kernel, copy_kernel) = (mesh.connectivity_kernel(), copy.connectivity_kernel());
+    assert_eq!(copy_kernel.num_edges(), kernel.num_edges());
+    for v in kernel.vertex_ids() {
+        assert_eq!(copy.vertex(v), mesh.vertex(v));
+    }
+    for e in kernel.edge_ids() {
+        assert_eq!(copy_kernel[e].vertex, kernel[e].vertex);
+        assert_eq!(copy_kernel[e].opposite, kernel[e].opposite);
+        assert_eq!(copy.egde(e), mesh.egde(e));
+    }
+}
@@ -0,0 +1,542 @@
+//! Reading and writing PLY files, in the ascii and binary formats.
+//!
+//! The reader uses the `vertex` element's positions (`x`, `y`, `z`), normals (`nx`, `ny`, `nz`)
+//! and texture coordinates (`u` and `v`, or `s` and `t`), and the `face` element's vertex index
+//! list. Normals and texture coordinates are given to all the corners of their vertex. Other
+//! elements and properties are skipped.
+
+use std::io;
+use std::io::{ Read, Write };
+use std::str;
+
+use half_edge::kernel::{ KernelIndex, VertexId };
+use extra::mesh::{ Mesh, Position3D };
+use extra::mesh_io::{ Corner, CornerAttributes, ImportedMesh, ImportError };
+use extra::mesh_io::{ mesh_from_polygons, vertex_indices, check_no_holes };
+use vodk_math::{ Vec2, Vec3 };
+
+#[cfg(test)]
+use half_edge::kernel::vertex_id;
+
+/// The encoding of the data that follows the header of a PLY file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ScalarType { I8, U8, I16, U16, I32, U32, F32, F64 }
+
+#[derive(Clone, Debug)]
+enum PropertyType {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, PropertyType)>,
+}
+
+/// Read a mesh from the content of a PLY file.
+///
+/// The vertex ids of the mesh follow the order of the vertices in the file.
+pub fn read_ply<H: KernelIndex>(input: &mut Read) -> Result<ImportedMesh<H>, ImportError<H>> {
+    let mut data = Vec::new();
+    try!(input.read_to_end(&mut data));
+
+    let (format, elements, body_start) = try!(parse_header(&data));
+    let mut body = match format {
+        PlyFormat::Ascii => {
+            let text = match str::from_utf8(&data[body_start..]) {
+                Ok(text) => text,
+                Err(_) => { return Err(syntax_error("the ascii data is not valid utf-8")); }
+            };
+            Body::Ascii(text.split_whitespace())
+        }
+        _ => Body::Binary {
+            data: &data[body_start..],
+            offset: 0,
+            big_endian: format == PlyFormat::BinaryBigEndian,
+        },
+    };
+
+    let mut positions = Vec::new();
+    let mut vertex_corners = Vec::new();
+    let mut polygons = Vec::new();
+    for element in &elements {
+        for _ in 0..element.count {
+            let mut xyz = [0.0; 3];
+            let mut normal = [0.0; 3];
+            let mut uv = [0.0; 2];
+            let (mut has_normal, mut has_uv) = (false, false);
+            let mut indices = Vec::new();
+            for &(ref name, ref property) in &element.properties {
+                match *property {
+                    PropertyType::Scalar(ty) => {
+                        let value = try!(body.read(ty)) as f32;
+                        match &name[..] {
+                            "x" => { xyz[0] = value; }
+                            "y" => { xyz[1] = value; }
+                            "z" => { xyz[2] = value; }
+                            "nx" => { normal[0] = value; has_normal = true; }
+                            "ny" => { normal[1] = value; }
+                            "nz" => { normal[2] = value; }
+                            "u" | "s" => { uv[0] = value; has_uv = true; }
+                            "v" | "t" => { uv[1] = value; }
+                            _ => {}
+                        }
+                    }
+                    PropertyType::List { count, item } => {
+                        let count = try!(body.read(count));
+                        if count < 0.0 {
+                            return Err(syntax_error("negative list length"));
+                        }
+                        let is_face = element.name == "face"
+                            && (name == "vertex_indices" || name == "vertex_index");
+                        for _ in 0..(count as usize) {
+                            let value = try!(body.read(item));
+                            if is_face {
+                                indices.push(value as i64);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if element.name == "vertex" {
+                positions.push(Vec3::new(xyz[0], xyz[1], xyz[2]));
+                let normal = Vec3::new(normal[0], normal[1], normal[2]);
+                let uv = Vec2::new(uv[0], uv[1]);
+                vertex_corners.push(Corner {
+                    normal: if has_normal { Some(normal) } else { None },
+                    uv: if has_uv { Some(uv) } else { None },
+                });
+            } else if element.name == "face" {
+                polygons.push(indices);
+            }
+        }
+    }
+
+    let mut polygon_corners = Vec::with_capacity(polygons.len());
+    for (face, indices) in polygons.iter().enumerate() {
+        let mut corners = Vec::with_capacity(indices.len());
+        for &index in indices {
+            if index < 0 || index as usize >= positions.len() {
+                return Err(ImportError::InvalidIndex { face: face, index: index });
+            }
+            corners.push((index as usize, vertex_corners[index as usize]));
+        }
+        polygon_corners.push(corners);
+    }
+
+    return mesh_from_polygons(positions, &polygon_corners);
+}
+
+/// Write a mesh in the PLY format.
+///
+/// Normals and texture coordinates are written when at least one corner has them, with zeros
+/// for the corners that don't. PLY files store them per vertex, so a vertex whose corners have
+/// different attributes is written once for each of them, the copies being added after the
+/// vertices of the mesh. The faces on either side of such a seam are not connected in the file.
+pub fn write_ply<V: Position3D, E: CornerAttributes, F, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    output: &mut Write,
+    format: PlyFormat,
+) -> io::Result<()> {
+    let kernel = mesh.connectivity_kernel();
+    try!(check_no_holes(kernel));
+
+    let indices = vertex_indices(kernel);
+    let vertex_ids: Vec<VertexId<H>> = kernel.vertex_ids().collect();
+    // The vertices of the file, as the index of a vertex of the mesh and its attributes.
+    let mut file_vertices: Vec<(usize, Option<Vec3>, Option<Vec2>)> = (0..vertex_ids.len())
+        .map(|i| (i, None, None))
+        .collect();
+    // The different attributes of the corners of each vertex, and the file vertex they use.
+    let mut variants: Vec<Vec<(Option<Vec3>, Option<Vec2>, usize)>> = vec![
+        Vec::new(); vertex_ids.len()
+    ];
+    let mut polygons = Vec::with_capacity(kernel.num_faces());
+    for face in kernel.face_ids() {
+        let mut polygon = Vec::new();
+        for e in kernel.walk_edge_ids_around_face(face) {
+            let vertex = indices[&kernel[e].vertex];
+            let (normal, uv) = (mesh.egde(e).corner_normal(), mesh.egde(e).corner_uv());
+            let existing = variants[vertex].iter().find(|variant| {
+                variant.0 == normal && variant.1 == uv
+            }).map(|variant| variant.2);
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    let index = if variants[vertex].is_empty() {
+                        file_vertices[vertex] = (vertex, normal, uv);
+                        vertex
+                    } else {
+                        file_vertices.push((vertex, normal, uv));
+                        file_vertices.len() - 1
+                    };
+                    variants[vertex].push((normal, uv, index));
+                    index
+                }
+            };
+            polygon.push(index);
+        }
+        if polygon.len() > 255 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "faces with more than 255 vertices can't be exported"
+            ));
+        }
+        polygons.push(polygon);
+    }
+    let has_normals = file_vertices.iter().any(|v| v.1.is_some());
+    let has_uvs = file_vertices.iter().any(|v| v.2.is_some());
+
+    let format_name = match format {
+        PlyFormat::Ascii => "ascii",
+        PlyFormat::BinaryLittleEndian => "binary_little_endian",
+        PlyFormat::BinaryBigEndian => "binary_big_endian",
+    };
+    try!(write!(output, "ply\nformat {} 1.0\n", format_name));
+    try!(write!(output, "element vertex {}\n", file_vertices.len()));
+    try!(write!(output, "property float x\nproperty float y\nproperty float z\n"));
+    if has_normals {
+        try!(write!(output, "property float nx\nproperty float ny\nproperty float nz\n"));
+    }
+    if has_uvs {
+        try!(write!(output, "property float u\nproperty float v\n"));
+    }
+    try!(write!(output, "element face {}\n", polygons.len()));
+    try!(write!(output, "property list uchar int vertex_indices\nend_header\n"));
+
+    let big_endian = format == PlyFormat::BinaryBigEndian;
+    let mut values = Vec::with_capacity(8);
+    for &(vertex, normal, uv) in &file_vertices {
+        let p = mesh.vertex(vertex_ids[vertex]).position();
+        values.clear();
+        values.extend_from_slice(&[p.x, p.y, p.z]);
+        if has_normals {
+            let n = normal.unwrap_or(Vec3::new(0.0, 0.0, 0.0));
+            values.extend_from_slice(&[n.x, n.y, n.z]);
+        }
+        if has_uvs {
+            let uv = uv.unwrap_or(Vec2::new(0.0, 0.0));
+            values.extend_from_slice(&[uv.x, uv.y]);
+        }
+        if format == PlyFormat::Ascii {
+            let line: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+            try!(writeln!(output, "{}", line.join(" ")));
+        } else {
+            for &value in &values {
+                try!(output.write_all(&bytes_u32(value.to_bits(), big_endian)));
+            }
+        }
+    }
+    for polygon in &polygons {
+        if format == PlyFormat::Ascii {
+            let mut line = polygon.len().to_string();
+            for index in polygon {
+                line.push_str(&format!(" {}", index));
+            }
+            try!(writeln!(output, "{}", line));
+        } else {
+            try!(output.write_all(&[polygon.len() as u8]));
+            for &index in polygon {
+                try!(output.write_all(&bytes_u32(index as u32, big_endian)));
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+fn syntax_error<H: KernelIndex>(message: &str) -> ImportError<H> {
+    return ImportError::Syntax(message.to_string());
+}
+
+fn bytes_u32(value: u32, big_endian: bool) -> [u8; 4] {
+    let b = [value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8];
+    return if big_endian { [b[3], b[2], b[1], b[0]] } else { b };
+}
+
+fn parse_scalar_type<H: KernelIndex>(name: &str) -> Result<ScalarType, ImportError<H>> {
+    return Ok(match name {
+        "char" | "int8" => ScalarType::I8,
+        "uchar" | "uint8" => ScalarType::U8,
+        "short" | "int16" => ScalarType::I16,
+        "ushort" | "uint16" => ScalarType::U16,
+        "int" | "int32" => ScalarType::I32,
+        "uint" | "uint32" => ScalarType::U32,
+        "float" | "float32" => ScalarType::F32,
+        "double" | "float64" => ScalarType::F64,
+        _ => { return Err(ImportError::Syntax(format!("unknown property type {:?}", name))); }
+    });
+}
+
+// Returns the format, the elements, and the offset at which the data starts.
+fn parse_header<H: KernelIndex>(
+    data: &[u8],
+) -> Result<(PlyFormat, Vec<Element>, usize), ImportError<H>> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut offset = 0;
+    let mut line_number = 0;
+    loop {
+        let end = match data[offset..].iter().position(|&b| b == b'\n') {
+            Some(end) => offset + end,
+            None => { return Err(syntax_error("the header is not terminated by end_header")); }
+        };
+        let line = match str::from_utf8(&data[offset..end]) {
+            Ok(line) => line.trim_right_matches('\r'),
+            Err(_) => { return Err(syntax_error("the header is not valid utf-8")); }
+        };
+        offset = end + 1;
+        line_number += 1;
+
+        let error = |message: &str| ImportError::Syntax(format!("line {}: {}", line_number, message));
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if line_number == 1 {
+            if tokens != ["ply"] {
+                return Err(error("not a ply file"));
+            }
+            continue;
+        }
+        match tokens.first().map(|t| *t) {
+            Some("format") => {
+                format = Some(match tokens.get(1).map(|t| *t) {
+                    Some("ascii") => PlyFormat::Ascii,
+                    Some("binary_little_endian") => PlyFormat::BinaryLittleEndian,
+                    Some("binary_big_endian") => PlyFormat::BinaryBigEndian,
+                    _ => { return Err(error("unknown format")); }
+                });
+            }
+            Some("element") => {
+                if tokens.len() != 3 {
+                    return Err(error("expected an element name and count"));
+                }
+                let count = match tokens[2].parse() {
+                    Ok(count) => count,
+                    Err(_) => { return Err(error("invalid element count")); }
+                };
+                elements.push(Element {
+                    name: tokens[1].to_string(),
+                    count: count,
+                    properties: Vec::new(),
+                });
+            }
+            Some("property") => {
+                let property = match (tokens.get(1).map(|t| *t), tokens.len()) {
+                    (Some("list"), 5) => (tokens[4], PropertyType::List {
+                        count: try!(parse_scalar_type(tokens[2])),
+                        item: try!(parse_scalar_type(tokens[3])),
+                    }),
+                    (Some(ty), 3) => (tokens[2], PropertyType::Scalar(try!(parse_scalar_type(ty)))),
+                    _ => { return Err(error("invalid property")); }
+                };
+                match elements.last_mut() {
+                    Some(element) => { element.properties.push((property.0.to_string(), property.1)); }
+                    None => { return Err(error("property outside of an element")); }
+                }
+            }
+            Some("end_header") => { break; }
+            Some("comment") | Some("obj_info") | None => {}
+            Some(_) => { return Err(error("unknown header keyword")); }
+        }
+    }
+
+    return match format {
+        Some(format) => Ok((format, elements, offset)),
+        None => Err(syntax_error("the header has no format")),
+    };
+}
+
+enum Body<'l> {
+    Ascii(str::SplitWhitespace<'l>),
+    Binary { data: &'l [u8], offset: usize, big_endian: bool },
+}
+
+impl<'l> Body<'l> {
+    fn read<H: KernelIndex>(&mut self, ty: ScalarType) -> Result<f64, ImportError<H>> {
+        match *self {
+            Body::Ascii(ref mut tokens) => {
+                return match tokens.next().map(|t| t.parse::<f64>()) {
+                    Some(Ok(value)) => Ok(value),
+                    Some(Err(_)) => Err(syntax_error("invalid number in the data")),
+                    None => Err(syntax_error("unexpected end of the data")),
+                };
+            }
+            Body::Binary { data, ref mut offset, big_endian } => {
+                let size = match ty {
+                    ScalarType::I8 | ScalarType::U8 => 1,
+                    ScalarType::I16 | ScalarType::U16 => 2,
+                    ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+                    ScalarType::F64 => 8,
+                };
+                if *offset + size > data.len() {
+                    return Err(syntax_error("unexpected end of the data"));
+                }
+                let mut bits: u64 = 0;
+                for i in 0..size {
+                    let byte = if big_endian { data[*offset + i] } else { data[*offset + size - 1 - i] };
+                    bits = (bits << 8) | byte as u64;
+                }
+                *offset += size;
+                return Ok(match ty {
+                    ScalarType::I8 => bits as u8 as i8 as f64,
+                    ScalarType::U8 => bits as u8 as f64,
+                    ScalarType::I16 => bits as u16 as i16 as f64,
+                    ScalarType::U16 => bits as u16 as f64,
+                    ScalarType::I32 => bits as u32 as i32 as f64,
+                    ScalarType::U32 => bits as u32 as f64,
+                    ScalarType::F32 => f32::from_bits(bits as u32) as f64,
+                    ScalarType::F64 => f64::from_bits(bits),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+static SQUARE: &'static str = "ply
+format ascii 1.0
+comment Two triangles with a quality element in between.
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+element quality 1
+property list uchar float values
+element face 2
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 255
+1 0 0 0 0 1 255
+1 1 0 0 0 1 0
+0 1 0 0 0 1 0
+3 0.5 0.25 1
+3 0 1 2
+3 0 2 3
+";
+
+#[test]
+fn test_read_ascii_ply() {
+    let mesh = read_ply(&mut SQUARE.as_bytes()).unwrap();
+    let kernel = mesh.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.num_vertices(), 4);
+    assert_eq!(kernel.num_faces(), 2);
+    assert_eq!(kernel.num_edges(), 6);
+    assert_eq!(*mesh.vertex(vertex_id(2)), Vec3::new(1.0, 1.0, 0.0));
+    for e in kernel.edge_ids() {
+        assert_eq!(*mesh.egde(e), Corner { normal: Some(Vec3::new(0.0, 0.0, 1.0)), uv: None });
+    }
+}
+
+#[test]
+fn test_ply_round_trip() {
+    let mesh: ImportedMesh = read_ply(&mut SQUARE.as_bytes()).unwrap();
+    for &format in &[PlyFormat::Ascii, PlyFormat::BinaryLittleEndian, PlyFormat::BinaryBigEndian] {
+        let mut data = Vec::new();
+        write_ply(&mesh, &mut data, format).unwrap();
+        let copy = read_ply(&mut &data[..]).unwrap();
+        let (kernel, copy_kernel) = (mesh.connectivity_kernel(), copy.connectivity_kernel());
+        assert_eq!(copy_kernel.num_edges(), kernel.num_edges());
+        for v in kernel.vertex_ids() {
+            assert_eq!(copy.vertex(v), mesh.vertex(v));
+        }
+        for e in kernel.edge_ids() {
+            assert_eq!(copy_kernel[e].vertex, kernel[e].vertex);
+            assert_eq!(copy_kernel[e].opposite, kernel[e].opposite);
+        }
+    }
+}
+
+#[test]
+fn test_ply_round_trip_corner_attributes() {
+    let mut mesh: ImportedMesh = read_ply(&mut SQUARE.as_bytes()).unwrap();
+    let edges: Vec<_> = mesh.connectivity_kernel().edge_ids().collect();
+    for &e in &edges {
+        let p = *mesh.vertex(mesh.connectivity_kernel()[e].vertex);
+        mesh.egde_mut(e).uv = Some(Vec2::new(p.x, p.y));
+    }
+
+    let round_trip = |mesh: &ImportedMesh, format| {
+        let mut data = Vec::new();
+        write_ply(mesh, &mut data, format).unwrap();
+        let copy: ImportedMesh = read_ply(&mut &data[..]).unwrap();
+        let (kernel, copy_kernel) = (mesh.connectivity_kernel(), copy.connectivity_kernel());
+        assert_eq!(copy_kernel.num_edges(), kernel.num_edges());
+        for e in kernel.edge_ids() {
+            assert_eq!(copy.vertex(copy_kernel[e].vertex), mesh.vertex(kernel[e].vertex));
+            assert_eq!(copy.egde(e), mesh.egde(e));
+        }
+        return copy_kernel.num_vertices();
+    };
+
+    for &format in &[PlyFormat::Ascii, PlyFormat::BinaryLittleEndian, PlyFormat::BinaryBigEndian] {
+        assert_eq!(round_trip(&mesh, format), 4);
+    }
+
+    // Two quads with separate texture coordinates, so the vertices of the edge they share are
+    // written twice and the quads are no longer connected in the file.
+    //  0---1---2
+    //  |   |   |
+    //  3---4---5
+    let positions = (0..6).map(|i| Vec3::new((i % 3) as f32, (i / 3) as f32, 0.0)).collect();
+    let polygons: Vec<Vec<(usize, Corner)>> = [[0, 3, 4, 1], [1, 4, 5, 2]].iter().enumerate().map(
+        |(face, quad)| quad.iter().map(|&i| {
+            let uv = Vec2::new((i % 3) as f32 + face as f32 * 10.0, (i / 3) as f32);
+            (i, Corner { normal: Some(Vec3::new(0.0, 0.0, 1.0)), uv: Some(uv) })
+        }).collect()
+    ).collect();
+    let mesh = mesh_from_polygons(positions, &polygons).unwrap();
+    for &format in &[PlyFormat::Ascii, PlyFormat::BinaryLittleEndian, PlyFormat::BinaryBigEndian] {
+        assert_eq!(round_trip(&mesh, format), 8);
+    }
+}
+
+#[test]
+fn test_read_invalid_ply() {
+    let read = |text: &[u8]| read_ply(&mut &text[..]).err().unwrap();
+    let header = "ply\nformat binary_little_endian 1.0\nelement vertex 3\n\
+                  property float x\nproperty float y\nproperty float z\n\
+                  element face 2\nproperty list uchar int vertex_indices\nend_header\n";
+
+    // The data stops in the middle of the second vertex.
+    let mut data = header.as_bytes().to_vec();
+    data.extend_from_slice(&[0; 16]);
+    match read(&data) {
+        ImportError::Syntax(message) => { assert_eq!(message, "unexpected end of the data"); }
+        error => panic!("{:?}", error),
+    }
+
+    // The same triangle twice.
+    let mut data = header.as_bytes().to_vec();
+    data.extend_from_slice(&[0; 36]);
+    for _ in 0..2 {
+        data.push(3);
+        for i in 0..3 {
+            data.extend_from_slice(&bytes_u32(i, false));
+        }
+    }
+    match read(&data) {
+        ImportError::NonManifoldEdge { face: 1, from, to } => {
+            assert_eq!((from, to), (vertex_id(0), vertex_id(1)));
+        }
+        error => panic!("{:?}", error),
+    }
+
+    match read(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty half x\nend_header\n0\n") {
+        ImportError::Syntax(message) => { assert_eq!(message, "unknown property type \"half\""); }
+        error => panic!("{:?}", error),
+    }
+}
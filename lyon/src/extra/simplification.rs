@@ -0,0 +1,539 @@
+//! Mesh simplification with quadric error metrics.
+//!
+//! Each vertex accumulates the quadrics of the planes of the triangles around it, which measure
+//! the squared distance to these planes. Edges are collapsed in the order of the error of the
+//! vertex they are merged into, as described by Garland and Heckbert. The merged vertex is
+//! placed on the collapsed edge, at the point that minimizes the error, and all of its
+//! attributes are interpolated with `Blend`.
+//!
+//! Vertices on the boundary of the mesh or on a seam don't move: edges can be collapsed into
+//! them but not away from them, and the edges that connect two of them are never collapsed.
+//! Collapses that would fold a triangle over or make the mesh non-manifold are skipped.
+
+use std::cmp::Ordering;
+use std::collections::{ BinaryHeap, HashMap };
+use std::f32;
+
+use half_edge::kernel::*;
+use half_edge::id_internals::is_valid;
+use extra::mesh::{ Mesh, Position3D, Blend, edge_key, edges_around_vertex };
+use vodk_math::Vec3;
+
+#[cfg(test)]
+use half_edge::builder::PolygonBuilder;
+#[cfg(test)]
+use vodk_id::IdVec;
+
+/// Edge attributes that tell which edges separate vertices that must be kept in place, for
+/// example where texture coordinates are discontinuous.
+///
+/// Both half edges of a seam should be marked, since collapses can remove either of them.
+pub trait Seam {
+    fn is_seam(&self) -> bool;
+}
+
+impl Seam for () {
+    fn is_seam(&self) -> bool { false }
+}
+
+/// Parameters of `Mesh::simplify`.
+pub struct SimplificationOptions {
+    /// Stop collapsing edges when the mesh has this many faces or less.
+    pub target_face_count: usize,
+    /// Never collapse an edge if it would move the merged vertex further than this from its
+    /// original planes, in squared distance.
+    pub max_error: f32,
+}
+
+impl SimplificationOptions {
+    /// Simplify as much as possible.
+    pub fn new() -> SimplificationOptions {
+        SimplificationOptions {
+            target_face_count: 0,
+            max_error: f32::MAX,
+        }
+    }
+}
+
+/// The reason why a mesh can't be simplified.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SimplificationError<H: KernelIndex = Index> {
+    /// Only meshes made of triangles without holes can be simplified.
+    NonTriangularFace(FaceId<H>),
+}
+
+impl<V: Position3D + Blend, E: Seam, F, H: KernelIndex> Mesh<V, E, F, H> {
+    /// Collapse edges until the mesh has no more faces than the target, or the error of the next
+    /// collapse is above the maximum. Returns the number of collapsed edges.
+    pub fn simplify(
+        &mut self,
+        options: &SimplificationOptions,
+    ) -> Result<usize, SimplificationError<H>> {
+        let mut vertices: HashMap<VertexId<H>, VertexState> = HashMap::new();
+        {
+            let kernel = self.connectivity_kernel();
+            for v in kernel.vertex_ids() {
+                vertices.insert(v, VertexState { quadric: Quadric::zero(), locked: false, stamp: 0 });
+            }
+            for face in kernel.face_ids() {
+                let edges: Vec<EdgeId<H>> = kernel.walk_edge_ids_around_face(face).collect();
+                if edges.len() != 3 || !kernel[face].inner_edges.is_empty() {
+                    return Err(SimplificationError::NonTriangularFace(face));
+                }
+                let corners: Vec<VertexId<H>> = edges.iter().map(|&e| kernel[e].vertex).collect();
+                let p: Vec<Vec3> = corners.iter().map(|&v| self.position3d(v)).collect();
+                let normal = (p[1] - p[0]).cross(&(p[2] - p[0]));
+                let length = normal.length();
+                if length == 0.0 {
+                    continue;
+                }
+                let normal = normal * (1.0 / length);
+                // Weighted by the area of the triangle.
+                let quadric = Quadric::from_plane(normal, -normal.dot(&p[0]), length * 0.5);
+                for v in &corners {
+                    let state = vertices.get_mut(v).unwrap();
+                    state.quadric = state.quadric.add(&quadric);
+                }
+            }
+            for e in kernel.edge_ids() {
+                if !is_valid(kernel[e].opposite) || self.egde(e).is_seam() {
+                    vertices.get_mut(&kernel[e].vertex).unwrap().locked = true;
+                    vertices.get_mut(&kernel[kernel[e].next].vertex).unwrap().locked = true;
+                }
+            }
+        }
+
+        let mut queue = BinaryHeap::new();
+        for e in self.connectivity_kernel().edge_ids() {
+            if edge_key(self.connectivity_kernel(), e) == e {
+                push_collapse(self, &vertices, e, &mut queue);
+            }
+        }
+
+        let mut num_collapses = 0;
+        while self.connectivity_kernel().num_faces() > options.target_face_count {
+            let collapse = match queue.pop() {
+                Some(collapse) => collapse,
+                None => { break; }
+            };
+            let edge = collapse.edge;
+            {
+                let kernel = self.connectivity_kernel();
+                let stale = !kernel.contains_edge(edge)
+                    || kernel[edge].vertex != collapse.from
+                    || kernel[kernel[edge].next].vertex != collapse.to
+                    || vertices[&collapse.from].stamp != collapse.stamps.0
+                    || vertices[&collapse.to].stamp != collapse.stamps.1;
+                if stale {
+                    continue;
+                }
+            }
+            if collapse.cost > options.max_error {
+                break;
+            }
+
+            let a = self.position3d(collapse.from);
+            let b = self.position3d(collapse.to);
+            let position = a + (b - a) * collapse.t;
+            if !can_collapse(self, edge, position) {
+                continue;
+            }
+
+            // Keep the vertex that is locked, if any.
+            let to_locked = vertices[&collapse.to].locked;
+            let collapsed_edge = if to_locked {
+                self.connectivity_kernel()[edge].opposite
+            } else {
+                edge
+            };
+            let attribute = V::blend(&[
+                (self.vertex(collapse.from), 1.0 - collapse.t),
+                (self.vertex(collapse.to), collapse.t),
+            ]);
+            let kept = self.collapse_edge(collapsed_edge, attribute);
+            let removed = if kept == collapse.from { collapse.to } else { collapse.from };
+            let removed_state = vertices.remove(&removed).unwrap();
+            {
+                let state = vertices.get_mut(&kept).unwrap();
+                state.quadric = state.quadric.add(&removed_state.quadric);
+                state.locked |= removed_state.locked;
+                state.stamp += 1;
+            }
+            num_collapses += 1;
+
+            for e in edges_around_vertex(self.connectivity_kernel(), kept) {
+                let key = edge_key(self.connectivity_kernel(), e);
+                push_collapse(self, &vertices, key, &mut queue);
+            }
+        }
+
+        return Ok(num_collapses);
+    }
+}
+
+struct VertexState {
+    quadric: Quadric,
+    locked: bool,
+    // Incremented each time the vertex changes, to recognize outdated collapses in the queue.
+    stamp: u32,
+}
+
+// A symmetric 4x4 matrix q such that the error of a point p is [p, 1] * q * [p, 1].
+#[derive(Copy, Clone)]
+struct Quadric([[f32; 4]; 4]);
+
+impl Quadric {
+    fn zero() -> Quadric { Quadric([[0.0; 4]; 4]) }
+
+    // The squared distance to the plane n.p + d = 0, multiplied by a weight.
+    fn from_plane(n: Vec3, d: f32, weight: f32) -> Quadric {
+        let plane = [n.x, n.y, n.z, d];
+        let mut q = Quadric::zero();
+        for i in 0..4 {
+            for j in 0..4 {
+                q.0[i][j] = plane[i] * plane[j] * weight;
+            }
+        }
+        return q;
+    }
+
+    fn add(&self, other: &Quadric) -> Quadric {
+        let mut q = *self;
+        for i in 0..4 {
+            for j in 0..4 {
+                q.0[i][j] += other.0[i][j];
+            }
+        }
+        return q;
+    }
+
+    // u * q * v
+    fn product(&self, u: [f32; 4], v: [f32; 4]) -> f32 {
+        let mut sum = 0.0;
+        for i in 0..4 {
+            for j in 0..4 {
+                sum += u[i] * self.0[i][j] * v[j];
+            }
+        }
+        return sum;
+    }
+}
+
+// An edge collapse waiting in the queue, ordered so that the smallest cost comes first.
+struct Collapse<H: KernelIndex> {
+    cost: f32,
+    // Where the merged vertex goes on the edge, from 0 at its origin to 1 at its end.
+    t: f32,
+    edge: EdgeId<H>,
+    from: VertexId<H>,
+    to: VertexId<H>,
+    stamps: (u32, u32),
+}
+
+impl<H: KernelIndex> PartialEq for Collapse<H> {
+    fn eq(&self, other: &Collapse<H>) -> bool { self.cost == other.cost }
+}
+
+impl<H: KernelIndex> Eq for Collapse<H> {}
+
+impl<H: KernelIndex> PartialOrd for Collapse<H> {
+    fn partial_cmp(&self, other: &Collapse<H>) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<H: KernelIndex> Ord for Collapse<H> {
+    fn cmp(&self, other: &Collapse<H>) -> Ordering {
+        other.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal)
+    }
+}
+
+fn push_collapse<V: Position3D, E, F, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    vertices: &HashMap<VertexId<H>, VertexState>,
+    edge: EdgeId<H>,
+    queue: &mut BinaryHeap<Collapse<H>>,
+) {
+    let kernel = mesh.connectivity_kernel();
+    if !is_valid(kernel[edge].opposite) {
+        return;
+    }
+    let from = kernel[edge].vertex;
+    let to = kernel[kernel[edge].next].vertex;
+    let (a, b) = (&vertices[&from], &vertices[&to]);
+    let t = match (a.locked, b.locked) {
+        (true, true) => { return; }
+        (true, false) => 0.0,
+        (false, true) => 1.0,
+        (false, false) => {
+            // The error along the edge is a second degree polynomial of t.
+            let q = a.quadric.add(&b.quadric);
+            let p = mesh.position3d(from);
+            let d = mesh.position3d(to) - p;
+            let p = [p.x, p.y, p.z, 1.0];
+            let d = [d.x, d.y, d.z, 0.0];
+            let a2 = q.product(d, d);
+            let b1 = q.product(d, p);
+            if a2 > 0.0 { (-b1 / a2).max(0.0).min(1.0) } else { 0.5 }
+        }
+    };
+    let quadric = a.quadric.add(&b.quadric);
+    let p = mesh.position3d(from);
+    let p = p + (mesh.position3d(to) - p) * t;
+    let p = [p.x, p.y, p.z, 1.0];
+    queue.push(Collapse {
+        cost: quadric.product(p, p).max(0.0),
+        t: t,
+        edge: edge,
+        from: from,
+        to: to,
+        stamps: (a.stamp, b.stamp),
+    });
+}
+
+// Whether collapsing an edge into a point keeps the mesh manifold and doesn't turn any
+// triangle over.
+fn can_collapse<V: Position3D, E, F, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    edge: EdgeId<H>,
+    position: Vec3,
+) -> bool {
+    let kernel = mesh.connectivity_kernel();
+    let opposite = kernel[edge].opposite;
+    let a = kernel[edge].vertex;
+    let b = kernel[opposite].vertex;
+    let c = kernel[kernel[edge].prev].vertex;
+    let d = kernel[kernel[opposite].prev].vertex;
+    let edge_faces = [kernel[edge].face, kernel[opposite].face];
+
+    // The only vertices connected to both a and b must be the ones of the two triangles.
+    let a_neighbours = neighbours(kernel, a);
+    for v in neighbours(kernel, b) {
+        if v != c && v != d && a_neighbours.contains(&v) {
+            return false;
+        }
+    }
+
+    // The two other vertices of each remaining triangle, used to detect the triangles of a and
+    // b that would end up on top of each other.
+    let mut a_triangles = Vec::new();
+    for &(v, other) in &[(a, b), (b, a)] {
+        for face in kernel.walk_face_ids_around_vertex(v) {
+            if edge_faces.contains(&face) {
+                continue;
+            }
+            let corners: Vec<VertexId<H>> = kernel.walk_edge_ids_around_face(face).map(|e| {
+                kernel[e].vertex
+            }).collect();
+            let others: Vec<VertexId<H>> = corners.iter().cloned().filter(|&x| x != v).collect();
+            if v == a {
+                a_triangles.push(others.clone());
+            } else if a_triangles.iter().any(|t| t.contains(&others[0]) && t.contains(&others[1])) {
+                return false;
+            }
+
+            let old: Vec<Vec3> = corners.iter().map(|&x| mesh.position3d(x)).collect();
+            let new: Vec<Vec3> = corners.iter().map(|&x| {
+                if x == v || x == other { position } else { mesh.position3d(x) }
+            }).collect();
+            let old_normal = (old[1] - old[0]).cross(&(old[2] - old[0]));
+            let new_normal = (new[1] - new[0]).cross(&(new[2] - new[0]));
+            if old_normal.dot(&new_normal) <= 0.0 {
+                return false;
+            }
+        }
+    }
+
+    return true;
+}
+
+fn neighbours<H: KernelIndex>(kernel: &ConnectivityKernel<H>, v: VertexId<H>) -> Vec<VertexId<H>> {
+    return edges_around_vertex(kernel, v).iter().map(|&e| {
+        if kernel[e].vertex == v { kernel[kernel[e].next].vertex } else { kernel[e].vertex }
+    }).collect();
+}
+
+#[cfg(test)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct TestEdge { seam: bool }
+
+#[cfg(test)]
+impl Seam for TestEdge {
+    fn is_seam(&self) -> bool { self.seam }
+}
+
+// A square grid of n by n cells split in triangles, with the heights given by a function.
+#[cfg(test)]
+fn grid<H: Fn(f32, f32) -> f32>(n: u16, height: H) -> Mesh<Vec3, TestEdge, ()> {
+    let mut positions = Vec::new();
+    for y in 0..(n + 1) {
+        for x in 0..(n + 1) {
+            positions.push(Vec3::new(x as f32, y as f32, height(x as f32, y as f32)));
+        }
+    }
+    let mut builder = PolygonBuilder::new();
+    let mut faces = IdVec::new();
+    for y in 0..n {
+        for x in 0..n {
+            let v = |x: u16, y: u16| vertex_id(y * (n + 1) + x);
+            let triangles = [
+                [v(x, y), v(x + 1, y), v(x + 1, y + 1)],
+                [v(x, y), v(x + 1, y + 1), v(x, y + 1)],
+            ];
+            for triangle in &triangles {
+                let face = builder.add_polygon(triangle).unwrap();
+                faces.set(face, ());
+            }
+        }
+    }
+    let kernel = builder.finish();
+    let mut edges = IdVec::new();
+    for e in kernel.edge_ids() {
+        edges.set(e, TestEdge::default());
+    }
+    return Mesh::from_parts(kernel, IdVec::from_vec(positions), edges, faces);
+}
+
+#[cfg(test)]
+fn boundary_vertices<V, E, F>(mesh: &Mesh<V, E, F>) -> Vec<VertexId> {
+    let kernel = mesh.connectivity_kernel();
+    let mut vertices: Vec<VertexId> = kernel.edge_ids().filter(|&e| {
+        !is_valid(kernel[e].opposite)
+    }).map(|e| kernel[e].vertex).collect();
+    vertices.sort_by_key(|v| v.handle);
+    return vertices;
+}
+
+#[test]
+fn test_simplify_flat_grid() {
+    let mut mesh = grid(6, |_, _| 0.0);
+    let boundary = boundary_vertices(&mesh);
+    assert_eq!(boundary.len(), 24);
+
+    // Without error, everything that isn't on the boundary can go.
+    let mut options = SimplificationOptions::new();
+    options.max_error = 0.0001;
+    let collapses = mesh.simplify(&options).unwrap();
+    let kernel = mesh.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(collapses, 25);
+    assert_eq!(kernel.num_faces(), 72 - 2 * 25);
+    assert_eq!(kernel.num_vertices(), 24);
+    assert_eq!(boundary_vertices(&mesh), boundary);
+    for v in kernel.vertex_ids() {
+        assert_eq!(mesh.vertex(v).z, 0.0);
+    }
+    for f in kernel.face_ids() {
+        let p: Vec<Vec3> = kernel.walk_edge_ids_around_face(f).map(|e| {
+            *mesh.vertex(kernel[e].vertex)
+        }).collect();
+        assert!((p[1] - p[0]).cross(&(p[2] - p[0])).z > 0.0);
+    }
+}
+
+#[test]
+fn test_simplify_target_and_error() {
+    // A bump in the middle of a flat grid.
+    let height = |x: f32, y: f32| if x > 2.0 && x < 6.0 && y > 2.0 && y < 6.0 { 1.0 } else { 0.0 };
+    let mut mesh = grid(8, height);
+    let mut options = SimplificationOptions::new();
+    options.max_error = 0.0001;
+    mesh.simplify(&options).unwrap();
+    assert_eq!(mesh.connectivity_kernel().validate(), vec![]);
+    // Without error, the vertices only slide along the surface.
+    let kernel = mesh.connectivity_kernel();
+    assert!(kernel.num_vertices() < 81);
+    assert!(kernel.vertex_ids().any(|v| mesh.vertex(v).z == 1.0));
+    for v in kernel.vertex_ids() {
+        let p = *mesh.vertex(v);
+        assert_eq!(p.z, height(p.x, p.y));
+    }
+
+    let mut mesh = grid(8, height);
+    let mut options = SimplificationOptions::new();
+    options.target_face_count = 100;
+    mesh.simplify(&options).unwrap();
+    assert_eq!(mesh.connectivity_kernel().validate(), vec![]);
+    assert!(mesh.connectivity_kernel().num_faces() <= 100);
+    assert!(mesh.connectivity_kernel().num_faces() >= 99);
+}
+
+#[test]
+fn test_simplify_seams() {
+    let mut mesh = grid(6, |_, _| 0.0);
+    // A seam along the middle row.
+    {
+        let seam: Vec<EdgeId> = mesh.connectivity_kernel().edge_ids().filter(|&e| {
+            let kernel = mesh.connectivity_kernel();
+            mesh.vertex(kernel[e].vertex).y == 3.0 && mesh.vertex(kernel[kernel[e].next].vertex).y == 3.0
+        }).collect();
+        assert_eq!(seam.len(), 12);
+        for e in seam {
+            mesh.egde_mut(e).seam = true;
+        }
+    }
+    mesh.simplify(&SimplificationOptions::new()).unwrap();
+    assert_eq!(mesh.connectivity_kernel().validate(), vec![]);
+    for x in 0..7 {
+        let v = vertex_id(3 * 7 + x);
+        assert!(mesh.connectivity_kernel().contains_vertex(v));
+        assert_eq!(*mesh.vertex(v), Vec3::new(x as f32, 3.0, 0.0));
+    }
+}
+
+#[test]
+fn test_simplify_closed_mesh() {
+    // An octahedron.
+    let corners = [
+        Vec3::new(1.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0),
+    ];
+    let triangles = [
+        [0, 2, 4], [2, 1, 4], [1, 3, 4], [3, 0, 4], [2, 0, 5], [1, 2, 5], [3, 1, 5], [0, 3, 5],
+    ];
+    let mut builder = PolygonBuilder::new();
+    for t in &triangles {
+        builder.add_polygon(&[vertex_id(t[0]), vertex_id(t[1]), vertex_id(t[2])]).unwrap();
+    }
+    let kernel = builder.finish();
+    let mut edges = IdVec::new();
+    for e in kernel.edge_ids() {
+        edges.set(e, ());
+    }
+    let mut faces = IdVec::new();
+    for f in kernel.face_ids() {
+        faces.set(f, ());
+    }
+    let mut mesh: Mesh<Vec3, (), ()> = Mesh::from_parts(
+        kernel, IdVec::from_vec(corners.to_vec()), edges, faces
+    );
+
+    // Nothing can be removed without error.
+    let mut options = SimplificationOptions::new();
+    options.max_error = 0.0001;
+    assert_eq!(mesh.simplify(&options), Ok(0));
+
+    // Down to a tetrahedron, the smallest closed mesh.
+    assert_eq!(mesh.simplify(&SimplificationOptions::new()), Ok(2));
+    let kernel = mesh.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.num_faces(), 4);
+    assert_eq!(kernel.num_vertices(), 4);
+    assert!(kernel.edge_ids().all(|e| is_valid(kernel[e].opposite)));
+}
+
+#[test]
+fn test_simplify_polygons() {
+    let mut builder = PolygonBuilder::new();
+    builder.add_polygon(&[vertex_id(0), vertex_id(1), vertex_id(2), vertex_id(3)]).unwrap();
+    let kernel = builder.finish();
+    let positions = vec![
+        Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0),
+    ];
+    let mut mesh: Mesh<Vec3, (), ()> = Mesh::from_parts(
+        kernel, IdVec::from_vec(positions), IdVec::new(), IdVec::new()
+    );
+    assert_eq!(
+        mesh.simplify(&SimplificationOptions::new()),
+        Err(SimplificationError::NonTriangularFace(face_id(0)))
+    );
+}
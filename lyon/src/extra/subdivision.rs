@@ -0,0 +1,556 @@
+//! Subdivision surfaces.
+//!
+//! Loop subdivision works on triangle meshes, and Catmull-Clark subdivision on meshes made of
+//! any kind of polygons, producing quads. Both create a new mesh and leave the original one
+//! untouched.
+//!
+//! Boundary edges and the edges marked as creases are kept sharp: the points inserted on them
+//! only depend on their two vertices, and a vertex that is on exactly two sharp edges follows
+//! the curve they form. Vertices that are on more than two sharp edges are corners and don't
+//! move.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use half_edge::kernel::*;
+use half_edge::builder::{ PolygonBuilder, PolygonError };
+use half_edge::id_internals::{ is_valid, checked_id };
+use extra::mesh::{ Mesh, Blend, edge_key, edges_around_vertex };
+use vodk_id::IdVec;
+
+#[cfg(test)]
+use extra::test_utils::{ test_mesh, assert_close };
+#[cfg(test)]
+use vodk_math::Vec3;
+
+/// Edge attributes that tell which edges must be kept sharp.
+///
+/// An edge is a crease if either of its half edges is marked as one. The half edges that are
+/// created along a crease copy its attributes, so creases remain sharp when a mesh is subdivided
+/// again.
+pub trait Crease {
+    fn is_crease(&self) -> bool;
+}
+
+impl Crease for () {
+    fn is_crease(&self) -> bool { false }
+}
+
+/// The reason why a mesh can't be subdivided.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SubdivisionError<H: KernelIndex = Index> {
+    /// Loop subdivision only works on triangles.
+    NonTriangularFace(FaceId<H>),
+    /// Faces with holes can't be subdivided, they must be triangulated first.
+    FaceWithHoles(FaceId<H>),
+    /// The subdivided mesh has more vertices or half edges than its ids can address.
+    TooLarge,
+    /// The polygons of the subdivided mesh don't fit together, which happens when a face of
+    /// the original mesh goes through both half edges of an edge.
+    InvalidPolygon(PolygonError<H>),
+}
+
+impl<V, E, F, H> Mesh<V, E, F, H>
+where V: Blend, E: Crease + Clone + Default, F: Clone + Default, H: KernelIndex {
+    /// Create a mesh with four triangles for each triangle of this mesh, using Loop's rules.
+    pub fn loop_subdivision(&self) -> Result<Mesh<V, E, F, H>, SubdivisionError<H>> {
+        let kernel = self.connectivity_kernel();
+        for face in kernel.face_ids() {
+            try!(check_face(kernel, face));
+            if kernel.walk_edge_ids_around_face(face).count() != 3 {
+                return Err(SubdivisionError::NonTriangularFace(face));
+            }
+        }
+
+        let mut vertices = Vec::new();
+        let mut vertex_points = HashMap::new();
+        for v in kernel.vertex_ids() {
+            let neighbours = neighbours(self, v);
+            let point = match sharp_vertex_rule(self, v, &neighbours) {
+                Some(point) => point,
+                None => {
+                    let n = neighbours.len() as f32;
+                    let c = 3.0 / 8.0 + (2.0 * PI / n).cos() / 4.0;
+                    let beta = (5.0 / 8.0 - c * c) / n;
+                    let mut weights = vec![(self.vertex(v), 1.0 - n * beta)];
+                    for &(neighbour, _) in &neighbours {
+                        weights.push((self.vertex(neighbour), beta));
+                    }
+                    V::blend(&weights)
+                }
+            };
+            vertex_points.insert(v, try!(new_vertex(vertices.len())));
+            vertices.push(point);
+        }
+
+        let mut edge_points = HashMap::new();
+        for e in kernel.edge_ids() {
+            if edge_key(kernel, e) != e {
+                continue;
+            }
+            let a = self.vertex(kernel[e].vertex);
+            let b = self.vertex(kernel[kernel[e].next].vertex);
+            let point = if is_sharp(self, e) {
+                V::blend(&[(a, 0.5), (b, 0.5)])
+            } else {
+                let opposite = kernel[e].opposite;
+                let c = self.vertex(kernel[kernel[e].prev].vertex);
+                let d = self.vertex(kernel[kernel[opposite].prev].vertex);
+                V::blend(&[(a, 3.0 / 8.0), (b, 3.0 / 8.0), (c, 1.0 / 8.0), (d, 1.0 / 8.0)])
+            };
+            edge_points.insert(e, try!(new_vertex(vertices.len())));
+            vertices.push(point);
+        }
+
+        let mut polygons = Vec::new();
+        for face in kernel.face_ids() {
+            //          a
+            //         / \
+            //       ab---ca
+            //       / \ / \
+            //      b---bc--c
+            let edges: Vec<EdgeId<H>> = kernel.walk_edge_ids_around_face(face).collect();
+            let corners: Vec<VertexId<H>> = edges.iter().map(|&e| {
+                vertex_points[&kernel[e].vertex]
+            }).collect();
+            let mids: Vec<VertexId<H>> = edges.iter().map(|&e| {
+                edge_points[&edge_key(kernel, e)]
+            }).collect();
+            for i in 0..3 {
+                let prev = (i + 2) % 3;
+                polygons.push(SubPolygon {
+                    face: face,
+                    sides: vec![
+                        (corners[i], Some(edges[i])),
+                        (mids[i], None),
+                        (mids[prev], Some(edges[prev])),
+                    ],
+                });
+            }
+            polygons.push(SubPolygon {
+                face: face,
+                sides: vec![(mids[0], None), (mids[1], None), (mids[2], None)],
+            });
+        }
+
+        return build_mesh(self, vertices, &polygons);
+    }
+
+    /// Create a mesh with one quad for each corner of the faces of this mesh, using the rules
+    /// of Catmull and Clark.
+    pub fn catmull_clark_subdivision(&self) -> Result<Mesh<V, E, F, H>, SubdivisionError<H>> {
+        let kernel = self.connectivity_kernel();
+        for face in kernel.face_ids() {
+            try!(check_face(kernel, face));
+        }
+
+        let mut face_points = Vec::new();
+        let mut face_indices = HashMap::new();
+        for face in kernel.face_ids() {
+            let edges: Vec<EdgeId<H>> = kernel.walk_edge_ids_around_face(face).collect();
+            let w = 1.0 / edges.len() as f32;
+            let weights: Vec<(&V, f32)> = edges.iter().map(|&e| {
+                (self.vertex(kernel[e].vertex), w)
+            }).collect();
+            face_indices.insert(face, face_points.len());
+            face_points.push(V::blend(&weights));
+        }
+
+        let mut edge_points = Vec::new();
+        let mut edge_indices = HashMap::new();
+        for e in kernel.edge_ids() {
+            if edge_key(kernel, e) != e {
+                continue;
+            }
+            let a = self.vertex(kernel[e].vertex);
+            let b = self.vertex(kernel[kernel[e].next].vertex);
+            let point = if is_sharp(self, e) {
+                V::blend(&[(a, 0.5), (b, 0.5)])
+            } else {
+                let f1 = &face_points[face_indices[&kernel[e].face]];
+                let f2 = &face_points[face_indices[&kernel[kernel[e].opposite].face]];
+                V::blend(&[(a, 0.25), (b, 0.25), (f1, 0.25), (f2, 0.25)])
+            };
+            edge_indices.insert(e, edge_points.len());
+            edge_points.push(point);
+        }
+
+        let mut vertices = Vec::new();
+        let mut vertex_indices = HashMap::new();
+        for v in kernel.vertex_ids() {
+            let neighbours = neighbours(self, v);
+            let point = match sharp_vertex_rule(self, v, &neighbours) {
+                Some(point) => point,
+                None => {
+                    // (n - 2) / n * v + (sum of the neighbours and of the face points) / n²
+                    let n = neighbours.len() as f32;
+                    let mut weights = vec![(self.vertex(v), (n - 2.0) / n)];
+                    for &(neighbour, _) in &neighbours {
+                        weights.push((self.vertex(neighbour), 1.0 / (n * n)));
+                    }
+                    for face in kernel.walk_face_ids_around_vertex(v) {
+                        weights.push((&face_points[face_indices[&face]], 1.0 / (n * n)));
+                    }
+                    V::blend(&weights)
+                }
+            };
+            vertex_indices.insert(v, vertices.len());
+            vertices.push(point);
+        }
+
+        let num_vertices = vertices.len();
+        let num_edge_points = edge_points.len();
+        vertices.extend(edge_points.into_iter());
+        vertices.extend(face_points.into_iter());
+
+        let mut polygons = Vec::new();
+        for face in kernel.face_ids() {
+            //   v[i-1]--mid[i-1]--v[i]
+            //     |        |        |
+            //     |     center----mid[i]
+            //     |                 |
+            let center = try!(new_vertex(num_vertices + num_edge_points + face_indices[&face]));
+            let edges: Vec<EdgeId<H>> = kernel.walk_edge_ids_around_face(face).collect();
+            let n = edges.len();
+            for i in 0..n {
+                let prev = edges[(i + n - 1) % n];
+                let corner = try!(new_vertex(vertex_indices[&kernel[edges[i]].vertex]));
+                let mid = edge_indices[&edge_key(kernel, edges[i])];
+                let prev_mid = edge_indices[&edge_key(kernel, prev)];
+                let mid = try!(new_vertex(num_vertices + mid));
+                let prev_mid = try!(new_vertex(num_vertices + prev_mid));
+                polygons.push(SubPolygon {
+                    face: face,
+                    sides: vec![
+                        (corner, Some(edges[i])),
+                        (mid, None),
+                        (center, None),
+                        (prev_mid, Some(prev)),
+                    ],
+                });
+            }
+        }
+
+        return build_mesh(self, vertices, &polygons);
+    }
+}
+
+// A polygon of the subdivided mesh.
+struct SubPolygon<H: KernelIndex> {
+    // The face it comes from.
+    face: FaceId<H>,
+    // Each vertex, along with the half edge of the original mesh that the side going from the
+    // vertex to the next one lies on, if any.
+    sides: Vec<(VertexId<H>, Option<EdgeId<H>>)>,
+}
+
+fn new_vertex<H: KernelIndex>(index: usize) -> Result<VertexId<H>, SubdivisionError<H>> {
+    return checked_id(index).ok_or(SubdivisionError::TooLarge);
+}
+
+fn build_mesh<V, E: Clone + Default, F: Clone + Default, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    vertices: Vec<V>,
+    polygons: &[SubPolygon<H>],
+) -> Result<Mesh<V, E, F, H>, SubdivisionError<H>> {
+    // There are more half edges than faces.
+    let num_edges: usize = polygons.iter().map(|polygon| polygon.sides.len()).sum();
+    if num_edges > H::max_count() {
+        return Err(SubdivisionError::TooLarge);
+    }
+
+    let mut builder = PolygonBuilder::new();
+    let mut edges = IdVec::new();
+    let mut faces = IdVec::new();
+    for polygon in polygons {
+        let ids: Vec<VertexId<H>> = polygon.sides.iter().map(|side| side.0).collect();
+        let face = try!(builder.add_polygon(&ids).map_err(SubdivisionError::InvalidPolygon));
+        faces.set(face, mesh.face(polygon.face).clone());
+
+        let mut e = builder.kernel()[face].first_edge;
+        for side in &polygon.sides {
+            let attribute = match side.1 {
+                Some(original) => mesh.egde(original).clone(),
+                None => E::default(),
+            };
+            edges.set(e, attribute);
+            e = builder.kernel()[e].next;
+        }
+    }
+    return Ok(Mesh::from_parts(builder.finish(), IdVec::from_vec(vertices), edges, faces));
+}
+
+fn check_face<H: KernelIndex>(
+    kernel: &ConnectivityKernel<H>,
+    face: FaceId<H>,
+) -> Result<(), SubdivisionError<H>> {
+    if !kernel[face].inner_edges.is_empty() {
+        return Err(SubdivisionError::FaceWithHoles(face));
+    }
+    return Ok(());
+}
+
+fn is_sharp<V, E: Crease, F, H: KernelIndex>(mesh: &Mesh<V, E, F, H>, e: EdgeId<H>) -> bool {
+    let opposite = mesh.connectivity_kernel()[e].opposite;
+    return !is_valid(opposite) || mesh.egde(e).is_crease() || mesh.egde(opposite).is_crease();
+}
+
+// The vertices connected to a vertex by an edge, and whether that edge is sharp.
+fn neighbours<V, E: Crease, F, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    v: VertexId<H>,
+) -> Vec<(VertexId<H>, bool)> {
+    let kernel = mesh.connectivity_kernel();
+    return edges_around_vertex(kernel, v).into_iter().map(|e| {
+        let (from, to) = (kernel[e].vertex, kernel[kernel[e].next].vertex);
+        (if from == v { to } else { from }, is_sharp(mesh, e))
+    }).collect();
+}
+
+// The rules shared by both schemes for the vertices that are on sharp edges or on no edge at
+// all, or None if the vertex is smooth.
+fn sharp_vertex_rule<V: Blend, E, F, H: KernelIndex>(
+    mesh: &Mesh<V, E, F, H>,
+    v: VertexId<H>,
+    neighbours: &[(VertexId<H>, bool)],
+) -> Option<V> {
+    if neighbours.is_empty() {
+        // The smooth rules divide by the valence, and there is nothing to blend with anyway.
+        return Some(V::blend(&[(mesh.vertex(v), 1.0)]));
+    }
+    let sharp: Vec<VertexId<H>> = neighbours.iter().filter(|n| n.1).map(|n| n.0).collect();
+    return match sharp.len() {
+        0 | 1 => None,
+        2 => Some(V::blend(&[
+            (mesh.vertex(v), 0.75),
+            (mesh.vertex(sharp[0]), 0.125),
+            (mesh.vertex(sharp[1]), 0.125),
+        ])),
+        _ => Some(V::blend(&[(mesh.vertex(v), 1.0)])),
+    };
+}
+
+#[cfg(test)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct TestEdge { crease: bool }
+
+#[cfg(test)]
+impl Crease for TestEdge {
+    fn is_crease(&self) -> bool { self.crease }
+}
+
+#[cfg(test)]
+fn polygon_mesh(
+    positions: Vec<Vec3>,
+    polygons: &[&[u16]],
+    creases: bool,
+) -> Mesh<Vec3, TestEdge, u32> {
+    return test_mesh(positions, polygons, TestEdge { crease: creases }, &|i| i as u32);
+}
+
+#[cfg(test)]
+fn cube(creases: bool) -> Mesh<Vec3, TestEdge, u32> {
+    let positions = (0..8).map(|i| {
+        let c = |bit| if i & bit == 0 { -1.0 } else { 1.0 };
+        Vec3::new(c(1), c(2), c(4))
+    }).collect();
+    return polygon_mesh(positions, &[
+        &[0, 4, 6, 2], &[1, 3, 7, 5],
+        &[0, 1, 5, 4], &[2, 6, 7, 3],
+        &[0, 2, 3, 1], &[4, 5, 7, 6],
+    ], creases);
+}
+
+#[cfg(test)]
+fn find_vertex(mesh: &Mesh<Vec3, TestEdge, u32>, position: Vec3) -> Option<VertexId> {
+    mesh.connectivity_kernel().vertex_ids().find(|&v| (*mesh.vertex(v) - position).length() < 0.0001)
+}
+
+#[test]
+fn test_loop_subdivision() {
+    let tetrahedron = polygon_mesh(
+        vec![
+            Vec3::new(1.0, 1.0, 1.0), Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, 1.0, -1.0), Vec3::new(-1.0, -1.0, 1.0),
+        ],
+        &[&[0, 1, 2], &[0, 3, 1], &[0, 2, 3], &[1, 3, 2]],
+        false
+    );
+    let mesh = tetrahedron.loop_subdivision().unwrap();
+    let kernel = mesh.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.num_vertices(), 10);
+    assert_eq!(kernel.num_edges(), 48);
+    assert_eq!(kernel.num_faces(), 16);
+    assert!(kernel.edge_ids().all(|e| is_valid(kernel[e].opposite)));
+
+    // With a valence of 3, vertices keep 7/16 of their position and get 3/16 of each neighbour.
+    assert_close(*mesh.vertex(vertex_id(0)), Vec3::new(0.25, 0.25, 0.25));
+    // Edge points get 3/8 of their vertices and 1/8 of the two other vertices of the triangles.
+    assert!(find_vertex(&mesh, Vec3::new(0.5, 0.0, 0.0)).is_some());
+    for f in kernel.face_ids() {
+        assert_eq!(kernel.walk_edge_ids_around_face(f).count(), 3);
+    }
+
+    assert_eq!(
+        cube(false).loop_subdivision().err(),
+        Some(SubdivisionError::NonTriangularFace(face_id(0)))
+    );
+}
+
+#[test]
+fn test_loop_subdivision_boundary() {
+    let triangle = polygon_mesh(
+        vec![Vec3::new(0.0, 0.0, 0.0), Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 4.0, 0.0)],
+        &[&[0, 1, 2]],
+        false
+    );
+    let mesh = triangle.loop_subdivision().unwrap();
+    assert_eq!(mesh.connectivity_kernel().validate(), vec![]);
+    assert_eq!(mesh.connectivity_kernel().num_faces(), 4);
+    // The corners are on two boundary edges.
+    assert_close(*mesh.vertex(vertex_id(0)), Vec3::new(0.5, 0.5, 0.0));
+    assert_close(*mesh.vertex(vertex_id(1)), Vec3::new(3.0, 0.5, 0.0));
+    // Points on boundary edges are midpoints.
+    assert!(find_vertex(&mesh, Vec3::new(2.0, 2.0, 0.0)).is_some());
+    assert!(find_vertex(&mesh, Vec3::new(2.0, 0.0, 0.0)).is_some());
+    // Each face is split in four triangles that keep its attribute.
+    assert!(mesh.connectivity_kernel().face_ids().all(|f| *mesh.face(f) == 0));
+}
+
+#[test]
+fn test_catmull_clark_subdivision() {
+    let mesh = cube(false).catmull_clark_subdivision().unwrap();
+    let kernel = mesh.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    assert_eq!(kernel.num_vertices(), 8 + 12 + 6);
+    assert_eq!(kernel.num_faces(), 24);
+    assert_eq!(kernel.num_edges(), 96);
+    for f in kernel.face_ids() {
+        assert_eq!(kernel.walk_edge_ids_around_face(f).count(), 4);
+    }
+    // Each face of the cube is split in four quads that keep its attribute.
+    for i in 0..6 {
+        assert_eq!(kernel.face_ids().filter(|&f| *mesh.face(f) == i).count(), 4);
+    }
+
+    assert_close(*mesh.vertex(vertex_id(7)), Vec3::new(5.0 / 9.0, 5.0 / 9.0, 5.0 / 9.0));
+    assert!(find_vertex(&mesh, Vec3::new(0.75, 0.75, 0.0)).is_some());
+    assert!(find_vertex(&mesh, Vec3::new(1.0, 0.0, 0.0)).is_some());
+
+    // A second level.
+    let mesh = mesh.catmull_clark_subdivision().unwrap();
+    assert_eq!(mesh.connectivity_kernel().validate(), vec![]);
+    assert_eq!(mesh.connectivity_kernel().num_faces(), 96);
+}
+
+#[test]
+fn test_catmull_clark_creases() {
+    // When all of the edges are creases the cube keeps its shape.
+    let mesh = cube(true).catmull_clark_subdivision().unwrap();
+    assert_close(*mesh.vertex(vertex_id(7)), Vec3::new(1.0, 1.0, 1.0));
+    assert!(find_vertex(&mesh, Vec3::new(1.0, 1.0, 0.0)).is_some());
+
+    // The half edges along the creases are creases, the ones inside the faces are not.
+    let kernel = mesh.connectivity_kernel();
+    let creases = kernel.edge_ids().filter(|&e| mesh.egde(e).crease).count();
+    assert_eq!(creases, 48);
+    for e in kernel.edge_ids() {
+        let a = *mesh.vertex(kernel[e].vertex);
+        let b = *mesh.vertex(kernel[kernel[e].next].vertex);
+        let on_cube_edge = [a, b].iter().all(|p| {
+            [p.x, p.y, p.z].iter().filter(|c| c.abs() == 1.0).count() >= 2
+        });
+        assert_eq!(mesh.egde(e).crease, on_cube_edge);
+    }
+
+    // A single crease: its vertices are darts and only its midpoint is a simple average.
+    let mut mesh = cube(false);
+    {
+        let kernel = mesh.connectivity_kernel();
+        let edge = kernel.edge_ids().find(|&e| {
+            kernel[e].vertex == vertex_id(7) && kernel[kernel[e].next].vertex == vertex_id(5)
+        }).unwrap();
+        mesh.egde_mut(edge).crease = true;
+    }
+    let mesh = mesh.catmull_clark_subdivision().unwrap();
+    assert_close(*mesh.vertex(vertex_id(7)), Vec3::new(5.0 / 9.0, 5.0 / 9.0, 5.0 / 9.0));
+    assert!(find_vertex(&mesh, Vec3::new(1.0, 0.0, 1.0)).is_some());
+}
+
+#[test]
+fn test_subdivision_id_range() {
+    // Subdividing a grid of 70x70 quads makes more half edges than 16-bit ids can address.
+    let size = 70;
+    let mut positions = Vec::new();
+    for y in 0..(size + 1) {
+        for x in 0..(size + 1) {
+            positions.push(Vec3::new(x as f32, y as f32, 0.0));
+        }
+    }
+    let mut quads = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            let v = (y * (size + 1) + x) as u16;
+            quads.push([v, v + 1, v + size as u16 + 2, v + size as u16 + 1]);
+        }
+    }
+    let edge = TestEdge { crease: false };
+    let small: Mesh<Vec3, TestEdge, u32> = test_mesh(
+        positions.clone(), &quads, edge, &|i| i as u32
+    );
+    match small.catmull_clark_subdivision() {
+        Err(SubdivisionError::TooLarge) => {}
+        other => panic!("{:?}", other.err()),
+    }
+
+    let large: Mesh<Vec3, TestEdge, u32, u32> = test_mesh(positions, &quads, edge, &|i| i as u32);
+    let mesh = large.catmull_clark_subdivision().unwrap();
+    assert_eq!(mesh.connectivity_kernel().num_faces(), 4 * size * size);
+    assert_eq!(mesh.connectivity_kernel().validate(), vec![]);
+}
+
+#[test]
+fn test_subdivision_isolated_vertex() {
+    // Vertex 3 isn't on any polygon.
+    let positions = vec![
+        Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(5.0, 5.0, 5.0), Vec3::new(1.0, 1.0, 0.0),
+    ];
+    let mesh = polygon_mesh(positions, &[&[0, 1, 2], &[2, 1, 4]], false);
+    let isolated = vertex_id(3);
+    assert_eq!(mesh.connectivity_kernel().walk_edge_ids_around_vertex(isolated).count(), 0);
+
+    let subdivided = mesh.loop_subdivision().unwrap();
+    assert_close(*subdivided.vertex(isolated), Vec3::new(5.0, 5.0, 5.0));
+    let subdivided = mesh.catmull_clark_subdivision().unwrap();
+    assert_close(*subdivided.vertex(isolated), Vec3::new(5.0, 5.0, 5.0));
+}
+
+#[test]
+fn test_subdivision_bridge_edge() {
+    // A square face with a hole, connected to its outer loop by an edge that the face goes
+    // through in both directions.
+    let mut kernel = ConnectivityKernel::from_loop(vertex_range(0, 4));
+    let face = kernel.first_face().unwrap();
+    let hole = kernel.add_hole(face, vertex_range(4, 4));
+    let outer = kernel[face].first_edge;
+    let inner = kernel[hole].first_edge;
+    kernel.connect_edges(outer, kernel[inner].opposite);
+    assert!(kernel[face].inner_edges.is_empty());
+
+    let positions = (0..8).map(|i| {
+        let scale = if i < 4 { 2.0 } else { 1.0 };
+        let c = |bit| if i & bit == 0 { -scale } else { scale };
+        Vec3::new(c(1), c(2), 0.0)
+    }).collect();
+    let edges = IdVec::from_vec(vec![TestEdge::default(); kernel.num_edges()]);
+    let faces = IdVec::from_vec(vec![0; kernel.num_faces()]);
+    let mesh: Mesh<Vec3, TestEdge, u32> = Mesh::from_parts(
+        kernel, IdVec::from_vec(positions), edges, faces
+    );
+    match mesh.catmull_clark_subdivision() {
+        Err(SubdivisionError::InvalidPolygon(PolygonError::NonManifoldEdge(..))) => {}
+        other => panic!("{:?}", other.err()),
+    }
+}
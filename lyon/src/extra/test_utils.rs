@@ -0,0 +1,35 @@
+//! Mesh fixtures and assertions shared by the tests of the mesh algorithms.
+
+use half_edge::kernel::*;
+use half_edge::builder::PolygonBuilder;
+use half_edge::id_internals::checked_id;
+use extra::mesh::Mesh;
+use vodk_id::IdVec;
+use vodk_math::Vec3;
+
+/// Build a mesh out of polygons given as lists of vertex indices, with a copy of the same
+/// attribute on each half edge and the attribute of each face computed from its polygon's index.
+pub fn test_mesh<V, E: Clone, F, P: AsRef<[u16]>, H: KernelIndex>(
+    vertices: Vec<V>,
+    polygons: &[P],
+    edge: E,
+    face: &Fn(usize) -> F,
+) -> Mesh<V, E, F, H> {
+    let mut builder = PolygonBuilder::new();
+    let mut faces = IdVec::new();
+    for (i, polygon) in polygons.iter().enumerate() {
+        let ids: Vec<VertexId<H>> = polygon.as_ref().iter().map(|&v| {
+            checked_id(v as usize).unwrap()
+        }).collect();
+        let id = builder.add_polygon(&ids).unwrap();
+        assert_eq!(id.handle.to_index(), faces.len());
+        faces.push(face(i));
+    }
+    let kernel = builder.finish();
+    let edges = IdVec::from_vec(vec![edge; kernel.num_edges()]);
+    return Mesh::from_parts(kernel, IdVec::from_vec(vertices), edges, faces);
+}
+
+pub fn assert_close(a: Vec3, b: Vec3) {
+    assert!((a - b).length() < 0.0001, "{:?} != {:?}", a, b);
+}
@@ -0,0 +1,473 @@
+//! Voronoi diagram of the points of a Delaunay triangulation.
+//!
+//! The diagram is the dual of the triangulation: the circumcenters of the triangles are its
+//! vertices, and the cell of each point is the polygon of the circumcenters of the triangles
+//! around it. The cells of the points on the convex hull are unbounded, so the cells are
+//! clipped to a rectangle.
+
+use std::collections::HashMap;
+
+use half_edge::kernel::*;
+use half_edge::builder::{ PolygonBuilder, PolygonError };
+use half_edge::id_internals::{ checked_id, Index };
+use extra::delaunay::DelaunayTriangulation;
+use extra::mesh::Mesh;
+use vodk_id::IdVec;
+use vodk_math::{ Vec2, Rect };
+
+#[cfg(test)]
+use half_edge::id_internals::is_valid;
+#[cfg(test)]
+use extra::mesh::Position2D;
+
+/// The reason why a Voronoi diagram can't be built.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VoronoiError<H: KernelIndex = Index> {
+    /// The diagram has more vertices or half edges than its ids can address.
+    TooLarge,
+    /// The cells don't fit together, which can happen when constraint edges make the
+    /// triangulation not Delaunay.
+    InvalidCell(PolygonError<H>),
+}
+
+// An end of an edge of a cell, before clipping.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum End<H: KernelIndex> {
+    // The circumcenter of a triangle.
+    Center(FaceId<H>),
+    // Infinitely far, for the cells of the points on the convex hull.
+    Infinite,
+}
+
+// A vertex of the diagram.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum DiagramVertex<H: KernelIndex> {
+    Center(FaceId<H>),
+    // Where the edge between the cells of two points, sorted by id, enters or leaves the
+    // rectangle.
+    Boundary(VertexId<H>, VertexId<H>, bool),
+    // A corner of the rectangle, counter-clockwise from the one with the smallest coordinates.
+    Corner(usize),
+}
+
+impl<H: KernelIndex> DelaunayTriangulation<H> {
+    /// Build the Voronoi diagram of the points, clipped to a rectangle, with each cell's point
+    /// as its face attribute.
+    ///
+    /// The cells are counter-clockwise, and the neighbouring cells share their edges. The
+    /// points inside of the rectangle all get a cell, and the points outside of it only get
+    /// one if their cell overlaps the rectangle. Constraint edges make the triangulation not
+    /// Delaunay, in which case cells can overlap.
+    pub fn voronoi_diagram(
+        &self,
+        bounds: &Rect,
+    ) -> Result<Mesh<Vec2, (), VertexId<H>, H>, VoronoiError<H>> {
+        let kernel = self.kernel();
+        let sites: Vec<VertexId<H>> = kernel.vertex_ids().collect();
+        let mut diagram = Diagram {
+            min: Vec2::new(bounds.x, bounds.y),
+            max: Vec2::new(bounds.x_most(), bounds.y_most()),
+            ids: HashMap::new(),
+            positions: Vec::new(),
+            perimeter: HashMap::new(),
+        };
+
+        let mut cells = Vec::new();
+        let mut cell_sites = Vec::new();
+        for (site, edges) in sites.iter().cloned().zip(self.cell_edges(&sites)) {
+            let mut clipped = Vec::with_capacity(edges.len());
+            for &(neighbour, start, end) in &edges {
+                // Clip the edges shared by two cells the same way for both of them.
+                let ends = if site.handle < neighbour.handle {
+                    self.clip_edge(&mut diagram, site, neighbour, start, end)
+                } else {
+                    self.clip_edge(&mut diagram, neighbour, site, end, start)
+                        .map(|(start, end)| (end, start))
+                };
+                if let Some(ends) = ends {
+                    clipped.push(ends);
+                }
+            }
+
+            let cell = if clipped.is_empty() {
+                // The cell either contains the rectangle or doesn't overlap it.
+                let center = (diagram.min + diagram.max) * 0.5;
+                let distance = (self.position(site) - center).length();
+                if edges.iter().any(|e| (self.position(e.0) - center).length() < distance) {
+                    continue;
+                }
+                (0..4).map(DiagramVertex::Corner).collect()
+            } else {
+                diagram.close_cell(&clipped)
+            };
+            if cell.len() < 3 {
+                continue;
+            }
+            let mut ids = Vec::with_capacity(cell.len());
+            for vertex in cell {
+                ids.push(try!(diagram.vertex_id(self, vertex)));
+            }
+            cells.push(ids);
+            cell_sites.push(site);
+        }
+
+        let num_edges: usize = cells.iter().map(|cell| cell.len()).sum();
+        if num_edges > H::max_count() {
+            return Err(VoronoiError::TooLarge);
+        }
+        let mut builder = PolygonBuilder::new();
+        for cell in &cells {
+            try!(builder.add_polygon(cell).map_err(VoronoiError::InvalidCell));
+        }
+
+        let kernel = builder.finish();
+        let edges = IdVec::from_vec(vec![(); kernel.num_edges()]);
+        return Ok(Mesh::from_parts(
+            kernel,
+            IdVec::from_vec(diagram.positions),
+            edges,
+            IdVec::from_vec(cell_sites),
+        ));
+    }
+
+    // The edges of the cell of each point, counter-clockwise, as the neighbouring point that
+    // the edge separates it from and the ends of the edge.
+    fn cell_edges(&self, sites: &[VertexId<H>]) -> Vec<Vec<(VertexId<H>, End<H>, End<H>)>> {
+        let kernel = self.kernel();
+        let outer_face = match self.outer_face() {
+            Some(face) => face,
+            None => {
+                // The points are on a line and the cells are strips between them.
+                let mut sorted: Vec<(usize, VertexId<H>)> = sites.iter().cloned().enumerate()
+                    .collect();
+                if sites.len() > 1 {
+                    let origin = self.position(sites[0]);
+                    let direction = self.position(sites[1]) - origin;
+                    sorted.sort_by(|a, b| {
+                        let a = (self.position(a.1) - origin).dot(&direction);
+                        let b = (self.position(b.1) - origin).dot(&direction);
+                        a.partial_cmp(&b).unwrap()
+                    });
+                }
+                let mut edges = vec![Vec::new(); sites.len()];
+                for pair in sorted.windows(2) {
+                    let ((i, a), (j, b)) = (pair[0], pair[1]);
+                    edges[i].push((b, End::Infinite, End::Infinite));
+                    edges[j].push((a, End::Infinite, End::Infinite));
+                }
+                return edges;
+            }
+        };
+
+        return sites.iter().map(|&site| {
+            // The edge of the cell that separates it from the destination of a half edge goes
+            // from the circumcenter of the triangle on the right of the half edge to the one
+            // on its left.
+            let mut edges = Vec::new();
+            let first_edge = kernel[site].first_edge;
+            let mut e = first_edge;
+            loop {
+                let opposite = kernel[e].opposite;
+                let end = |face| if face == outer_face { End::Infinite } else { End::Center(face) };
+                let neighbour = kernel[opposite].vertex;
+                edges.push((neighbour, end(kernel[opposite].face), end(kernel[e].face)));
+                e = kernel[kernel[e].prev].opposite;
+                if e == first_edge {
+                    break;
+                }
+            }
+            edges
+        }).collect();
+    }
+
+    // Clip the edge between the cells of a and b, going counter-clockwise around a, to the
+    // rectangle. Returns None if the edge is outside of it.
+    fn clip_edge(
+        &self,
+        diagram: &mut Diagram<H>,
+        a: VertexId<H>,
+        b: VertexId<H>,
+        start: End<H>,
+        end: End<H>,
+    ) -> Option<(DiagramVertex<H>, DiagramVertex<H>)> {
+        // The bisector, going with a on its left.
+        let (pa, pb) = (self.position(a), self.position(b));
+        let direction = Vec2::new(pa.y - pb.y, pb.x - pa.x);
+        let infinity = ::std::f64::INFINITY;
+        let (origin, direction, t0, t1) = match (start, end) {
+            (End::Center(f), End::Center(g)) => {
+                let origin = self.circumcenter(f);
+                (origin, self.circumcenter(g) - origin, 0.0, 1.0)
+            }
+            (End::Infinite, End::Center(g)) => (self.circumcenter(g), direction, -infinity, 0.0),
+            (End::Center(f), End::Infinite) => (self.circumcenter(f), direction, 0.0, infinity),
+            (End::Infinite, End::Infinite) => ((pa + pb) * 0.5, direction, -infinity, infinity),
+        };
+
+        let ((ta, side_a), (tb, side_b)) = match diagram.clip(origin, direction, t0, t1) {
+            Some(range) => range,
+            None => { return None; }
+        };
+        let mut clipped_end = |t: f64, side: Option<usize>, finite: End<H>, is_end: bool| {
+            match (side, finite) {
+                (None, End::Center(face)) => DiagramVertex::Center(face),
+                (side, _) => {
+                    let vertex = DiagramVertex::Boundary(a, b, is_end);
+                    let point = Vec2::new(
+                        (origin.x as f64 + direction.x as f64 * t) as f32,
+                        (origin.y as f64 + direction.y as f64 * t) as f32,
+                    );
+                    diagram.add_boundary_point(vertex, point, side.unwrap_or(0));
+                    vertex
+                }
+            }
+        };
+        let start = clipped_end(ta, side_a, start, false);
+        let end = clipped_end(tb, side_b, end, true);
+        return Some((start, end));
+    }
+
+    fn circumcenter(&self, face: FaceId<H>) -> Vec2 {
+        let kernel = self.kernel();
+        let e = kernel[face].first_edge;
+        let a = self.position(kernel[e].vertex);
+        let b = self.position(kernel[kernel[e].next].vertex);
+        let c = self.position(kernel[kernel[e].prev].vertex);
+
+        let (bx, by) = (b.x as f64 - a.x as f64, b.y as f64 - a.y as f64);
+        let (cx, cy) = (c.x as f64 - a.x as f64, c.y as f64 - a.y as f64);
+        let d = 2.0 * (bx * cy - by * cx);
+        let (b2, c2) = (bx * bx + by * by, cx * cx + cy * cy);
+        let x = (cy * b2 - by * c2) / d;
+        let y = (bx * c2 - cx * b2) / d;
+        return Vec2::new(a.x + x as f32, a.y + y as f32);
+    }
+}
+
+// The vertices of a diagram being built, and the rectangle it is clipped to.
+struct Diagram<H: KernelIndex> {
+    min: Vec2,
+    max: Vec2,
+    ids: HashMap<DiagramVertex<H>, VertexId<H>>,
+    positions: Vec<Vec2>,
+    // The position of the points on the boundary of the rectangle, and how far they are
+    // along it, counter-clockwise from the first corner, with one unit per side.
+    perimeter: HashMap<DiagramVertex<H>, (Vec2, f32)>,
+}
+
+impl<H: KernelIndex> Diagram<H> {
+    // The part of the points origin + direction * t, with t between t0 and t1, that is in the
+    // rectangle, as its ends and the sides of the rectangle that they are on, if any. The
+    // sides are counted counter-clockwise from the one with the smallest y.
+    fn clip(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        t0: f64,
+        t1: f64,
+    ) -> Option<((f64, Option<usize>), (f64, Option<usize>))> {
+        let (ox, oy) = (origin.x as f64, origin.y as f64);
+        let (dx, dy) = (direction.x as f64, direction.y as f64);
+        // Liang-Barsky: the points are inside of side i where p * t <= q.
+        let sides = [
+            (-dy, oy - self.min.y as f64),
+            (dx, self.max.x as f64 - ox),
+            (dy, self.max.y as f64 - oy),
+            (-dx, ox - self.min.x as f64),
+        ];
+        let (mut start, mut end) = ((t0, None), (t1, None));
+        for (i, &(p, q)) in sides.iter().enumerate() {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                if q / p > start.0 {
+                    start = (q / p, Some(i));
+                }
+            } else if q / p < end.0 {
+                end = (q / p, Some(i));
+            }
+        }
+        if start.0 > end.0 || (start.0 == end.0 && (start.1.is_some() || end.1.is_some())) {
+            return None;
+        }
+        return Some((start, end));
+    }
+
+    fn add_boundary_point(&mut self, vertex: DiagramVertex<H>, point: Vec2, side: usize) {
+        let (min, max) = (self.min, self.max);
+        let clamp = |v: f32, min: f32, max: f32| v.max(min).min(max);
+        let (x, y) = (clamp(point.x, min.x, max.x), clamp(point.y, min.y, max.y));
+        let (point, along) = match side {
+            0 => (Vec2::new(x, min.y), (x - min.x) / (max.x - min.x)),
+            1 => (Vec2::new(max.x, y), 1.0 + (y - min.y) / (max.y - min.y)),
+            2 => (Vec2::new(x, max.y), 2.0 + (max.x - x) / (max.x - min.x)),
+            _ => (Vec2::new(min.x, y), 3.0 + (max.y - y) / (max.y - min.y)),
+        };
+        self.perimeter.insert(vertex, (point, along));
+    }
+
+    // Join the clipped edges of a cell, adding the corners of the rectangle that are between
+    // an edge that leaves the rectangle and the next one, which enters it again.
+    fn close_cell(
+        &self,
+        edges: &[(DiagramVertex<H>, DiagramVertex<H>)],
+    ) -> Vec<DiagramVertex<H>> {
+        let mut cell = Vec::new();
+        for (i, &(start, end)) in edges.iter().enumerate() {
+            if cell.last() != Some(&start) {
+                cell.push(start);
+            }
+            cell.push(end);
+
+            let next = edges[(i + 1) % edges.len()].0;
+            if next == end {
+                continue;
+            }
+            if let (Some(&(_, from)), Some(&(_, to))) =
+                (self.perimeter.get(&end), self.perimeter.get(&next)) {
+                if from == to {
+                    continue;
+                }
+                let to = if to < from { to + 4.0 } else { to };
+                let mut corners: Vec<f32> = (0..8).map(|c| c as f32).filter(|&c| {
+                    c > from && c < to
+                }).collect();
+                corners.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                cell.extend(corners.iter().map(|&c| DiagramVertex::Corner(c as usize % 4)));
+            }
+        }
+        if cell.len() > 1 && cell.first() == cell.last() {
+            cell.pop();
+        }
+        return cell;
+    }
+
+    fn vertex_id(
+        &mut self,
+        triangulation: &DelaunayTriangulation<H>,
+        vertex: DiagramVertex<H>,
+    ) -> Result<VertexId<H>, VoronoiError<H>> {
+        if let Some(&id) = self.ids.get(&vertex) {
+            return Ok(id);
+        }
+        let id = match checked_id(self.positions.len()) {
+            Some(id) => id,
+            None => { return Err(VoronoiError::TooLarge); }
+        };
+        let position = match vertex {
+            DiagramVertex::Center(face) => triangulation.circumcenter(face),
+            DiagramVertex::Boundary(..) => self.perimeter[&vertex].0,
+            DiagramVertex::Corner(c) => Vec2::new(
+                if c == 1 || c == 2 { self.max.x } else { self.min.x },
+                if c >= 2 { self.max.y } else { self.min.y },
+            ),
+        };
+        self.ids.insert(vertex, id);
+        self.positions.push(position);
+        return Ok(id);
+    }
+}
+
+// Check that the cells are convex, counter-clockwise, contain their site, that their corners
+// are at least as close to their site as to any other point, and that they cover the
+// rectangle.
+#[cfg(test)]
+fn check_cells(t: &DelaunayTriangulation, voronoi: &Mesh<Vec2, (), VertexId>, bounds: &Rect) {
+    let kernel = voronoi.connectivity_kernel();
+    assert_eq!(kernel.validate(), vec![]);
+    let mut area = 0.0;
+    for face in kernel.face_ids() {
+        let site = *voronoi.face(face);
+        let p = t.position(site);
+        let corners: Vec<Vec2> = kernel.walk_edge_ids_around_face(face).map(|e| {
+            voronoi.vertex(kernel[e].vertex).position()
+        }).collect();
+
+        for i in 0..corners.len() {
+            let (a, b) = (corners[i], corners[(i + 1) % corners.len()]);
+            assert!((b - a).cross(p - a) > 0.0);
+            let c = corners[(i + 2) % corners.len()];
+            assert!((b - a).cross(c - b) >= -0.0001);
+            area += a.cross(b) * 0.5;
+        }
+
+        for &corner in &corners {
+            assert!(corner.x >= bounds.x && corner.x <= bounds.x_most());
+            assert!(corner.y >= bounds.y && corner.y <= bounds.y_most());
+            let distance = (corner - p).length();
+            for v in t.kernel().vertex_ids() {
+                assert!(distance <= (corner - t.position(v)).length() + 0.0001);
+            }
+        }
+    }
+    assert!((area - bounds.width * bounds.height).abs() < 0.001);
+}
+
+#[test]
+fn test_voronoi_diagram() {
+    // A jittered grid.
+    let mut t = DelaunayTriangulation::new();
+    for y in 0..8 {
+        for x in 0..8 {
+            let jitter = ((x * 7 + y * 13) % 5) as f32 * 0.05;
+            t.insert(Vec2::new(x as f32 + jitter, y as f32 - jitter)).unwrap();
+        }
+    }
+    let bounds = Rect::new(-1.0, -1.5, 9.0, 9.5);
+    let voronoi = t.voronoi_diagram(&bounds).unwrap();
+    check_cells(&t, &voronoi, &bounds);
+
+    // Every point gets a cell, including the ones on the hull.
+    let kernel = voronoi.connectivity_kernel();
+    let mut sites: Vec<VertexId> = kernel.face_ids().map(|f| *voronoi.face(f)).collect();
+    sites.sort_by_key(|v| v.handle);
+    assert_eq!(sites, t.kernel().vertex_ids().collect::<Vec<VertexId>>());
+
+    // Neighbouring cells share their edges, the others are on the rectangle.
+    let shared = kernel.edge_ids().filter(|&e| is_valid(kernel[e].opposite)).count();
+    assert!(shared > kernel.num_edges() / 2);
+    for e in kernel.edge_ids().filter(|&e| !is_valid(kernel[e].opposite)) {
+        let a = voronoi.vertex(kernel[e].vertex).position();
+        let b = voronoi.vertex(kernel[kernel[e].next].vertex).position();
+        assert!((a.x == b.x && (a.x == bounds.x || a.x == bounds.x_most()))
+            || (a.y == b.y && (a.y == bounds.y || a.y == bounds.y_most())));
+    }
+}
+
+#[test]
+fn test_voronoi_diagram_without_triangles() {
+    let bounds = Rect::new(-1.0, -1.0, 4.0, 4.0);
+    let mut t = DelaunayTriangulation::new();
+    t.insert(Vec2::new(0.0, 0.0)).unwrap();
+    let voronoi = t.voronoi_diagram(&bounds).unwrap();
+    check_cells(&t, &voronoi, &bounds);
+    assert_eq!(voronoi.connectivity_kernel().num_faces(), 1);
+
+    // Points on a line have strips as cells.
+    t.insert(Vec2::new(1.0, 1.0)).unwrap();
+    let b = t.insert(Vec2::new(2.0, 2.0)).unwrap();
+    let voronoi = t.voronoi_diagram(&bounds).unwrap();
+    check_cells(&t, &voronoi, &bounds);
+    assert_eq!(voronoi.connectivity_kernel().num_faces(), 3);
+
+    // A square around a point, which gets the only bounded cell.
+    t.remove(b).unwrap();
+    t.insert(Vec2::new(1.0, 0.0)).unwrap();
+    t.insert(Vec2::new(0.0, 1.0)).unwrap();
+    let center = t.insert(Vec2::new(0.5, 0.5)).unwrap();
+    let voronoi = t.voronoi_diagram(&bounds).unwrap();
+    check_cells(&t, &voronoi, &bounds);
+    assert_eq!(voronoi.connectivity_kernel().num_faces(), 5);
+    let kernel = voronoi.connectivity_kernel();
+    let cell = kernel.face_ids().find(|&f| *voronoi.face(f) == center).unwrap();
+    assert_eq!(kernel.walk_edge_ids_around_face(cell).count(), 4);
+
+    // The cell of a point far outside of the rectangle doesn't overlap it.
+    let far = t.insert(Vec2::new(10.0, 10.0)).unwrap();
+    let voronoi = t.voronoi_diagram(&bounds).unwrap();
+    check_cells(&t, &voronoi, &bounds);
+    assert_eq!(voronoi.connectivity_kernel().num_faces(), 5);
+    assert!(voronoi.connectivity_kernel().face_ids().all(|f| *voronoi.face(f) != far));
+}
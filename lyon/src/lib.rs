@@ -1,7 +1,8 @@
 extern crate vodk_alloc;
 extern crate vodk_id;
 extern crate vodk_math;
+extern crate vodk_geom;
 
-//pub mod half_edge;
+pub use vodk_geom::half_edge;
 pub mod tesselation;
-//pub mod extra;
+pub mod extra;
@@ -1,14 +1,21 @@
 #![allow(dead_code)]
 
 use tesselation::vertex_builder::VertexBufferBuilder;
+use tesselation::math_utils::num_arc_segments;
+use tesselation::path_tesselator::{ stroke_polyline, StrokeOptions };
 use tesselation::{ Index };
 
-use vodk_math::{ Vector2D, Rectangle };
+use vodk_math::{ Vector2D, Vec2, Rectangle, Untyped };
 use vodk_math::units::{ Texels };
 
+#[cfg(test)]
+use tesselation::vertex_builder::{ VertexBuffers, simple_vertex_builder };
+#[cfg(test)]
+use vodk_math::{ vec2, Rect };
+
 use std::f32::consts::PI;
 
-pub struct RoundedRectangle<U> {
+pub struct RoundedRectangle<U = Untyped> {
     rect: Rectangle<U>,
     top_left_radius: f32,
     top_right_radius: f32,
@@ -16,6 +23,65 @@ pub struct RoundedRectangle<U> {
     bottom_right_radius: f32,
 }
 
+impl<U> RoundedRectangle<U> {
+    pub fn new(
+        rect: Rectangle<U>,
+        top_left_radius: f32,
+        top_right_radius: f32,
+        bottom_right_radius: f32,
+        bottom_left_radius: f32
+    ) -> RoundedRectangle<U> {
+        RoundedRectangle {
+            rect: rect,
+            top_left_radius: top_left_radius,
+            top_right_radius: top_right_radius,
+            bottom_left_radius: bottom_left_radius,
+            bottom_right_radius: bottom_right_radius,
+        }
+    }
+
+    /// A rounded rectangle with the same radius at each corner.
+    pub fn with_radius(rect: Rectangle<U>, radius: f32) -> RoundedRectangle<U> {
+        RoundedRectangle::new(rect, radius, radius, radius, radius)
+    }
+
+    /// Push the points of the outline, clockwise in a y-down coordinate system, starting with
+    /// the top-left corner.
+    ///
+    /// The radii are clamped to half of the size of the rectangle.
+    pub fn outline(&self, tolerance: f32, output: &mut Vec<Vector2D<U>>) {
+        let r = &self.rect;
+        let max_radius = r.width.min(r.height) * 0.5;
+        let corners = [
+            (self.top_left_radius, r.x, r.y, PI),
+            (self.top_right_radius, r.x_most(), r.y, PI * 1.5),
+            (self.bottom_right_radius, r.x_most(), r.y_most(), 0.0),
+            (self.bottom_left_radius, r.x, r.y_most(), PI * 0.5),
+        ];
+        for &(radius, x, y, start_angle) in &corners {
+            let radius = radius.max(0.0).min(max_radius);
+            // Center of the arc.
+            let cx = if x == r.x { x + radius } else { x - radius };
+            let cy = if y == r.y { y + radius } else { y - radius };
+            let num_segments = if radius > 0.0 {
+                num_arc_segments(radius, PI * 0.5, tolerance)
+            } else {
+                0
+            };
+            for i in 0..(num_segments + 1) {
+                let angle = start_angle + PI * 0.5 * i as f32 / (num_segments.max(1)) as f32;
+                let p = Vector2D::new(cx + radius * angle.cos(), cy + radius * angle.sin());
+                if output.last() != Some(&p) {
+                    output.push(p);
+                }
+            }
+        }
+        if output.len() > 1 && output.first() == output.last() {
+            output.pop();
+        }
+    }
+}
+
 pub fn tesselate_triangle<Input, Output: VertexBufferBuilder<Input>>(
     v1: Input,
     v2: Input,
@@ -76,12 +142,18 @@ pub fn tesselate_rectangle_with_uv<A, Output: VertexBufferBuilder<(Vector2D<A>,
     );
 }
 
+/// Tesselate the fill of a rounded rectangle, the number of segments of the corners depending on
+/// the tolerance.
 pub fn tesselate_rounded_rectangle<U, Output: VertexBufferBuilder<Vector2D<U>>>(
-    _rect: &RoundedRectangle<U>,
+    rect: &RoundedRectangle<U>,
+    tolerance: f32,
     output: &mut Output
 ) {
-    output.begin_geometry();
-    panic!("TODO!");
+    let mut points = Vec::new();
+    rect.outline(tolerance, &mut points);
+    let r = &rect.rect;
+    let center = Vector2D::new(r.x + r.width * 0.5, r.y + r.height * 0.5);
+    tesselate_convex_fan(center, &points[..], output);
 }
 
 pub fn tesselate_ellipsis<U, Output: VertexBufferBuilder<Vector2D<U>>>(
@@ -101,6 +173,130 @@ pub fn tesselate_ellipsis<U, Output: VertexBufferBuilder<Vector2D<U>>>(
     }
 }
 
+/// The number of segments needed to approximate an ellipse within a given tolerance.
+pub fn num_ellipsis_segments(radius: Vector2D, tolerance: f32) -> u32 {
+    // The largest radius gives the worst error.
+    return num_arc_segments(radius.x.max(radius.y), 2.0 * PI, tolerance).max(3);
+}
+
+/// Same as tesselate_ellipsis, the number of vertices depending on the tolerance.
+pub fn tesselate_ellipsis_with_tolerance<U, Output: VertexBufferBuilder<Vector2D<U>>>(
+    center: Vector2D<U>,
+    radius: Vector2D<U>,
+    tolerance: f32,
+    output: &mut Output
+) {
+    let num_segments = num_ellipsis_segments(Vector2D::new(radius.x, radius.y), tolerance);
+    tesselate_ellipsis(center, radius, num_segments + 1, output);
+}
+
+pub fn tesselate_circle<U, Output: VertexBufferBuilder<Vector2D<U>>>(
+    center: Vector2D<U>,
+    radius: f32,
+    tolerance: f32,
+    output: &mut Output
+) {
+    tesselate_ellipsis_with_tolerance(center, Vector2D::new(radius, radius), tolerance, output);
+}
+
+/// Tesselate the stroke of a rectangle.
+pub fn stroke_rectangle<Output: VertexBufferBuilder<Vec2>>(
+    rect: &Rectangle,
+    width: f32,
+    options: &StrokeOptions,
+    output: &mut Output
+) {
+    let points = [rect.top_left(), rect.top_right(), rect.bottom_right(), rect.bottom_left()];
+    stroke_closed_polyline(&points[..], width, options, output);
+}
+
+/// Tesselate the stroke of a rounded rectangle, the number of segments of the corners depending
+/// on the tolerance of the stroke options.
+pub fn stroke_rounded_rectangle<Output: VertexBufferBuilder<Vec2>>(
+    rect: &RoundedRectangle,
+    width: f32,
+    options: &StrokeOptions,
+    output: &mut Output
+) {
+    let mut points = Vec::new();
+    rect.outline(outer_tolerance(rect, width, options.tolerance), &mut points);
+    stroke_closed_polyline(&points[..], width, options, output);
+}
+
+/// Tesselate the stroke of an ellipse, the number of segments depending on the tolerance of the
+/// stroke options.
+pub fn stroke_ellipsis<Output: VertexBufferBuilder<Vec2>>(
+    center: Vec2,
+    radius: Vec2,
+    width: f32,
+    options: &StrokeOptions,
+    output: &mut Output
+) {
+    let half_width = width * 0.5;
+    let num_segments = num_ellipsis_segments(
+        radius + Vec2::new(half_width, half_width),
+        options.tolerance
+    );
+    let mut points = Vec::with_capacity(num_segments as usize);
+    for i in 0..num_segments {
+        let angle = i as f32 * 2.0 * PI / num_segments as f32;
+        points.push(center + Vec2::new(radius.x * angle.cos(), radius.y * angle.sin()));
+    }
+    stroke_closed_polyline(&points[..], width, options, output);
+}
+
+pub fn stroke_circle<Output: VertexBufferBuilder<Vec2>>(
+    center: Vec2,
+    radius: f32,
+    width: f32,
+    options: &StrokeOptions,
+    output: &mut Output
+) {
+    stroke_ellipsis(center, Vec2::new(radius, radius), width, options, output);
+}
+
+fn stroke_closed_polyline<Output: VertexBufferBuilder<Vec2>>(
+    points: &[Vec2],
+    width: f32,
+    options: &StrokeOptions,
+    output: &mut Output
+) {
+    output.begin_geometry();
+    let widths = vec![width; points.len()];
+    stroke_polyline(points, &widths[..], true, options, output);
+}
+
+// The corners of the outer side of the stroke have a larger radius than the ones of the
+// rectangle, which is equivalent to a smaller tolerance on the rectangle's corners.
+fn outer_tolerance<U>(rect: &RoundedRectangle<U>, width: f32, tolerance: f32) -> f32 {
+    let max_radius = rect.top_left_radius
+        .max(rect.top_right_radius)
+        .max(rect.bottom_right_radius)
+        .max(rect.bottom_left_radius);
+    if max_radius <= 0.0 {
+        return tolerance;
+    }
+    return tolerance * max_radius / (max_radius + width * 0.5);
+}
+
+// Fill a convex polygon with a fan of triangles around a point.
+fn tesselate_convex_fan<U, Output: VertexBufferBuilder<Vector2D<U>>>(
+    center: Vector2D<U>,
+    points: &[Vector2D<U>],
+    output: &mut Output
+) {
+    output.begin_geometry();
+    let c = output.push_vertex(center);
+    let first = c + 1;
+    for &p in points {
+        output.push_vertex(p);
+    }
+    let n = points.len() as Index;
+    for i in 0..n {
+        output.push_indices(c, first + i, first + (i + 1) % n);
+    }
+}
+
 /*
 tesselate_rect_with_uv(text[i].rect(), cache.uv_for(text[i].key), output);
 
@@ -114,4 +310,57 @@ tesselate_quad(
     output
 );
 
-*/
\ No newline at end of file
+*/
+#[test]
+fn test_circle_tolerance() {
+    let mut buffers: VertexBuffers<Vec2> = VertexBuffers::new();
+    let mut num_vertices = Vec::new();
+    for &(radius, tolerance) in &[(1.0, 0.1), (10.0, 0.1), (100.0, 0.1), (100.0, 1.0)] {
+        buffers.vertices.clear();
+        buffers.indices.clear();
+        tesselate_circle(vec2(0.0, 0.0), radius, tolerance, &mut simple_vertex_builder(&mut buffers));
+        // The middle of each segment is within the tolerance of the circle.
+        for tri in buffers.indices.chunks(3) {
+            let mid = (buffers.vertices[tri[1] as usize] + buffers.vertices[tri[2] as usize]) * 0.5;
+            assert!(radius - mid.length() <= tolerance * 1.001);
+        }
+        num_vertices.push(buffers.vertices.len());
+    }
+    assert!(num_vertices[0] < num_vertices[1]);
+    assert!(num_vertices[1] < num_vertices[2]);
+    assert!(num_vertices[3] < num_vertices[2]);
+}
+
+#[test]
+fn test_rounded_rectangle() {
+    let rect = RoundedRectangle::new(Rect::new(0.0, 0.0, 20.0, 10.0), 0.0, 2.0, 100.0, 5.0);
+    let mut points = Vec::new();
+    rect.outline(0.1, &mut points);
+    assert_eq!(points[0], vec2(0.0, 0.0));
+    for p in &points {
+        assert!(p.x >= 0.0 && p.x <= 20.0 && p.y >= 0.0 && p.y <= 10.0);
+    }
+
+    let mut buffers: VertexBuffers<Vec2> = VertexBuffers::new();
+    tesselate_rounded_rectangle(&rect, 0.1, &mut simple_vertex_builder(&mut buffers));
+    assert_eq!(buffers.vertices.len(), points.len() + 1);
+    assert_eq!(buffers.indices.len(), points.len() * 3);
+    let mut area = 0.0;
+    for tri in buffers.indices.chunks(3) {
+        let a = buffers.vertices[tri[0] as usize];
+        let b = buffers.vertices[tri[1] as usize];
+        let c = buffers.vertices[tri[2] as usize];
+        area += (b - a).cross(c - a) * 0.5;
+    }
+    // The bottom right radius is clamped to 5. The corners are inside of the exact arcs, at
+    // most at the tolerance from them.
+    let expected = 200.0 - (4.0 + 25.0 + 25.0) * (1.0 - PI / 4.0);
+    assert!(area < expected);
+    assert!(area > expected - 0.1 * 60.0);
+
+    buffers.vertices.clear();
+    buffers.indices.clear();
+    stroke_rounded_rectangle(&rect, 1.0, &StrokeOptions::new(), &mut simple_vertex_builder(&mut buffers));
+    assert!(buffers.vertices.len() >= 2 * points.len());
+    assert_eq!(buffers.indices.len(), buffers.vertices.len() * 3);
+}
@@ -2,7 +2,7 @@ use tesselation::{ Index };
 use tesselation::vertex_builder::VertexBufferBuilder;
 use tesselation::path_builder::CurveBuilder;
 
-use vodk_math::{ Vector2D, Unit, Untyped };
+use vodk_math::{ Vector2D, Unit };
 
 use std::mem::swap;
 
@@ -265,8 +265,8 @@ pub fn find_cubic_bezier_inflection_approximation_range<U: Unit>(
     *max = t + tf * (1.0 - t);
 }
 
-pub fn flatten_cubic_bezier<Builder: CurveBuilder>(
-    bezier: CubicBezierSegment<Untyped>,
+pub fn flatten_cubic_bezier<U: Unit, Builder: CurveBuilder<U>>(
+    bezier: CubicBezierSegment<U>,
     tolerance: f32,
     path: &mut Builder
 ) {
@@ -369,8 +369,8 @@ pub fn flatten_cubic_bezier<Builder: CurveBuilder>(
 }
 
 
-fn flatten_cubic_bezier_segment<Builder: CurveBuilder>(
-    mut bezier: CubicBezierSegment<Untyped>,
+fn flatten_cubic_bezier_segment<U: Unit, Builder: CurveBuilder<U>>(
+    mut bezier: CubicBezierSegment<U>,
     tolerance: f32,
     path: &mut Builder
 ) {
@@ -0,0 +1,351 @@
+//! Checks the output of a fill tessellation against the path it was generated from.
+//!
+//! This is meant for tests and debugging: the checks are quadratic in the number of triangles
+//! and edges.
+
+use tesselation::path::*;
+use tesselation::math_utils::segment_intersection;
+use tesselation::Index;
+
+use vodk_math::{ Vector2D, Unit, Untyped };
+
+use std::fmt;
+
+#[cfg(test)]
+use tesselation::path_builder::PathBuilder;
+#[cfg(test)]
+use vodk_math::vec2;
+
+/// The result of validate_fill.
+///
+/// Triangles are designated by their index in the index buffer divided by three.
+pub struct FillValidationReport<U: Unit = Untyped> {
+    pub num_triangles: usize,
+    /// Triangles with an index that is out of the vertex buffer. They are ignored by the
+    /// other checks.
+    pub invalid_triangles: Vec<usize>,
+    /// Triangles with a zero area.
+    pub degenerate_triangles: Vec<usize>,
+    /// Triangles that are not wound like the majority of the other triangles.
+    pub inconsistent_winding: Vec<usize>,
+    /// Pairs of triangles which have overlapping interiors.
+    pub overlapping_triangles: Vec<(usize, usize)>,
+    /// Edges of the flattened path that are not covered by edges of the mesh.
+    pub missing_edges: Vec<(Vector2D<U>, Vector2D<U>)>,
+    /// The area of the flattened path under the fill rule.
+    pub path_area: f32,
+    /// The sum of the areas of the triangles.
+    pub triangles_area: f32,
+    /// The maximum difference between path_area and triangles_area for the report to be valid.
+    pub area_tolerance: f32,
+}
+
+impl<U: Unit> FillValidationReport<U> {
+    pub fn area_matches(&self) -> bool {
+        (self.path_area - self.triangles_area).abs() <= self.area_tolerance
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.invalid_triangles.is_empty() &&
+        self.degenerate_triangles.is_empty() &&
+        self.inconsistent_winding.is_empty() &&
+        self.overlapping_triangles.is_empty() &&
+        self.missing_edges.is_empty() &&
+        self.area_matches()
+    }
+}
+
+impl<U: Unit> fmt::Display for FillValidationReport<U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "{} triangles: {}",
+            self.num_triangles, if self.is_valid() { "valid" } else { "invalid" }
+        ));
+        if !self.invalid_triangles.is_empty() {
+            try!(writeln!(f, "  out of range indices in triangles {:?}", self.invalid_triangles));
+        }
+        if !self.degenerate_triangles.is_empty() {
+            try!(writeln!(f, "  degenerate triangles {:?}", self.degenerate_triangles));
+        }
+        if !self.inconsistent_winding.is_empty() {
+            try!(writeln!(f, "  inconsistent winding in triangles {:?}", self.inconsistent_winding));
+        }
+        for &(a, b) in &self.overlapping_triangles {
+            try!(writeln!(f, "  triangles {} and {} overlap", a, b));
+        }
+        for &(a, b) in &self.missing_edges {
+            try!(writeln!(f, "  missing edge ({}, {}) -> ({}, {})", a.x, a.y, b.x, b.y));
+        }
+        try!(writeln!(f, "  path area {}, triangles area {}{}",
+            self.path_area, self.triangles_area,
+            if self.area_matches() { "" } else { " (mismatch)" }
+        ));
+        return Ok(());
+    }
+}
+
+impl<U: Unit> fmt::Debug for FillValidationReport<U> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(self, f) }
+}
+
+/// Check the triangles generated by a fill tessellation against the path.
+///
+/// The path is flattened with the provided tolerance, and all sub-paths are considered closed,
+/// like the fill tessellator does. Distances and areas are compared with an epsilon that is
+/// relative to the size of the path.
+pub fn validate_fill<U: Unit>(
+    path: PathSlice<U>,
+    fill_rule: FillRule,
+    vertices: &[Vector2D<U>],
+    indices: &[Index],
+    tolerance: f32
+) -> FillValidationReport<U> {
+    let edges = flattened_edges(path, tolerance);
+    let aabb = path.aabb();
+    let epsilon = 0.0001 * aabb.width.max(aabb.height).max(1.0);
+
+    let mut report = FillValidationReport {
+        num_triangles: indices.len() / 3,
+        invalid_triangles: Vec::new(),
+        degenerate_triangles: Vec::new(),
+        inconsistent_winding: Vec::new(),
+        overlapping_triangles: Vec::new(),
+        missing_edges: Vec::new(),
+        path_area: filled_area(&edges[..], fill_rule),
+        triangles_area: 0.0,
+        area_tolerance: 0.0,
+    };
+
+    // (triangle index, vertices, signed area, is degenerate)
+    let mut triangles = Vec::new();
+    for (i, tri) in indices.chunks(3).enumerate() {
+        if tri.len() < 3 || tri.iter().any(|&idx| idx as usize >= vertices.len()) {
+            report.invalid_triangles.push(i);
+            continue;
+        }
+        let t = [vertices[tri[0] as usize], vertices[tri[1] as usize], vertices[tri[2] as usize]];
+        let area = (t[1] - t[0]).cross(t[2] - t[0]) * 0.5;
+        let longest_edge = (t[1] - t[0]).length()
+            .max((t[2] - t[1]).length())
+            .max((t[0] - t[2]).length());
+        // A triangle is degenerate if its height is below the epsilon.
+        let degenerate = area.abs() * 2.0 <= epsilon * longest_edge;
+        report.triangles_area += area.abs();
+        triangles.push((i, t, area, degenerate));
+    }
+
+    let num_positive = triangles.iter().filter(|t| !t.3 && t.2 > 0.0).count();
+    let num_negative = triangles.iter().filter(|t| !t.3 && t.2 < 0.0).count();
+    let positive = num_positive >= num_negative;
+    for &(i, _, area, degenerate) in &triangles {
+        if degenerate {
+            report.degenerate_triangles.push(i);
+        } else if (area > 0.0) != positive {
+            report.inconsistent_winding.push(i);
+        }
+    }
+
+    for a in 0..triangles.len() {
+        for b in (a + 1)..triangles.len() {
+            if triangles[a].3 || triangles[b].3 {
+                continue;
+            }
+            if triangles_overlap(&triangles[a].1, &triangles[b].1, epsilon) {
+                report.overlapping_triangles.push((triangles[a].0, triangles[b].0));
+            }
+        }
+    }
+
+    let mut mesh_edges = Vec::new();
+    for &(_, t, _, _) in &triangles {
+        mesh_edges.push((t[0], t[1]));
+        mesh_edges.push((t[1], t[2]));
+        mesh_edges.push((t[2], t[0]));
+    }
+    for &edge in &edges {
+        if !is_edge_covered(edge, &mesh_edges[..], epsilon) {
+            report.missing_edges.push(edge);
+        }
+    }
+
+    let perimeter: f32 = edges.iter().map(|e| (e.1 - e.0).length()).sum();
+    report.area_tolerance = epsilon * perimeter.max(1.0);
+
+    return report;
+}
+
+fn flattened_edges<U: Unit>(path: PathSlice<U>, tolerance: f32) -> Vec<(Vector2D<U>, Vector2D<U>)> {
+    let mut edges = Vec::new();
+    let mut points: Vec<Vector2D<U>> = Vec::new();
+    for id in path.path_ids() {
+        points.clear();
+        path.sub_path(id).flatten(tolerance, &mut points);
+        let n = points.len();
+        for i in 0..n {
+            let edge = (points[i], points[(i + 1) % n]);
+            if edge.0 != edge.1 {
+                edges.push(edge);
+            }
+        }
+    }
+    return edges;
+}
+
+// The area of the region covered by the edges under the fill rule, computed by splitting the
+// plane into horizontal slabs in which edges don't cross, and summing the trapezoids that are
+// inside.
+fn filled_area<U: Unit>(edges: &[(Vector2D<U>, Vector2D<U>)], fill_rule: FillRule) -> f32 {
+    let mut ys = Vec::new();
+    for (i, e1) in edges.iter().enumerate() {
+        ys.push(e1.0.y);
+        for e2 in &edges[(i + 1)..] {
+            if let Some(p) = segment_intersection(e1.0, e1.1, e2.0, e2.1) {
+                ys.push(p.y);
+            }
+        }
+    }
+    ys.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    ys.dedup();
+
+    let mut area = 0.0;
+    let mut crossings = Vec::new();
+    for slab in ys.windows(2) {
+        let (y0, y1) = (slab[0], slab[1]);
+        let y_mid = (y0 + y1) * 0.5;
+        crossings.clear();
+        for e in edges {
+            let (top, bottom, winding) = if e.0.y < e.1.y { (e.0, e.1, 1) } else { (e.1, e.0, -1) };
+            if top.y > y0 || bottom.y < y1 || top.y == bottom.y {
+                continue;
+            }
+            let x_at = |y: f32| top.x + (bottom.x - top.x) * (y - top.y) / (bottom.y - top.y);
+            crossings.push((x_at(y_mid), x_at(y0), x_at(y1), winding));
+        }
+        crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+        let mut winding_number = 0;
+        for i in 0..crossings.len() {
+            winding_number += crossings[i].3;
+            if i + 1 < crossings.len() && fill_rule.is_inside(winding_number) {
+                let (a, b) = (crossings[i], crossings[i + 1]);
+                area += ((b.1 - a.1) + (b.2 - a.2)) * 0.5 * (y1 - y0);
+            }
+        }
+    }
+    return area;
+}
+
+// Separating axis test. Triangles that only share an edge or a vertex don't overlap.
+fn triangles_overlap<U: Unit>(a: &[Vector2D<U>; 3], b: &[Vector2D<U>; 3], epsilon: f32) -> bool {
+    for &(tri, other) in &[(a, b), (b, a)] {
+        for i in 0..3 {
+            let edge = tri[(i + 1) % 3] - tri[i];
+            let axis = Vector2D::new(-edge.y, edge.x) / edge.length();
+            let (min1, max1) = project(tri, axis);
+            let (min2, max2) = project(other, axis);
+            if max1 <= min2 + epsilon || max2 <= min1 + epsilon {
+                return false;
+            }
+        }
+    }
+    return true;
+}
+
+fn project<U: Unit>(tri: &[Vector2D<U>; 3], axis: Vector2D<U>) -> (f32, f32) {
+    let mut min = tri[0].dot(&axis);
+    let mut max = min;
+    for p in &tri[1..] {
+        let d = p.dot(&axis);
+        if d < min { min = d; }
+        if d > max { max = d; }
+    }
+    return (min, max);
+}
+
+// An edge of the path is covered if the mesh edges that lie on it cover its whole length. The
+// mesh can split path edges, for example where they intersect other edges.
+fn is_edge_covered<U: Unit>(
+    edge: (Vector2D<U>, Vector2D<U>),
+    mesh_edges: &[(Vector2D<U>, Vector2D<U>)],
+    epsilon: f32
+) -> bool {
+    let v = edge.1 - edge.0;
+    let length = v.length();
+    let direction = v / length;
+    let normal = Vector2D::new(-direction.y, direction.x);
+
+    let mut intervals = Vec::new();
+    for &(a, b) in mesh_edges {
+        let (da, db) = ((a - edge.0).dot(&normal), (b - edge.0).dot(&normal));
+        if da.abs() > epsilon || db.abs() > epsilon {
+            continue;
+        }
+        let (ta, tb) = ((a - edge.0).dot(&direction), (b - edge.0).dot(&direction));
+        intervals.push((ta.min(tb), ta.max(tb)));
+    }
+    intervals.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut covered = 0.0;
+    for &(start, end) in &intervals {
+        if start > covered + epsilon {
+            break;
+        }
+        if end > covered {
+            covered = end;
+        }
+    }
+    return covered >= length - epsilon;
+}
+
+#[cfg(test)]
+fn square(builder: &mut PathBuilder, x: f32, y: f32, size: f32) {
+    builder.move_to(vec2(x, y));
+    builder.line_to(vec2(x + size, y));
+    builder.line_to(vec2(x + size, y + size));
+    builder.line_to(vec2(x, y + size));
+    builder.close();
+}
+
+#[test]
+fn test_validate_square() {
+    let mut builder = PathBuilder::new();
+    square(&mut builder, 0.0, 0.0, 10.0);
+    let path = builder.finish();
+    let vertices = [vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(10.0, 10.0), vec2(0.0, 10.0)];
+
+    let report = validate_fill(path.as_slice(), FillRule::EvenOdd, &vertices, &[0, 1, 2, 0, 2, 3], 0.1);
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(report.path_area, 100.0);
+
+    // Missing triangle.
+    let report = validate_fill(path.as_slice(), FillRule::EvenOdd, &vertices, &[0, 1, 2], 0.1);
+    assert!(!report.area_matches());
+    assert_eq!(report.missing_edges.len(), 2);
+
+    // Overlapping, badly wound, degenerate and invalid triangles.
+    let report = validate_fill(
+        path.as_slice(), FillRule::EvenOdd, &vertices,
+        &[0, 1, 2, 0, 2, 3, 0, 1, 3, 1, 1, 2, 0, 1, 7], 0.1
+    );
+    assert_eq!(report.overlapping_triangles, vec![(0, 2), (1, 2)]);
+    assert_eq!(report.degenerate_triangles, vec![3]);
+    assert_eq!(report.invalid_triangles, vec![4]);
+    assert_eq!(report.inconsistent_winding, vec![]);
+    let report = validate_fill(path.as_slice(), FillRule::EvenOdd, &vertices, &[0, 1, 2, 0, 3, 2], 0.1);
+    assert_eq!(report.inconsistent_winding, vec![1]);
+}
+
+#[test]
+fn test_validate_fill_rule_area() {
+    // Two nested squares with the same orientation, and a square overlapping the outer one.
+    let mut builder = PathBuilder::new();
+    square(&mut builder, 0.0, 0.0, 10.0);
+    square(&mut builder, 1.0, 1.0, 3.0);
+    square(&mut builder, 5.0, 5.0, 10.0);
+    let path = builder.finish();
+
+    let even_odd = validate_fill(path.as_slice(), FillRule::EvenOdd, &[], &[], 0.1);
+    assert_eq!(even_odd.path_area, 100.0 - 9.0 - 25.0 + 75.0);
+    let non_zero = validate_fill(path.as_slice(), FillRule::NonZero, &[], &[], 0.1);
+    assert_eq!(non_zero.path_area, 100.0 + 100.0 - 25.0);
+    assert_eq!(non_zero.missing_edges.len(), 12);
+}
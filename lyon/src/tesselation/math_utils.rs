@@ -85,6 +85,16 @@ pub fn segment_intersection<U>(
     return None;
 }
 
+/// The number of segments needed to approximate an arc of a given radius and angle, such that
+/// the distance between the arc and the segments is at most the tolerance.
+pub fn num_arc_segments(radius: f32, angle: f32, tolerance: f32) -> u32 {
+    if radius <= tolerance {
+        return 1;
+    }
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    return ((angle / step).ceil() as u32).max(1);
+}
+
 #[test]
 fn test_segment_intersection() {
 
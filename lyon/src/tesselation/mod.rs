@@ -4,6 +4,13 @@ pub mod math_utils;
 pub mod path;
 pub mod path_builder;
 pub mod path_tesselator;
+pub mod path_simplification;
+pub mod path_measure;
+pub mod path_morphing;
+pub mod path_clipping;
+pub mod tiled_tesselation;
+pub mod fill_validation;
+pub mod svg_path;
 pub mod vertex_builder;
 pub mod rust_logo;
 
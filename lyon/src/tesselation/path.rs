@@ -3,7 +3,10 @@ use tesselation::{
     VertexSlice, MutVertexSlice,
 };
 
-use vodk_math::{ Vec2, Rect, };
+use tesselation::bezier::{ QuadraticBezierSegment, CubicBezierSegment, flatten_cubic_bezier };
+use tesselation::path_builder::CurveBuilder;
+
+use vodk_math::{ Vector2D, Rectangle, Unit, Untyped };
 
 use vodk_id::{ Id, IdRange, ToIndex };
 
@@ -19,37 +22,55 @@ pub enum PointType {
     Control,
 }
 
+/// How the inside of a path is determined from the winding number of a point.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FillRule {
+    EvenOdd,
+    NonZero,
+}
+
+impl FillRule {
+    pub fn is_inside(self, winding_number: i32) -> bool {
+        return match self {
+            FillRule::EvenOdd => { winding_number % 2 != 0 }
+            FillRule::NonZero => { winding_number != 0 }
+        };
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
-pub struct PointData {
-    pub position: Vec2,
+pub struct PointData<U: Unit = Untyped> {
+    pub position: Vector2D<U>,
     pub point_type: PointType,
 }
 
 #[derive(Clone, Debug)]
-pub struct Path {
-    vertices: Vec<PointData>,
-    sub_paths: Vec<PathInfo>,
+pub struct Path<U: Unit = Untyped> {
+    vertices: Vec<PointData<U>>,
+    sub_paths: Vec<PathInfo<U>>,
 }
 
-impl Path {
-    pub fn new() -> Path {
+impl<U: Unit> Path<U> {
+    pub fn new() -> Path<U> {
         Path { vertices: Vec::new(), sub_paths: Vec::new() }
     }
 
-    pub fn from_vec(vertices: Vec<PointData>, sub_paths: Vec<PathInfo>) -> Path {
+    pub fn from_vec(vertices: Vec<PointData<U>>, sub_paths: Vec<PathInfo<U>>) -> Path<U> {
         Path {
             vertices: vertices,
             sub_paths: sub_paths,
         }
     }
 
-    pub fn vertices(&self) -> VertexSlice<PointData> { VertexSlice::new(&self.vertices[..]) }
+    pub fn vertices(&self) -> VertexSlice<PointData<U>> { VertexSlice::new(&self.vertices[..]) }
 
-    pub fn mut_vertices(&mut self) -> MutVertexSlice<PointData> { MutVertexSlice::new(&mut self.vertices[..]) }
+    pub fn mut_vertices(&mut self) -> MutVertexSlice<PointData<U>> { MutVertexSlice::new(&mut self.vertices[..]) }
 
     pub fn num_vertices(&self) -> usize { self.as_slice().num_vertices() }
 
-    pub fn sub_path(&self, id: PathId) -> SubPathSlice {
+    pub fn num_sub_paths(&self) -> usize { self.sub_paths.len() }
+
+    pub fn sub_path(&self, id: PathId) -> SubPathSlice<U> {
         SubPathSlice {
             vertices: VertexSlice::new(&self.vertices[..]),
             info: &self.sub_paths[id.handle.to_index()]
@@ -60,12 +81,14 @@ impl Path {
         IdRange::new(0, self.sub_paths.len() as u16)
     }
 
-    pub fn as_slice(&self) -> PathSlice {
+    pub fn as_slice(&self) -> PathSlice<U> {
         PathSlice {
             vertices: VertexSlice::new(&self.vertices[..]),
             sub_paths: &self.sub_paths[..],
         }
     }
+
+    pub fn aabb(&self) -> Rectangle<U> { self.as_slice().aabb() }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -93,15 +116,19 @@ impl Iterator for PathVertexIdRange {
     }
 }
 
-#[derive(Copy, Clone)]
-pub struct PathSlice<'l> {
-    vertices: VertexSlice<'l,PointData>,
-    sub_paths: &'l[PathInfo],
+pub struct PathSlice<'l, U: Unit + 'l = Untyped> {
+    vertices: VertexSlice<'l, PointData<U>>,
+    sub_paths: &'l[PathInfo<U>],
 }
 
-impl<'l> PathSlice<'l> {
+impl<'l, U: Unit> Copy for PathSlice<'l, U> {}
+impl<'l, U: Unit> Clone for PathSlice<'l, U> {
+    fn clone(&self) -> PathSlice<'l, U> { *self }
+}
 
-    pub fn vertices(&self) -> VertexSlice<PointData> { self.vertices }
+impl<'l, U: Unit> PathSlice<'l, U> {
+
+    pub fn vertices(&self) -> VertexSlice<PointData<U>> { self.vertices }
 
     pub fn vertex_ids(&self, sub_path: PathId) -> PathVertexIdRange {
         PathVertexIdRange {
@@ -114,7 +141,7 @@ impl<'l> PathSlice<'l> {
 
     pub fn num_sub_paths(&self) -> usize { self.sub_paths.len() }
 
-    pub fn sub_path(&self, id: PathId) -> SubPathSlice {
+    pub fn sub_path(&self, id: PathId) -> SubPathSlice<U> {
         SubPathSlice {
             vertices: self.vertices,
             info: &self.sub_paths[id.handle.to_index()]
@@ -125,10 +152,31 @@ impl<'l> PathSlice<'l> {
         IdRange::new(0, self.sub_paths.len() as u16)
     }
 
-    pub fn vertex(&self, id: PathVertexId) -> &PointData {
+    pub fn vertex(&self, id: PathVertexId) -> &PointData<U> {
         &self.vertices[id.vertex_id]
     }
 
+    /// The bounding rectangle of all of the sub-paths, including their control points.
+    pub fn aabb(&self) -> Rectangle<U> {
+        if self.sub_paths.is_empty() {
+            return Rectangle::new(0.0, 0.0, 0.0, 0.0);
+        }
+        let first = self.sub_paths[0].aabb;
+        let mut top_left = first.top_left();
+        let mut bottom_right = first.bottom_right();
+        for info in &self.sub_paths[1..] {
+            let (tl, br) = (info.aabb.top_left(), info.aabb.bottom_right());
+            if tl.x < top_left.x { top_left.x = tl.x; }
+            if tl.y < top_left.y { top_left.y = tl.y; }
+            if br.x > bottom_right.x { bottom_right.x = br.x; }
+            if br.y > bottom_right.y { bottom_right.y = br.y; }
+        }
+        return Rectangle::new(
+            top_left.x, top_left.y,
+            bottom_right.x - top_left.x, bottom_right.y - top_left.y,
+        );
+    }
+
     pub fn next(&self, id: PathVertexId) -> PathVertexId {
         PathVertexId {
             path_id: id.path_id,
@@ -144,16 +192,20 @@ impl<'l> PathSlice<'l> {
     }
 }
 
-#[derive(Copy, Clone)]
-pub struct SubPathSlice<'l> {
-    vertices: VertexSlice<'l, PointData>,
-    info: &'l PathInfo,
+pub struct SubPathSlice<'l, U: Unit + 'l = Untyped> {
+    vertices: VertexSlice<'l, PointData<U>>,
+    info: &'l PathInfo<U>,
+}
+
+impl<'l, U: Unit> Copy for SubPathSlice<'l, U> {}
+impl<'l, U: Unit> Clone for SubPathSlice<'l, U> {
+    fn clone(&self) -> SubPathSlice<'l, U> { *self }
 }
 
-impl<'l> SubPathSlice<'l> {
-    pub fn info(&self) -> &'l PathInfo { self.info }
+impl<'l, U: Unit> SubPathSlice<'l, U> {
+    pub fn info(&self) -> &'l PathInfo<U> { self.info }
 
-    pub fn vertex(&self, id: VertexId) -> &PointData { &self.vertices[id] }
+    pub fn vertex(&self, id: VertexId) -> &PointData<U> { &self.vertices[id] }
 
     pub fn first(&self) -> VertexId { self.info.range.first }
 
@@ -177,22 +229,80 @@ impl<'l> SubPathSlice<'l> {
         return Id::new(if id.handle == first { last } else { id.handle - 1 });
     }
 
-    pub fn next_vertex(&self, id: VertexId) -> &PointData {
+    pub fn next_vertex(&self, id: VertexId) -> &PointData<U> {
         self.vertex(self.next(id))
     }
 
-    pub fn previous_vertex(&self, id: VertexId) -> &PointData {
+    pub fn previous_vertex(&self, id: VertexId) -> &PointData<U> {
         self.vertex(self.previous(id))
     }
 
     pub fn vertex_ids(&self) -> VertexIdRange { self.info().range }
 
     pub fn num_vertices(&self) -> usize { self.vertices.len() }
+
+    /// Approximate the sub-path with a sequence of line segments and push the resulting points
+    /// in the output.
+    ///
+    /// The first point is not repeated at the end of closed sub-paths.
+    pub fn flatten<Output: CurveBuilder<U>>(&self, tolerance: f32, output: &mut Output) {
+        let first = self.vertex(self.first()).position;
+        let mut from = first;
+        let mut ctrl = [first, first];
+        let mut num_ctrl = 0;
+
+        output.push_vertex(first);
+        let mut ids = self.vertex_ids();
+        ids.next();
+        for id in ids {
+            let v = self.vertex(id);
+            if v.point_type == PointType::Control {
+                debug_assert!(num_ctrl < 2);
+                ctrl[num_ctrl] = v.position;
+                num_ctrl += 1;
+                continue;
+            }
+            flatten_segment(from, &ctrl[..num_ctrl], v.position, tolerance, output);
+            from = v.position;
+            num_ctrl = 0;
+        }
+
+        // The last vertex of closed sub-paths ending with a curve is removed by the path
+        // builder, leaving trailing control points.
+        if num_ctrl > 0 {
+            let mut points = Vec::new();
+            flatten_segment(from, &ctrl[..num_ctrl], first, tolerance, &mut points);
+            points.pop();
+            for p in points {
+                output.push_vertex(p);
+            }
+        }
+    }
+}
+
+fn flatten_segment<U: Unit, Output: CurveBuilder<U>>(
+    from: Vector2D<U>,
+    ctrl: &[Vector2D<U>],
+    to: Vector2D<U>,
+    tolerance: f32,
+    output: &mut Output
+) {
+    match ctrl.len() {
+        0 => { output.push_vertex(to); }
+        1 => {
+            let curve = QuadraticBezierSegment { from: from, cp: ctrl[0], to: to };
+            flatten_cubic_bezier(curve.to_cubic(), tolerance, output);
+        }
+        _ => {
+            let curve = CubicBezierSegment { from: from, cp1: ctrl[0], cp2: ctrl[1], to: to };
+            flatten_cubic_bezier(curve, tolerance, output);
+        }
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
-pub struct PathInfo {
-    pub aabb: Rect,
+pub struct PathInfo<U: Unit = Untyped> {
+    pub aabb: Rectangle<U>,
     pub range: VertexIdRange,
     pub has_beziers: Option<bool>,
     pub is_closed: bool,
@@ -5,19 +5,21 @@ use tesselation::{
 //    crash,
 };
 
-use vodk_math::{ Vec2, vec2, Rect };
+use vodk_math::{ Vector2D, Rectangle, Unit, Untyped };
+#[cfg(test)]
+use vodk_math::{ vec2, Rect };
 
-pub trait CurveBuilder {
-    fn push_vertex(&mut self, v: Vec2);
+pub trait CurveBuilder<U: Unit = Untyped> {
+    fn push_vertex(&mut self, v: Vector2D<U>);
 }
 
-pub struct PathBuilder {
-    vertices: Vec<PointData>,
-    path_info: Vec<PathInfo>,
-    last_position: Vec2,
-    last_ctrl: Vec2,
-    top_left: Vec2,
-    bottom_right: Vec2,
+pub struct PathBuilder<U: Unit = Untyped> {
+    vertices: Vec<PointData<U>>,
+    path_info: Vec<PathInfo<U>>,
+    last_position: Vector2D<U>,
+    last_ctrl: Vector2D<U>,
+    top_left: Vector2D<U>,
+    bottom_right: Vector2D<U>,
     tolerance: f32,
     offset: u16,
     // flags
@@ -26,15 +28,15 @@ pub struct PathBuilder {
     building: bool,
 }
 
-impl PathBuilder {
-    pub fn new() -> PathBuilder {
+impl<U: Unit> PathBuilder<U> {
+    pub fn new() -> PathBuilder<U> {
         PathBuilder {
             vertices: Vec::with_capacity(512),
             path_info: Vec::with_capacity(16),
-            last_position: vec2(0.0, 0.0),
-            last_ctrl: vec2(0.0, 0.0),
-            top_left: vec2(0.0, 0.0),
-            bottom_right: vec2(0.0, 0.0),
+            last_position: Vector2D::new(0.0, 0.0),
+            last_ctrl: Vector2D::new(0.0, 0.0),
+            top_left: Vector2D::new(0.0, 0.0),
+            bottom_right: Vector2D::new(0.0, 0.0),
             offset: 0,
             tolerance: 0.05,
             has_beziers: false,
@@ -43,7 +45,7 @@ impl PathBuilder {
         }
     }
 
-    pub fn finish(mut self) -> Path {
+    pub fn finish(mut self) -> Path<U> {
         if self.building {
             self.end();
         }
@@ -54,7 +56,7 @@ impl PathBuilder {
 
     pub fn set_tolerance(&mut self, tolerance: f32) { self.tolerance = tolerance }
 
-    pub fn move_to(&mut self, to: Vec2)
+    pub fn move_to(&mut self, to: Vector2D<U>)
     {
         if self.building {
             self.end_sub_path(false);
@@ -65,18 +67,18 @@ impl PathBuilder {
         self.push(to, PointType::Normal);
     }
 
-    pub fn line_to(&mut self, to: Vec2) {
+    pub fn line_to(&mut self, to: Vector2D<U>) {
         self.last_ctrl = to;
         self.push(to, PointType::Normal);
     }
 
-    pub fn relative_line_to(&mut self, to: Vec2) {
+    pub fn relative_line_to(&mut self, to: Vector2D<U>) {
         let offset = self.last_position;
         assert!(!offset.x.is_nan() && !offset.y.is_nan());
         self.push(offset + to, PointType::Normal);
     }
 
-    pub fn quadratic_bezier_to(&mut self, ctrl: Vec2, to: Vec2) {
+    pub fn quadratic_bezier_to(&mut self, ctrl: Vector2D<U>, to: Vector2D<U>) {
         self.last_ctrl = ctrl;
         if self.flatten {
             let from = self.last_position;
@@ -89,12 +91,12 @@ impl PathBuilder {
         }
     }
 
-    pub fn relative_quadratic_bezier_to(&mut self, ctrl: Vec2, to: Vec2) {
+    pub fn relative_quadratic_bezier_to(&mut self, ctrl: Vector2D<U>, to: Vector2D<U>) {
         let offset = self.last_position;
         self.quadratic_bezier_to(ctrl + offset, to + offset);
     }
 
-    pub fn cubic_bezier_to(&mut self, ctrl1: Vec2, ctrl2: Vec2, to: Vec2) {
+    pub fn cubic_bezier_to(&mut self, ctrl1: Vector2D<U>, ctrl2: Vector2D<U>, to: Vector2D<U>) {
         self.last_ctrl = ctrl2;
         if self.flatten {
             flatten_cubic_bezier(
@@ -115,49 +117,49 @@ impl PathBuilder {
         }
     }
 
-    pub fn relative_cubic_bezier_to(&mut self, ctrl1: Vec2, ctrl2: Vec2, to: Vec2) {
+    pub fn relative_cubic_bezier_to(&mut self, ctrl1: Vector2D<U>, ctrl2: Vector2D<U>, to: Vector2D<U>) {
         let offset = self.last_position;
         self.cubic_bezier_to(ctrl1 + offset, ctrl2 + offset, to + offset);
     }
 
-    pub fn cubic_bezier_symetry_to(&mut self, ctrl2: Vec2, to: Vec2) {
+    pub fn cubic_bezier_symetry_to(&mut self, ctrl2: Vector2D<U>, to: Vector2D<U>) {
         let ctrl = self.last_position + (self.last_position - self.last_ctrl);
         self.cubic_bezier_to(ctrl, ctrl2, to);
     }
 
-    pub fn relative_cubic_bezier_symetry_to(&mut self, ctrl2: Vec2, to: Vec2) {
+    pub fn relative_cubic_bezier_symetry_to(&mut self, ctrl2: Vector2D<U>, to: Vector2D<U>) {
         let ctrl = self.last_position - self.last_ctrl;
         self.relative_cubic_bezier_to(ctrl, ctrl2, to);
     }
 
-    pub fn quadratic_bezier_symetry_to(&mut self, to: Vec2) {
+    pub fn quadratic_bezier_symetry_to(&mut self, to: Vector2D<U>) {
         let ctrl = self.last_position + (self.last_position - self.last_ctrl);
         self.quadratic_bezier_to(ctrl, to);
     }
 
-    pub fn relative_quadratic_bezier_symetry_to(&mut self, to: Vec2) {
+    pub fn relative_quadratic_bezier_symetry_to(&mut self, to: Vector2D<U>) {
         let ctrl = self.last_position - self.last_ctrl;
         self.relative_quadratic_bezier_to(ctrl, to);
     }
 
     pub fn horizontal_line_to(&mut self, x: f32) {
         let y = self.last_position.y;
-        self.line_to(vec2(x, y));
+        self.line_to(Vector2D::new(x, y));
     }
 
     pub fn relative_horizontal_line_to(&mut self, dx: f32) {
         let p = self.last_position;
-        self.line_to(vec2(p.x + dx, p.y));
+        self.line_to(Vector2D::new(p.x + dx, p.y));
     }
 
     pub fn vertical_line_to(&mut self, y: f32) {
         let x = self.last_position.x;
-        self.line_to(vec2(x, y));
+        self.line_to(Vector2D::new(x, y));
     }
 
     pub fn relative_vertical_line_to(&mut self, dy: f32) {
         let p = self.last_position;
-        self.line_to(vec2(p.x, p.y + dy));
+        self.line_to(Vector2D::new(p.x, p.y + dy));
     }
 
     pub fn end(&mut self) -> PathId { self.end_sub_path(false) }
@@ -183,7 +185,7 @@ impl PathBuilder {
         let vertex_count = last - offset + 1;
 
         let vertex_range = vertex_id_range(self.offset, self.offset + vertex_count as u16);
-        let aabb = Rect::new(
+        let aabb = Rectangle::new(
             self.top_left.x, self.top_left.y,
             self.bottom_right.x - self.top_left.x, self.bottom_right.y - self.top_left.y,
         );
@@ -200,7 +202,7 @@ impl PathBuilder {
         return index;
     }
 
-    fn push(&mut self, point: Vec2, ptype: PointType) {
+    fn push(&mut self, point: Vector2D<U>, ptype: PointType) {
         if self.building && point == self.last_position {
             return;
         }
@@ -223,8 +225,12 @@ impl PathBuilder {
     }
 }
 
-impl CurveBuilder for PathBuilder {
-    fn push_vertex(&mut self, v: Vec2) { self.push(v, PointType::Normal); }
+impl<U: Unit> CurveBuilder<U> for PathBuilder<U> {
+    fn push_vertex(&mut self, v: Vector2D<U>) { self.push(v, PointType::Normal); }
+}
+
+impl<U: Unit> CurveBuilder<U> for Vec<Vector2D<U>> {
+    fn push_vertex(&mut self, v: Vector2D<U>) { self.push(v); }
 }
 
 #[test]
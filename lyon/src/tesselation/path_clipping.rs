@@ -0,0 +1,398 @@
+//! Clipping paths against axis-aligned rectangles and convex polygons.
+//!
+//! Closed sub-paths are clipped as polygons (Sutherland-Hodgman), so the result stays closed
+//! along the clip boundary and can be filled. Open sub-paths are clipped as polylines and may
+//! be split into several open sub-paths.
+//!
+//! Sub-paths that are entirely inside of the clip region are copied as is, including their
+//! curves. Other sub-paths are flattened with the provided tolerance before being clipped.
+//! Clipping a concave polygon can produce degenerate zero-area edges along the clip boundary
+//! which don't affect the filled area.
+
+use tesselation::path::*;
+use tesselation::path_builder::PathBuilder;
+
+use vodk_math::{ Vector2D, Rectangle, Unit };
+
+#[cfg(test)]
+use vodk_math::{ vec2, Rect };
+
+/// Clip a path against an axis-aligned rectangle.
+pub fn clip_path_to_rect<U: Unit>(
+    path: PathSlice<U>,
+    rect: &Rectangle<U>,
+    tolerance: f32
+) -> Path<U> {
+    let clip = [rect.top_left(), rect.top_right(), rect.bottom_right(), rect.bottom_left()];
+    return clip_path_to_convex_polygon(path, &clip[..], tolerance);
+}
+
+/// Clip a path against a convex polygon.
+///
+/// The polygon can have either orientation. If it is degenerate the result is empty.
+pub fn clip_path_to_convex_polygon<U: Unit>(
+    path: PathSlice<U>,
+    polygon: &[Vector2D<U>],
+    tolerance: f32
+) -> Path<U> {
+    let mut builder = PathBuilder::new();
+    build_clipped_path(path, polygon, false, tolerance, &mut builder);
+    return builder.finish();
+}
+
+/// Clip a path against a convex polygon and add the result to a path builder.
+///
+/// If `fill` is true, open sub-paths are clipped as if they were closed, which is how the fill
+/// tessellator interprets them. Closed sub-paths that are reduced to a segment or a point are
+/// removed.
+pub fn build_clipped_path<U: Unit>(
+    path: PathSlice<U>,
+    polygon: &[Vector2D<U>],
+    fill: bool,
+    tolerance: f32,
+    output: &mut PathBuilder<U>
+) {
+    let orientation = polygon_orientation(polygon);
+    if orientation == 0.0 {
+        return;
+    }
+    let clip = ClipPolygon { points: polygon, orientation: orientation };
+    let clip_aabb = bounding_rect(polygon);
+
+    let mut points: Vec<Vector2D<U>> = Vec::new();
+    let mut tmp: Vec<Vector2D<U>> = Vec::new();
+    for id in path.path_ids() {
+        let sub_path = path.sub_path(id);
+        let aabb = sub_path.info().aabb;
+        if !rects_overlap(&aabb, &clip_aabb) {
+            continue;
+        }
+        let corners = [aabb.top_left(), aabb.top_right(), aabb.bottom_right(), aabb.bottom_left()];
+        if corners.iter().all(|p| clip.contains(*p)) {
+            copy_sub_path(sub_path, output);
+            continue;
+        }
+
+        points.clear();
+        sub_path.flatten(tolerance, &mut points);
+        if fill || sub_path.info().is_closed {
+            clip.clip_polygon(&mut points, &mut tmp);
+            // The signed area isn't enough, the lobes of self-intersecting sub-paths can
+            // cancel each other out.
+            if !is_degenerate(&points[..]) {
+                output.move_to(points[0]);
+                for &p in &points[1..] {
+                    output.line_to(p);
+                }
+                output.close();
+            }
+        } else {
+            clip.clip_polyline(&points[..], output);
+        }
+    }
+}
+
+struct ClipPolygon<'l, U: Unit + 'l> {
+    points: &'l [Vector2D<U>],
+    // Positive or negative depending on the winding order of the polygon.
+    orientation: f32,
+}
+
+impl<'l, U: Unit> ClipPolygon<'l, U> {
+    fn edge(&self, i: usize) -> (Vector2D<U>, Vector2D<U>) {
+        (self.points[i], self.points[(i + 1) % self.points.len()])
+    }
+
+    // Positive inside of the edge, negative outside.
+    fn side(&self, edge: (Vector2D<U>, Vector2D<U>), p: Vector2D<U>) -> f32 {
+        (edge.1 - edge.0).cross(p - edge.0) * self.orientation
+    }
+
+    fn contains(&self, p: Vector2D<U>) -> bool {
+        for i in 0..self.points.len() {
+            if self.side(self.edge(i), p) < 0.0 {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    // Sutherland-Hodgman, the result is written in place.
+    fn clip_polygon(&self, points: &mut Vec<Vector2D<U>>, tmp: &mut Vec<Vector2D<U>>) {
+        for i in 0..self.points.len() {
+            if points.is_empty() {
+                return;
+            }
+            let edge = self.edge(i);
+            tmp.clear();
+            let n = points.len();
+            for j in 0..n {
+                let p = points[j];
+                let q = points[(j + 1) % n];
+                let sp = self.side(edge, p);
+                let sq = self.side(edge, q);
+                if sp >= 0.0 {
+                    tmp.push(p);
+                }
+                if (sp >= 0.0) != (sq >= 0.0) {
+                    let t = sp / (sp - sq);
+                    let x = p + (q - p) * t;
+                    if tmp.last() != Some(&x) {
+                        tmp.push(x);
+                    }
+                }
+            }
+            ::std::mem::swap(points, tmp);
+        }
+    }
+
+    // Cyrus-Beck clipping of each segment of the polyline.
+    fn clip_polyline(&self, points: &[Vector2D<U>], output: &mut PathBuilder<U>) {
+        let mut building = false;
+        for i in 1..points.len() {
+            let (p0, p1) = (points[i - 1], points[i]);
+            let d = p1 - p0;
+            let mut t_enter = 0.0;
+            let mut t_exit = 1.0;
+            let mut rejected = false;
+            for e in 0..self.points.len() {
+                let edge = self.edge(e);
+                let f0 = self.side(edge, p0);
+                let fd = (edge.1 - edge.0).cross(d) * self.orientation;
+                if fd == 0.0 {
+                    if f0 < 0.0 {
+                        rejected = true;
+                        break;
+                    }
+                    continue;
+                }
+                let t = -f0 / fd;
+                if fd > 0.0 {
+                    if t > t_enter { t_enter = t; }
+                } else {
+                    if t < t_exit { t_exit = t; }
+                }
+                if t_enter > t_exit {
+                    rejected = true;
+                    break;
+                }
+            }
+
+            if rejected {
+                if building {
+                    output.end();
+                    building = false;
+                }
+                continue;
+            }
+
+            if !building || t_enter > 0.0 {
+                if building {
+                    output.end();
+                }
+                output.move_to(p0 + d * t_enter);
+                building = true;
+            }
+            output.line_to(p0 + d * t_exit);
+            if t_exit < 1.0 {
+                output.end();
+                building = false;
+            }
+        }
+        if building {
+            output.end();
+        }
+    }
+}
+
+fn polygon_orientation<U: Unit>(polygon: &[Vector2D<U>]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..polygon.len() {
+        area += polygon[i].cross(polygon[(i + 1) % polygon.len()]);
+    }
+    return if area > 0.0 { 1.0 } else if area < 0.0 { -1.0 } else { 0.0 };
+}
+
+// True if the polygon has less than three points or if all of its points are aligned.
+fn is_degenerate<U: Unit>(polygon: &[Vector2D<U>]) -> bool {
+    if polygon.len() < 3 {
+        return true;
+    }
+    let origin = polygon[0];
+    let direction = match polygon.iter().find(|p| **p != origin) {
+        Some(p) => *p - origin,
+        None => { return true; }
+    };
+    return polygon.iter().all(|p| (*p - origin).cross(direction) == 0.0);
+}
+
+fn bounding_rect<U: Unit>(points: &[Vector2D<U>]) -> Rectangle<U> {
+    let mut min = points[0];
+    let mut max = points[0];
+    for p in points {
+        if p.x < min.x { min.x = p.x; }
+        if p.y < min.y { min.y = p.y; }
+        if p.x > max.x { max.x = p.x; }
+        if p.y > max.y { max.y = p.y; }
+    }
+    return Rectangle::new(min.x, min.y, max.x - min.x, max.y - min.y);
+}
+
+// Unlike Rectangle::intersects, touching rectangles and empty rectangles are considered
+// overlapping.
+fn rects_overlap<U: Unit>(a: &Rectangle<U>, b: &Rectangle<U>) -> bool {
+    a.x <= b.x_most() && b.x <= a.x_most() && a.y <= b.y_most() && b.y <= a.y_most()
+}
+
+fn copy_sub_path<U: Unit>(sub_path: SubPathSlice<U>, output: &mut PathBuilder<U>) {
+    let first = sub_path.vertex(sub_path.first()).position;
+    let mut ctrl = [first, first];
+    let mut num_ctrl = 0;
+    output.move_to(first);
+    let mut ids = sub_path.vertex_ids();
+    ids.next();
+    for id in ids {
+        let v = sub_path.vertex(id);
+        if v.point_type == PointType::Control {
+            ctrl[num_ctrl] = v.position;
+            num_ctrl += 1;
+            continue;
+        }
+        push_segment(&ctrl[..num_ctrl], v.position, output);
+        num_ctrl = 0;
+    }
+    if sub_path.info().is_closed {
+        if num_ctrl > 0 {
+            push_segment(&ctrl[..num_ctrl], first, output);
+        }
+        output.close();
+    } else {
+        output.end();
+    }
+}
+
+fn push_segment<U: Unit>(ctrl: &[Vector2D<U>], to: Vector2D<U>, output: &mut PathBuilder<U>) {
+    match ctrl.len() {
+        0 => { output.line_to(to); }
+        1 => { output.quadratic_bezier_to(ctrl[0], to); }
+        _ => { output.cubic_bezier_to(ctrl[0], ctrl[1], to); }
+    }
+}
+
+#[test]
+fn test_clip_to_rect() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(-5.0, -5.0));
+    builder.line_to(vec2(5.0, -5.0));
+    builder.line_to(vec2(5.0, 5.0));
+    builder.line_to(vec2(-5.0, 5.0));
+    builder.close();
+    // Entirely outside.
+    builder.move_to(vec2(20.0, 20.0));
+    builder.line_to(vec2(30.0, 20.0));
+    builder.line_to(vec2(30.0, 30.0));
+    builder.close();
+    // Entirely inside, with a curve.
+    builder.move_to(vec2(6.0, 6.0));
+    builder.quadratic_bezier_to(vec2(8.0, 6.0), vec2(8.0, 8.0));
+    builder.close();
+    let path = builder.finish();
+
+    let clipped = clip_path_to_rect(path.as_slice(), &Rect::new(0.0, 0.0, 10.0, 10.0), 0.1);
+    assert_eq!(clipped.num_sub_paths(), 2);
+
+    let square = clipped.sub_path(path_id(0));
+    assert!(square.info().is_closed);
+    assert_eq!(square.vertex_ids().count, 4);
+    assert_eq!(square.info().aabb, Rect::new(0.0, 0.0, 5.0, 5.0));
+
+    let curve = clipped.sub_path(path_id(1));
+    assert_eq!(curve.vertex_ids().count, 3);
+    assert_eq!(curve.vertex(curve.next(curve.first())).point_type, PointType::Control);
+}
+
+#[test]
+fn test_clip_polyline() {
+    // Goes through the rectangle, leaves it and comes back.
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(-5.0, 5.0));
+    builder.line_to(vec2(15.0, 5.0));
+    builder.line_to(vec2(15.0, 8.0));
+    builder.line_to(vec2(5.0, 8.0));
+    builder.end();
+    let path = builder.finish();
+
+    let clipped = clip_path_to_rect(path.as_slice(), &Rect::new(0.0, 0.0, 10.0, 10.0), 0.1);
+    assert_eq!(clipped.num_sub_paths(), 2);
+    let first = clipped.sub_path(path_id(0));
+    assert!(!first.info().is_closed);
+    assert_eq!(first.vertex(first.first()).position, vec2(0.0, 5.0));
+    assert_eq!(first.vertex(first.last()).position, vec2(10.0, 5.0));
+    let second = clipped.sub_path(path_id(1));
+    assert_eq!(second.vertex(second.first()).position, vec2(10.0, 8.0));
+    assert_eq!(second.vertex(second.last()).position, vec2(5.0, 8.0));
+}
+
+#[test]
+fn test_clip_to_convex_polygon() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(0.0, 0.0));
+    builder.line_to(vec2(10.0, 0.0));
+    builder.line_to(vec2(10.0, 10.0));
+    builder.line_to(vec2(0.0, 10.0));
+    builder.close();
+    let path = builder.finish();
+
+    // A triangle covering the bottom-left half of the square, in both orientations.
+    let triangle = [vec2(-10.0, -10.0), vec2(20.0, 20.0), vec2(-10.0, 20.0)];
+    let reversed = [vec2(-10.0, 20.0), vec2(20.0, 20.0), vec2(-10.0, -10.0)];
+    for clip in &[&triangle[..], &reversed[..]] {
+        let clipped = clip_path_to_convex_polygon(path.as_slice(), clip, 0.1);
+        assert_eq!(clipped.num_sub_paths(), 1);
+        let sub_path = clipped.sub_path(path_id(0));
+        assert!(sub_path.info().is_closed);
+        let mut area = 0.0;
+        for id in sub_path.vertex_ids() {
+            area += sub_path.vertex(id).position.cross(sub_path.next_vertex(id).position);
+        }
+        assert_eq!(area.abs() * 0.5, 50.0);
+    }
+
+    // A degenerate clip polygon removes everything.
+    let line = [vec2(0.0, 0.0), vec2(1.0, 1.0), vec2(2.0, 2.0)];
+    assert_eq!(clip_path_to_convex_polygon(path.as_slice(), &line[..], 0.1).num_sub_paths(), 0);
+}
+
+#[test]
+fn test_clip_self_intersecting() {
+    // A bowtie, the signed areas of its two lobes cancel each other out.
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(0.0, 0.0));
+    builder.line_to(vec2(10.0, 10.0));
+    builder.line_to(vec2(10.0, 0.0));
+    builder.line_to(vec2(0.0, 10.0));
+    builder.close();
+    let path = builder.finish();
+
+    let clipped = clip_path_to_rect(path.as_slice(), &Rect::new(-1.0, 2.0, 12.0, 6.0), 0.1);
+    assert_eq!(clipped.num_sub_paths(), 1);
+    let bowtie = clipped.sub_path(path_id(0));
+    assert!(bowtie.info().is_closed);
+    assert_eq!(bowtie.info().aabb, Rect::new(0.0, 2.0, 10.0, 6.0));
+    let mut area = 0.0;
+    for id in bowtie.vertex_ids() {
+        area += bowtie.vertex(id).position.cross(bowtie.next_vertex(id).position);
+    }
+    assert_eq!(area, 0.0);
+
+    // A square that only touches the rectangle is reduced to a segment and removed.
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(10.0, 0.0));
+    builder.line_to(vec2(20.0, 0.0));
+    builder.line_to(vec2(20.0, 10.0));
+    builder.line_to(vec2(10.0, 10.0));
+    builder.close();
+    let path = builder.finish();
+    let clipped = clip_path_to_rect(path.as_slice(), &Rect::new(0.0, 0.0, 10.0, 10.0), 0.1);
+    assert_eq!(clipped.num_sub_paths(), 0);
+}
@@ -0,0 +1,206 @@
+//! Arc-length measurement of paths.
+//!
+//! Useful to place things along a path, for example to lay out text on a curve.
+
+use tesselation::path::*;
+use tesselation::path_builder::PathBuilder;
+
+use vodk_math::{ Vector2D, Unit, Untyped };
+
+#[cfg(test)]
+use vodk_math::vec2;
+
+struct Segment<U: Unit> {
+    from: Vector2D<U>,
+    to: Vector2D<U>,
+    // Distance from the beginning of the path to the start of the segment.
+    distance: f32,
+    length: f32,
+    sub_path: usize,
+}
+
+/// Measures the length of a path and gives access to positions and tangents at given
+/// distances along it.
+///
+/// Curves are flattened with the tolerance passed to the constructor, which determines
+/// the precision of the measurements. Sub-paths are measured one after the other in the
+/// order of the path, and closed sub-paths include their closing segment.
+pub struct PathMeasure<U: Unit = Untyped> {
+    segments: Vec<Segment<U>>,
+    length: f32,
+}
+
+impl<U: Unit> PathMeasure<U> {
+    pub fn new(path: PathSlice<U>, tolerance: f32) -> PathMeasure<U> {
+        let mut segments = Vec::new();
+        let mut distance = 0.0;
+        let mut points: Vec<Vector2D<U>> = Vec::new();
+        for id in path.path_ids() {
+            let sub_path = path.sub_path(id);
+            points.clear();
+            sub_path.flatten(tolerance, &mut points);
+            if sub_path.info().is_closed && points.len() > 1 {
+                let first = points[0];
+                points.push(first);
+            }
+            for i in 1..points.len() {
+                let (from, to) = (points[i - 1], points[i]);
+                let length = (to - from).length();
+                if length == 0.0 {
+                    continue;
+                }
+                segments.push(Segment {
+                    from: from,
+                    to: to,
+                    distance: distance,
+                    length: length,
+                    sub_path: id.handle as usize,
+                });
+                distance += length;
+            }
+        }
+
+        return PathMeasure {
+            segments: segments,
+            length: distance,
+        };
+    }
+
+    /// The total length of the path.
+    pub fn length(&self) -> f32 { self.length }
+
+    /// Returns the position at a given distance from the beginning of the path.
+    ///
+    /// The distance is clamped to the length of the path. Returns None if the path is empty.
+    pub fn position(&self, distance: f32) -> Option<Vector2D<U>> {
+        return self.position_and_tangent(distance).map(|(p, _)| p);
+    }
+
+    /// Returns the normalized tangent at a given distance from the beginning of the path.
+    pub fn tangent(&self, distance: f32) -> Option<Vector2D<U>> {
+        return self.position_and_tangent(distance).map(|(_, t)| t);
+    }
+
+    /// Returns the position and the normalized tangent at a given distance from the
+    /// beginning of the path.
+    pub fn position_and_tangent(&self, distance: f32) -> Option<(Vector2D<U>, Vector2D<U>)> {
+        if self.segments.is_empty() {
+            return None;
+        }
+        let segment = &self.segments[self.segment_index(distance)];
+        let t = ((distance - segment.distance) / segment.length).max(0.0).min(1.0);
+        let v = segment.to - segment.from;
+        return Some((segment.from + v * t, v / segment.length));
+    }
+
+    /// Adds the portion of the path between two distances to a path builder.
+    ///
+    /// The extracted portion starts with a move_to and is left open. A new sub-path is started
+    /// each time the range crosses into the next sub-path of the measured path.
+    pub fn extract(&self, start: f32, end: f32, output: &mut PathBuilder<U>) {
+        let start = start.max(0.0);
+        let end = end.min(self.length);
+        if self.segments.is_empty() || start >= end {
+            return;
+        }
+
+        let first = self.segment_index(start);
+        let last = self.segment_index(end);
+        let mut sub_path = self.segments[first].sub_path;
+        output.move_to(self.position(start).unwrap());
+        for i in first..(last + 1) {
+            let segment = &self.segments[i];
+            if segment.sub_path != sub_path {
+                output.end();
+                output.move_to(segment.from);
+                sub_path = segment.sub_path;
+            }
+            if i == last {
+                output.line_to(self.position(end).unwrap());
+            } else {
+                output.line_to(segment.to);
+            }
+        }
+        output.end();
+    }
+
+    // Index of the segment that contains the provided distance.
+    fn segment_index(&self, distance: f32) -> usize {
+        let mut low = 0;
+        let mut high = self.segments.len();
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            if self.segments[mid].distance <= distance {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        return low;
+    }
+}
+
+#[cfg(test)]
+fn square_path() -> Path {
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(0.0, 0.0));
+    builder.line_to(vec2(10.0, 0.0));
+    builder.line_to(vec2(10.0, 10.0));
+    builder.line_to(vec2(0.0, 10.0));
+    builder.close();
+    return builder.finish();
+}
+
+#[test]
+fn test_path_measure_square() {
+    let path = square_path();
+    let measure = PathMeasure::new(path.as_slice(), 0.1);
+    assert_eq!(measure.length(), 40.0);
+    assert_eq!(measure.position_and_tangent(15.0), Some((vec2(10.0, 5.0), vec2(0.0, 1.0))));
+    assert_eq!(measure.position(35.0), Some(vec2(0.0, 5.0)));
+    assert_eq!(measure.tangent(35.0), Some(vec2(0.0, -1.0)));
+    // Out of range distances are clamped.
+    assert_eq!(measure.position(-1.0), Some(vec2(0.0, 0.0)));
+    assert_eq!(measure.position(100.0), Some(vec2(0.0, 0.0)));
+
+    let mut builder = PathBuilder::new();
+    measure.extract(5.0, 25.0, &mut builder);
+    let extracted = builder.finish();
+    assert_eq!(extracted.num_vertices(), 4);
+    assert_eq!(extracted.vertices().nth(0).position, vec2(5.0, 0.0));
+    assert_eq!(extracted.vertices().nth(1).position, vec2(10.0, 0.0));
+    assert_eq!(extracted.vertices().nth(2).position, vec2(10.0, 10.0));
+    assert_eq!(extracted.vertices().nth(3).position, vec2(5.0, 10.0));
+    assert!(!extracted.sub_path(path_id(0)).info().is_closed);
+}
+
+#[test]
+fn test_path_measure_curve() {
+    // A quarter of circle approximated with a cubic bézier curve.
+    let k = 0.5522847;
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(10.0, 0.0));
+    builder.cubic_bezier_to(vec2(10.0, 10.0 * k), vec2(10.0 * k, 10.0), vec2(0.0, 10.0));
+    builder.end();
+    builder.move_to(vec2(20.0, 0.0));
+    builder.line_to(vec2(30.0, 0.0));
+    let path = builder.finish();
+
+    let measure = PathMeasure::new(path.as_slice(), 0.01);
+    let quarter = ::std::f32::consts::PI * 5.0;
+    assert!((measure.length() - quarter - 10.0).abs() < 0.05);
+
+    let (p, t) = measure.position_and_tangent(quarter * 0.5).unwrap();
+    let diagonal = (0.5 as f32).sqrt();
+    assert!((p - vec2(10.0 * diagonal, 10.0 * diagonal)).length() < 0.05);
+    assert!((t - vec2(-diagonal, diagonal)).length() < 0.05);
+
+    // Extracting across sub-paths starts a new sub-path.
+    let mut builder = PathBuilder::new();
+    measure.extract(quarter - 1.0, quarter + 5.0, &mut builder);
+    let extracted = builder.finish();
+    assert_eq!(extracted.path_ids().count, 2);
+    let second = extracted.sub_path(path_id(1));
+    assert_eq!(second.vertex(second.first()).position, vec2(20.0, 0.0));
+    assert!((second.vertex(second.last()).position - vec2(25.0, 0.0)).length() < 0.05);
+}
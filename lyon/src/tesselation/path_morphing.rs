@@ -0,0 +1,368 @@
+//! Shape morphing.
+//!
+//! match_paths converts two paths into a pair of paths that have exactly the same structure
+//! (same number of sub-paths and the same sequence of vertex types), so that intermediate
+//! shapes can be generated by interpolating the vertices with interpolate_paths.
+
+use tesselation::{ vertex_id_range };
+use tesselation::path::*;
+use tesselation::bezier::{ CubicBezierSegment, QuadraticBezierSegment };
+
+use vodk_math::{ Vector2D, Rectangle, Unit };
+
+#[cfg(test)]
+use tesselation::path_builder::PathBuilder;
+#[cfg(test)]
+use vodk_math::{ Vec2, vec2 };
+
+struct Contour<U: Unit> {
+    start: Vector2D<U>,
+    segments: Vec<CubicBezierSegment<U>>,
+    is_closed: bool,
+}
+
+/// Produce a pair of paths with the same structure out of two arbitrary paths.
+///
+/// All segments are converted to cubic bézier curves. Sub-paths are paired in order, and
+/// the sub-path with fewer segments of each pair has its longest segments split until both
+/// have the same number of segments. When both sub-paths of a pair are closed, the second
+/// one is reversed if needed to match the orientation of the first one, and its starting
+/// point is rotated to minimize the distance between matching vertices, which reduces the
+/// amount of twisting during the animation.
+///
+/// Sub-paths that don't have a counterpart are paired with a sub-path collapsed to their
+/// centroid, so that they grow or shrink in place.
+pub fn match_paths<U: Unit>(a: PathSlice<U>, b: PathSlice<U>) -> (Path<U>, Path<U>) {
+    let mut contours_a = contours(a);
+    let mut contours_b = contours(b);
+
+    while contours_a.len() < contours_b.len() {
+        let c = collapsed(&contours_b[contours_a.len()]);
+        contours_a.push(c);
+    }
+    while contours_b.len() < contours_a.len() {
+        let c = collapsed(&contours_a[contours_b.len()]);
+        contours_b.push(c);
+    }
+
+    for (ca, cb) in contours_a.iter_mut().zip(contours_b.iter_mut()) {
+        if ca.is_closed && cb.is_closed && signed_area(ca) * signed_area(cb) < 0.0 {
+            reverse(cb);
+        }
+
+        let n = ca.segments.len().max(cb.segments.len());
+        split_to_count(ca, n);
+        split_to_count(cb, n);
+
+        if ca.is_closed && cb.is_closed {
+            align_start(ca, cb);
+        }
+    }
+
+    // Closed sub-paths are stored without repeating their first point, open ones keep all of
+    // their points. If only one sub-path of a pair is closed, both use the open layout.
+    let keep_last: Vec<bool> = contours_a.iter().zip(contours_b.iter()).map(|(ca, cb)| {
+        !(ca.is_closed && cb.is_closed)
+    }).collect();
+
+    return (build_path(&contours_a, &keep_last), build_path(&contours_b, &keep_last));
+}
+
+/// Linearly interpolate between two paths that have the same structure, for example the
+/// paths produced by match_paths.
+///
+/// The sub-paths of the result are closed if they are closed in either of the paths.
+pub fn interpolate_paths<U: Unit>(a: PathSlice<U>, b: PathSlice<U>, t: f32) -> Path<U> {
+    assert_eq!(a.num_vertices(), b.num_vertices());
+    assert_eq!(a.num_sub_paths(), b.num_sub_paths());
+
+    let mut vertices = Vec::with_capacity(a.num_vertices());
+    for (va, vb) in a.vertices().iter().zip(b.vertices().iter()) {
+        debug_assert_eq!(va.point_type, vb.point_type);
+        vertices.push(PointData {
+            position: va.position + (vb.position - va.position) * t,
+            point_type: va.point_type,
+        });
+    }
+
+    let mut sub_paths = Vec::with_capacity(a.num_sub_paths());
+    for id in a.path_ids() {
+        let (info_a, info_b) = (a.sub_path(id).info(), b.sub_path(id).info());
+        assert_eq!(info_a.range, info_b.range);
+        sub_paths.push(PathInfo {
+            aabb: bounding_rect(&vertices[info_a.range.first.handle as usize..
+                                          (info_a.range.first.handle + info_a.range.count) as usize]),
+            range: info_a.range,
+            has_beziers: info_a.has_beziers,
+            is_closed: info_a.is_closed || info_b.is_closed,
+        });
+    }
+
+    return Path::from_vec(vertices, sub_paths);
+}
+
+fn line_segment<U: Unit>(from: Vector2D<U>, to: Vector2D<U>) -> CubicBezierSegment<U> {
+    CubicBezierSegment {
+        from: from,
+        cp1: from + (to - from) / 3.0,
+        cp2: from + (to - from) * (2.0 / 3.0),
+        to: to,
+    }
+}
+
+fn curve_segment<U: Unit>(
+    from: Vector2D<U>,
+    ctrl: &[Vector2D<U>],
+    to: Vector2D<U>
+) -> CubicBezierSegment<U> {
+    match ctrl.len() {
+        0 => line_segment(from, to),
+        1 => QuadraticBezierSegment { from: from, cp: ctrl[0], to: to }.to_cubic(),
+        _ => CubicBezierSegment { from: from, cp1: ctrl[0], cp2: ctrl[1], to: to },
+    }
+}
+
+fn contours<U: Unit>(path: PathSlice<U>) -> Vec<Contour<U>> {
+    let mut result = Vec::new();
+    for id in path.path_ids() {
+        let sub_path = path.sub_path(id);
+        let start = sub_path.vertex(sub_path.first()).position;
+        let mut segments = Vec::new();
+        let mut from = start;
+        let mut ctrl = [start, start];
+        let mut num_ctrl = 0;
+        let mut ids = sub_path.vertex_ids();
+        ids.next();
+        for vid in ids {
+            let v = sub_path.vertex(vid);
+            if v.point_type == PointType::Control {
+                debug_assert!(num_ctrl < 2);
+                ctrl[num_ctrl] = v.position;
+                num_ctrl += 1;
+                continue;
+            }
+            if num_ctrl > 0 || v.position != from {
+                segments.push(curve_segment(from, &ctrl[..num_ctrl], v.position));
+            }
+            from = v.position;
+            num_ctrl = 0;
+        }
+        let is_closed = sub_path.info().is_closed;
+        if is_closed && (num_ctrl > 0 || from != start) {
+            segments.push(curve_segment(from, &ctrl[..num_ctrl], start));
+        }
+        result.push(Contour { start: start, segments: segments, is_closed: is_closed });
+    }
+    return result;
+}
+
+fn collapsed<U: Unit>(contour: &Contour<U>) -> Contour<U> {
+    // The last point of closed contours is the first one, don't count it twice.
+    let mut center = Vector2D::new(0.0, 0.0);
+    let mut count = 0;
+    for segment in &contour.segments {
+        center = center + segment.from;
+        count += 1;
+    }
+    if !contour.is_closed || count == 0 {
+        center = center + contour.segments.last().map(|s| s.to).unwrap_or(contour.start);
+        count += 1;
+    }
+    center = center / count as f32;
+    return Contour { start: center, segments: Vec::new(), is_closed: contour.is_closed };
+}
+
+// Approximation of the signed area using the end points and the middle of each segment.
+fn signed_area<U: Unit>(contour: &Contour<U>) -> f32 {
+    let mut area = 0.0;
+    for segment in &contour.segments {
+        let mid = segment.sample(0.5);
+        area += segment.from.cross(mid) + mid.cross(segment.to);
+    }
+    return area * 0.5;
+}
+
+fn reverse<U: Unit>(contour: &mut Contour<U>) {
+    contour.segments.reverse();
+    for segment in &mut contour.segments {
+        *segment = CubicBezierSegment {
+            from: segment.to,
+            cp1: segment.cp2,
+            cp2: segment.cp1,
+            to: segment.from,
+        };
+    }
+    if let Some(first) = contour.segments.first() {
+        contour.start = first.from;
+    }
+}
+
+fn approximate_length<U: Unit>(segment: &CubicBezierSegment<U>) -> f32 {
+    (segment.cp1 - segment.from).length()
+        + (segment.cp2 - segment.cp1).length()
+        + (segment.to - segment.cp2).length()
+}
+
+fn split_to_count<U: Unit>(contour: &mut Contour<U>, count: usize) {
+    if contour.segments.is_empty() {
+        let p = contour.start;
+        for _ in 0..count {
+            contour.segments.push(CubicBezierSegment { from: p, cp1: p, cp2: p, to: p });
+        }
+        return;
+    }
+    while contour.segments.len() < count {
+        let mut longest = 0;
+        let mut max_length = -1.0;
+        for (i, segment) in contour.segments.iter().enumerate() {
+            let length = approximate_length(segment);
+            if length > max_length {
+                max_length = length;
+                longest = i;
+            }
+        }
+        let (first, second) = contour.segments[longest].split(0.5);
+        contour.segments[longest] = first;
+        contour.segments.insert(longest + 1, second);
+    }
+}
+
+// Rotate the segments of b so that its vertices are as close as possible to the ones of a.
+fn align_start<U: Unit>(a: &Contour<U>, b: &mut Contour<U>) {
+    let n = b.segments.len();
+    let mut best_offset = 0;
+    let mut best_cost = ::std::f32::MAX;
+    for offset in 0..n {
+        let mut cost = 0.0;
+        for i in 0..n {
+            cost += (a.segments[i].from - b.segments[(i + offset) % n].from).square_length();
+        }
+        if cost < best_cost {
+            best_cost = cost;
+            best_offset = offset;
+        }
+    }
+    if best_offset != 0 {
+        let mut rotated = Vec::with_capacity(n);
+        for i in 0..n {
+            rotated.push(b.segments[(i + best_offset) % n]);
+        }
+        b.segments = rotated;
+        b.start = b.segments[0].from;
+    }
+}
+
+fn bounding_rect<U: Unit>(vertices: &[PointData<U>]) -> Rectangle<U> {
+    if vertices.is_empty() {
+        return Rectangle::new(0.0, 0.0, 0.0, 0.0);
+    }
+    let mut min = vertices[0].position;
+    let mut max = min;
+    for v in vertices {
+        let p = v.position;
+        if p.x < min.x { min.x = p.x; }
+        if p.y < min.y { min.y = p.y; }
+        if p.x > max.x { max.x = p.x; }
+        if p.y > max.y { max.y = p.y; }
+    }
+    return Rectangle::new(min.x, min.y, max.x - min.x, max.y - min.y);
+}
+
+// Builds the path directly rather than with a PathBuilder, which would drop repeated
+// points and change the structure of the path.
+fn build_path<U: Unit>(contours: &[Contour<U>], keep_last: &[bool]) -> Path<U> {
+    let mut vertices = Vec::new();
+    let mut sub_paths = Vec::new();
+    for (contour, &keep_last) in contours.iter().zip(keep_last.iter()) {
+        let first = vertices.len();
+        vertices.push(PointData { position: contour.start, point_type: PointType::Normal });
+        let n = contour.segments.len();
+        for (i, segment) in contour.segments.iter().enumerate() {
+            vertices.push(PointData { position: segment.cp1, point_type: PointType::Control });
+            vertices.push(PointData { position: segment.cp2, point_type: PointType::Control });
+            if keep_last || i + 1 < n {
+                vertices.push(PointData { position: segment.to, point_type: PointType::Normal });
+            }
+        }
+        sub_paths.push(PathInfo {
+            aabb: bounding_rect(&vertices[first..]),
+            range: vertex_id_range(first as u16, vertices.len() as u16),
+            has_beziers: Some(n > 0),
+            is_closed: contour.is_closed,
+        });
+    }
+    return Path::from_vec(vertices, sub_paths);
+}
+
+#[cfg(test)]
+fn polygon(points: &[Vec2]) -> Path {
+    let mut builder = PathBuilder::new();
+    builder.move_to(points[0]);
+    for &p in &points[1..] {
+        builder.line_to(p);
+    }
+    builder.close();
+    return builder.finish();
+}
+
+#[test]
+fn test_match_paths_structure() {
+    let square = polygon(&[vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(10.0, 10.0), vec2(0.0, 10.0)]);
+    let triangle = polygon(&[vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(5.0, 10.0)]);
+
+    let (a, b) = match_paths(square.as_slice(), triangle.as_slice());
+    assert_eq!(a.num_vertices(), b.num_vertices());
+    // Four cubic segments without repeating the first point.
+    assert_eq!(a.num_vertices(), 12);
+    for (va, vb) in a.vertices().iter().zip(b.vertices().iter()) {
+        assert_eq!(va.point_type, vb.point_type);
+    }
+
+    // The end points of the interpolation are the matched paths.
+    let start = interpolate_paths(a.as_slice(), b.as_slice(), 0.0);
+    let end = interpolate_paths(a.as_slice(), b.as_slice(), 1.0);
+    for i in 0..a.num_vertices() {
+        assert_eq!(start.vertices().as_slice()[i].position, a.vertices().as_slice()[i].position);
+        assert_eq!(end.vertices().as_slice()[i].position, b.vertices().as_slice()[i].position);
+    }
+    let half = interpolate_paths(a.as_slice(), b.as_slice(), 0.5);
+    assert_eq!(half.vertices().nth(0).position, vec2(0.0, 0.0));
+    assert!(half.sub_path(path_id(0)).info().is_closed);
+}
+
+#[test]
+fn test_match_paths_alignment() {
+    let a = polygon(&[vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(10.0, 10.0), vec2(0.0, 10.0)]);
+    // Same square with a different starting point and the opposite orientation.
+    let b = polygon(&[vec2(10.0, 10.0), vec2(10.0, 0.0), vec2(0.0, 0.0), vec2(0.0, 10.0)]);
+
+    let (ma, mb) = match_paths(a.as_slice(), b.as_slice());
+    for (va, vb) in ma.vertices().iter().zip(mb.vertices().iter()) {
+        assert!(va.position.fuzzy_eq(vb.position));
+    }
+}
+
+#[test]
+fn test_match_paths_extra_sub_path() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(0.0, 0.0));
+    builder.line_to(vec2(10.0, 0.0));
+    builder.line_to(vec2(10.0, 10.0));
+    builder.close();
+    builder.move_to(vec2(20.0, 0.0));
+    builder.line_to(vec2(30.0, 0.0));
+    builder.line_to(vec2(30.0, 12.0));
+    builder.close();
+    let two = builder.finish();
+    let one = polygon(&[vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(0.0, 10.0)]);
+
+    let (a, b) = match_paths(two.as_slice(), one.as_slice());
+    assert_eq!(a.num_sub_paths(), 2);
+    assert_eq!(b.num_sub_paths(), 2);
+    assert_eq!(a.num_vertices(), b.num_vertices());
+    // The missing sub-path is collapsed to the centroid of its counterpart.
+    let collapsed = b.sub_path(path_id(1));
+    for id in collapsed.vertex_ids() {
+        assert!(collapsed.vertex(id).position.fuzzy_eq(vec2(80.0 / 3.0, 4.0)));
+    }
+}
@@ -0,0 +1,535 @@
+//! Simplification of dense polylines and fitting of cubic bézier curves.
+//!
+//! All of the functions in this module operate on flattened sub-paths and produce new paths.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use tesselation::path::*;
+use tesselation::path_builder::PathBuilder;
+use tesselation::bezier::CubicBezierSegment;
+
+use vodk_math::{ Vec2, vec2, Untyped };
+
+/// Simplify a path using the Ramer–Douglas–Peucker algorithm.
+///
+/// Points that are closer than tolerance to the simplified polyline are removed.
+/// Curves are flattened with flattening_tolerance beforehand.
+pub fn simplify_path_rdp(path: PathSlice, tolerance: f32, flattening_tolerance: f32) -> Path {
+    let mut builder = PathBuilder::new();
+    let mut points = Vec::new();
+    let mut keep = Vec::new();
+    for id in path.path_ids() {
+        let sub_path = path.sub_path(id);
+        points.clear();
+        sub_path.flatten(flattening_tolerance, &mut points);
+
+        keep.clear();
+        keep.resize(points.len(), false);
+        let n = points.len();
+        if n > 0 {
+            keep[0] = true;
+            if sub_path.info().is_closed && n > 2 {
+                // Split the loop at the point that is the farthest from the first one.
+                let mut far = 0;
+                let mut max_dist = 0.0;
+                for i in 1..n {
+                    let d = (points[i] - points[0]).square_length();
+                    if d > max_dist {
+                        max_dist = d;
+                        far = i;
+                    }
+                }
+                keep[far] = true;
+                rdp(&points[..], 0, far, tolerance, &mut keep);
+                // The closing segment goes from the last point back to the first one.
+                points.push(points[0]);
+                keep.push(true);
+                rdp(&points[..], far, n, tolerance, &mut keep);
+                points.pop();
+                keep.pop();
+            } else {
+                keep[n - 1] = true;
+                rdp(&points[..], 0, n - 1, tolerance, &mut keep);
+            }
+        }
+
+        add_sub_path(
+            &mut builder,
+            points.iter().zip(keep.iter()).filter(|&(_, k)| *k).map(|(p, _)| *p),
+            sub_path.info().is_closed
+        );
+    }
+    return builder.finish();
+}
+
+fn rdp(points: &[Vec2], first: usize, last: usize, tolerance: f32, keep: &mut [bool]) {
+    if last <= first + 1 {
+        return;
+    }
+
+    let mut max_dist = 0.0;
+    let mut split = first;
+    for i in (first + 1)..last {
+        let d = distance_to_segment(points[i], points[first], points[last]);
+        if d > max_dist {
+            max_dist = d;
+            split = i;
+        }
+    }
+
+    if max_dist > tolerance {
+        keep[split] = true;
+        rdp(points, first, split, tolerance, keep);
+        rdp(points, split, last, tolerance, keep);
+    }
+}
+
+/// Simplify a path using Visvalingam's algorithm.
+///
+/// Points are removed in order of increasing effective area (the area of the triangle they
+/// form with their neighbours) until all remaining points have an effective area above min_area.
+/// Curves are flattened with flattening_tolerance beforehand.
+pub fn simplify_path_visvalingam(path: PathSlice, min_area: f32, flattening_tolerance: f32) -> Path {
+    let mut builder = PathBuilder::new();
+    let mut points = Vec::new();
+    for id in path.path_ids() {
+        let sub_path = path.sub_path(id);
+        let is_closed = sub_path.info().is_closed;
+        points.clear();
+        sub_path.flatten(flattening_tolerance, &mut points);
+
+        let keep = visvalingam(&points[..], is_closed, min_area);
+
+        add_sub_path(
+            &mut builder,
+            points.iter().zip(keep.iter()).filter(|&(_, k)| *k).map(|(p, _)| *p),
+            is_closed
+        );
+    }
+    return builder.finish();
+}
+
+struct Candidate {
+    area: f32,
+    index: usize,
+    version: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Candidate) -> bool { self.area == other.area }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for Candidate {
+    // Reversed so that the BinaryHeap pops the smallest area first.
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        other.area.partial_cmp(&self.area).unwrap_or(Ordering::Equal)
+    }
+}
+
+fn visvalingam(points: &[Vec2], is_closed: bool, min_area: f32) -> Vec<bool> {
+    let n = points.len();
+    let mut keep = vec![true; n];
+    let min_points = if is_closed { 3 } else { 2 };
+    if n <= min_points {
+        return keep;
+    }
+
+    let mut prev: Vec<usize> = (0..n).map(|i| (i + n - 1) % n).collect();
+    let mut next: Vec<usize> = (0..n).map(|i| (i + 1) % n).collect();
+    let mut versions = vec![0u32; n];
+    let mut count = n;
+
+    let removable = |i: usize| is_closed || (i != 0 && i != n - 1);
+    let area = |p: usize, i: usize, n: usize| {
+        ((points[n] - points[p]).cross(points[i] - points[p]) * 0.5).abs()
+    };
+
+    let mut heap = BinaryHeap::with_capacity(n);
+    for i in 0..n {
+        if removable(i) {
+            heap.push(Candidate { area: area(prev[i], i, next[i]), index: i, version: 0 });
+        }
+    }
+
+    while let Some(candidate) = heap.pop() {
+        let i = candidate.index;
+        if !keep[i] || candidate.version != versions[i] {
+            // Stale entry.
+            continue;
+        }
+        if candidate.area >= min_area || count <= min_points {
+            break;
+        }
+
+        keep[i] = false;
+        count -= 1;
+        let (p, nx) = (prev[i], next[i]);
+        next[p] = nx;
+        prev[nx] = p;
+
+        // The neighbours' areas can't be smaller than the area of the removed point, otherwise
+        // they would be removed before points that were already considered.
+        for &j in &[p, nx] {
+            if removable(j) {
+                versions[j] += 1;
+                let a = area(prev[j], j, next[j]).max(candidate.area);
+                heap.push(Candidate { area: a, index: j, version: versions[j] });
+            }
+        }
+    }
+
+    return keep;
+}
+
+/// Approximate each sub-path of a path with a sequence of cubic bézier curves.
+///
+/// The curves stay within tolerance of the flattened input. This is typically used to turn
+/// dense polylines (for example freehand input) into compact curve paths.
+/// This is an implementation of the algorithm described by Philip J. Schneider in
+/// "An Algorithm for Automatically Fitting Digitized Curves" (Graphics Gems, 1990).
+pub fn fit_cubic_beziers(path: PathSlice, tolerance: f32, flattening_tolerance: f32) -> Path {
+    let mut builder = PathBuilder::new();
+    let mut points: Vec<Vec2> = Vec::new();
+    for id in path.path_ids() {
+        let sub_path = path.sub_path(id);
+        let is_closed = sub_path.info().is_closed;
+        points.clear();
+        sub_path.flatten(flattening_tolerance, &mut points);
+        if is_closed && points.len() > 1 {
+            let first = points[0];
+            points.push(first);
+        }
+
+        let n = points.len();
+        if n == 0 {
+            continue;
+        }
+
+        builder.move_to(points[0]);
+        if n > 1 {
+            let left_tangent = (points[1] - points[0]).normalized();
+            let right_tangent = (points[n - 2] - points[n - 1]).normalized();
+            fit_cubic(
+                &points[..], left_tangent, right_tangent,
+                tolerance * tolerance, &mut builder
+            );
+        }
+
+        if is_closed { builder.close(); } else { builder.end(); }
+    }
+    return builder.finish();
+}
+
+// Fit a cubic bézier curve to points, subdividing if needed, and add the resulting curves to the
+// path builder.
+fn fit_cubic(
+    points: &[Vec2],
+    left_tangent: Vec2,
+    right_tangent: Vec2,
+    square_tolerance: f32,
+    builder: &mut PathBuilder
+) {
+    let n = points.len();
+    let from = points[0];
+    let to = points[n - 1];
+
+    if n == 2 {
+        let d = (to - from).length() / 3.0;
+        builder.cubic_bezier_to(from + left_tangent * d, to + right_tangent * d, to);
+        return;
+    }
+
+    let mut params = chord_length_parameterize(points);
+    let mut curve = generate_bezier(points, &params[..], left_tangent, right_tangent);
+    let (mut max_error, mut split) = compute_max_error(points, &params[..], &curve);
+    if max_error < square_tolerance {
+        builder.cubic_bezier_to(curve.cp1, curve.cp2, curve.to);
+        return;
+    }
+
+    // If the error is not too large, try to improve the parameterization.
+    if max_error < square_tolerance * 4.0 {
+        for _ in 0..4 {
+            params = reparameterize(points, &params[..], &curve);
+            curve = generate_bezier(points, &params[..], left_tangent, right_tangent);
+            let (e, s) = compute_max_error(points, &params[..], &curve);
+            max_error = e;
+            split = s;
+            if max_error < square_tolerance {
+                builder.cubic_bezier_to(curve.cp1, curve.cp2, curve.to);
+                return;
+            }
+        }
+    }
+
+    // Fitting failed, split at the point of maximum error and fit each half.
+    let split = split.max(1).min(n - 2);
+    let center_tangent = center_tangent(points, split);
+    fit_cubic(&points[..(split + 1)], left_tangent, center_tangent, square_tolerance, builder);
+    fit_cubic(&points[split..], -center_tangent, right_tangent, square_tolerance, builder);
+}
+
+fn center_tangent(points: &[Vec2], i: usize) -> Vec2 {
+    let v = points[i - 1] - points[i + 1];
+    if v.square_length() < 0.0000001 {
+        // The curve goes back on itself, use the normal of the incoming direction.
+        let d = points[i] - points[i - 1];
+        return vec2(-d.y, d.x).normalized();
+    }
+    return v.normalized();
+}
+
+fn chord_length_parameterize(points: &[Vec2]) -> Vec<f32> {
+    let mut params = Vec::with_capacity(points.len());
+    params.push(0.0);
+    for i in 1..points.len() {
+        let d = params[i - 1] + (points[i] - points[i - 1]).length();
+        params.push(d);
+    }
+    let total = params[points.len() - 1];
+    if total > 0.0 {
+        for p in &mut params {
+            *p = *p / total;
+        }
+    }
+    return params;
+}
+
+// Least-squares fit of the control points, with the tangents at both ends fixed.
+fn generate_bezier(
+    points: &[Vec2],
+    params: &[f32],
+    left_tangent: Vec2,
+    right_tangent: Vec2
+) -> CubicBezierSegment<Untyped> {
+    let from = points[0];
+    let to = points[points.len() - 1];
+
+    let mut c = [[0.0f32; 2]; 2];
+    let mut x = [0.0f32; 2];
+    for (p, &u) in points.iter().zip(params.iter()) {
+        let (b0, b1, b2, b3) = bernstein(u);
+        let a0 = left_tangent * b1;
+        let a1 = right_tangent * b2;
+        c[0][0] += a0.dot(&a0);
+        c[0][1] += a0.dot(&a1);
+        c[1][1] += a1.dot(&a1);
+        let tmp = *p - (from * (b0 + b1) + to * (b2 + b3));
+        x[0] += a0.dot(&tmp);
+        x[1] += a1.dot(&tmp);
+    }
+    c[1][0] = c[0][1];
+
+    let det_c0_c1 = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let det_c0_x = c[0][0] * x[1] - c[1][0] * x[0];
+    let det_x_c1 = x[0] * c[1][1] - x[1] * c[0][1];
+
+    let (mut alpha_l, mut alpha_r) = if det_c0_c1 == 0.0 { (0.0, 0.0) }
+                                     else { (det_x_c1 / det_c0_c1, det_c0_x / det_c0_c1) };
+
+    // Fall back to a simple heuristic if the solution is degenerate.
+    let segment_length = (to - from).length();
+    let epsilon = 0.000001 * segment_length;
+    if alpha_l < epsilon || alpha_r < epsilon {
+        alpha_l = segment_length / 3.0;
+        alpha_r = alpha_l;
+    }
+
+    return CubicBezierSegment {
+        from: from,
+        cp1: from + left_tangent * alpha_l,
+        cp2: to + right_tangent * alpha_r,
+        to: to,
+    };
+}
+
+fn bernstein(u: f32) -> (f32, f32, f32, f32) {
+    let t = 1.0 - u;
+    return (t * t * t, 3.0 * u * t * t, 3.0 * u * u * t, u * u * u);
+}
+
+// Return the maximum square distance between the points and the curve and the index of the
+// point where it happens.
+fn compute_max_error(
+    points: &[Vec2],
+    params: &[f32],
+    curve: &CubicBezierSegment<Untyped>
+) -> (f32, usize) {
+    let mut max_error = 0.0;
+    let mut split = points.len() / 2;
+    for i in 1..(points.len() - 1) {
+        let d = (curve.sample(params[i]) - points[i]).square_length();
+        if d >= max_error {
+            max_error = d;
+            split = i;
+        }
+    }
+    return (max_error, split);
+}
+
+// Improve the parameters with a Newton-Raphson iteration.
+fn reparameterize(
+    points: &[Vec2],
+    params: &[f32],
+    curve: &CubicBezierSegment<Untyped>
+) -> Vec<f32> {
+    let d1 = [
+        (curve.cp1 - curve.from) * 3.0,
+        (curve.cp2 - curve.cp1) * 3.0,
+        (curve.to - curve.cp2) * 3.0,
+    ];
+    let d2 = [
+        (d1[1] - d1[0]) * 2.0,
+        (d1[2] - d1[1]) * 2.0,
+    ];
+
+    return points.iter().zip(params.iter()).map(|(p, &u)| {
+        let t = 1.0 - u;
+        let q = curve.sample(u) - *p;
+        let q1 = d1[0] * (t * t) + d1[1] * (2.0 * t * u) + d1[2] * (u * u);
+        let q2 = d2[0] * t + d2[1] * u;
+        let denominator = q1.dot(&q1) + q.dot(&q2);
+        if denominator == 0.0 {
+            return u;
+        }
+        return (u - q.dot(&q1) / denominator).max(0.0).min(1.0);
+    }).collect();
+}
+
+fn add_sub_path<Points: Iterator<Item=Vec2>>(
+    builder: &mut PathBuilder,
+    mut points: Points,
+    is_closed: bool
+) {
+    if let Some(first) = points.next() {
+        builder.move_to(first);
+        for p in points {
+            builder.line_to(p);
+        }
+        if is_closed { builder.close(); } else { builder.end(); }
+    }
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let sqr_len = ab.square_length();
+    if sqr_len == 0.0 {
+        return (p - a).length();
+    }
+    let t = ((p - a).dot(&ab) / sqr_len).max(0.0).min(1.0);
+    return (p - (a + ab * t)).length();
+}
+
+#[cfg(test)]
+fn noisy_line(n: usize) -> Path {
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(0.0, 0.0));
+    for i in 1..n {
+        let noise = if i % 2 == 0 { 0.01 } else { -0.01 };
+        builder.line_to(vec2(i as f32, noise));
+    }
+    builder.end();
+    return builder.finish();
+}
+
+#[cfg(test)]
+fn flattened_points(path: &Path) -> Vec<Vec2> {
+    let mut points = Vec::new();
+    for id in path.path_ids() {
+        path.sub_path(id).flatten(0.01, &mut points);
+    }
+    return points;
+}
+
+#[test]
+fn test_rdp() {
+    let path = noisy_line(20);
+    let simplified = simplify_path_rdp(path.as_slice(), 0.1, 0.01);
+    assert_eq!(simplified.num_vertices(), 2);
+    assert_eq!(simplified.vertices().nth(0).position, vec2(0.0, 0.0));
+    assert_eq!(simplified.vertices().nth(1).position, vec2(19.0, -0.01));
+
+    // A tolerance smaller than the noise keeps everything.
+    let simplified = simplify_path_rdp(path.as_slice(), 0.001, 0.01);
+    assert_eq!(simplified.num_vertices(), 20);
+
+    // A closed square with extra points on its edges.
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(0.0, 0.0));
+    builder.line_to(vec2(1.0, 0.0));
+    builder.line_to(vec2(2.0, 0.0));
+    builder.line_to(vec2(2.0, 1.0));
+    builder.line_to(vec2(2.0, 2.0));
+    builder.line_to(vec2(1.0, 2.0));
+    builder.line_to(vec2(0.0, 2.0));
+    builder.line_to(vec2(0.0, 1.0));
+    builder.close();
+    let simplified = simplify_path_rdp(builder.finish().as_slice(), 0.1, 0.01);
+    assert_eq!(simplified.num_vertices(), 4);
+    assert!(simplified.sub_path(path_id(0)).info().is_closed);
+}
+
+#[test]
+fn test_visvalingam() {
+    let path = noisy_line(20);
+    let simplified = simplify_path_visvalingam(path.as_slice(), 0.1, 0.01);
+    assert_eq!(simplified.num_vertices(), 2);
+
+    let simplified = simplify_path_visvalingam(path.as_slice(), 0.0001, 0.01);
+    assert_eq!(simplified.num_vertices(), 20);
+
+    // Large features are preserved.
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(0.0, 0.0));
+    builder.line_to(vec2(5.0, 0.01));
+    builder.line_to(vec2(10.0, 0.0));
+    builder.line_to(vec2(10.0, 10.0));
+    builder.line_to(vec2(0.0, 10.0));
+    builder.close();
+    let simplified = simplify_path_visvalingam(builder.finish().as_slice(), 1.0, 0.01);
+    assert_eq!(simplified.num_vertices(), 4);
+}
+
+#[test]
+fn test_fit_cubic_beziers() {
+    // Sample a circle densely.
+    let mut builder = PathBuilder::new();
+    let n = 200;
+    for i in 0..n {
+        let a = i as f32 / n as f32 * 2.0 * ::std::f32::consts::PI;
+        let p = vec2(a.cos() * 100.0, a.sin() * 100.0);
+        if i == 0 { builder.move_to(p); } else { builder.line_to(p); }
+    }
+    builder.close();
+    let input = builder.finish();
+
+    let tolerance = 0.5;
+    let fitted = fit_cubic_beziers(input.as_slice(), tolerance, 0.01);
+    let info = fitted.sub_path(path_id(0)).info();
+    assert!(info.is_closed);
+    assert_eq!(info.has_beziers, Some(true));
+    // Much more compact than the input.
+    assert!(fitted.num_vertices() < 40);
+
+    // The fitted curve stays close to the circle.
+    for p in flattened_points(&fitted) {
+        assert!((p.length() - 100.0).abs() < tolerance * 2.0);
+    }
+}
+
+#[test]
+fn test_fit_cubic_beziers_polyline() {
+    let fitted = fit_cubic_beziers(noisy_line(50).as_slice(), 0.5, 0.01);
+    // A nearly straight line fits in a single curve.
+    assert_eq!(fitted.num_vertices(), 4);
+    for p in flattened_points(&fitted) {
+        assert!(p.y.abs() < 0.5);
+    }
+}
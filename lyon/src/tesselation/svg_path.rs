@@ -0,0 +1,371 @@
+//! Parsing of SVG path data, as found in the `d` attribute of SVG `path` elements.
+//!
+//! All commands are supported, in absolute and relative forms. Elliptic arcs are converted to
+//! cubic bézier curves.
+
+use tesselation::path_builder::PathBuilder;
+
+use vodk_math::{ Vector2D, Unit };
+
+use std::f32::consts::PI;
+
+#[cfg(test)]
+use tesselation::path::*;
+#[cfg(test)]
+use vodk_math::vec2;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SvgPathError {
+    /// The path data doesn't start with a move-to command.
+    MissingMoveTo,
+    /// An unknown command at a given byte offset.
+    UnknownCommand(usize),
+    /// A number or a flag was expected at a given byte offset.
+    ExpectedNumber(usize),
+}
+
+/// Parse SVG path data and add the sub-paths to a path builder.
+pub fn parse_svg_path<U: Unit>(data: &str, builder: &mut PathBuilder<U>) -> Result<(), SvgPathError> {
+    let mut parser = Parser { bytes: data.as_bytes(), position: 0 };
+    let mut current: Vector2D<U> = Vector2D::new(0.0, 0.0);
+    let mut start = current;
+    // The control points of the previous command, for the smooth curve commands.
+    let mut last_cubic_ctrl: Option<Vector2D<U>> = None;
+    let mut last_quadratic_ctrl: Option<Vector2D<U>> = None;
+    let mut building = false;
+    let mut command = None;
+
+    loop {
+        parser.skip_separators();
+        let c = match parser.peek() {
+            Some(c) => c,
+            None => { break; }
+        };
+
+        if c.is_ascii_alphabetic() {
+            if command.is_none() && c != b'M' && c != b'm' {
+                return Err(SvgPathError::MissingMoveTo);
+            }
+            parser.position += 1;
+            if c == b'Z' || c == b'z' {
+                if building {
+                    builder.close();
+                    building = false;
+                }
+                current = start;
+                last_cubic_ctrl = None;
+                last_quadratic_ctrl = None;
+                command = Some(c);
+                continue;
+            }
+            command = Some(c);
+        } else if command.is_none() {
+            return Err(SvgPathError::MissingMoveTo);
+        }
+
+        let cmd = command.unwrap();
+        if cmd == b'Z' || cmd == b'z' {
+            // Numbers after a close command.
+            return Err(SvgPathError::UnknownCommand(parser.position));
+        }
+        let relative = cmd.is_ascii_lowercase();
+        let origin = if relative { current } else { Vector2D::new(0.0, 0.0) };
+
+        if cmd == b'M' || cmd == b'm' {
+            current = origin + try!(parser.point());
+            builder.move_to(current);
+            start = current;
+            building = true;
+            last_cubic_ctrl = None;
+            last_quadratic_ctrl = None;
+            // Subsequent pairs of coordinates are implicit line-to commands.
+            command = Some(if relative { b'l' } else { b'L' });
+            continue;
+        }
+
+        if !building {
+            // Drawing after a close command starts a new sub-path at the same position.
+            builder.move_to(current);
+            building = true;
+        }
+
+        let mut cubic_ctrl = None;
+        let mut quadratic_ctrl = None;
+        match cmd.to_ascii_uppercase() {
+            b'L' => {
+                current = origin + try!(parser.point());
+                builder.line_to(current);
+            }
+            b'H' => {
+                current.x = origin.x + try!(parser.number());
+                builder.line_to(current);
+            }
+            b'V' => {
+                current.y = origin.y + try!(parser.number());
+                builder.line_to(current);
+            }
+            b'C' => {
+                let ctrl1 = origin + try!(parser.point());
+                let ctrl2 = origin + try!(parser.point());
+                current = origin + try!(parser.point());
+                builder.cubic_bezier_to(ctrl1, ctrl2, current);
+                cubic_ctrl = Some(ctrl2);
+            }
+            b'S' => {
+                let ctrl1 = reflected(last_cubic_ctrl, current);
+                let ctrl2 = origin + try!(parser.point());
+                current = origin + try!(parser.point());
+                builder.cubic_bezier_to(ctrl1, ctrl2, current);
+                cubic_ctrl = Some(ctrl2);
+            }
+            b'Q' => {
+                let ctrl = origin + try!(parser.point());
+                current = origin + try!(parser.point());
+                builder.quadratic_bezier_to(ctrl, current);
+                quadratic_ctrl = Some(ctrl);
+            }
+            b'T' => {
+                let ctrl = reflected(last_quadratic_ctrl, current);
+                current = origin + try!(parser.point());
+                builder.quadratic_bezier_to(ctrl, current);
+                quadratic_ctrl = Some(ctrl);
+            }
+            b'A' => {
+                let rx = try!(parser.number());
+                let ry = try!(parser.number());
+                let x_rotation = try!(parser.number());
+                let large_arc = try!(parser.flag());
+                let sweep = try!(parser.flag());
+                let to = origin + try!(parser.point());
+                arc_to(builder, current, Vector2D::new(rx, ry), x_rotation, large_arc, sweep, to);
+                current = to;
+            }
+            _ => {
+                return Err(SvgPathError::UnknownCommand(parser.position - 1));
+            }
+        }
+        last_cubic_ctrl = cubic_ctrl;
+        last_quadratic_ctrl = quadratic_ctrl;
+    }
+
+    if building {
+        builder.end();
+    }
+
+    return Ok(());
+}
+
+struct Parser<'l> {
+    bytes: &'l [u8],
+    position: usize,
+}
+
+impl<'l> Parser<'l> {
+    fn peek(&self) -> Option<u8> { self.bytes.get(self.position).cloned() }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == b',' || c.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<f32, SvgPathError> {
+        self.skip_separators();
+        let start = self.position;
+        if let Some(b'-') | Some(b'+') = self.peek() {
+            self.position += 1;
+        }
+        let mut has_dot = false;
+        let mut has_digits = false;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                has_digits = true;
+            } else if c == b'.' && !has_dot {
+                has_dot = true;
+            } else {
+                break;
+            }
+            self.position += 1;
+        }
+        if !has_digits {
+            self.position = start;
+            return Err(SvgPathError::ExpectedNumber(start));
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            let mantissa_end = self.position;
+            self.position += 1;
+            if let Some(b'-') | Some(b'+') = self.peek() {
+                self.position += 1;
+            }
+            let exponent_start = self.position;
+            while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+                self.position += 1;
+            }
+            if self.position == exponent_start {
+                self.position = mantissa_end;
+            }
+        }
+        let text = ::std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+        return text.parse().map_err(|_| SvgPathError::ExpectedNumber(start));
+    }
+
+    // Flags are single characters and don't need to be separated from what follows.
+    fn flag(&mut self) -> Result<bool, SvgPathError> {
+        self.skip_separators();
+        let result = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => { return Err(SvgPathError::ExpectedNumber(self.position)); }
+        };
+        self.position += 1;
+        return Ok(result);
+    }
+
+    fn point<U: Unit>(&mut self) -> Result<Vector2D<U>, SvgPathError> {
+        let x = try!(self.number());
+        let y = try!(self.number());
+        return Ok(Vector2D::new(x, y));
+    }
+}
+
+fn reflected<U: Unit>(ctrl: Option<Vector2D<U>>, current: Vector2D<U>) -> Vector2D<U> {
+    return match ctrl {
+        Some(ctrl) => current + current - ctrl,
+        None => current,
+    };
+}
+
+// Convert an arc from the endpoint parametrization of SVG to the center parametrization (see
+// the implementation notes of the SVG specification), and approximate it with one cubic bézier
+// curve per quarter of ellipse at most.
+fn arc_to<U: Unit>(
+    builder: &mut PathBuilder<U>,
+    from: Vector2D<U>,
+    radii: Vector2D<U>,
+    x_rotation_degrees: f32,
+    large_arc: bool,
+    sweep: bool,
+    to: Vector2D<U>
+) {
+    let mut rx = radii.x.abs();
+    let mut ry = radii.y.abs();
+    if from == to {
+        return;
+    }
+    if rx == 0.0 || ry == 0.0 {
+        builder.line_to(to);
+        return;
+    }
+
+    let phi = x_rotation_degrees * PI / 180.0;
+    let (sin_phi, cos_phi) = (phi.sin(), phi.cos());
+    let rotate = |x: f32, y: f32| Vector2D::<U>::new(cos_phi * x - sin_phi * y, sin_phi * x + cos_phi * y);
+
+    let half_diff = (from - to) * 0.5;
+    let x1 = cos_phi * half_diff.x + sin_phi * half_diff.y;
+    let y1 = -sin_phi * half_diff.x + cos_phi * half_diff.y;
+
+    // Scale the radii up if they are too small to join the two points.
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut coef = (num / den).max(0.0).sqrt();
+    if large_arc == sweep {
+        coef = -coef;
+    }
+    let cx1 = coef * rx * y1 / ry;
+    let cy1 = -coef * ry * x1 / rx;
+    let center = rotate(cx1, cy1) + (from + to) * 0.5;
+
+    // The signed angle between two vectors.
+    let angle = |ux: f32, uy: f32, vx: f32, vy: f32| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let start_angle = angle(1.0, 0.0, (x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut sweep_angle = angle(
+        (x1 - cx1) / rx, (y1 - cy1) / ry,
+        (-x1 - cx1) / rx, (-y1 - cy1) / ry
+    );
+    if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= 2.0 * PI;
+    } else if sweep && sweep_angle < 0.0 {
+        sweep_angle += 2.0 * PI;
+    }
+
+    let num_segments = (sweep_angle.abs() / (PI * 0.5)).ceil().max(1.0) as u32;
+    let step = sweep_angle / num_segments as f32;
+    let k = 4.0 / 3.0 * (step / 4.0).tan();
+    let point = |a: f32| center + rotate(rx * a.cos(), ry * a.sin());
+    let derivative = |a: f32| rotate(-rx * a.sin(), ry * a.cos());
+    for i in 0..num_segments {
+        let a0 = start_angle + step * i as f32;
+        let a1 = a0 + step;
+        let end = if i == num_segments - 1 { to } else { point(a1) };
+        builder.cubic_bezier_to(
+            point(a0) + derivative(a0) * k,
+            point(a1) - derivative(a1) * k,
+            end
+        );
+    }
+}
+
+#[test]
+fn test_parse_svg_path() {
+    let mut builder = PathBuilder::new();
+    parse_svg_path("M10,10 h 10 v10 H10 z m 20 0 l5-5 5,5Z M0 0L1e1 0", &mut builder).unwrap();
+    let path = builder.finish();
+    assert_eq!(path.num_sub_paths(), 3);
+
+    let square = path.sub_path(path_id(0));
+    assert!(square.info().is_closed);
+    let positions: Vec<_> = square.vertex_ids().map(|id| square.vertex(id).position).collect();
+    assert_eq!(positions, vec![vec2(10.0, 10.0), vec2(20.0, 10.0), vec2(20.0, 20.0), vec2(10.0, 20.0)]);
+
+    // The relative move-to is relative to the start of the previous sub-path.
+    let triangle = path.sub_path(path_id(1));
+    assert_eq!(triangle.vertex(triangle.first()).position, vec2(30.0, 10.0));
+    assert_eq!(triangle.vertex(triangle.last()).position, vec2(40.0, 10.0));
+
+    let line = path.sub_path(path_id(2));
+    assert!(!line.info().is_closed);
+    assert_eq!(line.vertex(line.last()).position, vec2(10.0, 0.0));
+}
+
+#[test]
+fn test_parse_svg_curves() {
+    let mut builder = PathBuilder::new();
+    parse_svg_path("M0 0 C 0 10 10 10 10 0 S 20 -10 20 0 Q 25 5 30 0 T 40 0", &mut builder).unwrap();
+    let path = builder.finish();
+    let points: Vec<_> = path.vertices().as_slice().iter().map(|v| (v.position, v.point_type)).collect();
+    assert_eq!(points[4], (vec2(10.0, -10.0), PointType::Control));
+    assert_eq!(points[9], (vec2(35.0, -5.0), PointType::Control));
+    assert_eq!(points[10], (vec2(40.0, 0.0), PointType::Normal));
+
+    // Half of a circle of radius 10.
+    let mut builder = PathBuilder::new();
+    builder.set_flattening(true);
+    builder.set_tolerance(0.01);
+    parse_svg_path("M 0 0 a 10 10 0 0 1 20 0", &mut builder).unwrap();
+    let path = builder.finish();
+    for v in path.vertices().as_slice() {
+        assert!(((v.position - vec2(10.0, 0.0)).length() - 10.0).abs() < 0.05);
+        assert!(v.position.y <= 0.001);
+    }
+    let last = path.vertices().as_slice().last().unwrap().position;
+    assert_eq!(last, vec2(20.0, 0.0));
+}
+
+#[test]
+fn test_parse_svg_errors() {
+    let mut builder: PathBuilder = PathBuilder::new();
+    assert_eq!(parse_svg_path("L 10 10", &mut builder), Err(SvgPathError::MissingMoveTo));
+    assert_eq!(parse_svg_path("M 10 10 X 1", &mut builder), Err(SvgPathError::UnknownCommand(8)));
+    assert_eq!(parse_svg_path("M 10 10 L 5", &mut builder), Err(SvgPathError::ExpectedNumber(11)));
+}
@@ -0,0 +1,266 @@
+//! Tessellation of large sets of paths into a grid of fixed-size tiles.
+//!
+//! Each tile gets its own vertex and index buffers containing only the geometry that overlaps
+//! it, clipped at the tile's edges, so that tiles can be culled and streamed independently.
+//! The grid is aligned with the origin, the tile at column `c` and row `r` covering the
+//! rectangle `(c * width, r * height, width, height)`.
+
+use tesselation::path::*;
+use tesselation::path_builder::PathBuilder;
+use tesselation::path_clipping::build_clipped_path;
+use tesselation::path_tesselator::{ tesselate_path_fill, TesselatorOptions };
+use tesselation::vertex_builder::{ VertexBuffers, vertex_builder, Identity, Index };
+
+use vodk_math::{ Vector2D, Rectangle, Size2D, Unit, Untyped };
+
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+use vodk_math::{ vec2, Rect };
+
+/// The tessellated geometry of one tile.
+pub struct Tile<U: Unit = Untyped> {
+    pub column: i32,
+    pub row: i32,
+    pub rect: Rectangle<U>,
+    pub geometry: VertexBuffers<Vector2D<U>>,
+}
+
+/// A sparse grid of tiles. Tiles that don't overlap any geometry are not stored.
+pub struct TiledGeometry<U: Unit = Untyped> {
+    tile_width: f32,
+    tile_height: f32,
+    // Sorted by row and then by column.
+    tiles: Vec<Tile<U>>,
+}
+
+impl<U: Unit> TiledGeometry<U> {
+    pub fn tile_size(&self) -> Size2D<U> { Size2D::new(self.tile_width, self.tile_height) }
+
+    /// The non-empty tiles, sorted by row and then by column.
+    pub fn tiles(&self) -> &[Tile<U>] { &self.tiles[..] }
+
+    pub fn tile(&self, column: i32, row: i32) -> Option<&Tile<U>> {
+        return match self.tiles.binary_search_by(|t| (t.row, t.column).cmp(&(row, column))) {
+            Ok(idx) => Some(&self.tiles[idx]),
+            Err(_) => None,
+        };
+    }
+
+    /// The non-empty tiles that overlap a rectangle, for example the visible area.
+    pub fn tiles_in_rect(&self, rect: &Rectangle<U>) -> Vec<&Tile<U>> {
+        let (first_column, last_column) = tile_range(rect.x, rect.x_most(), self.tile_width);
+        let (first_row, last_row) = tile_range(rect.y, rect.y_most(), self.tile_height);
+        return self.tiles.iter().filter(|t| {
+            t.column >= first_column && t.column <= last_column &&
+            t.row >= first_row && t.row <= last_row
+        }).collect();
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TiledTesselationError {
+    /// The tessellation of a path failed.
+    Tesselation,
+    /// A tile has more vertices than its indices can address, smaller tiles may fix this.
+    TooManyVertices { column: i32, row: i32 },
+}
+
+/// Tessellate the fill of a set of paths into tiles of a given size.
+///
+/// Paths are clipped to each tile they overlap and tessellated separately, in order, so the
+/// geometry of a path that comes after another one in the slice is also after it in the index
+/// buffer of the tiles they share. Curves are flattened with the provided tolerance.
+pub fn tesselate_fill_tiled<U: Unit>(
+    paths: &[PathSlice<U>],
+    tile_size: Size2D<U>,
+    tolerance: f32,
+    options: &TesselatorOptions
+) -> Result<TiledGeometry<U>, TiledTesselationError> {
+    assert!(tile_size.width > 0.0 && tile_size.height > 0.0);
+    let (tile_width, tile_height) = (tile_size.width, tile_size.height);
+
+    let mut tiles: BTreeMap<(i32, i32), Tile<U>> = BTreeMap::new();
+    let mut scratch = VertexBuffers::new();
+    for path in paths {
+        if path.num_vertices() == 0 {
+            continue;
+        }
+        let aabb = path.aabb();
+        let (first_column, last_column) = tile_range(aabb.x, aabb.x_most(), tile_width);
+        let (first_row, last_row) = tile_range(aabb.y, aabb.y_most(), tile_height);
+        for row in first_row..(last_row + 1) {
+            for column in first_column..(last_column + 1) {
+                let rect = Rectangle::new(
+                    column as f32 * tile_width,
+                    row as f32 * tile_height,
+                    tile_width,
+                    tile_height
+                );
+                let clip = [rect.top_left(), rect.top_right(), rect.bottom_right(), rect.bottom_left()];
+
+                let mut builder = PathBuilder::new();
+                builder.set_flattening(true);
+                builder.set_tolerance(tolerance);
+                build_clipped_path(*path, &clip[..], true, tolerance, &mut builder);
+                let clipped = builder.finish();
+                if clipped.num_sub_paths() == 0 {
+                    continue;
+                }
+
+                let tile = tiles.entry((row, column)).or_insert_with(|| {
+                    Tile {
+                        column: column,
+                        row: row,
+                        rect: rect,
+                        geometry: VertexBuffers::new(),
+                    }
+                });
+                // Tessellate separately so that the size of the tile can be checked before
+                // offsetting the indices.
+                try!(tesselate_path_fill(
+                    clipped.as_slice(),
+                    options,
+                    &mut vertex_builder(&mut scratch, Identity)
+                ).map_err(|_| TiledTesselationError::Tesselation));
+                let offset = tile.geometry.vertices.len();
+                if offset + scratch.vertices.len() > Index::max_value() as usize + 1 {
+                    return Err(TiledTesselationError::TooManyVertices { column: column, row: row });
+                }
+                tile.geometry.vertices.extend(scratch.vertices.drain(..));
+                tile.geometry.indices.extend(
+                    scratch.indices.drain(..).map(|i| (i as usize + offset) as Index)
+                );
+            }
+        }
+    }
+
+    return Ok(TiledGeometry {
+        tile_width: tile_width,
+        tile_height: tile_height,
+        tiles: tiles.into_iter().map(|(_, tile)| tile).collect(),
+    });
+}
+
+// The first and last tiles overlapping the [min, max] interval along an axis. Geometry that
+// only touches the edge of a tile does not overlap it.
+fn tile_range(min: f32, max: f32, tile_size: f32) -> (i32, i32) {
+    let first = (min / tile_size).floor() as i32;
+    let last = (max / tile_size).ceil() as i32 - 1;
+    return (first, ::std::cmp::max(first, last));
+}
+
+#[cfg(test)]
+fn triangles_area(geometry: &VertexBuffers<Vector2D<Untyped>>) -> f32 {
+    let mut area = 0.0;
+    for tri in geometry.indices.chunks(3) {
+        let a = geometry.vertices[tri[0] as usize];
+        let b = geometry.vertices[tri[1] as usize];
+        let c = geometry.vertices[tri[2] as usize];
+        area += (b - a).cross(c - a).abs() * 0.5;
+    }
+    return area;
+}
+
+#[test]
+fn test_tiled_square() {
+    // A 20x20 square centered on the corner shared by four 16x16 tiles.
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(6.0, 6.0));
+    builder.line_to(vec2(26.0, 6.0));
+    builder.line_to(vec2(26.0, 26.0));
+    builder.line_to(vec2(6.0, 26.0));
+    builder.close();
+    let path = builder.finish();
+
+    let tiled = tesselate_fill_tiled(
+        &[path.as_slice()],
+        Size2D::new(16.0, 16.0),
+        0.1,
+        &TesselatorOptions::new()
+    ).unwrap();
+
+    assert_eq!(tiled.tiles().len(), 4);
+    let expected = [(0, 0, 100.0), (1, 0, 100.0), (0, 1, 100.0), (1, 1, 100.0)];
+    for &(column, row, area) in &expected {
+        let tile = tiled.tile(column, row).unwrap();
+        assert_eq!(tile.rect, Rect::new(column as f32 * 16.0, row as f32 * 16.0, 16.0, 16.0));
+        assert_eq!(triangles_area(&tile.geometry), area);
+        for v in &tile.geometry.vertices {
+            assert!(v.x >= tile.rect.x && v.x <= tile.rect.x_most());
+            assert!(v.y >= tile.rect.y && v.y <= tile.rect.y_most());
+        }
+    }
+    assert!(tiled.tile(2, 0).is_none());
+
+    assert_eq!(tiled.tiles_in_rect(&Rect::new(0.0, 20.0, 10.0, 10.0)).len(), 1);
+}
+
+#[test]
+fn test_tiled_paths_share_tiles() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(1.0, 1.0));
+    builder.line_to(vec2(5.0, 1.0));
+    builder.line_to(vec2(5.0, 5.0));
+    builder.close();
+    let a = builder.finish();
+
+    // Touches the edge of the first tile without overlapping it, and has a curve.
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(10.0, 2.0));
+    builder.line_to(vec2(15.0, 2.0));
+    builder.quadratic_bezier_to(vec2(15.0, 8.0), vec2(10.0, 8.0));
+    builder.close();
+    let b = builder.finish();
+
+    let tiled = tesselate_fill_tiled(
+        &[a.as_slice(), b.as_slice(), a.as_slice()],
+        Size2D::new(10.0, 10.0),
+        0.1,
+        &TesselatorOptions::new()
+    ).unwrap();
+
+    assert_eq!(tiled.tiles().len(), 2);
+    let first = tiled.tile(0, 0).unwrap();
+    // The first path is tessellated twice in the same tile.
+    assert_eq!(first.geometry.vertices.len(), 6);
+    assert_eq!(first.geometry.indices.len(), 6);
+    assert_eq!(triangles_area(&first.geometry), 16.0);
+
+    let second = tiled.tile(1, 0).unwrap();
+    assert!(second.geometry.vertices.len() > 4);
+    for v in &second.geometry.vertices {
+        assert!(v.x >= 10.0);
+    }
+}
+
+#[test]
+fn test_tiled_too_many_vertices() {
+    let mut builder = PathBuilder::new();
+    builder.move_to(vec2(1.0, 1.0));
+    builder.line_to(vec2(5.0, 1.0));
+    builder.line_to(vec2(5.0, 5.0));
+    builder.close();
+    let triangle = builder.finish();
+
+    // Three vertices per triangle, more than 65536 vertices in the same tile.
+    let paths = vec![triangle.as_slice(); 22000];
+    let result = tesselate_fill_tiled(
+        &paths[..],
+        Size2D::new(10.0, 10.0),
+        0.1,
+        &TesselatorOptions::new()
+    );
+    assert_eq!(result.err(), Some(TiledTesselationError::TooManyVertices { column: 0, row: 0 }));
+
+    // It fits when the indices can address all of the vertices.
+    let tiled = tesselate_fill_tiled(
+        &paths[..21845],
+        Size2D::new(10.0, 10.0),
+        0.1,
+        &TesselatorOptions::new()
+    ).unwrap();
+    let tile = tiled.tile(0, 0).unwrap();
+    assert_eq!(tile.geometry.vertices.len(), 65535);
+    assert_eq!(*tile.geometry.indices.last().unwrap(), 65534);
+}
@@ -2,6 +2,9 @@
 
 use std::marker::PhantomData;
 
+use vodk_math::{ Vector2D, Rectangle };
+use vodk_math::units::Texels;
+
 pub type Index = u16;
 
 
@@ -70,6 +73,9 @@ pub trait VertexBufferBuilder<Input> {
 
     fn push_indices(&mut self, a: Index, b: Index, c: Index);
 
+    /// Push the two indices of a line, for geometry meant to be drawn with a line-list primitive.
+    fn push_line_indices(&mut self, a: Index, b: Index);
+
     fn num_vertices(&self) -> usize;
 
     /// Set the vertex offset to the index of the next vertex to be pushed.
@@ -101,7 +107,7 @@ impl<VertexType> VertexBufferBuilder<VertexType> for VertexBuffers<VertexType> {
 
     fn push_vertex(&mut self, p: VertexType) -> Index {
         self.vertices.push(p);
-        return self.vertices.len() as Index - 1;
+        return (self.vertices.len() - 1) as Index;
     }
 
     fn push_indices(&mut self, a: Index, b: Index, c: Index) {
@@ -110,6 +116,11 @@ impl<VertexType> VertexBufferBuilder<VertexType> for VertexBuffers<VertexType> {
         self.indices.push(c);
     }
 
+    fn push_line_indices(&mut self, a: Index, b: Index) {
+        self.indices.push(a);
+        self.indices.push(b);
+    }
+
     fn num_vertices(&self) -> usize { self.vertices.len() }
 
     fn begin_geometry(&mut self) {
@@ -167,6 +178,10 @@ impl<'l,
         );
     }
 
+    fn push_line_indices(&mut self, a: Index, b: Index) {
+        self.buffers.push_line_indices(a + self.vertex_offset, b + self.vertex_offset);
+    }
+
     fn num_vertices(&self) -> usize { self.buffers.num_vertices() }
 
     fn begin_geometry(&mut self) {
@@ -229,6 +244,49 @@ pub fn simple_vertex_builder<'l, VertexType> (buffers: &'l mut VertexBuffers<Ver
     }
 }
 
+/// A VertexBufferBuilder adapter that generates texture coordinates from the position of the
+/// vertices, mapping a rectangle to the [0..1] range.
+///
+/// Algorithms that only produce positions can be used with it to output (position, uv) pairs.
+pub struct UvFromRect<'l, U, Output: 'l> {
+    rect: Rectangle<U>,
+    output: &'l mut Output,
+}
+
+impl<'l, U, Output> UvFromRect<'l, U, Output> {
+    /// Constructor
+    pub fn new(rect: Rectangle<U>, output: &'l mut Output) -> UvFromRect<'l, U, Output> {
+        UvFromRect { rect: rect, output: output }
+    }
+
+    pub fn uv(&self, p: Vector2D<U>) -> Vector2D<Texels> {
+        let w = if self.rect.width == 0.0 { 1.0 } else { self.rect.width };
+        let h = if self.rect.height == 0.0 { 1.0 } else { self.rect.height };
+        return Vector2D::new((p.x - self.rect.x) / w, (p.y - self.rect.y) / h);
+    }
+}
+
+impl<'l,
+    U,
+    Output: VertexBufferBuilder<(Vector2D<U>, Vector2D<Texels>)>
+> VertexBufferBuilder<Vector2D<U>> for UvFromRect<'l, U, Output> {
+
+    fn push_vertex(&mut self, p: Vector2D<U>) -> Index {
+        let uv = self.uv(p);
+        self.output.push_vertex((p, uv))
+    }
+
+    fn push_indices(&mut self, a: Index, b: Index, c: Index) { self.output.push_indices(a, b, c); }
+
+    fn push_line_indices(&mut self, a: Index, b: Index) { self.output.push_line_indices(a, b); }
+
+    fn num_vertices(&self) -> usize { self.output.num_vertices() }
+
+    fn begin_geometry(&mut self) { self.output.begin_geometry(); }
+
+    fn end_geometry(&mut self) -> (Range, Range) { self.output.end_geometry() }
+}
+
 #[cfg(test)]
 #[derive(PartialEq, Debug)]
 struct Vertex2d {
@@ -300,3 +358,65 @@ fn test_simple_quad() {
     assert_eq!(buffers.vertices[7], Vertex2d { position: [10.0, 11.0], color: green });
     assert_eq!(&buffers.indices[..], &[0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]);
 }
+
+#[test]
+fn test_uv_from_rect() {
+    use vodk_math::{ vec2, Rect };
+
+    let mut buffers: VertexBuffers<(Vector2D, Vector2D<Texels>)> = VertexBuffers::new();
+    {
+        let mut builder = simple_vertex_builder(&mut buffers);
+        let mut uv_builder = UvFromRect::new(Rect::new(10.0, 10.0, 20.0, 40.0), &mut builder);
+        uv_builder.begin_geometry();
+        uv_builder.push_vertex(vec2(10.0, 10.0));
+        uv_builder.push_vertex(vec2(20.0, 10.0));
+        uv_builder.push_vertex(vec2(20.0, 50.0));
+        uv_builder.push_vertex(vec2(10.0, 50.0));
+    }
+
+    assert!(buffers.vertices[0].1.fuzzy_eq(Vector2D::new(0.0, 0.0)));
+    assert!(buffers.vertices[1].1.fuzzy_eq(Vector2D::new(0.5, 0.0)));
+    assert!(buffers.vertices[2].1.fuzzy_eq(Vector2D::new(0.5, 1.0)));
+    assert!(buffers.vertices[3].1.fuzzy_eq(Vector2D::new(0.0, 1.0)));
+    assert_eq!(buffers.vertices[2].0, vec2(20.0, 50.0));
+}
+
+#[test]
+fn test_custom_builder_line_indices() {
+    use vodk_math::{ vec2, Vec2 };
+    use tesselation::path_builder::PathBuilder;
+    use tesselation::path_tesselator::tesselate_path_hairline;
+
+    // A builder that keeps lines separate from triangles.
+    struct Lines { vertices: Vec<Vec2>, lines: Vec<(Index, Index)> }
+    impl VertexBufferBuilder<Vec2> for Lines {
+        fn push_vertex(&mut self, p: Vec2) -> Index {
+            self.vertices.push(p);
+            return self.vertices.len() as Index - 1;
+        }
+        fn push_indices(&mut self, _a: Index, _b: Index, _c: Index) {
+            panic!("The hairline stroke should not generate triangles");
+        }
+        fn push_line_indices(&mut self, a: Index, b: Index) { self.lines.push((a, b)); }
+        fn num_vertices(&self) -> usize { self.vertices.len() }
+        fn begin_geometry(&mut self) {}
+        fn end_geometry(&mut self) -> (Range, Range) {
+            let num_indices = 2 * self.lines.len() as Index;
+            return (Range::new(0, self.vertices.len() as Index), Range::new(0, num_indices));
+        }
+    }
+
+    let mut path = PathBuilder::new();
+    path.move_to(vec2(0.0, 0.0));
+    path.line_to(vec2(10.0, 0.0));
+    path.line_to(vec2(10.0, 10.0));
+    path.close();
+    let path = path.finish();
+
+    let mut lines = Lines { vertices: Vec::new(), lines: Vec::new() };
+    let (vertices, indices) = tesselate_path_hairline(path.as_slice(), &mut lines);
+    assert_eq!(vertices.count, 3);
+    assert_eq!(indices.count, 6);
+    assert_eq!(lines.vertices, vec![vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(10.0, 10.0)]);
+    assert_eq!(lines.lines, vec![(0, 1), (1, 2), (2, 0)]);
+}
@@ -7,18 +7,22 @@ pub struct Untyped;
 impl Unit for Untyped { fn name() -> &'static str { "Untyped" } }
 
 // In texture space (0 .. 1) origin: top-left
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Texels;
 impl Unit for Texels { fn name() -> &'static str { "Texels" } }
 
 // In screen pixels (0 .. X) origin: top-left
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Screen;
 impl Unit for Screen { fn name() -> &'static str { "Screen" } }
 
 // In world space (-X .. Y)
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct World;
 impl Unit for World { fn name() -> &'static str { "World" } }
 
 // Local coordinates, for instance mesh vertices...
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Local;
 impl Unit for Local { fn name() -> &'static str { "Local" } }
 
@@ -0,0 +1,412 @@
+//! Command-line tessellation of SVG path data.
+//!
+//! Run with --help for the list of options.
+
+extern crate lyon;
+extern crate vodk_math;
+
+use lyon::tesselation::path::Path;
+use lyon::tesselation::path_builder::PathBuilder;
+use lyon::tesselation::svg_path::{ parse_svg_path, SvgPathError };
+use lyon::tesselation::vertex_builder::{ VertexBuffers, vertex_builder, Identity, Index };
+use lyon::tesselation::path_tesselator::{
+    TesselatorOptions, StrokeOptions,
+    tesselate_path_fill, tesselate_path_stroke_with_width,
+};
+
+use vodk_math::Vec2;
+
+use std::env;
+use std::fs::File;
+use std::io::{ self, Read, Write, BufWriter };
+use std::process;
+use std::time::Instant;
+use std::u32;
+
+const USAGE: &'static str = "\
+Usage: tesselate [OPTIONS] [PATH_DATA...]
+
+Tessellates SVG path data passed as arguments and/or read from a file, and writes the
+resulting triangles. Statistics are printed to the standard error.
+
+Options:
+    -i, --input FILE        Read paths from a file, one path per line. Empty lines and lines
+                            starting with # are ignored. Use - for the standard input.
+    -o, --output FILE       Write the triangles to a file instead of the standard output.
+    -f, --format FORMAT     obj (default), svg or bin.
+    -t, --tolerance VALUE   Maximum distance between curves and their approximation.
+                            Default: 0.05.
+        --fill-rule RULE    evenodd (default). The nonzero fill rule is not supported yet.
+    -s, --stroke WIDTH      Tessellate strokes of the given width instead of fills.
+    -h, --help              Print this message.
+
+The bin format is little-endian: the bytes \"LYON\", the u32 vertex count, the u32 index
+count, the vertices as pairs of f32 and the indices as u32, three per triangle.
+";
+
+#[derive(Copy, Clone, PartialEq)]
+enum Format { Obj, Svg, Binary }
+
+struct Options {
+    inputs: Vec<String>,
+    paths: Vec<String>,
+    output: Option<String>,
+    format: Format,
+    tolerance: f32,
+    stroke_width: Option<f32>,
+}
+
+/// The triangles of all of the paths.
+///
+/// The tessellators index the vertices with 16 bit integers, each path is tessellated separately
+/// and its indices are widened to 32 bits, so that only the size of a single path is limited.
+struct Geometry {
+    vertices: Vec<Vec2>,
+    indices: Vec<u32>,
+}
+
+impl Geometry {
+    fn new() -> Geometry { Geometry { vertices: Vec::new(), indices: Vec::new() } }
+
+    fn append(&mut self, buffers: &VertexBuffers<Vec2>) -> Result<(), String> {
+        let max_path_vertices = Index::max_value() as usize + 1;
+        if buffers.vertices.len() > max_path_vertices {
+            return Err(format!("the path has more than {} vertices", max_path_vertices));
+        }
+        let offset = self.vertices.len();
+        if offset + buffers.vertices.len() > u32::MAX as usize {
+            return Err(format!("the output has more than {} vertices", u32::MAX));
+        }
+        self.vertices.extend_from_slice(&buffers.vertices[..]);
+        self.indices.extend(buffers.indices.iter().map(|&i| (offset + i as usize) as u32));
+        return Ok(());
+    }
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(message) => { fail(&message); }
+    };
+
+    let mut sources = Vec::new();
+    for data in &options.paths {
+        sources.push(("argument".to_string(), data.clone()));
+    }
+    for input in &options.inputs {
+        let text = match read_input(input) {
+            Ok(text) => text,
+            Err(e) => { fail(&format!("can't read {}: {}", input, e)); }
+        };
+        for (line, data) in text.lines().enumerate() {
+            let data = data.trim();
+            if data.is_empty() || data.starts_with('#') {
+                continue;
+            }
+            sources.push((format!("{}:{}", input, line + 1), data.to_string()));
+        }
+    }
+    if sources.is_empty() {
+        fail("no path data");
+    }
+
+    let start_time = Instant::now();
+
+    let mut paths = Vec::new();
+    for &(ref origin, ref data) in &sources {
+        let mut builder = PathBuilder::new();
+        builder.set_flattening(true);
+        builder.set_tolerance(options.tolerance);
+        if let Err(e) = parse_svg_path(data, &mut builder) {
+            fail(&format!("{}: {}", origin, describe_error(e)));
+        }
+        paths.push(builder.finish());
+    }
+    let parse_time = start_time.elapsed();
+
+    let mut geometry = Geometry::new();
+    let mut buffers: VertexBuffers<Vec2> = VertexBuffers::new();
+    for (path, &(ref origin, _)) in paths.iter().zip(sources.iter()) {
+        buffers.vertices.clear();
+        buffers.indices.clear();
+        {
+            let mut output = vertex_builder(&mut buffers, Identity);
+            match options.stroke_width {
+                Some(mut width) => {
+                    let mut stroke_options = StrokeOptions::new();
+                    stroke_options.tolerance = options.tolerance;
+                    tesselate_path_stroke_with_width(
+                        path.as_slice(), &mut width, &stroke_options, &mut output
+                    );
+                }
+                None => {
+                    let fill_options = TesselatorOptions::new();
+                    if tesselate_path_fill(path.as_slice(), &fill_options, &mut output).is_err() {
+                        fail(&format!("{}: tessellation failed", origin));
+                    }
+                }
+            }
+        }
+        if let Err(message) = geometry.append(&buffers) {
+            fail(&format!("{}: {}", origin, message));
+        }
+    }
+    let tesselation_time = start_time.elapsed() - parse_time;
+
+    let result = match options.output {
+        Some(ref file_name) => {
+            match File::create(file_name) {
+                Ok(file) => write_output(&geometry, options.format, &mut BufWriter::new(file)),
+                Err(e) => Err(e),
+            }
+        }
+        None => {
+            let stdout = io::stdout();
+            let mut lock = stdout.lock();
+            write_output(&geometry, options.format, &mut lock)
+        }
+    };
+    if let Err(e) = result {
+        fail(&format!("can't write the output: {}", e));
+    }
+
+    print_statistics(&paths, &geometry, parse_time, tesselation_time);
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, String> {
+    let mut options = Options {
+        inputs: Vec::new(),
+        paths: Vec::new(),
+        output: None,
+        format: Format::Obj,
+        tolerance: 0.05,
+        stroke_width: None,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> Result<String, String> {
+            args.next().ok_or(format!("missing value for {}", name))
+        };
+        match &arg[..] {
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            "-i" | "--input" => { options.inputs.push(try!(value(&arg))); }
+            "-o" | "--output" => { options.output = Some(try!(value(&arg))); }
+            "-f" | "--format" => {
+                options.format = match &try!(value(&arg))[..] {
+                    "obj" => Format::Obj,
+                    "svg" => Format::Svg,
+                    "bin" => Format::Binary,
+                    other => { return Err(format!("unknown format {}", other)); }
+                };
+            }
+            "-t" | "--tolerance" => {
+                options.tolerance = try!(parse_positive(&arg, &try!(value(&arg))));
+            }
+            "--fill-rule" => {
+                // The fill tessellator only implements the evenodd fill rule.
+                match &try!(value(&arg))[..] {
+                    "evenodd" => {}
+                    "nonzero" => { return Err("unsupported fill rule nonzero".to_string()); }
+                    other => { return Err(format!("unknown fill rule {}", other)); }
+                }
+            }
+            "-s" | "--stroke" => {
+                options.stroke_width = Some(try!(parse_positive(&arg, &try!(value(&arg)))));
+            }
+            _ => {
+                if arg.starts_with('-') && arg.len() > 1 && !arg[1..].starts_with(|c: char| c.is_digit(10)) {
+                    return Err(format!("unknown option {}", arg));
+                }
+                options.paths.push(arg.clone());
+            }
+        }
+    }
+
+    return Ok(options);
+}
+
+fn parse_positive(name: &str, value: &str) -> Result<f32, String> {
+    return match value.parse::<f32>() {
+        Ok(v) if v > 0.0 => Ok(v),
+        _ => Err(format!("invalid value for {}: {}", name, value)),
+    };
+}
+
+fn read_input(name: &str) -> io::Result<String> {
+    let mut text = String::new();
+    if name == "-" {
+        try!(io::stdin().read_to_string(&mut text));
+    } else {
+        try!(try!(File::open(name)).read_to_string(&mut text));
+    }
+    return Ok(text);
+}
+
+fn describe_error(e: SvgPathError) -> String {
+    return match e {
+        SvgPathError::MissingMoveTo => "the path data must start with a move-to command".to_string(),
+        SvgPathError::UnknownCommand(offset) => format!("unknown command at offset {}", offset),
+        SvgPathError::ExpectedNumber(offset) => format!("expected a number at offset {}", offset),
+    };
+}
+
+fn write_output(geometry: &Geometry, format: Format, out: &mut Write) -> io::Result<()> {
+    match format {
+        Format::Obj => {
+            for v in &geometry.vertices {
+                try!(writeln!(out, "v {} {} 0", v.x, v.y));
+            }
+            for tri in geometry.indices.chunks(3) {
+                // Obj indices start at one.
+                let (a, b, c) = (tri[0] as u64 + 1, tri[1] as u64 + 1, tri[2] as u64 + 1);
+                try!(writeln!(out, "f {} {} {}", a, b, c));
+            }
+        }
+        Format::Svg => {
+            let (min, max) = bounds(&geometry.vertices[..]);
+            let size = (max - min).x.max((max - min).y).max(1.0);
+            try!(writeln!(out,
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+                min.x, min.y, max.x - min.x, max.y - min.y
+            ));
+            try!(writeln!(out,
+                "<g fill=\"lightsteelblue\" stroke=\"black\" stroke-width=\"{}\" stroke-linejoin=\"round\">",
+                size * 0.002
+            ));
+            for tri in geometry.indices.chunks(3) {
+                let (a, b, c) = (
+                    geometry.vertices[tri[0] as usize],
+                    geometry.vertices[tri[1] as usize],
+                    geometry.vertices[tri[2] as usize]
+                );
+                try!(writeln!(out, "<path d=\"M {} {} L {} {} L {} {} Z\"/>", a.x, a.y, b.x, b.y, c.x, c.y));
+            }
+            try!(writeln!(out, "</g>\n</svg>"));
+        }
+        Format::Binary => {
+            try!(out.write_all(b"LYON"));
+            try!(write_u32(out, geometry.vertices.len() as u32));
+            try!(write_u32(out, geometry.indices.len() as u32));
+            for v in &geometry.vertices {
+                try!(write_u32(out, v.x.to_bits()));
+                try!(write_u32(out, v.y.to_bits()));
+            }
+            for &i in &geometry.indices {
+                try!(write_u32(out, i));
+            }
+        }
+    }
+    return out.flush();
+}
+
+fn write_u32(out: &mut Write, v: u32) -> io::Result<()> {
+    out.write_all(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8])
+}
+
+fn bounds(points: &[Vec2]) -> (Vec2, Vec2) {
+    if points.is_empty() {
+        return (Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0));
+    }
+    let mut min = points[0];
+    let mut max = points[0];
+    for p in points {
+        min.x = min.x.min(p.x);
+        min.y = min.y.min(p.y);
+        max.x = max.x.max(p.x);
+        max.y = max.y.max(p.y);
+    }
+    return (min, max);
+}
+
+fn print_statistics(
+    paths: &[Path],
+    geometry: &Geometry,
+    parse_time: ::std::time::Duration,
+    tesselation_time: ::std::time::Duration
+) {
+    let num_sub_paths: usize = paths.iter().map(|p| p.num_sub_paths()).sum();
+    let num_path_vertices: usize = paths.iter().map(|p| p.num_vertices()).sum();
+    let (min, max) = bounds(&geometry.vertices[..]);
+    let ms = |d: ::std::time::Duration| d.as_secs() as f64 * 1000.0 + d.subsec_nanos() as f64 / 1000000.0;
+    let stderr = io::stderr();
+    let mut err = stderr.lock();
+    let _ = writeln!(err, "paths:        {} ({} sub-paths, {} vertices after flattening)",
+        paths.len(), num_sub_paths, num_path_vertices
+    );
+    let _ = writeln!(err, "vertices:     {}", geometry.vertices.len());
+    let _ = writeln!(err, "triangles:    {}", geometry.indices.len() / 3);
+    let _ = writeln!(err, "bounds:       ({}, {}) -> ({}, {})", min.x, min.y, max.x, max.y);
+    let _ = writeln!(err, "parsing:      {:.3} ms", ms(parse_time));
+    let _ = writeln!(err, "tessellation: {:.3} ms", ms(tesselation_time));
+}
+
+fn fail(message: &str) -> ! {
+    let _ = writeln!(io::stderr(), "tesselate: {}\n\n{}", message, USAGE);
+    process::exit(1);
+}
+
+#[cfg(test)]
+fn args(args: &[&str]) -> Vec<String> { args.iter().map(|a| a.to_string()).collect() }
+
+#[test]
+fn test_parse_args() {
+    let options = parse_args(args(&[
+        "-i", "paths.txt", "--format", "bin", "-t", "0.5", "-s", "2", "M 0 0 L 1 1", "-1",
+    ])).unwrap();
+    assert_eq!(options.inputs, vec!["paths.txt".to_string()]);
+    assert!(options.format == Format::Binary);
+    assert_eq!(options.tolerance, 0.5);
+    assert_eq!(options.stroke_width, Some(2.0));
+    // Negative numbers are path data.
+    assert_eq!(options.paths, vec!["M 0 0 L 1 1".to_string(), "-1".to_string()]);
+
+    assert_eq!(parse_args(args(&["-f", "png"])).err(), Some("unknown format png".to_string()));
+    assert_eq!(parse_args(args(&["-t", "0"])).err(), Some("invalid value for -t: 0".to_string()));
+    assert_eq!(parse_args(args(&["-o"])).err(), Some("missing value for -o".to_string()));
+
+    let options = parse_args(args(&["--fill-rule", "evenodd", "M 0 0 L 1 1 L 0 1 Z"])).unwrap();
+    assert_eq!(options.paths, vec!["M 0 0 L 1 1 L 0 1 Z".to_string()]);
+    assert_eq!(
+        parse_args(args(&["--fill-rule", "nonzero"])).err(),
+        Some("unsupported fill rule nonzero".to_string())
+    );
+    assert_eq!(
+        parse_args(args(&["--fill-rule", "winding"])).err(),
+        Some("unknown fill rule winding".to_string())
+    );
+}
+
+#[test]
+fn test_output_past_16_bit_indices() {
+    let mut buffers: VertexBuffers<Vec2> = VertexBuffers::new();
+    for i in 0..40000 {
+        buffers.vertices.push(Vec2::new(i as f32, 0.0));
+    }
+    buffers.indices.extend_from_slice(&[0, 1, 39999]);
+
+    let mut geometry = Geometry::new();
+    geometry.append(&buffers).unwrap();
+    geometry.append(&buffers).unwrap();
+    assert_eq!(&geometry.indices[3..], &[40000, 40001, 79999]);
+
+    let mut obj = Vec::new();
+    write_output(&geometry, Format::Obj, &mut obj).unwrap();
+    let obj = String::from_utf8(obj).unwrap();
+    assert!(obj.ends_with("f 40001 40002 80000\n"));
+
+    let mut bin = Vec::new();
+    write_output(&geometry, Format::Binary, &mut bin).unwrap();
+    assert_eq!(bin.len(), 4 + 8 + 80000 * 8 + 6 * 4);
+    assert_eq!(&bin[bin.len() - 4..], &[0x7f, 0x38, 0x01, 0x00]);
+
+    // A single path can't have more vertices than the tessellators can index.
+    buffers.vertices.extend((0..30000).map(|i| Vec2::new(i as f32, 1.0)));
+    assert_eq!(
+        geometry.append(&buffers).err(),
+        Some("the path has more than 65536 vertices".to_string())
+    );
+}